@@ -0,0 +1,125 @@
+//! The child table of an enhanced suffix array
+//!
+//! The up, down, and next ℓ-index arrays of Abouelhoda, Kurtz, and
+//! Ohlebusch[^esa] encode the children of every LCP-interval, which
+//! lets the suffix and LCP arrays stand in for a suffix tree in both
+//! top-down and bottom-up traversals.
+//!
+//! [^esa]: <https://doi.org/10.1016/S1570-8667(03)00065-0>
+use crate::types::{FromUsize, Int};
+
+// --------------------------------------------------
+/// The child table, where each array has one more entry than the
+/// suffix array and a value of 0 is undefined
+#[derive(Debug, PartialEq)]
+pub struct ChildTable<T>
+where
+    T: Int + FromUsize<T> + Sized + Send + Sync + serde::ser::Serialize,
+{
+    /// For each rank `i`, the first ℓ-index of the last child interval
+    /// that ends at `i - 1` and whose LCP exceeds that at `i`
+    pub up: Vec<T>,
+
+    /// For each rank `i`, the first ℓ-index of the first child interval
+    /// that starts at `i` and whose LCP exceeds that at `i`
+    pub down: Vec<T>,
+
+    /// For each rank `i`, the next rank with the same LCP, if both
+    /// are ℓ-indexes of the same interval
+    pub next_l_index: Vec<T>,
+}
+
+// --------------------------------------------------
+impl<T> ChildTable<T>
+where
+    T: Int + FromUsize<T> + Sized + Send + Sync + serde::ser::Serialize,
+{
+    /// Compute the child table from an LCP array
+    ///
+    /// Args:
+    /// * `lcp`: the LCP of each suffix to the one before it
+    pub fn new(lcp: &[T]) -> ChildTable<T> {
+        let len = lcp.len();
+        let mut table = ChildTable {
+            up: vec![T::default(); len + 1],
+            down: vec![T::default(); len + 1],
+            next_l_index: vec![T::default(); len + 1],
+        };
+
+        // The bounds of the text share nothing with any suffix
+        let lcp_at = |i: usize| -> i64 {
+            if i == 0 || i == len {
+                -1
+            } else {
+                lcp[i].to_usize() as i64
+            }
+        };
+
+        let mut stack = vec![0];
+        let mut last_index: Option<usize> = None;
+        for i in 1..=len {
+            while lcp_at(i) < lcp_at(*stack.last().unwrap()) {
+                let last = stack.pop().unwrap();
+                let top = *stack.last().unwrap();
+                if lcp_at(i) <= lcp_at(top) && lcp_at(top) != lcp_at(last) {
+                    table.down[top] = T::from_usize(last);
+                }
+                last_index = Some(last);
+            }
+            if let Some(last) = last_index.take() {
+                table.up[i] = T::from_usize(last);
+            }
+            stack.push(i);
+        }
+
+        let mut stack = vec![0];
+        for i in 1..len {
+            while lcp_at(i) < lcp_at(*stack.last().unwrap()) {
+                stack.pop();
+            }
+            let top = *stack.last().unwrap();
+            if lcp_at(i) == lcp_at(top) {
+                table.next_l_index[top] = T::from_usize(i);
+                stack.pop();
+            }
+            stack.push(i);
+        }
+
+        table
+    }
+
+    // --------------------------------------------------
+    /// The first ℓ-index of the interval of ranks `first..=last`, which
+    /// must hold more than one suffix
+    ///
+    /// Args:
+    /// * `first`: the first rank of the interval
+    /// * `last`: the last rank of the interval
+    pub fn first_l_index(&self, first: usize, last: usize) -> usize {
+        let up = self.up[last + 1].to_usize();
+        if first < up && up <= last {
+            up
+        } else {
+            self.down[first].to_usize()
+        }
+    }
+
+    // --------------------------------------------------
+    /// The ℓ-indexes of the interval of ranks `first..=last`, which
+    /// split it into its child intervals
+    ///
+    /// Args:
+    /// * `first`: the first rank of the interval
+    /// * `last`: the last rank of the interval
+    pub fn l_indexes(&self, first: usize, last: usize) -> Vec<usize> {
+        let mut indexes = vec![self.first_l_index(first, last)];
+        loop {
+            let next = self.next_l_index[*indexes.last().unwrap()].to_usize();
+            if next == 0 || next > last {
+                break;
+            }
+            indexes.push(next);
+        }
+        indexes
+    }
+}
@@ -0,0 +1,238 @@
+//! The document array of a suffix array
+//!
+//! The document array holds the sequence of each suffix in rank order.
+//! With the rank of the previous suffix from the same sequence and a
+//! range minimum query over those ranks, Muthukrishnan's document
+//! listing[^listing] reports the distinct sequences of a range of ranks
+//! in time proportional to their number rather than to the size of the
+//! range.
+//!
+//! [^listing]: <https://doi.org/10.5555/545381.545469>
+use crate::types::{FromUsize, Int};
+use std::ops::Range;
+
+/// The number of ranks in each block of the range minimum query
+const BLOCK_LEN: usize = 64;
+
+// --------------------------------------------------
+/// The document array with the arrays needed to list and count the
+/// documents of a range of ranks
+#[derive(Debug, PartialEq)]
+pub struct DocumentArray<T>
+where
+    T: Int + FromUsize<T> + Sized + Send + Sync + serde::ser::Serialize,
+{
+    /// For each rank, the document of its suffix
+    pub documents: Vec<T>,
+
+    /// For each rank, one more than the previous rank with the same
+    /// document, or 0 when it is the first
+    pub previous: Vec<T>,
+
+    /// The ranks of each document in order, grouped by document
+    pub ranks: Vec<T>,
+
+    /// The start of each document's ranks in `ranks`
+    offsets: Vec<usize>,
+
+    /// For each level `k`, the rank of the least `previous` in each
+    /// run of `2^k` blocks
+    block_minima: Vec<Vec<T>>,
+}
+
+// --------------------------------------------------
+impl<T> DocumentArray<T>
+where
+    T: Int + FromUsize<T> + Sized + Send + Sync + serde::ser::Serialize,
+{
+    /// Compute the arrays from the document of each rank
+    ///
+    /// Args:
+    /// * `documents`: the document of the suffix at each rank
+    /// * `num_documents`: the number of documents
+    pub fn new(documents: Vec<T>, num_documents: usize) -> DocumentArray<T> {
+        let mut last: Vec<usize> = vec![0; num_documents];
+        let previous: Vec<T> = documents
+            .iter()
+            .enumerate()
+            .map(|(rank, doc)| {
+                let prev = last[doc.to_usize()];
+                last[doc.to_usize()] = rank + 1;
+                T::from_usize(prev)
+            })
+            .collect();
+
+        let offsets = Self::offsets(&documents, num_documents);
+        let mut next = offsets.clone();
+        let mut ranks = vec![T::default(); documents.len()];
+        for (rank, doc) in documents.iter().enumerate() {
+            let doc = doc.to_usize();
+            ranks[next[doc]] = T::from_usize(rank);
+            next[doc] += 1;
+        }
+
+        Self::from_arrays(documents, previous, ranks, num_documents)
+    }
+
+    // --------------------------------------------------
+    /// Restore the document array from the arrays of `new`
+    ///
+    /// Args:
+    /// * `documents`: the document of the suffix at each rank
+    /// * `previous`: one more than the previous rank of each document
+    /// * `ranks`: the ranks of each document, grouped by document
+    /// * `num_documents`: the number of documents
+    pub fn from_arrays(
+        documents: Vec<T>,
+        previous: Vec<T>,
+        ranks: Vec<T>,
+        num_documents: usize,
+    ) -> DocumentArray<T> {
+        let offsets = Self::offsets(&documents, num_documents);
+
+        let mut block_minima: Vec<Vec<T>> = vec![previous
+            .chunks(BLOCK_LEN)
+            .enumerate()
+            .map(|(block, values)| {
+                let start = block * BLOCK_LEN;
+                T::from_usize(start + argmin(values))
+            })
+            .collect()];
+        let mut width = 1;
+        while 2 * width <= block_minima[0].len() {
+            let level = block_minima.last().unwrap();
+            let next: Vec<T> = (0..level.len() - width)
+                .map(|i| lesser(&previous, level[i], level[i + width]))
+                .collect();
+            block_minima.push(next);
+            width *= 2;
+        }
+
+        DocumentArray {
+            documents,
+            previous,
+            ranks,
+            offsets,
+            block_minima,
+        }
+    }
+
+    // --------------------------------------------------
+    /// The start of each document's ranks when grouped by document
+    ///
+    /// Args:
+    /// * `documents`: the document of the suffix at each rank
+    /// * `num_documents`: the number of documents
+    fn offsets(documents: &[T], num_documents: usize) -> Vec<usize> {
+        let mut offsets = vec![0; num_documents + 1];
+        for doc in documents {
+            offsets[doc.to_usize() + 1] += 1;
+        }
+        for i in 1..offsets.len() {
+            offsets[i] += offsets[i - 1];
+        }
+        offsets
+    }
+
+    // --------------------------------------------------
+    /// The rank of the least `previous` in a nonempty range of ranks
+    ///
+    /// Args:
+    /// * `ranks`: the range of ranks
+    fn range_minimum(&self, ranks: Range<usize>) -> usize {
+        let first_block = ranks.start.div_ceil(BLOCK_LEN);
+        let last_block = ranks.end / BLOCK_LEN;
+        if first_block >= last_block {
+            return ranks.start + argmin(&self.previous[ranks]);
+        }
+
+        // Scan the partial blocks at either end
+        let mut best: Option<T> = None;
+        let mut consider = |rank: T| {
+            best = Some(match best {
+                Some(prev) => lesser(&self.previous, prev, rank),
+                _ => rank,
+            })
+        };
+        let head = ranks.start..first_block * BLOCK_LEN;
+        if !head.is_empty() {
+            consider(T::from_usize(head.start + argmin(&self.previous[head])));
+        }
+
+        let num_blocks = last_block - first_block;
+        let level = num_blocks.ilog2() as usize;
+        let minima = &self.block_minima[level];
+        consider(minima[first_block]);
+        consider(minima[last_block - (1 << level)]);
+
+        let tail = last_block * BLOCK_LEN..ranks.end;
+        if !tail.is_empty() {
+            consider(T::from_usize(tail.start + argmin(&self.previous[tail])));
+        }
+        best.unwrap().to_usize()
+    }
+
+    // --------------------------------------------------
+    /// The distinct documents in a range of ranks with the number of
+    /// their suffixes in the range, ordered by document
+    ///
+    /// Args:
+    /// * `ranks`: the range of ranks
+    pub fn list(&self, ranks: Range<usize>) -> Vec<(usize, usize)> {
+        // A document is reported at its first rank in the range, whose
+        // previous rank falls before the range
+        let mut found = vec![];
+        let mut pending = vec![ranks.clone()];
+        while let Some(range) = pending.pop() {
+            if range.is_empty() {
+                continue;
+            }
+            let rank = self.range_minimum(range.clone());
+            if self.previous[rank].to_usize() > ranks.start {
+                continue;
+            }
+            found.push(self.documents[rank].to_usize());
+            pending.push(range.start..rank);
+            pending.push(rank + 1..range.end);
+        }
+        found.sort_unstable();
+
+        found
+            .into_iter()
+            .map(|doc| {
+                let doc_ranks = &self.ranks[self.offsets[doc]..self.offsets[doc + 1]];
+                let first = doc_ranks.partition_point(|r| r.to_usize() < ranks.start);
+                let last = doc_ranks.partition_point(|r| r.to_usize() < ranks.end);
+                (doc, last - first)
+            })
+            .collect()
+    }
+}
+
+// --------------------------------------------------
+/// The offset of the first least value
+///
+/// Args:
+/// * `values`: a nonempty slice
+fn argmin<T: Int>(values: &[T]) -> usize {
+    values
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, val)| **val)
+        .map_or(0, |(i, _)| i)
+}
+
+// --------------------------------------------------
+/// Whichever rank has the lesser value, preferring the first on ties
+///
+/// Args:
+/// * `values`: the values of each rank
+/// * `rank1`: a rank
+/// * `rank2`: another rank
+fn lesser<T: Int>(values: &[T], rank1: T, rank2: T) -> T {
+    if values[rank2.to_usize()] < values[rank1.to_usize()] {
+        rank2
+    } else {
+        rank1
+    }
+}
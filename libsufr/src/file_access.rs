@@ -1,18 +1,46 @@
 //! # Low memory access to Sufr's on-disk arrays (text/SA/LCP)
 
-use anyhow::{bail, Result};
 use crate::{
+    types::{FromUsize, Int},
     util::slice_u8_to_vec,
-    types::{FromUsize, Int}
 };
+use anyhow::{bail, Result};
 use std::{
-    mem,
     cmp::min,
     fs::File,
-    io::{Read, Seek, SeekFrom},
+    io::{self, Cursor, Read, Seek, SeekFrom},
+    mem,
     ops::Range,
+    sync::Arc,
 };
 
+// --------------------------------------------------
+/// The bytes behind a `FileAccess`: either a _.sufr_ file on disk or
+/// the serialized bytes of a suffix array that lives only in memory
+#[derive(Debug)]
+enum Source {
+    File(File),
+    Memory(Cursor<Arc<[u8]>>),
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Source::File(file) => file.read(buf),
+            Source::Memory(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl Seek for Source {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Source::File(file) => file.seek(pos),
+            Source::Memory(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
 // --------------------------------------------------
 /// Struct to mediate file access to on-disk arrays of text, suffix/LCP arrays
 #[derive(Debug)]
@@ -20,8 +48,8 @@ pub struct FileAccess<T>
 where
     T: Int + FromUsize<T> + Sized + Send + Sync + serde::ser::Serialize,
 {
-    /// A read-only filehandle to the _.sufr_ file 
-    file: File,
+    /// A read-only filehandle to the _.sufr_ file or its in-memory bytes
+    file: Source,
 
     /// Internal buffer for reading a portion of the file
     buffer: Vec<T>,
@@ -38,7 +66,7 @@ where
     /// The starting byte position of the structure being read (text/SA/LCP)
     start_position: u64,
 
-    /// The current position after reading a portion of the structure 
+    /// The current position after reading a portion of the structure
     /// from disk and into the `buffer`
     current_position: u64,
 
@@ -53,7 +81,7 @@ impl<T> FileAccess<T>
 where
     T: Int + FromUsize<T> + Sized + Send + Sync + serde::ser::Serialize,
 {
-    /// Create a read-only file access to a portion of a _.sufr_ file 
+    /// Create a read-only file access to a portion of a _.sufr_ file
     /// representing the text, suffix array, or LCP array.
     /// This struct must be initialized using an `Int` of `u8` for the `text`
     /// or `u32`/`u64` for the SA/LCP.
@@ -65,8 +93,23 @@ where
     /// * `num_elements`: the length of the text/SA/LCP
     pub fn new(filename: &str, start: u64, num_elements: usize) -> Result<Self> {
         let file = File::open(filename)?;
+        Ok(Self::from_source(Source::File(file), start, num_elements))
+    }
+
+    /// Create a read-only access to a portion of the serialized bytes
+    /// of a suffix array held in memory, laid out as in a _.sufr_ file.
+    ///
+    /// Args:
+    /// * `bytes`: the serialized suffix array
+    /// * `start`: the byte position of the array
+    /// * `num_elements`: the length of the text/SA/LCP
+    pub fn from_bytes(bytes: Arc<[u8]>, start: u64, num_elements: usize) -> Self {
+        Self::from_source(Source::Memory(Cursor::new(bytes)), start, num_elements)
+    }
+
+    fn from_source(file: Source, start: u64, num_elements: usize) -> Self {
         let size = num_elements * mem::size_of::<T>();
-        Ok(FileAccess {
+        FileAccess {
             file,
             buffer: vec![],
             buffer_size: 2usize.pow(30),
@@ -76,7 +119,7 @@ where
             current_position: start,
             end_position: start + size as u64,
             exhausted: false,
-        })
+        }
     }
 
     /// Reset the buffer to start reading from the beginning.
@@ -88,7 +131,7 @@ where
     }
 
     /// Create a `FileAccessIter` iterator.
-    pub fn iter(&mut self) -> FileAccessIter<T> {
+    pub fn iter(&mut self) -> FileAccessIter<'_, T> {
         FileAccessIter { file_access: self }
     }
 
@@ -9,6 +9,8 @@
 //! [^patro]: <https://doi.org/10.4230/LIPIcs.WABI.2023.16>
 //!
 //! * Most people should use [suffix_array] to create and interact with suffix arrays
+//! * Use [sharded_suffix_array] to split very large texts into several
+//!   smaller suffix arrays
 //!
 //! If you want lower-level access to Sufr's internals:
 //!
@@ -21,7 +23,10 @@
 //! * Jack Roddy <jroddy@arizona.edu>
 //! * Travis Wheeler <twheeler@arizona.edu>
 
+mod child_table;
+mod document_array;
 mod file_access;
+pub mod sharded_suffix_array;
 pub mod suffix_array;
 pub mod sufr_builder;
 pub mod sufr_file;
@@ -33,14 +38,87 @@ pub mod util;
 #[cfg(test)]
 mod tests {
     use super::{
+        child_table::ChildTable,
+        document_array::DocumentArray,
+        sharded_suffix_array::ShardedSuffixArray,
+        suffix_array::SuffixArray,
         sufr_builder::SufrBuilder,
         sufr_file::SufrFile,
-        types::{SufrBuilderArgs, OUTFILE_VERSION},
+        sufr_search::LcpLr,
+        types::{
+            Alphabet, AppendOptions, BisectOptions, BuildSummary, CommonOptions,
+            CommonSubstring, ComplexityOptions, CountOptions, ExtractOptions,
+            FillerRuns, LocateOptions, MapPeptidesOptions, MatchingStatisticsOptions,
+            MatchingStatisticsResult, MemsOptions, PeptideClass, RepeatsOptions,
+            ResidueMap, SearchOptions, SeedMask, Strand, SuffixStartFilter,
+            SufrBuilderArgs, OUTFILE_VERSION,
+        },
         util::read_sequence_file,
     };
     use anyhow::Result;
-    use std::path::Path;
-    use tempfile::NamedTempFile;
+    use std::{
+        collections::{HashMap, HashSet},
+        fs,
+        hash::{DefaultHasher, Hash, Hasher},
+        ops::Range,
+        path::Path,
+    };
+    use tempfile::{NamedTempFile, TempDir};
+
+    /// The arguments to build a DNA suffix array of a text, which each
+    /// test overrides as needed
+    fn test_args(
+        text: Vec<u8>,
+        sequence_starts: Vec<usize>,
+        sequence_names: Vec<String>,
+    ) -> SufrBuilderArgs {
+        SufrBuilderArgs {
+            text,
+            path: None,
+            low_memory: true,
+            max_query_len: None,
+            is_dna: true,
+            allow_ambiguity: false,
+            ignore_softmask: false,
+            sequence_starts,
+            sequence_names,
+            num_partitions: 4,
+            seed_masks: vec![],
+            random_seed: 42,
+            both_strands: false,
+            alphabet: None,
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
+        }
+    }
+
+    /// Pieces of a text to use as queries, each starting somewhere
+    /// different and with some positions changed to another residue,
+    /// leaving out any that span a sequence delimiter
+    ///
+    /// Args:
+    /// * `text`: the text
+    /// * `is_dna`: whether to change positions to _A_ instead of _W_
+    /// * `pieces`: the length and the positions to change of each piece
+    fn sample_queries(
+        text: &[u8],
+        is_dna: bool,
+        pieces: impl IntoIterator<Item = (usize, Vec<usize>)>,
+    ) -> Result<Vec<String>> {
+        let mut queries = vec![];
+        for (i, (len, changes)) in pieces.into_iter().enumerate() {
+            let start = (i * 7919) % (text.len() - len);
+            let mut query = text[start..start + len].to_vec();
+            for pos in changes {
+                query[pos] = if is_dna { b'A' } else { b'W' };
+            }
+            if !query.contains(&b'%') && !query.contains(&b'$') {
+                queries.push(String::from_utf8(query)?);
+            }
+        }
+        Ok(queries)
+    }
 
     #[test]
     fn test_write_read_suffix_file_32() -> Result<()> {
@@ -60,8 +138,13 @@ mod tests {
             sequence_starts: seq_data.start_positions.into_iter().collect(),
             sequence_names: seq_data.sequence_names,
             num_partitions: 2,
-            seed_mask: None,
+            seed_masks: vec![],
             random_seed: 0,
+            both_strands: false,
+            alphabet: None,
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
         };
         let res = SufrBuilder::<u32>::new(args);
         assert!(res.is_ok());
@@ -109,8 +192,13 @@ mod tests {
             sequence_starts: seq_data.start_positions.into_iter().collect(),
             sequence_names: seq_data.sequence_names,
             num_partitions: 2,
-            seed_mask: None,
+            seed_masks: vec![],
             random_seed: 0,
+            both_strands: false,
+            alphabet: None,
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
         };
 
         let res = SufrBuilder::<u64>::new(args);
@@ -157,8 +245,13 @@ mod tests {
             sequence_starts: seq_data.start_positions,
             sequence_names: seq_data.sequence_names,
             num_partitions: 2,
-            seed_mask: None,
+            seed_masks: vec![],
             random_seed: 0,
+            both_strands: false,
+            alphabet: None,
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
         };
         let res = SufrBuilder::<u32>::new(builder_args);
         assert!(res.is_ok());
@@ -236,8 +329,13 @@ mod tests {
             sequence_starts: seq_data.start_positions,
             sequence_names: seq_data.sequence_names,
             num_partitions: 1,
-            seed_mask: Some("101".to_string()),
+            seed_masks: vec!["101".to_string()],
             random_seed: 0,
+            both_strands: false,
+            alphabet: None,
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
         };
 
         // 7 $
@@ -281,8 +379,13 @@ mod tests {
             sequence_starts: seq_data.start_positions,
             sequence_names: seq_data.sequence_names,
             num_partitions: 1,
-            seed_mask: Some("11011".to_string()),
+            seed_masks: vec!["11011".to_string()],
             random_seed: 0,
+            both_strands: false,
+            alphabet: None,
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
         };
 
         //  0 16 $
@@ -339,8 +442,13 @@ mod tests {
             sequence_starts: seq_data.start_positions,
             sequence_names: seq_data.sequence_names,
             num_partitions: 1,
-            seed_mask: Some("11000111".to_string()),
+            seed_masks: vec!["11000111".to_string()],
             random_seed: 0,
+            both_strands: false,
+            alphabet: None,
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
         };
 
         let res = SufrBuilder::<u32>::new(builder_args);
@@ -363,4 +471,2358 @@ mod tests {
 
         Ok(())
     }
+
+    // --------------------------------------------------
+    #[test]
+    fn test_multiple_seed_masks() -> Result<()> {
+        let seq_file = Path::new("../data/inputs/3.fa");
+        let build = |seed_masks: &[&str]| -> Result<NamedTempFile> {
+            let seq_data = read_sequence_file(seq_file, b'%')?;
+            let outfile = NamedTempFile::new()?;
+            let builder_args = SufrBuilderArgs {
+                path: Some(outfile.path().to_string_lossy().to_string()),
+                num_partitions: 3,
+                seed_masks: seed_masks.iter().map(|m| m.to_string()).collect(),
+                random_seed: 0,
+                ..test_args(
+                    seq_data.seq,
+                    seq_data.start_positions,
+                    seq_data.sequence_names,
+                )
+            };
+            SufrBuilder::<u32>::new(builder_args)?;
+            Ok(outfile)
+        };
+        let read = |file: &NamedTempFile| -> Result<SufrFile<u32>> {
+            SufrFile::read(&file.path().to_string_lossy(), false)
+        };
+
+        assert!(build(&["101", "101"]).is_err());
+
+        let multi_file = build(&["101", "11011"])?;
+        let mut multi = read(&multi_file)?;
+        assert_eq!(multi.masked_indexes.len(), 2);
+        assert_eq!(
+            multi.metadata()?.seed_masks,
+            [SeedMask::new("101")?, SeedMask::new("11011")?]
+        );
+
+        // Each mask has the same arrays as a single-mask build
+        let single_files = [build(&["101"])?, build(&["11011"])?];
+        let mut singles = [read(&single_files[0])?, read(&single_files[1])?];
+        for (mask_num, single) in singles.iter_mut().enumerate() {
+            multi.use_mask(mask_num)?;
+            assert_eq!(multi.sort_type, single.sort_type);
+            assert_eq!(
+                multi.suffix_array_file.iter().collect::<Vec<_>>(),
+                single.suffix_array_file.iter().collect::<Vec<_>>()
+            );
+            assert_eq!(
+                multi.lcp_file.iter().collect::<Vec<_>>(),
+                single.lcp_file.iter().collect::<Vec<_>>()
+            );
+        }
+        multi.use_mask(0)?;
+
+        // Searches return the union of the hits from each mask
+        let queries = vec!["TGA".to_string(), "GAT".to_string()];
+        let positions = |sufr: &mut SufrFile<u32>| -> Result<Vec<HashSet<_>>> {
+            let opts = LocateOptions {
+                queries: queries.clone(),
+                max_query_len: None,
+                low_memory: true,
+            };
+            Ok(sufr
+                .locate(opts)?
+                .into_iter()
+                .map(|res| {
+                    res.positions
+                        .into_iter()
+                        .map(|p| (p.sequence_name, p.sequence_position))
+                        .collect()
+                })
+                .collect())
+        };
+        let multi_hits = positions(&mut multi)?;
+        let hits0 = positions(&mut singles[0])?;
+        let hits1 = positions(&mut singles[1])?;
+        for (query_num, hits) in multi_hits.iter().enumerate() {
+            let union: HashSet<_> =
+                hits0[query_num].union(&hits1[query_num]).cloned().collect();
+            assert_eq!(hits, &union);
+            assert!(hits.len() > hits0[query_num].len().max(hits1[query_num].len()));
+        }
+
+        // Each rank is in the suffix array of the mask that found the hit
+        let mask_suffixes: Vec<Vec<u32>> = (0..2)
+            .map(|mask_num| {
+                multi.use_mask(mask_num)?;
+                Ok(multi.suffix_array_file.iter().collect())
+            })
+            .collect::<Result<_>>()?;
+        multi.use_mask(0)?;
+        let located = multi.locate(LocateOptions {
+            queries: queries.clone(),
+            max_query_len: None,
+            low_memory: true,
+        })?;
+        let mut masks_used = HashSet::new();
+        for pos in located.iter().flat_map(|res| &res.positions) {
+            assert_eq!(mask_suffixes[pos.mask_num][pos.rank] as usize, pos.suffix);
+            masks_used.insert(pos.mask_num);
+        }
+        assert_eq!(masks_used.len(), 2);
+
+        let count_opts = CountOptions {
+            queries: queries.clone(),
+            max_query_len: None,
+            low_memory: false,
+        };
+        let counts: Vec<_> = multi.count(count_opts)?.iter().map(|c| c.count).collect();
+        assert_eq!(
+            counts,
+            multi_hits.iter().map(|h| h.len()).collect::<Vec<_>>()
+        );
+
+        // Extract finds the hits of a query matching only the second mask
+        let multi_file = build(&["11110111", "11111011"])?;
+        let mut multi = read(&multi_file)?;
+        let query = "AGCTTGTC".to_string();
+        let search_opts = SearchOptions {
+            queries: vec![query.clone()],
+            max_query_len: None,
+            low_memory: true,
+            find_suffixes: true,
+        };
+        assert!(multi.suffix_search(&search_opts)?[0].locations.is_none());
+
+        let extract_opts = ExtractOptions {
+            queries: vec![query],
+            max_query_len: None,
+            low_memory: true,
+            prefix_len: None,
+            suffix_len: Some(8),
+        };
+        let res = multi.extract(extract_opts)?;
+        let hits: Vec<_> = res[0]
+            .sequences
+            .iter()
+            .map(|seq| (seq.sequence_name.as_str(), seq.sequence_range.clone()))
+            .collect();
+        assert_eq!(hits, [("1", 0..8)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_both_strands() -> Result<()> {
+        let outfile = NamedTempFile::new()?;
+        let outpath = outfile.path().to_string_lossy().to_string();
+        let args = SufrBuilderArgs {
+            path: Some(outpath.clone()),
+            num_partitions: 2,
+            random_seed: 0,
+            both_strands: true,
+            ..test_args(
+                b"AACCG%ttg$".to_vec(),
+                vec![0, 6],
+                vec!["a".to_string(), "b".to_string()],
+            )
+        };
+        let builder = SufrBuilder::<u32>::new(args)?;
+        assert!(builder.both_strands);
+
+        let mut sufr_file: SufrFile<u32> = SufrFile::read(&outpath, false)?;
+        assert!(sufr_file.both_strands);
+        assert_eq!(sufr_file.text, b"AACCG%TTG%CGGTT%CAA$");
+        assert_eq!(sufr_file.num_sequences, 4);
+        assert_eq!(sufr_file.sequence_starts, [0, 6, 10, 16]);
+        assert_eq!(sufr_file.sequence_names, ["a", "b"]);
+
+        let opts = LocateOptions {
+            queries: vec!["GGT".to_string(), "CAA".to_string(), "TTG".to_string()],
+            max_query_len: None,
+            low_memory: false,
+        };
+        let res = sufr_file.locate(opts)?;
+        let hits: Vec<Vec<_>> = res
+            .iter()
+            .map(|r| {
+                r.positions
+                    .iter()
+                    .map(|p| (p.sequence_name.as_str(), p.sequence_position, p.strand))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(hits[0], [("a", 1, Strand::Reverse)]);
+        assert_eq!(hits[1], [("b", 0, Strand::Reverse)]);
+        assert_eq!(hits[2], [("b", 0, Strand::Forward)]);
+
+        let opts = ExtractOptions {
+            queries: vec!["GGT".to_string()],
+            max_query_len: None,
+            low_memory: false,
+            prefix_len: Some(1),
+            suffix_len: None,
+        };
+        let res = sufr_file.extract(opts)?;
+        let seq = &res[0].sequences[0];
+        assert_eq!(seq.sequence_name, "a");
+        assert_eq!(seq.strand, Strand::Reverse);
+        assert_eq!(seq.sequence_start, 0);
+        assert_eq!(seq.sequence_range, 0..5);
+        assert_eq!(seq.suffix_offset, 1);
+
+        // Reverse complements are only defined for nucleotides
+        let args = SufrBuilderArgs {
+            path: Some(outpath),
+            is_dna: false,
+            num_partitions: 2,
+            random_seed: 0,
+            both_strands: true,
+            ..test_args(b"AACCG$".to_vec(), vec![0], vec!["a".to_string()])
+        };
+        assert!(SufrBuilder::<u32>::new(args).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_residue_map() -> Result<()> {
+        let outfile = NamedTempFile::new()?;
+        let outpath = outfile.path().to_string_lossy().to_string();
+        let args = SufrBuilderArgs {
+            path: Some(outpath.clone()),
+            is_dna: false,
+            num_partitions: 2,
+            random_seed: 0,
+            alphabet: Some(Alphabet::Protein),
+            residue_map: Some(ResidueMap::new("murphy-10")?),
+            ..test_args(
+                b"MKLVSTIR%AKIVQ$".to_vec(),
+                vec![0, 9],
+                vec!["a".to_string(), "b".to_string()],
+            )
+        };
+        let builder = SufrBuilder::<u32>::new(args.clone())?;
+        assert_eq!(builder.text, b"LKLLSSLK%AKLLE$");
+
+        // The original residues are stored
+        let mut sufr_file: SufrFile<u32> = SufrFile::read(&outpath, false)?;
+        assert_eq!(sufr_file.text, b"MKLVSTIR%AKIVQ$");
+        assert_eq!(sufr_file.residue_map, builder.residue_map);
+
+        // Queries are found in the reduced space
+        for low_memory in [true, false] {
+            let opts = LocateOptions {
+                queries: vec!["KIV".to_string(), "TS".to_string(), "W".to_string()],
+                max_query_len: None,
+                low_memory,
+            };
+            let res = sufr_file.locate(opts)?;
+            let hits: Vec<Vec<_>> = res
+                .iter()
+                .map(|r| {
+                    let mut hits: Vec<_> = r
+                        .positions
+                        .iter()
+                        .map(|p| (p.sequence_name.as_str(), p.sequence_position))
+                        .collect();
+                    hits.sort();
+                    hits
+                })
+                .collect();
+            assert_eq!(hits[0], [("a", 1), ("b", 1)]);
+            assert_eq!(hits[1], [("a", 4)]);
+            assert!(hits[2].is_empty());
+        }
+
+        // The groups cannot merge residues with the sequence delimiter
+        let args = SufrBuilderArgs {
+            text: b"MKLVSTIRXAKIVQ$".to_vec(),
+            residue_map: Some(ResidueMap::new("groups:kr,xb")?),
+            ..args
+        };
+        let res = SufrBuilder::<u32>::new(args);
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Residue map 'groups:kr,xb' cannot use the sequence delimiter"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_suffix_start_filter() -> Result<()> {
+        let outfile = NamedTempFile::new()?;
+        let outpath = outfile.path().to_string_lossy().to_string();
+        let args = SufrBuilderArgs {
+            path: Some(outpath.clone()),
+            is_dna: false,
+            num_partitions: 2,
+            random_seed: 0,
+            alphabet: Some(Alphabet::Protein),
+            suffix_start_filter: Some(SuffixStartFilter::new("trypsin")?),
+            ..test_args(
+                b"MKLVSTIRPAKR%AKIVQ$".to_vec(),
+                vec![0, 13],
+                vec!["a".to_string(), "b".to_string()],
+            )
+        };
+        SufrBuilder::<u32>::new(args)?;
+
+        // Only the protein N-termini, cleavage sites, and sentinel are indexed
+        let mut sufr_file: SufrFile<u32> = SufrFile::read(&outpath, false)?;
+        assert_eq!(
+            sufr_file.suffix_start_filter,
+            Some(SuffixStartFilter::new("trypsin")?)
+        );
+        assert_eq!(sufr_file.len_suffixes, 6);
+        let mut suffixes: Vec<_> = sufr_file.suffix_array_file.iter().collect();
+        suffixes.sort();
+        assert_eq!(suffixes, [0, 2, 11, 13, 15, 18]);
+
+        let opts = LocateOptions {
+            queries: vec!["LV".to_string(), "PAK".to_string(), "IVQ".to_string()],
+            max_query_len: None,
+            low_memory: false,
+        };
+        let res = sufr_file.locate(opts)?;
+        let hits: Vec<Vec<_>> = res
+            .iter()
+            .map(|r| {
+                r.positions
+                    .iter()
+                    .map(|p| (p.sequence_name.as_str(), p.sequence_position))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(hits[0], [("a", 2)]);
+        assert!(hits[1].is_empty());
+        assert_eq!(hits[2], [("b", 2)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_semi_specific_digest() -> Result<()> {
+        let outfile = NamedTempFile::new()?;
+        let outpath = outfile.path().to_string_lossy().to_string();
+        let args = SufrBuilderArgs {
+            path: Some(outpath.clone()),
+            is_dna: false,
+            num_partitions: 2,
+            random_seed: 0,
+            alphabet: Some(Alphabet::Protein),
+            suffix_start_filter: Some(SuffixStartFilter::new("semi:trypsin")?),
+            ..test_args(
+                b"MKLVSTIRPAKR%AKIVQ$".to_vec(),
+                vec![0, 13],
+                vec!["a".to_string(), "b".to_string()],
+            )
+        };
+        SufrBuilder::<u32>::new(args)?;
+
+        // Every suffix but the one at the delimiter is indexed
+        let mut sufr_file: SufrFile<u32> = SufrFile::read(&outpath, true)?;
+        assert_eq!(sufr_file.len_suffixes, 18);
+
+        // A hit needs a cleavage site or a sequence boundary at either end
+        let queries = vec![
+            "LV".to_string(),
+            "PAK".to_string(),
+            "TI".to_string(),
+            "IV".to_string(),
+            "K".to_string(),
+        ];
+        let opts = LocateOptions {
+            queries: queries.clone(),
+            max_query_len: None,
+            low_memory: true,
+        };
+        let res = sufr_file.locate(opts)?;
+        let hits: Vec<Vec<_>> = res
+            .iter()
+            .map(|r| {
+                r.positions
+                    .iter()
+                    .map(|p| (p.sequence_name.as_str(), p.sequence_position))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(hits[0], [("a", 2)]);
+        assert_eq!(hits[1], [("a", 8)]);
+        assert!(hits[2].is_empty());
+        assert_eq!(hits[3], [("b", 2)]);
+        assert_eq!(hits[4].len(), 3);
+
+        let counts: Vec<_> = sufr_file
+            .count(CountOptions {
+                queries,
+                max_query_len: None,
+                low_memory: true,
+            })?
+            .into_iter()
+            .map(|res| res.count)
+            .collect();
+        assert_eq!(counts, [1, 1, 0, 1, 3]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_filler_runs() -> Result<()> {
+        let text = b"MXXXXXXXXKLXXXXXXXXPQAAAAAAAXXXXXXXXXXXX%XXXXXXXXKAAAAAAAAL$";
+        type Arrays = (usize, Vec<u32>, Vec<u32>);
+        let build = |filler_runs: Option<FillerRuns>,
+                     max_query_len: Option<usize>|
+         -> Result<Arrays> {
+            let outfile = NamedTempFile::new()?;
+            let outpath = outfile.path().to_string_lossy().to_string();
+            let args = SufrBuilderArgs {
+                path: Some(outpath.clone()),
+                is_dna: false,
+                num_partitions: 3,
+                random_seed: 0,
+                filler_runs,
+                max_query_len,
+                ..test_args(
+                    text.to_vec(),
+                    vec![0, 41],
+                    vec!["a".to_string(), "b".to_string()],
+                )
+            };
+            let builder = SufrBuilder::<u32>::new(args)?;
+            let num_runs = builder.filler_ranges.len();
+            let mut sufr_file: SufrFile<u32> = SufrFile::read(&outpath, false)?;
+            Ok((
+                num_runs,
+                sufr_file.suffix_array_file.iter().collect(),
+                sufr_file.lcp_file.iter().collect(),
+            ))
+        };
+
+        // Skipping over the runs of Xs or homopolymers gives the same arrays
+        let (num_runs, sa, lcp) = build(None, None)?;
+        assert_eq!(num_runs, 0);
+        assert_eq!(
+            build(Some(FillerRuns::new("X", 4)?), None)?,
+            (4, sa.clone(), lcp.clone())
+        );
+        assert_eq!(build(Some(FillerRuns::new("*", 4)?), None)?, (6, sa, lcp));
+
+        // The LCPs stay within the maximum query length inside the runs
+        let (_, sa, lcp) = build(None, Some(3))?;
+        assert!(lcp.iter().all(|&len| len <= 3));
+        assert_eq!(
+            build(Some(FillerRuns::new("*", 4)?), Some(3))?,
+            (6, sa, lcp)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_in_memory() -> Result<()> {
+        let seq_file = Path::new("../data/inputs/3.fa");
+        let args = |path: Option<String>| -> Result<SufrBuilderArgs> {
+            let seq_data = read_sequence_file(seq_file, b'%')?;
+            Ok(SufrBuilderArgs {
+                path,
+                num_partitions: 3,
+                random_seed: 0,
+                ..test_args(
+                    seq_data.seq,
+                    seq_data.start_positions,
+                    seq_data.sequence_names,
+                )
+            })
+        };
+
+        // The in-memory build serializes the same bytes as the file
+        let outfile = NamedTempFile::new()?;
+        let outpath = outfile.path().to_string_lossy().to_string();
+        let builder = SufrBuilder::<u32>::new(args(Some(outpath.clone()))?)?;
+        assert!(builder.to_bytes().is_err());
+        let builder = SufrBuilder::<u32>::in_memory(args(None)?)?;
+        let bytes = builder.to_bytes()?;
+        assert_eq!(bytes, fs::read(&outpath)?);
+
+        let mut on_disk: SufrFile<u32> = SufrFile::read(&outpath, false)?;
+        let mut in_memory: SufrFile<u32> = SufrFile::from_bytes(bytes.clone())?;
+        assert!(in_memory.filename.is_empty());
+        assert_eq!(in_memory.metadata()?.file_size, bytes.len());
+
+        // Queries give the same results, including with a subsampled
+        // suffix array that is not cached to disk
+        let queries = vec!["TGA".to_string(), "GAT".to_string(), "X".to_string()];
+        for (max_query_len, low_memory) in
+            [(None, true), (None, false), (Some(2), false)]
+        {
+            let count = |sufr: &mut SufrFile<u32>| {
+                sufr.count(CountOptions {
+                    queries: queries.clone(),
+                    max_query_len,
+                    low_memory,
+                })
+            };
+            assert_eq!(count(&mut in_memory)?, count(&mut on_disk)?);
+
+            let locate = |sufr: &mut SufrFile<u32>| {
+                sufr.locate(LocateOptions {
+                    queries: queries.clone(),
+                    max_query_len,
+                    low_memory,
+                })
+            };
+            assert_eq!(locate(&mut in_memory)?, locate(&mut on_disk)?);
+
+            let extract = |sufr: &mut SufrFile<u32>| {
+                sufr.extract(ExtractOptions {
+                    queries: queries.clone(),
+                    max_query_len,
+                    low_memory,
+                    prefix_len: Some(1),
+                    suffix_len: Some(3),
+                })
+            };
+            assert_eq!(extract(&mut in_memory)?, extract(&mut on_disk)?);
+
+            let bisect = |sufr: &mut SufrFile<u32>| {
+                sufr.bisect(BisectOptions {
+                    queries: vec!['T', 'G'],
+                    max_query_len,
+                    low_memory,
+                    prefix_result: None,
+                })
+            };
+            assert_eq!(bisect(&mut in_memory)?, bisect(&mut on_disk)?);
+        }
+
+        // Saving writes a readable copy of the same file
+        let savefile = NamedTempFile::new()?;
+        let savepath = savefile.path().to_string_lossy().to_string();
+        in_memory.save(&savepath)?;
+        assert_eq!(fs::read(&savepath)?, bytes);
+        let saved: SufrFile<u32> = SufrFile::read(&savepath, false)?;
+        assert_eq!(saved.len_suffixes, in_memory.len_suffixes);
+
+        Ok(())
+    }
+
+    // The bytes of a _.sufr_ file before the build summary at the end,
+    // which differs between files built in different ways
+    fn without_build_summary(mut bytes: Vec<u8>) -> Result<Vec<u8>> {
+        let len = bincode::serialized_size(&BuildSummary::default())? as usize;
+        bytes.truncate(bytes.len() - len);
+        Ok(bytes)
+    }
+
+    #[test]
+    fn test_append() -> Result<()> {
+        let seq_data = read_sequence_file(Path::new("../data/inputs/3.fa"), b'%')?;
+        let build = |text: &[u8],
+                     starts: &[usize],
+                     names: &[String],
+                     seed_masks: &[&str],
+                     max_query_len: Option<usize>|
+         -> Result<Vec<u8>> {
+            let args = SufrBuilderArgs {
+                max_query_len,
+                allow_ambiguity: true,
+                num_partitions: 3,
+                seed_masks: seed_masks.iter().map(|m| m.to_string()).collect(),
+                random_seed: 0,
+                ..test_args(text.to_vec(), starts.to_vec(), names.to_vec())
+            };
+            SufrBuilder::<u32>::in_memory(args)?.to_bytes()
+        };
+        type Arrays = Vec<(Vec<u32>, Vec<u32>)>;
+        let arrays = |sufr: &mut SufrFile<u32>, max_lcp: u32| -> Result<Arrays> {
+            let mut arrays = vec![];
+            for mask_num in 0..sufr.masked_indexes.len().max(1) {
+                if mask_num > 0 {
+                    sufr.use_mask(mask_num)?;
+                }
+                let mut suffixes: Vec<u32> = sufr.suffix_array_file.iter().collect();
+                // Suffixes that tie under a maximum query length or mask
+                // may be in either order, and LCPs past the maximum
+                // query length are not meaningful
+                suffixes.sort();
+                let lcp: Vec<u32> =
+                    sufr.lcp_file.iter().map(|lcp| lcp.min(max_lcp)).collect();
+                arrays.push((suffixes, lcp));
+            }
+            Ok(arrays)
+        };
+
+        // A final sequence that repeats the start of an earlier one
+        // moves old suffixes that ended at the old sentinel
+        let mut text = seq_data.seq.clone();
+        text.pop();
+        text.extend(b"%AGCTTTT$");
+        let mut starts = seq_data.start_positions.clone();
+        starts.push(seq_data.seq.len());
+        let mut names = seq_data.sequence_names.clone();
+        names.push("4".to_string());
+
+        let queries: Vec<String> = ["A", "GA", "TTT", "AGCTT", "CTGAACTGG"]
+            .iter()
+            .map(|query| query.to_string())
+            .collect();
+        let configs: [(&[&str], Option<usize>); 4] = [
+            (&[], None),
+            (&[], Some(3)),
+            (&["101", "11011"], None),
+            (&["1101"], Some(5)),
+        ];
+        for (seed_masks, max_query_len) in configs {
+            let full = build(&text, &starts, &names, seed_masks, max_query_len)?;
+            for split in 1..starts.len() {
+                let mut old_text = text[..starts[split] - 1].to_vec();
+                old_text.push(b'$');
+                let old = build(
+                    &old_text,
+                    &starts[..split],
+                    &names[..split],
+                    seed_masks,
+                    max_query_len,
+                )?;
+
+                let mut appended: SufrFile<u32> = SufrFile::from_bytes(old)?;
+                appended.append(AppendOptions {
+                    text: text[starts[split]..].to_vec(),
+                    sequence_starts: starts[split..]
+                        .iter()
+                        .map(|start| start - starts[split])
+                        .collect(),
+                    sequence_names: names[split..].to_vec(),
+                })?;
+
+                if seed_masks.is_empty() && max_query_len.is_none() {
+                    let outfile = NamedTempFile::new()?;
+                    let outpath = outfile.path().to_string_lossy().to_string();
+                    appended.save(&outpath)?;
+                    assert_eq!(
+                        without_build_summary(fs::read(&outpath)?)?,
+                        without_build_summary(full.clone())?
+                    );
+                }
+                let mut rebuilt: SufrFile<u32> = SufrFile::from_bytes(full.clone())?;
+                assert_eq!(appended.sequence_starts, rebuilt.sequence_starts);
+                assert_eq!(appended.sequence_names, rebuilt.sequence_names);
+                if seed_masks.is_empty() {
+                    let max_lcp = max_query_len.map_or(u32::MAX, |len| len as u32);
+                    assert_eq!(
+                        arrays(&mut appended, max_lcp)?,
+                        arrays(&mut rebuilt, max_lcp)?
+                    );
+                }
+
+                // Every mask finds the same suffixes
+                for mask_num in 0..seed_masks.len().max(1) {
+                    if mask_num > 0 {
+                        appended.use_mask(mask_num)?;
+                        rebuilt.use_mask(mask_num)?;
+                    }
+                    let suffixes =
+                        |sufr: &mut SufrFile<u32>| -> Result<Vec<Vec<usize>>> {
+                            let opts = LocateOptions {
+                                queries: queries.clone(),
+                                max_query_len: None,
+                                low_memory: true,
+                            };
+                            Ok(sufr
+                                .locate(opts)?
+                                .into_iter()
+                                .map(|res| {
+                                    let mut found: Vec<_> = res
+                                        .positions
+                                        .iter()
+                                        .map(|pos| pos.suffix)
+                                        .collect();
+                                    found.sort();
+                                    found
+                                })
+                                .collect())
+                        };
+                    assert_eq!(suffixes(&mut appended)?, suffixes(&mut rebuilt)?);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // --------------------------------------------------
+    #[test]
+    fn test_remove_sequences() -> Result<()> {
+        let seq_data = read_sequence_file(Path::new("../data/inputs/3.fa"), b'%')?;
+        let build = |keep: &[usize], both_strands: bool| -> Result<Vec<u8>> {
+            let mut text = vec![];
+            let mut starts = vec![];
+            let mut names = vec![];
+            for &i in keep {
+                if !starts.is_empty() {
+                    text.push(b'%');
+                }
+                starts.push(text.len());
+                names.push(seq_data.sequence_names[i].clone());
+                let end = seq_data
+                    .start_positions
+                    .get(i + 1)
+                    .map_or(seq_data.seq.len(), |next| *next)
+                    - 1;
+                text.extend(&seq_data.seq[seq_data.start_positions[i]..end]);
+            }
+            text.push(b'$');
+            let args = SufrBuilderArgs {
+                num_partitions: 3,
+                random_seed: 0,
+                both_strands,
+                ..test_args(text, starts, names)
+            };
+            SufrBuilder::<u32>::in_memory(args)?.to_bytes()
+        };
+
+        type Found = Vec<Vec<(String, usize, Strand)>>;
+        let queries: Vec<String> = ["A", "TG", "GTC", "CTGAC", "XX"]
+            .iter()
+            .map(|query| query.to_string())
+            .collect();
+        let search = |sufr: &mut SufrFile<u32>| -> Result<(Vec<usize>, Found, Found)> {
+            let counts = sufr
+                .count(CountOptions {
+                    queries: queries.clone(),
+                    max_query_len: None,
+                    low_memory: true,
+                })?
+                .into_iter()
+                .map(|res| res.count)
+                .collect();
+            let mut located: Found = sufr
+                .locate(LocateOptions {
+                    queries: queries.clone(),
+                    max_query_len: None,
+                    low_memory: true,
+                })?
+                .into_iter()
+                .map(|res| {
+                    res.positions
+                        .into_iter()
+                        .map(|pos| {
+                            (pos.sequence_name, pos.sequence_position, pos.strand)
+                        })
+                        .collect()
+                })
+                .collect();
+            let mut extracted: Found = sufr
+                .extract(ExtractOptions {
+                    queries: queries.clone(),
+                    max_query_len: None,
+                    low_memory: true,
+                    prefix_len: None,
+                    suffix_len: Some(3),
+                })?
+                .into_iter()
+                .map(|res| {
+                    res.sequences
+                        .into_iter()
+                        .map(|seq| {
+                            (seq.sequence_name, seq.sequence_range.start, seq.strand)
+                        })
+                        .collect()
+                })
+                .collect();
+            for found in located.iter_mut().chain(extracted.iter_mut()) {
+                found.sort_by_key(|(name, pos, strand)| {
+                    (name.clone(), *pos, *strand == Strand::Reverse)
+                });
+            }
+            Ok((counts, located, extracted))
+        };
+
+        for both_strands in [false, true] {
+            let full = build(&[0, 1, 2], both_strands)?;
+            for (removed, keep) in [
+                (vec!["1"], vec![1, 2]),
+                (vec!["2"], vec![0, 2]),
+                (vec!["3", "1"], vec![1]),
+            ] {
+                let names: Vec<String> =
+                    removed.iter().map(|name| name.to_string()).collect();
+                let mut sufr: SufrFile<u32> = SufrFile::from_bytes(full.clone())?;
+                sufr.remove_sequences(&names)?;
+                let expected = build(&keep, both_strands)?;
+                let mut rebuilt: SufrFile<u32> =
+                    SufrFile::from_bytes(expected.clone())?;
+                assert_eq!(search(&mut sufr)?, search(&mut rebuilt)?);
+
+                // Tombstones survive being written and read back
+                let outfile = NamedTempFile::new()?;
+                let outpath = outfile.path().to_string_lossy().to_string();
+                sufr.save(&outpath)?;
+                let mut reread: SufrFile<u32> = SufrFile::read(&outpath, false)?;
+                assert_eq!(reread.tombstones, sufr.tombstones);
+                assert_eq!(search(&mut reread)?, search(&mut rebuilt)?);
+
+                // Compacting gives the same arrays as building without them
+                reread.compact(2)?;
+                assert!(reread.tombstones.is_empty());
+                assert_eq!(
+                    without_build_summary(fs::read(&outpath)?)?,
+                    without_build_summary(expected)?
+                );
+            }
+        }
+
+        let mut sufr: SufrFile<u32> = SufrFile::from_bytes(build(&[0], false)?)?;
+        assert!(sufr.remove_sequences(&["2".to_string()]).is_err());
+        sufr.remove_sequences(&["1".to_string()])?;
+        assert!(sufr.compact(1).is_err());
+
+        Ok(())
+    }
+
+    // --------------------------------------------------
+    #[test]
+    fn test_sharded() -> Result<()> {
+        let seq_data = read_sequence_file(Path::new("../data/inputs/3.fa"), b'%')?;
+        let dir = TempDir::new()?;
+        let builder_args = |path: String, both_strands: bool| SufrBuilderArgs {
+            path: Some(path),
+            num_partitions: 3,
+            random_seed: 0,
+            both_strands,
+            ..test_args(
+                seq_data.seq.clone(),
+                seq_data.start_positions.clone(),
+                seq_data.sequence_names.clone(),
+            )
+        };
+
+        type Found = Vec<Vec<(String, usize, Strand, usize)>>;
+        let queries: Vec<String> = ["A", "TG", "GTC", "CTGAC", "XX"]
+            .iter()
+            .map(|query| query.to_string())
+            .collect();
+        let search =
+            |suffix_array: &mut SuffixArray| -> Result<(Vec<usize>, Found, Found)> {
+                let counts = suffix_array
+                    .count(CountOptions {
+                        queries: queries.clone(),
+                        max_query_len: None,
+                        low_memory: true,
+                    })?
+                    .into_iter()
+                    .map(|res| res.count)
+                    .collect();
+                let mut located: Found = suffix_array
+                    .locate(LocateOptions {
+                        queries: queries.clone(),
+                        max_query_len: None,
+                        low_memory: true,
+                    })?
+                    .into_iter()
+                    .map(|res| {
+                        res.positions
+                            .into_iter()
+                            .map(|pos| {
+                                (
+                                    pos.sequence_name,
+                                    pos.sequence_position,
+                                    pos.strand,
+                                    pos.suffix,
+                                )
+                            })
+                            .collect()
+                    })
+                    .collect();
+                let mut extracted: Found = suffix_array
+                    .extract(ExtractOptions {
+                        queries: queries.clone(),
+                        max_query_len: None,
+                        low_memory: true,
+                        prefix_len: Some(2),
+                        suffix_len: Some(3),
+                    })?
+                    .into_iter()
+                    .map(|res| {
+                        res.sequences
+                            .into_iter()
+                            .map(|seq| {
+                                (
+                                    seq.sequence_name,
+                                    seq.sequence_range.start,
+                                    seq.strand,
+                                    seq.suffix_offset,
+                                )
+                            })
+                            .collect()
+                    })
+                    .collect();
+                for found in located.iter_mut().chain(extracted.iter_mut()) {
+                    found.sort_by_key(|(name, pos, strand, _)| {
+                        (name.clone(), *pos, *strand == Strand::Reverse)
+                    });
+                }
+                Ok((counts, located, extracted))
+            };
+
+        for both_strands in [false, true] {
+            let path = dir.path().join("3.sufr").to_string_lossy().to_string();
+            SuffixArray::write(builder_args(path.clone(), both_strands))?;
+            let mut unsharded = SuffixArray::read(&path, true)?;
+            let (counts, mut located, extracted) = search(&mut unsharded)?;
+            let documents = unsharded.list_documents(LocateOptions {
+                queries: queries.clone(),
+                max_query_len: None,
+                low_memory: true,
+            })?;
+            if both_strands {
+                // The reverse complements are placed differently
+                for found in located.iter_mut() {
+                    for (_, _, _, suffix) in found.iter_mut() {
+                        *suffix = 0;
+                    }
+                }
+            }
+
+            // One shard per sequence, two shards, and one shard
+            let strands = if both_strands { 2 } else { 1 };
+            for max_shard_len in [50 * strands, 80 * strands, 1000] {
+                let manifest =
+                    dir.path().join("3.shards").to_string_lossy().to_string();
+                let path = ShardedSuffixArray::write(
+                    builder_args(manifest.clone(), both_strands),
+                    max_shard_len,
+                )?;
+                assert_eq!(path, manifest);
+                let mut sharded = SuffixArray::read(&path, true)?;
+                let (sharded_counts, mut sharded_located, sharded_extracted) =
+                    search(&mut sharded)?;
+                if both_strands {
+                    for found in sharded_located.iter_mut() {
+                        for (_, _, _, suffix) in found.iter_mut() {
+                            *suffix = 0;
+                        }
+                    }
+                }
+                assert_eq!(sharded_counts, counts);
+                assert_eq!(sharded_located, located);
+                assert_eq!(sharded_extracted, extracted);
+                assert_eq!(
+                    sharded.list_documents(LocateOptions {
+                        queries: queries.clone(),
+                        max_query_len: None,
+                        low_memory: true,
+                    })?,
+                    documents
+                );
+
+                let meta = sharded.metadata()?;
+                assert_eq!(meta.sequence_names, seq_data.sequence_names);
+                if !both_strands {
+                    assert_eq!(meta.text_len, seq_data.seq.len());
+                    assert_eq!(meta.sequence_starts, seq_data.start_positions);
+                    assert_eq!(
+                        sharded.string_at(seq_data.start_positions[2], Some(5))?,
+                        "TGATA"
+                    );
+                }
+            }
+        }
+
+        // A sequence cannot be split across shards
+        let manifest = dir.path().join("3.shards").to_string_lossy().to_string();
+        assert!(ShardedSuffixArray::write(builder_args(manifest, false), 30).is_err());
+
+        Ok(())
+    }
+
+    // --------------------------------------------------
+    #[test]
+    fn test_deterministic() -> Result<()> {
+        let seq_data =
+            read_sequence_file(Path::new("../data/inputs/long_dna_sequence.fa"), b'%')?;
+        let configs: [(&[&str], Option<usize>); 4] = [
+            (&[], None),
+            (&[], Some(5)),
+            (&["1101"], None),
+            (&["101", "11011"], Some(9)),
+        ];
+        for (seed_masks, max_query_len) in configs {
+            let build = |in_memory: bool| -> Result<u64> {
+                let outfile = NamedTempFile::new()?;
+                let args = SufrBuilderArgs {
+                    path: Some(outfile.path().to_string_lossy().to_string()),
+                    max_query_len,
+                    num_partitions: 16,
+                    seed_masks: seed_masks.iter().map(|m| m.to_string()).collect(),
+                    ..test_args(
+                        seq_data.seq.clone(),
+                        seq_data.start_positions.clone(),
+                        seq_data.sequence_names.clone(),
+                    )
+                };
+                let bytes = if in_memory {
+                    SufrBuilder::<u32>::in_memory(args)?.to_bytes()?
+                } else {
+                    SufrBuilder::<u32>::new(args)?;
+                    fs::read(outfile.path())?
+                };
+                let mut hasher = DefaultHasher::new();
+                bytes.hash(&mut hasher);
+                Ok(hasher.finish())
+            };
+
+            let mut hashes = HashSet::new();
+            for num_threads in [1, 4, 8] {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(num_threads)
+                    .build()?;
+                for in_memory in [false, true] {
+                    hashes.insert(pool.install(|| build(in_memory))?);
+                }
+            }
+            assert_eq!(hashes.len(), 1);
+        }
+
+        Ok(())
+    }
+
+    // --------------------------------------------------
+    #[test]
+    fn test_build_stats() -> Result<()> {
+        let outfile = NamedTempFile::new()?;
+        let filler_runs = FillerRuns::new("N", 5)?;
+        let builder_args = || SufrBuilderArgs {
+            path: Some(outfile.path().to_string_lossy().to_string()),
+            ignore_softmask: true,
+            num_partitions: 2,
+            filler_runs: Some(filler_runs.clone()),
+            ..test_args(
+                b"ACGTacgtNNNNNNACGT$".to_vec(),
+                vec![0],
+                vec!["1".to_string()],
+            )
+        };
+
+        let on_disk: SufrBuilder<u32> = SufrBuilder::new(builder_args())?;
+        let stats = &on_disk.stats;
+        assert_eq!(stats.num_partitions, stats.partition_sizes.len());
+        assert_eq!(stats.partition_sizes.iter().sum::<usize>(), 9);
+        assert!(stats.partition_skew >= 1.0);
+        assert_eq!(stats.num_suffixes, 9);
+        assert_eq!(stats.num_filtered_suffixes, 10);
+        assert_eq!(stats.num_softmasked, 4);
+        assert_eq!(stats.num_filler_runs, 1);
+        assert_eq!(stats.filler_len, 10);
+        assert_eq!(stats.max_lcp, 4);
+        assert_eq!(stats.mean_lcp, 1.25);
+        // The sorted suffix/LCP arrays of both partitions are on disk
+        // together before they are written to the output
+        assert_eq!(stats.peak_temp_bytes, 72);
+
+        // The summary is stored in the file and survives removals
+        let mut sufr_file: SufrFile<u32> =
+            SufrFile::read(&outfile.path().to_string_lossy(), false)?;
+        assert_eq!(sufr_file.build_summary, stats.summary());
+        sufr_file.remove_sequences(&["1".to_string()])?;
+        let sufr_file: SufrFile<u32> =
+            SufrFile::read(&outfile.path().to_string_lossy(), false)?;
+        assert_eq!(sufr_file.build_summary, stats.summary());
+        assert_eq!(sufr_file.metadata()?.build_summary, Some(stats.summary()));
+
+        // Only the timings and temporary files differ in memory
+        let in_memory: SufrBuilder<u32> = SufrBuilder::in_memory(builder_args())?;
+        assert_eq!(in_memory.stats.summary(), stats.summary());
+        assert_eq!(in_memory.stats.peak_temp_bytes, 0);
+
+        let json: serde_json::Value = serde_json::from_str(&stats.to_json()?)?;
+        assert_eq!(json["max_lcp"], 4);
+        assert_eq!(json["partition_sizes"].as_array().map(|v| v.len()), Some(2));
+
+        Ok(())
+    }
+
+    // --------------------------------------------------
+    #[test]
+    fn test_lcp_lr() -> Result<()> {
+        type Found = Vec<Option<(Range<usize>, Vec<u32>)>>;
+        let search = |sufr: &mut SufrFile<u32>,
+                      queries: &[String],
+                      max_query_len: Option<usize>,
+                      low_memory: bool|
+         -> Result<Found> {
+            let opts = SearchOptions {
+                queries: queries.to_vec(),
+                max_query_len,
+                low_memory,
+                find_suffixes: true,
+            };
+            Ok(sufr
+                .suffix_search(&opts)?
+                .into_iter()
+                .map(|res| {
+                    res.locations.map(|loc| {
+                        let mut suffixes = loc.suffixes;
+                        suffixes.sort();
+                        (loc.ranks, suffixes)
+                    })
+                })
+                .collect())
+        };
+
+        let configs = [
+            (
+                "../data/inputs/long_dna_sequence.fa",
+                true,
+                None,
+                None,
+                None,
+            ),
+            (
+                "../data/inputs/long_dna_sequence.fa",
+                true,
+                Some(6),
+                None,
+                None,
+            ),
+            (
+                "../data/inputs/long_dna_sequence.fa",
+                true,
+                None,
+                Some("1101"),
+                None,
+            ),
+            ("../data/inputs/uniprot.fa", false, None, None, None),
+            (
+                "../data/inputs/uniprot.fa",
+                false,
+                None,
+                None,
+                Some("murphy-10"),
+            ),
+        ];
+        for (filename, is_dna, max_query_len, seed_mask, residue_map) in configs {
+            let seq_data = read_sequence_file(Path::new(filename), b'%')?;
+            let text = &seq_data.seq;
+
+            // Substrings of the text of many lengths, some with a
+            // changed last character
+            let queries = sample_queries(
+                text,
+                is_dna,
+                (1..40).map(|len| {
+                    (len, if len % 3 == 1 { vec![len - 1] } else { vec![] })
+                }),
+            )?;
+
+            let args = SufrBuilderArgs {
+                max_query_len,
+                is_dna,
+                seed_masks: seed_mask.iter().map(|m| m.to_string()).collect(),
+                residue_map: residue_map.map(ResidueMap::new).transpose()?,
+                ..test_args(
+                    seq_data.seq.clone(),
+                    seq_data.start_positions.clone(),
+                    seq_data.sequence_names.clone(),
+                )
+            };
+            let bytes = SufrBuilder::<u32>::in_memory(args)?.to_bytes()?;
+            let mut sufr: SufrFile<u32> = SufrFile::from_bytes(bytes)?;
+            for runtime_max_query_len in [None, Some(4)] {
+                for low_memory in [true, false] {
+                    sufr.use_lcp_lr(false);
+                    let expected =
+                        search(&mut sufr, &queries, runtime_max_query_len, low_memory)?;
+                    assert!(expected.iter().any(|found| found.is_some()));
+                    sufr.use_lcp_lr(true);
+                    let found =
+                        search(&mut sufr, &queries, runtime_max_query_len, low_memory)?;
+                    assert_eq!(found, expected);
+                }
+            }
+        }
+
+        // The arrays of a small suffix array, whose LCPs are
+        // 0, 0, 4, 0, 3, 0, 2, 0, 1
+        let mut sufr: SufrFile<u32> = SufrFile::read("../data/inputs/1.sufr", false)?;
+        let lcp: Vec<u32> = sufr.lcp_file.iter().collect();
+        let lcp_lr = LcpLr::new(&lcp);
+        assert_eq!(lcp_lr.left, [0, 0, 4, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(lcp_lr.right, [0, 0, 0, 3, 0, 2, 0, 0, 0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lcp_intervals() -> Result<()> {
+        let configs = [
+            ("../data/inputs/long_dna_sequence.fa", true, None),
+            ("../data/inputs/uniprot.fa", false, None),
+            ("../data/inputs/uniprot.fa", false, Some("murphy-10")),
+        ];
+        for (filename, is_dna, residue_map) in configs {
+            let seq_data = read_sequence_file(Path::new(filename), b'%')?;
+            let text = &seq_data.seq;
+            let args = SufrBuilderArgs {
+                is_dna,
+                residue_map: residue_map.map(ResidueMap::new).transpose()?,
+                ..test_args(
+                    text.clone(),
+                    seq_data.start_positions.clone(),
+                    seq_data.sequence_names.clone(),
+                )
+            };
+            let bytes = SufrBuilder::<u32>::in_memory(args)?.to_bytes()?;
+            let mut sufr: SufrFile<u32> = SufrFile::from_bytes(bytes)?;
+            let lcp: Vec<usize> = sufr.lcp_file.iter().map(|v| v as usize).collect();
+            let len = lcp.len();
+
+            // Descend from the root to every interval
+            let mut internal = vec![];
+            let mut leaves = vec![];
+            let mut parents = vec![];
+            let mut stack = vec![sufr.root_interval()?];
+            while let Some(interval) = stack.pop() {
+                if interval.is_leaf() {
+                    leaves.push(interval.first);
+                    continue;
+                }
+
+                // An ℓ-interval is bounded by smaller LCPs
+                let min_lcp = lcp[interval.first + 1..=interval.last].iter().min();
+                assert_eq!(min_lcp, Some(&interval.lcp));
+                if interval.first > 0 {
+                    assert!(lcp[interval.first] < interval.lcp);
+                }
+                if interval.last + 1 < len {
+                    assert!(lcp[interval.last + 1] < interval.lcp);
+                }
+
+                let children = sufr.child_intervals(&interval)?;
+                assert!(children.len() > 1);
+                assert_eq!(children[0].first, interval.first);
+                assert_eq!(children.last().unwrap().last, interval.last);
+                for pair in children.windows(2) {
+                    assert_eq!(pair[0].last + 1, pair[1].first);
+                }
+                for child in &children {
+                    assert!(child.lcp > interval.lcp);
+                    if internal.len() % 97 == 0 {
+                        parents.push((child.clone(), interval.clone()));
+                    }
+                }
+                stack.extend(children.into_iter().rev());
+                internal.push(interval);
+            }
+            assert_eq!(leaves, (0..len).collect::<Vec<_>>());
+
+            for (child, parent) in parents {
+                assert_eq!(sufr.parent_interval(&child)?, Some(parent));
+            }
+
+            // The same intervals come from the bottom up
+            let mut bottom_up = vec![];
+            sufr.bottom_up_intervals(|interval| {
+                bottom_up.push(interval.clone());
+                Ok(())
+            })?;
+            assert_eq!(bottom_up.last(), internal.first());
+            let mut top_down = internal.clone();
+            top_down.sort_by_key(|i| (i.first, i.last));
+            bottom_up.sort_by_key(|i| (i.first, i.last));
+            assert_eq!(bottom_up, top_down);
+
+            // Finding substrings of the text agrees with counting
+            let queries = sample_queries(
+                text,
+                is_dna,
+                (1..40).map(|len| {
+                    (len, if len % 3 == 1 { vec![len - 1] } else { vec![] })
+                }),
+            )?;
+            let counts = sufr.count(CountOptions {
+                queries: queries.clone(),
+                max_query_len: None,
+                low_memory: true,
+            })?;
+            for (query, count) in queries.iter().zip(counts) {
+                let found = sufr.find_interval(query)?;
+                assert_eq!(found.as_ref().map_or(0, |i| i.count()), count.count);
+                if let Some(found) = found {
+                    assert!(found.lcp >= query.len());
+                }
+            }
+        }
+
+        // The child table of a small suffix array, whose LCPs are
+        // 0, 0, 4, 0, 3, 0, 2, 0, 1
+        let mut sufr: SufrFile<u32> = SufrFile::read("../data/inputs/1.sufr", false)?;
+        let lcp: Vec<u32> = sufr.lcp_file.iter().collect();
+        let child_table = ChildTable::new(&lcp);
+        assert_eq!(child_table.up, [0, 0, 0, 2, 0, 4, 0, 6, 0, 1]);
+        assert_eq!(child_table.down, [1, 2, 0, 4, 0, 6, 0, 8, 0, 0]);
+        assert_eq!(child_table.next_l_index, [0, 3, 0, 5, 0, 7, 0, 0, 0, 0]);
+        assert_eq!(child_table.l_indexes(0, 8), [1, 3, 5, 7]);
+
+        // Intervals need every suffix fully sorted
+        let args = SufrBuilderArgs {
+            max_query_len: Some(2),
+            num_partitions: 2,
+            ..test_args(b"ACGTNNACGT$".to_vec(), vec![0], vec!["1".to_string()])
+        };
+        let bytes = SufrBuilder::<u32>::in_memory(args)?.to_bytes()?;
+        let mut sufr: SufrFile<u32> = SufrFile::from_bytes(bytes)?;
+        let res = sufr.root_interval();
+        assert!(res.is_err());
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "LCP-intervals require a fully sorted suffix array"
+        );
+        assert!(sufr.bottom_up_intervals(|_| Ok(())).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mems() -> Result<()> {
+        let builder_args = |filename: &str,
+                            is_dna: bool,
+                            residue_map: Option<&str>,
+                            path: Option<String>|
+         -> Result<SufrBuilderArgs> {
+            let seq_data = read_sequence_file(Path::new(filename), b'%')?;
+            Ok(SufrBuilderArgs {
+                path,
+                is_dna,
+                residue_map: residue_map.map(ResidueMap::new).transpose()?,
+                ..test_args(
+                    seq_data.seq,
+                    seq_data.start_positions,
+                    seq_data.sequence_names,
+                )
+            })
+        };
+
+        let configs = [
+            ("../data/inputs/long_dna_sequence.fa", true, None),
+            ("../data/inputs/uniprot.fa", false, None),
+            ("../data/inputs/uniprot.fa", false, Some("murphy-10")),
+        ];
+        for (filename, is_dna, residue_map) in configs {
+            let args = builder_args(filename, is_dna, residue_map, None)?;
+            let map = args.residue_map.clone();
+            let mapped = |bytes: &[u8]| match &map {
+                Some(map) => map.map_bytes(bytes),
+                _ => bytes.to_vec(),
+            };
+            let text = mapped(&args.text);
+            let bytes = SufrBuilder::<u32>::in_memory(args.clone())?.to_bytes()?;
+            let mut sufr: SufrFile<u32> = SufrFile::from_bytes(bytes)?;
+            let suffixes: Vec<usize> =
+                sufr.suffix_array_file.iter().map(|v| v as usize).collect();
+
+            // Pieces of the text with a changed character every so often
+            let queries = sample_queries(
+                &args.text,
+                is_dna,
+                (0..4).map(|i| (80, (i + 10..80).step_by(17 + i).collect())),
+            )?;
+
+            // The left-maximal matches of every suffix by brute force
+            let occurrences = |substring: &[u8], haystack: &[u8]| {
+                haystack
+                    .windows(substring.len())
+                    .filter(|w| w == &substring)
+                    .count()
+            };
+            let mut matches = vec![];
+            for query in &queries {
+                let qry = mapped(query.as_bytes());
+                let mut found = vec![];
+                for query_pos in 0..qry.len() {
+                    for &suffix in &suffixes {
+                        if query_pos > 0
+                            && suffix > 0
+                            && text[suffix - 1] == qry[query_pos - 1]
+                        {
+                            continue;
+                        }
+                        let length = qry[query_pos..]
+                            .iter()
+                            .zip(&text[suffix..])
+                            .take_while(|(a, b)| a == b)
+                            .count();
+                        if length >= 5 {
+                            found.push((query_pos, suffix, length));
+                        }
+                    }
+                }
+                found.sort();
+                matches.push((qry, found));
+            }
+
+            for (min_len, unique) in [(8, false), (5, false), (8, true)] {
+                let res = sufr.mems(MemsOptions {
+                    queries: queries.clone(),
+                    min_len,
+                    unique,
+                })?;
+
+                for ((qry, all), found) in matches.iter().zip(res) {
+                    let expected: Vec<_> = all
+                        .iter()
+                        .filter(|&&(query_pos, _, length)| {
+                            let substring = &qry[query_pos..query_pos + length];
+                            length >= min_len
+                                && (!unique
+                                    || occurrences(substring, qry) == 1
+                                        && suffixes
+                                            .iter()
+                                            .filter(|&&s| {
+                                                text[s..].starts_with(substring)
+                                            })
+                                            .count()
+                                            == 1)
+                        })
+                        .cloned()
+                        .collect();
+                    let found: Vec<_> = found
+                        .mems
+                        .iter()
+                        .map(|mem| (mem.query_position, mem.suffix, mem.length))
+                        .collect();
+                    assert_eq!(found, expected);
+                    assert!(unique || !found.is_empty());
+                }
+            }
+        }
+
+        // Sharded indexes find the same matches
+        let dir = TempDir::new()?;
+        let path = dir.path().join("3.sufr").to_string_lossy().to_string();
+        let args = builder_args("../data/inputs/3.fa", true, None, Some(path.clone()))?;
+        SuffixArray::write(args.clone())?;
+        let mut unsharded = SuffixArray::read(&path, true)?;
+        let manifest = dir.path().join("3.shards").to_string_lossy().to_string();
+        let path = ShardedSuffixArray::write(
+            SufrBuilderArgs {
+                path: Some(manifest),
+                ..args
+            },
+            50,
+        )?;
+        let mut sharded = SuffixArray::read(&path, true)?;
+        for unique in [false, true] {
+            let opts = MemsOptions {
+                queries: vec![
+                    "TTCTGACTGCAAGGGCAATACGTGAGTAAAT".to_string(),
+                    "GGATTAAAAAAAGAGTGATA".to_string(),
+                ],
+                min_len: 4,
+                unique,
+            };
+            let expected = unsharded.mems(opts.clone())?;
+            assert!(expected.iter().all(|res| !res.mems.is_empty()));
+            assert_eq!(sharded.mems(opts)?, expected);
+        }
+
+        // MEMs need every suffix fully sorted
+        let args = SufrBuilderArgs {
+            max_query_len: Some(3),
+            path: None,
+            ..builder_args("../data/inputs/3.fa", true, None, None)?
+        };
+        let mut suffix_array = SuffixArray::in_memory(args)?;
+        let res = suffix_array.mems(MemsOptions {
+            queries: vec!["ACGT".to_string()],
+            min_len: 2,
+            unique: false,
+        });
+        assert!(res.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_matching_statistics() -> Result<()> {
+        let configs = [
+            ("../data/inputs/long_dna_sequence.fa", true, None, None),
+            ("../data/inputs/long_dna_sequence.fa", true, Some(6), None),
+            ("../data/inputs/uniprot.fa", false, None, Some("murphy-10")),
+        ];
+        for (filename, is_dna, max_query_len, residue_map) in configs {
+            let seq_data = read_sequence_file(Path::new(filename), b'%')?;
+            let args = SufrBuilderArgs {
+                max_query_len,
+                is_dna,
+                residue_map: residue_map.map(ResidueMap::new).transpose()?,
+                ..test_args(
+                    seq_data.seq.clone(),
+                    seq_data.start_positions,
+                    seq_data.sequence_names,
+                )
+            };
+            let map = args.residue_map.clone();
+            let mapped = |bytes: &[u8]| match &map {
+                Some(map) => map.map_bytes(bytes),
+                _ => bytes.to_vec(),
+            };
+            let text = mapped(&seq_data.seq);
+            let bytes = SufrBuilder::<u32>::in_memory(args)?.to_bytes()?;
+            let mut sufr: SufrFile<u32> = SufrFile::from_bytes(bytes)?;
+            let suffixes: Vec<usize> =
+                sufr.suffix_array_file.iter().map(|v| v as usize).collect();
+
+            // Pieces of the text with a changed character every so often
+            let queries = sample_queries(
+                &seq_data.seq,
+                is_dna,
+                (0..3).map(|i| (30, (i + 4..30).step_by(9).collect())),
+            )?;
+
+            for (runtime_max_query_len, low_memory) in
+                [(None, true), (None, false), (Some(4), true)]
+            {
+                let cap = [max_query_len, runtime_max_query_len]
+                    .into_iter()
+                    .flatten()
+                    .min()
+                    .unwrap_or(usize::MAX);
+                let res = sufr.matching_statistics(MatchingStatisticsOptions {
+                    queries: queries.clone(),
+                    max_query_len: runtime_max_query_len,
+                    low_memory,
+                })?;
+                for (query, found) in queries.iter().zip(res) {
+                    let qry = mapped(query.as_bytes());
+                    assert_eq!(found.statistics.len(), qry.len());
+                    for (start, stat) in found.statistics.iter().enumerate() {
+                        // The LCP of the query suffix with each text suffix
+                        let lcps: Vec<usize> = suffixes
+                            .iter()
+                            .map(|&suffix| {
+                                qry[start..]
+                                    .iter()
+                                    .zip(&text[suffix..])
+                                    .take_while(|(a, b)| a == b)
+                                    .count()
+                                    .min(cap)
+                            })
+                            .collect();
+                        let length = *lcps.iter().max().unwrap();
+                        assert_eq!(stat.length, length);
+                        let matching =
+                            lcps.iter().filter(|&&lcp| lcp >= length).count();
+                        assert_eq!(stat.ranks.len(), matching);
+                        assert!(stat.ranks.clone().all(|rank| lcps[rank] >= length));
+                    }
+                }
+            }
+        }
+
+        // Prefixes found only in removed sequences are not matches
+        let args = test_args(
+            b"ACGTTT%GGCCAA$".to_vec(),
+            vec![0, 7],
+            vec!["a".to_string(), "b".to_string()],
+        );
+        let bytes = SufrBuilder::<u32>::in_memory(args)?.to_bytes()?;
+        let mut sufr: SufrFile<u32> = SufrFile::from_bytes(bytes)?;
+        let opts = MatchingStatisticsOptions {
+            queries: vec!["GGCA".to_string()],
+            max_query_len: None,
+            low_memory: true,
+        };
+        let lengths = |res: Vec<MatchingStatisticsResult>| -> Vec<usize> {
+            res[0].statistics.iter().map(|stat| stat.length).collect()
+        };
+        assert_eq!(
+            lengths(sufr.matching_statistics(opts.clone())?),
+            [3, 2, 2, 1]
+        );
+        sufr.remove_sequences(&["b".to_string()])?;
+        assert_eq!(lengths(sufr.matching_statistics(opts)?), [1, 1, 1, 1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_repeats() -> Result<()> {
+        let configs = [
+            ("../data/inputs/smol.fa", true, None, None),
+            ("../data/inputs/3.fa", true, None, None),
+            (
+                "../data/inputs/uniprot.fa",
+                false,
+                Some("murphy-10"),
+                Some(12),
+            ),
+        ];
+        for (filename, is_dna, residue_map, num_seqs) in configs {
+            let mut seq_data = read_sequence_file(Path::new(filename), b'%')?;
+            if let Some(num_seqs) = num_seqs {
+                let end = seq_data.start_positions[num_seqs];
+                seq_data.seq.truncate(end);
+                seq_data.seq[end - 1] = b'$';
+                seq_data.start_positions.truncate(num_seqs);
+                seq_data.sequence_names.truncate(num_seqs);
+            }
+            let args = SufrBuilderArgs {
+                is_dna,
+                residue_map: residue_map.map(ResidueMap::new).transpose()?,
+                ..test_args(
+                    seq_data.seq.clone(),
+                    seq_data.start_positions,
+                    seq_data.sequence_names,
+                )
+            };
+            let text = match &args.residue_map {
+                Some(map) => map.map_bytes(&seq_data.seq),
+                _ => seq_data.seq.clone(),
+            };
+            let bytes = SufrBuilder::<u32>::in_memory(args)?.to_bytes()?;
+            let mut sufr: SufrFile<u32> = SufrFile::from_bytes(bytes)?;
+            let suffixes: Vec<usize> =
+                sufr.suffix_array_file.iter().map(|v| v as usize).collect();
+
+            // The occurrences of every substring within a sequence
+            let symbol =
+                |pos: usize| text.get(pos).copied().filter(|&c| c != b'%' && c != b'$');
+            let mut occurrences: HashMap<&[u8], Vec<usize>> = HashMap::new();
+            for &suffix in &suffixes {
+                let mut end = suffix;
+                while symbol(end).is_some() {
+                    end += 1;
+                    occurrences
+                        .entry(&text[suffix..end])
+                        .or_default()
+                        .push(suffix);
+                }
+            }
+
+            // Whether the flanking characters differ or all are distinct
+            let differ = |flanks: &[Option<u8>]| {
+                flanks.iter().any(|c| c.is_none() || c != &flanks[0])
+            };
+            let distinct = |flanks: &[Option<u8>]| {
+                let mut seen = HashSet::new();
+                flanks.iter().all(|c| c.is_none() || seen.insert(c))
+            };
+
+            for (min_len, supermaximal) in [(1, false), (4, false), (2, true)] {
+                let mut expected: Vec<(usize, Vec<usize>)> = occurrences
+                    .iter()
+                    .filter(|(substring, positions)| {
+                        let lefts: Vec<_> = positions
+                            .iter()
+                            .map(|&p| p.checked_sub(1).and_then(symbol))
+                            .collect();
+                        let rights: Vec<_> = positions
+                            .iter()
+                            .map(|&p| symbol(p + substring.len()))
+                            .collect();
+                        substring.len() >= min_len
+                            && positions.len() > 1
+                            && if supermaximal {
+                                distinct(&lefts) && distinct(&rights)
+                            } else {
+                                differ(&lefts) && differ(&rights)
+                            }
+                    })
+                    .map(|(substring, positions)| {
+                        let mut positions = positions.clone();
+                        positions.sort();
+                        (substring.len(), positions)
+                    })
+                    .collect();
+                expected.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+                assert!(!expected.is_empty());
+
+                let repeats = sufr.repeats(RepeatsOptions {
+                    min_len,
+                    supermaximal,
+                    max_repeats: None,
+                })?;
+                for repeat in &repeats {
+                    let start = repeat.positions[0].suffix;
+                    assert_eq!(
+                        repeat.sequence.as_bytes(),
+                        &seq_data.seq[start..start + repeat.length]
+                    );
+                    assert_eq!(repeat.ranks.len(), repeat.positions.len());
+                    for pos in &repeat.positions {
+                        assert!(repeat.ranks.contains(&pos.rank));
+                        assert_eq!(suffixes[pos.rank], pos.suffix);
+                    }
+                }
+                let mut found: Vec<(usize, Vec<usize>)> = repeats
+                    .iter()
+                    .map(|repeat| {
+                        let positions =
+                            repeat.positions.iter().map(|p| p.suffix).collect();
+                        (repeat.length, positions)
+                    })
+                    .collect();
+                found.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+                assert_eq!(found, expected);
+
+                // The top repeats are the longest
+                let top = sufr.repeats(RepeatsOptions {
+                    min_len,
+                    supermaximal,
+                    max_repeats: Some(3),
+                })?;
+                let top_lengths: Vec<_> = top.iter().map(|r| r.length).collect();
+                let lengths: Vec<_> = expected.iter().take(3).map(|r| r.0).collect();
+                assert_eq!(top_lengths, lengths);
+            }
+
+            // Removing a sequence drops its occurrences of each repeat
+            let all = sufr.repeats(RepeatsOptions {
+                min_len: 1,
+                supermaximal: false,
+                max_repeats: None,
+            })?;
+            let name = sufr.sequence_names[0].clone();
+            let first_end = sufr
+                .sequence_starts
+                .get(1)
+                .map_or(text.len(), |&s| s as usize);
+            sufr.remove_sequences(&[name])?;
+            let expected: Vec<(usize, Vec<usize>)> = all
+                .into_iter()
+                .map(|repeat| {
+                    let ranks = repeat
+                        .positions
+                        .iter()
+                        .filter(|p| p.suffix >= first_end)
+                        .map(|p| p.rank)
+                        .collect();
+                    (repeat.length, ranks)
+                })
+                .filter(|(_, ranks): &(usize, Vec<usize>)| ranks.len() > 1)
+                .collect();
+            let found: Vec<(usize, Vec<usize>)> = sufr
+                .repeats(RepeatsOptions {
+                    min_len: 1,
+                    supermaximal: false,
+                    max_repeats: None,
+                })?
+                .into_iter()
+                .map(|repeat| {
+                    let ranks = repeat.positions.iter().map(|p| p.rank).collect();
+                    (repeat.length, ranks)
+                })
+                .collect();
+            assert_eq!(found, expected);
+        }
+
+        // Repeats need every suffix fully sorted
+        let seq_data = read_sequence_file(Path::new("../data/inputs/3.fa"), b'%')?;
+        let mut suffix_array = SuffixArray::in_memory(SufrBuilderArgs {
+            max_query_len: Some(3),
+            ..test_args(
+                seq_data.seq,
+                seq_data.start_positions,
+                seq_data.sequence_names,
+            )
+        })?;
+        let res = suffix_array.repeats(RepeatsOptions {
+            min_len: 2,
+            supermaximal: false,
+            max_repeats: None,
+        });
+        assert!(res.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_common() -> Result<()> {
+        let builder_args =
+            |text: Vec<u8>, starts: Vec<usize>, names: Vec<String>| SufrBuilderArgs {
+                is_dna: false,
+                ..test_args(text, starts, names)
+            };
+        let mut seq_data =
+            read_sequence_file(Path::new("../data/inputs/uniprot.fa"), b'%')?;
+        let end = seq_data.start_positions[24];
+        seq_data.seq.truncate(end);
+        seq_data.seq[end - 1] = b'$';
+        seq_data.start_positions.truncate(24);
+        seq_data.sequence_names.truncate(24);
+        let mut all = SuffixArray::in_memory(builder_args(
+            seq_data.seq.clone(),
+            seq_data.start_positions.clone(),
+            seq_data.sequence_names.clone(),
+        ))?;
+        let sequences = all.sequences()?;
+        assert_eq!(sequences.len(), seq_data.sequence_names.len());
+
+        // Compare a few sequences with a suffix array of the others
+        let (queries, targets) = sequences.split_at(4);
+        let mut text = vec![];
+        let mut starts = vec![];
+        for (_, seq) in targets {
+            starts.push(text.len());
+            text.extend(seq.as_bytes());
+            text.push(b'%');
+        }
+        *text.last_mut().unwrap() = b'$';
+        let names = targets.iter().map(|(name, _)| name.clone()).collect();
+        let mut others = SuffixArray::in_memory(builder_args(text, starts, names))?;
+
+        let summary = |common: Vec<CommonSubstring>| {
+            common
+                .into_iter()
+                .map(|c| {
+                    (
+                        c.length,
+                        c.query_name,
+                        c.query_position,
+                        c.sequence_name,
+                        c.sequence_position,
+                        c.sequence,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let exclude: Vec<String> =
+            queries.iter().map(|(name, _)| name.clone()).collect();
+        for (min_len, max_results) in [(8, None), (5, Some(4))] {
+            let in_one = all.common(CommonOptions {
+                sequences: queries.to_vec(),
+                exclude: exclude.clone(),
+                min_len,
+                max_results,
+            })?;
+            let in_two = others.common(CommonOptions {
+                sequences: queries.to_vec(),
+                exclude: vec![],
+                min_len,
+                max_results,
+            })?;
+            assert!(!in_one.is_empty());
+            assert_eq!(summary(in_one.clone()), summary(in_two));
+
+            // The first is the longest common substring
+            let longest = queries
+                .iter()
+                .flat_map(|(_, query)| {
+                    targets.iter().map(move |(_, target)| {
+                        let (query, target) = (query.as_bytes(), target.as_bytes());
+                        (0..query.len())
+                            .flat_map(|i| (0..target.len()).map(move |j| (i, j)))
+                            .map(|(i, j)| {
+                                query[i..]
+                                    .iter()
+                                    .zip(&target[j..])
+                                    .take_while(|(a, b)| a == b)
+                                    .count()
+                            })
+                            .max()
+                            .unwrap_or(0)
+                    })
+                })
+                .max()
+                .unwrap();
+            assert_eq!(in_one[0].length, longest);
+            assert!(in_one.iter().all(|c| c.length >= min_len));
+            assert!(in_one.windows(2).all(|w| w[0].length >= w[1].length));
+            if let Some(max_results) = max_results {
+                assert_eq!(in_one.len(), max_results);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_kmers() -> Result<()> {
+        let configs = [
+            ("../data/inputs/long_dna_sequence.fa", true, 5, None, None),
+            (
+                "../data/inputs/long_dna_sequence.fa",
+                true,
+                5,
+                Some(7),
+                None,
+            ),
+            ("../data/inputs/3.fa", true, 3, None, None),
+            (
+                "../data/inputs/uniprot.fa",
+                false,
+                4,
+                None,
+                Some("murphy-10"),
+            ),
+        ];
+        for (filename, is_dna, k, max_query_len, residue_map) in configs {
+            let seq_data = read_sequence_file(Path::new(filename), b'%')?;
+            let args = SufrBuilderArgs {
+                max_query_len,
+                is_dna,
+                residue_map: residue_map.map(ResidueMap::new).transpose()?,
+                ..test_args(
+                    seq_data.seq.clone(),
+                    seq_data.start_positions,
+                    seq_data.sequence_names.clone(),
+                )
+            };
+            let map = args.residue_map.clone();
+            let bytes = SufrBuilder::<u32>::in_memory(args)?.to_bytes()?;
+            let mut sufr: SufrFile<u32> = SufrFile::from_bytes(bytes)?;
+            let suffixes: Vec<usize> =
+                sufr.suffix_array_file.iter().map(|v| v as usize).collect();
+
+            // The k-mers at the start of each indexed suffix
+            let text = sufr.text.clone();
+            let mut expected: HashMap<Vec<u8>, usize> = HashMap::new();
+            for &suffix in &suffixes {
+                let Some(kmer) = text.get(suffix..suffix + k) else {
+                    continue;
+                };
+                let is_valid = kmer.iter().all(|&c| {
+                    c != b'%' && c != b'$' && (!is_dna || b"ACGT".contains(&c))
+                });
+                if is_valid {
+                    let kmer =
+                        map.as_ref().map_or(kmer.to_vec(), |m| m.map_bytes(kmer));
+                    *expected.entry(kmer).or_default() += 1;
+                }
+            }
+
+            let kmers = sufr.kmers(k)?.collect::<Result<Vec<_>>>()?;
+            assert_eq!(kmers.len(), expected.len());
+            let mut prev_end = 0;
+            for kmer in &kmers {
+                assert_eq!(kmer.kmer.len(), k);
+                assert_eq!(Some(&kmer.count), expected.get(kmer.kmer.as_bytes()));
+                assert_eq!(kmer.ranks.len(), kmer.count);
+                assert!(kmer.ranks.start >= prev_end);
+                prev_end = kmer.ranks.end;
+            }
+            assert!(kmers.windows(2).all(|w| w[0].kmer < w[1].kmer));
+
+            // Removed sequences are not counted
+            let name = seq_data.sequence_names[0].clone();
+            sufr.remove_sequences(&[name])?;
+            let total: usize = sufr.kmers(k)?.map(|kmer| kmer.unwrap().count).sum();
+            assert!(total < expected.values().sum());
+        }
+
+        // The suffix array must be sorted to at least k
+        let seq_data = read_sequence_file(Path::new("../data/inputs/3.fa"), b'%')?;
+        let mut suffix_array = SuffixArray::in_memory(SufrBuilderArgs {
+            max_query_len: Some(3),
+            ..test_args(
+                seq_data.seq,
+                seq_data.start_positions,
+                seq_data.sequence_names,
+            )
+        })?;
+        assert!(suffix_array.kmers(3).is_ok());
+        assert!(suffix_array.kmers(4).is_err());
+        assert!(suffix_array.kmers(0).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_complexity_stats() -> Result<()> {
+        let configs = [
+            ("../data/inputs/smol.fa", true, None, None, None),
+            ("../data/inputs/smol.fa", true, Some(8), None, None),
+            ("../data/inputs/smol.fa", true, None, None, Some("seq2")),
+            ("../data/inputs/3.fa", true, None, None, None),
+            (
+                "../data/inputs/uniprot.fa",
+                false,
+                None,
+                Some("murphy-10"),
+                None,
+            ),
+        ];
+        for (filename, is_dna, max_query_len, residue_map, remove) in configs {
+            let mut seq_data = read_sequence_file(Path::new(filename), b'%')?;
+            if seq_data.start_positions.len() > 12 {
+                let end = seq_data.start_positions[12];
+                seq_data.seq.truncate(end);
+                seq_data.seq[end - 1] = b'$';
+                seq_data.start_positions.truncate(12);
+                seq_data.sequence_names.truncate(12);
+            }
+            let args = SufrBuilderArgs {
+                max_query_len,
+                is_dna,
+                residue_map: residue_map.map(ResidueMap::new).transpose()?,
+                ..test_args(
+                    seq_data.seq.clone(),
+                    seq_data.start_positions.clone(),
+                    seq_data.sequence_names.clone(),
+                )
+            };
+            let text = match &args.residue_map {
+                Some(map) => map.map_bytes(&seq_data.seq),
+                _ => seq_data.seq.clone(),
+            };
+            let bytes = SufrBuilder::<u32>::in_memory(args)?.to_bytes()?;
+            let mut sufr: SufrFile<u32> = SufrFile::from_bytes(bytes)?;
+            if let Some(name) = remove {
+                sufr.remove_sequences(&[name.to_string()])?;
+            }
+            let cap = max_query_len.unwrap_or(usize::MAX);
+
+            // Every substring at the start of an indexed suffix in the
+            // sequences that were kept
+            let starts = &seq_data.start_positions;
+            let record_of = |pos: usize| starts.partition_point(|&s| s <= pos) - 1;
+            let record_end =
+                |i: usize| starts.get(i + 1).map_or(text.len(), |&e| e) - 1;
+            let kept: Vec<usize> = (0..starts.len())
+                .filter(|&i| Some(seq_data.sequence_names[i].as_str()) != remove)
+                .collect();
+            let suffixes: Vec<usize> = sufr
+                .suffix_array_file
+                .iter()
+                .map(|v| v as usize)
+                .filter(|&s| kept.contains(&record_of(s)))
+                .collect();
+            let mut counts: HashMap<&[u8], usize> = HashMap::new();
+            for &suffix in &suffixes {
+                for end in suffix + 1..=record_end(record_of(suffix)) {
+                    *counts.entry(&text[suffix..end]).or_default() += 1;
+                }
+            }
+
+            let max_len = 6;
+            let min_repeat_len = 5;
+            let stats = sufr.complexity_stats(ComplexityOptions {
+                max_len,
+                min_repeat_len,
+            })?;
+
+            let by_len: Vec<usize> = (1..=max_len)
+                .map(|len| counts.keys().filter(|k| k.len() == len).count())
+                .collect();
+            assert_eq!(stats.distinct_by_len, by_len);
+            let distinct = counts.keys().filter(|k| k.len() <= cap).count();
+            assert_eq!(stats.distinct_substrings, distinct);
+
+            // The positions within a substring that occurs twice
+            let mut covered = HashSet::new();
+            for &suffix in &suffixes {
+                let end = record_end(record_of(suffix));
+                let repeat_len = (suffix + 1..=end)
+                    .take_while(|&e| counts[&text[suffix..e]] > 1)
+                    .count();
+                if repeat_len >= min_repeat_len {
+                    covered.extend(suffix..suffix + repeat_len);
+                }
+            }
+            let num_positions: usize =
+                kept.iter().map(|&i| record_end(i) - starts[i]).sum();
+            assert_eq!(stats.num_positions, num_positions);
+            assert_eq!(stats.repeat_positions, covered.len());
+
+            // Each sequence against the most distinct substrings for
+            // its length and the alphabet
+            assert_eq!(stats.sequences.len(), kept.len());
+            for (seq, &i) in stats.sequences.iter().zip(&kept) {
+                let mut seq_counts = HashSet::new();
+                for &suffix in suffixes.iter().filter(|&&s| record_of(s) == i) {
+                    for end in
+                        suffix + 1..=record_end(i).min(suffix.saturating_add(cap))
+                    {
+                        seq_counts.insert(&text[suffix..end]);
+                    }
+                }
+                let length = record_end(i) - starts[i];
+                let most: usize = (1..=length.min(cap))
+                    .map(|len| {
+                        by_len[0]
+                            .checked_pow(len as u32)
+                            .map_or(length - len + 1, |n| n.min(length - len + 1))
+                    })
+                    .sum();
+                assert_eq!(seq.name, seq_data.sequence_names[i]);
+                assert_eq!(seq.length, length);
+                assert_eq!(seq.distinct_substrings, seq_counts.len());
+                let complexity = seq_counts.len() as f64 / most as f64;
+                assert!((seq.linguistic_complexity - complexity).abs() < 1e-9);
+                assert!(
+                    seq.linguistic_complexity > 0. && seq.linguistic_complexity <= 1.
+                );
+            }
+        }
+
+        // The lengths cannot exceed the maximum query length
+        let seq_data = read_sequence_file(Path::new("../data/inputs/3.fa"), b'%')?;
+        let mut suffix_array = SuffixArray::in_memory(SufrBuilderArgs {
+            max_query_len: Some(3),
+            ..test_args(
+                seq_data.seq,
+                seq_data.start_positions,
+                seq_data.sequence_names,
+            )
+        })?;
+        let res = suffix_array.complexity_stats(ComplexityOptions {
+            max_len: 2,
+            min_repeat_len: 5,
+        });
+        assert!(res.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_documents() -> Result<()> {
+        // Every range of a document array lists the documents in it
+        let mut state: u64 = 42;
+        let documents: Vec<u32> = (0..1000)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                ((state >> 33) % 7) as u32
+            })
+            .collect();
+        let document_array = DocumentArray::new(documents.clone(), 7);
+        for start in (0..1000).step_by(37) {
+            for end in (start..=1000).step_by(53) {
+                let mut expected: HashMap<usize, usize> = HashMap::new();
+                for &doc in &documents[start..end] {
+                    *expected.entry(doc as usize).or_default() += 1;
+                }
+                let mut expected: Vec<_> = expected.into_iter().collect();
+                expected.sort();
+                assert_eq!(document_array.list(start..end), expected);
+            }
+        }
+
+        // The documents of a search match the names of the located hits
+        let configs = [
+            ("../data/inputs/uniprot.fa", false, vec![], None, vec![]),
+            (
+                "../data/inputs/uniprot.fa",
+                false,
+                vec!["1101"],
+                None,
+                vec![],
+            ),
+            (
+                "../data/inputs/uniprot.fa",
+                false,
+                vec![],
+                Some(3),
+                vec!["sp|Q6GZX3|002L_FRG3G"],
+            ),
+            (
+                "../data/inputs/3.fa",
+                true,
+                vec!["101", "11011"],
+                None,
+                vec![],
+            ),
+        ];
+        for (filename, is_dna, seed_masks, max_query_len, removed) in configs {
+            let seq_data = read_sequence_file(Path::new(filename), b'%')?;
+            let args = SufrBuilderArgs {
+                max_query_len,
+                is_dna,
+                seed_masks: seed_masks.iter().map(|m| m.to_string()).collect(),
+                both_strands: is_dna,
+                ..test_args(
+                    seq_data.seq.clone(),
+                    seq_data.start_positions,
+                    seq_data.sequence_names.clone(),
+                )
+            };
+            let bytes = SufrBuilder::<u32>::in_memory(args)?.to_bytes()?;
+            let mut sufr: SufrFile<u32> = SufrFile::from_bytes(bytes)?;
+            let removed: Vec<String> = removed.iter().map(|n| n.to_string()).collect();
+            if !removed.is_empty() {
+                sufr.remove_sequences(&removed)?;
+            }
+
+            let queries: Vec<String> = ["A", "L", "AC", "GT", "LLS", "ACGT", "WWWWW"]
+                .iter()
+                .map(|q| q.to_string())
+                .collect();
+            let opts = LocateOptions {
+                queries,
+                max_query_len: None,
+                low_memory: true,
+            };
+            let located = sufr.locate(opts.clone())?;
+            let listed = sufr.list_documents(opts)?;
+            assert_eq!(located.len(), listed.len());
+            for (loc, res) in located.iter().zip(&listed) {
+                let mut expected: HashMap<&str, usize> = HashMap::new();
+                for pos in &loc.positions {
+                    *expected.entry(pos.sequence_name.as_str()).or_default() += 1;
+                }
+                let mut seen: HashSet<&str> = HashSet::new();
+                for doc in &res.documents {
+                    assert_eq!(
+                        sufr.sequence_names[doc.sequence_num],
+                        doc.sequence_name
+                    );
+                    assert!(!removed.contains(&doc.sequence_name));
+                    assert!(seen.insert(&doc.sequence_name));
+                    assert_eq!(
+                        expected.get(doc.sequence_name.as_str()),
+                        Some(&doc.count),
+                        "{filename} {} {}",
+                        res.query,
+                        doc.sequence_name
+                    );
+                }
+                assert_eq!(seen.len(), expected.len());
+                assert!(res
+                    .documents
+                    .windows(2)
+                    .all(|pair| pair[0].sequence_num < pair[1].sequence_num));
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_peptides() -> Result<()> {
+        use PeptideClass::{Razor, Shared, Unique};
+        let seq_data =
+            read_sequence_file(Path::new("../data/inputs/uniprot.fa"), b'%')?;
+        let builder_args = |both_strands: bool| SufrBuilderArgs {
+            is_dna: false,
+            both_strands,
+            ..test_args(
+                seq_data.seq.clone(),
+                seq_data.start_positions.clone(),
+                seq_data.sequence_names.clone(),
+            )
+        };
+        let mut suffix_array = SuffixArray::in_memory(builder_args(false))?;
+
+        // Tryptic peptides of the 1109L proteins of African swine fever virus
+        let peptides = [
+            "ELEYWCTYAR",
+            "CSYFTDTR",
+            "VIDTENIK",
+            "MQLQAATR",
+            "CSYFTDSR",
+            "HLINQDDL",
+            "FWCTYAK",
+            "MQLQAATR",
+            "WWWWWWWW",
+        ];
+        let opts = MapPeptidesOptions {
+            peptides: peptides.iter().map(|p| p.to_string()).collect(),
+            max_query_len: None,
+            low_memory: true,
+        };
+        let mappings = suffix_array.map_peptides(opts)?;
+        assert_eq!(mappings.len(), peptides.len());
+
+        // Each hit is the peptide between its flanking residues
+        let text = &seq_data.seq;
+        let starts = &seq_data.start_positions;
+        for (mapping, peptide) in mappings.iter().zip(peptides) {
+            assert_eq!(mapping.peptide, peptide);
+            for hit in &mapping.hits {
+                let num = seq_data
+                    .sequence_names
+                    .iter()
+                    .position(|name| name == &hit.sequence_name)
+                    .unwrap();
+                let start = starts[num];
+                let end = starts.get(num + 1).map_or(text.len(), |next| *next) - 1;
+                let suffix = start + hit.sequence_position;
+                assert_eq!(suffix, hit.suffix);
+                assert_eq!(&text[suffix..suffix + peptide.len()], peptide.as_bytes());
+                let preceding = if suffix == start {
+                    '-'
+                } else {
+                    text[suffix - 1] as char
+                };
+                let following = if suffix + peptide.len() == end {
+                    '-'
+                } else {
+                    text[suffix + peptide.len()] as char
+                };
+                assert_eq!((hit.preceding, hit.following), (preceding, following));
+            }
+        }
+
+        let classes: Vec<Vec<(&str, PeptideClass)>> = mappings
+            .iter()
+            .map(|mapping| {
+                mapping
+                    .hits
+                    .iter()
+                    .map(|hit| (&hit.sequence_name[..], hit.class))
+                    .collect()
+            })
+            .collect();
+        let (k5, l5, p4, wa) = (
+            "sp|P0C9J0|1109L_ASFK5",
+            "sp|P26710|1109L_ASFL5",
+            "sp|P0C9J1|1109L_ASFP4",
+            "sp|P0C9I9|1109L_ASFWA",
+        );
+        assert_eq!(classes[0], [(k5, Unique)]);
+        assert_eq!(classes[1], [(p4, Unique)]);
+        assert_eq!(classes[2], [(p4, Unique)]);
+
+        // Both have four peptides, but P4 has more unique ones
+        assert_eq!(classes[3], [(p4, Razor), (wa, Shared)]);
+        assert_eq!(classes[4], [(l5, Shared), (wa, Razor)]);
+        assert_eq!(classes[5], [(wa, Unique)]);
+        assert_eq!(
+            classes[6],
+            [(k5, Shared), (l5, Shared), (p4, Razor), (wa, Shared)]
+        );
+        assert_eq!(classes[7], classes[3]);
+        assert!(classes[8].is_empty());
+
+        // The C-terminal peptide has no following residue
+        assert_eq!(mappings[5].hits[0].following, '-');
+
+        // Even when the proteins are separated by another delimiter
+        let mut suffix_array = SuffixArray::in_memory(SufrBuilderArgs {
+            text: b"MKPEPTIDEXAPEPTIDEK$".to_vec(),
+            sequence_starts: vec![0, 10],
+            sequence_names: vec!["a".to_string(), "b".to_string()],
+            num_partitions: 2,
+            ..builder_args(false)
+        })?;
+        let opts = MapPeptidesOptions {
+            peptides: vec!["PEPTIDE".to_string()],
+            max_query_len: None,
+            low_memory: true,
+        };
+        let flanks: Vec<_> = suffix_array.map_peptides(opts)?[0]
+            .hits
+            .iter()
+            .map(|hit| (hit.preceding, hit.following))
+            .collect();
+        assert_eq!(flanks, [('K', '-'), ('A', 'K')]);
+
+        // Peptides cannot map to reverse complements
+        let opts = MapPeptidesOptions {
+            peptides: vec!["ACGT".to_string()],
+            max_query_len: None,
+            low_memory: true,
+        };
+        let mut suffix_array = SuffixArray::in_memory(SufrBuilderArgs {
+            is_dna: true,
+            ..builder_args(true)
+        })?;
+        assert!(suffix_array.map_peptides(opts).is_err());
+
+        Ok(())
+    }
 }
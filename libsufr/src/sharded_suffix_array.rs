@@ -0,0 +1,637 @@
+//! Create and query suffix arrays split into shards
+//!
+//! A text of 2^32 characters or more needs `u64` suffix/LCP arrays,
+//! which are twice the size of `u32` arrays. A sharded index instead
+//! splits the sequences into several _.sufr_ files that each use `u32`
+//! integers and lists them in a JSON manifest. Queries fan out across
+//! the shards in parallel, and the results are merged with the suffix
+//! positions of the whole text.
+use crate::{
+    suffix_array::SuffixArrayTrait,
+    sufr_builder::SufrBuilder,
+    sufr_file::SufrFile,
+    types::{
+        AppendOptions, BisectOptions, BisectResult, ComplexityOptions, ComplexityStats,
+        CountOptions, CountResult, DocumentResult, ExtractOptions, ExtractResult, Kmer,
+        ListOptions, LocateOptions, LocateResult, MatchingStatisticsOptions,
+        MatchingStatisticsResult, MemsOptions, MemsResult, Repeat, RepeatsOptions,
+        SufrBuilderArgs, SufrMetadata, OUTFILE_VERSION, SENTINEL_CHARACTER,
+    },
+};
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Local};
+use log::info;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::Read,
+    mem,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+// --------------------------------------------------
+/// The manifest of a sharded index, which is written as JSON
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShardManifest {
+    /// The serialization version of the shards
+    pub version: u8,
+
+    /// The shards in the order of their sequences
+    pub shards: Vec<Shard>,
+}
+
+// --------------------------------------------------
+/// A shard of a sharded index
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Shard {
+    /// The _.sufr_ filename, relative to the manifest
+    pub filename: String,
+
+    /// The position of the shard's text in the text of all the shards
+    pub text_offset: usize,
+
+    /// The length of the shard's text
+    pub text_len: usize,
+
+    /// The index of the shard's first sequence among all the sequences
+    pub sequence_offset: usize,
+
+    /// The number of sequences in the shard
+    pub num_sequences: usize,
+}
+
+// --------------------------------------------------
+/// A suffix array split into shards of `u32` _.sufr_ files
+pub struct ShardedSuffixArray {
+    /// The manifest filename
+    pub filename: String,
+
+    /// The manifest listing the shards
+    pub manifest: ShardManifest,
+
+    /// The suffix array of each shard
+    shards: Vec<SufrFile<u32>>,
+}
+
+// --------------------------------------------------
+impl ShardedSuffixArray {
+    /// Create a sharded index, writing each shard to a _.sufr_ file
+    /// beside the manifest. The sequences are divided in order so
+    /// that each shard's text is no longer than `max_shard_len`
+    /// (counting the reverse complements when indexing both strands)
+    /// and shorter than 2^32. A single sequence cannot be split.
+    /// Returns the manifest filename, which is the `path` of the
+    /// `args` or "out.shards".
+    ///
+    /// Args:
+    /// * `args`: the same arguments as for an unsharded index
+    /// * `max_shard_len`: the maximum length of the text of a shard
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{
+    ///     sharded_suffix_array::ShardedSuffixArray,
+    ///     types::SufrBuilderArgs,
+    ///     util::read_sequence_file,
+    /// };
+    /// use std::path::Path;
+    /// use tempfile::TempDir;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let seq_data = read_sequence_file(Path::new("../data/inputs/3.fa"), b'%')?;
+    ///     let dir = TempDir::new()?;
+    ///     let manifest = dir.path().join("3.shards");
+    ///     let args = SufrBuilderArgs {
+    ///         text: seq_data.seq,
+    ///         path: Some(manifest.to_string_lossy().to_string()),
+    ///         low_memory: true,
+    ///         max_query_len: None,
+    ///         is_dna: true,
+    ///         allow_ambiguity: false,
+    ///         ignore_softmask: false,
+    ///         sequence_starts: seq_data.start_positions,
+    ///         sequence_names: seq_data.sequence_names,
+    ///         num_partitions: 4,
+    ///         seed_masks: vec![],
+    ///         random_seed: 42,
+    ///         both_strands: false,
+    ///         alphabet: None,
+    ///         residue_map: None,
+    ///         suffix_start_filter: None,
+    ///         filler_runs: None,
+    ///     };
+    ///     let path = ShardedSuffixArray::write(args, 80)?;
+    ///     let sharded = ShardedSuffixArray::read(&path, true)?;
+    ///     assert_eq!(sharded.manifest.shards.len(), 2);
+    ///     assert_eq!(sharded.manifest.shards[0].filename, "3.0.sufr");
+    ///     assert_eq!(sharded.manifest.shards[0].num_sequences, 2);
+    ///     assert_eq!(sharded.manifest.shards[1].text_offset, 72);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn write(mut args: SufrBuilderArgs, max_shard_len: usize) -> Result<String> {
+        // Take the text and sequences so that only the other (small)
+        // arguments are copied for each shard
+        let text = mem::take(&mut args.text);
+        let sequence_starts = mem::take(&mut args.sequence_starts);
+        let sequence_names = mem::take(&mut args.sequence_names);
+        if text.last() != Some(&SENTINEL_CHARACTER) {
+            bail!("Text must end with the sentinel character");
+        }
+        if sequence_starts.is_empty() {
+            bail!("No sequences to index");
+        }
+
+        // The reverse complements double the length of each shard's text
+        let strands = if args.both_strands { 2 } else { 1 };
+        let max_len = max_shard_len.min(u32::MAX as usize - 1) / strands;
+        let path = args.path.clone().unwrap_or("out.shards".to_string());
+        let manifest_path = PathBuf::from(&path);
+        let dir = manifest_path.parent().unwrap_or(Path::new(""));
+        let stem = manifest_path
+            .file_stem()
+            .map_or("out".to_string(), |stem| stem.to_string_lossy().to_string());
+
+        // Each shard holds the sequences from `first` up to `last`
+        // and ends with a sentinel in place of the next delimiter
+        let num_sequences = sequence_starts.len();
+        let seq_end =
+            |i: usize| sequence_starts.get(i + 1).map_or(text.len(), |next| *next) - 1;
+        let mut ranges = vec![];
+        let mut first = 0;
+        for i in 0..num_sequences {
+            let start = sequence_starts[i];
+            if seq_end(i) - start + 1 > max_len {
+                bail!(
+                    "Sequence \"{}\" is longer than the maximum shard length",
+                    sequence_names.get(i).map_or("", |name| name)
+                );
+            }
+            if i > first && seq_end(i) - sequence_starts[first] + 1 > max_len {
+                ranges.push(first..i);
+                first = i;
+            }
+        }
+        ranges.push(first..num_sequences);
+
+        let now = Instant::now();
+        let mut shards = vec![];
+        let mut text_offset = 0;
+        for (shard_num, range) in ranges.into_iter().enumerate() {
+            let text_start = sequence_starts[range.start];
+            let mut shard_text = text[text_start..seq_end(range.end - 1)].to_vec();
+            shard_text.push(SENTINEL_CHARACTER);
+            let filename = format!("{stem}.{shard_num}.sufr");
+            let shard_args = SufrBuilderArgs {
+                text: shard_text,
+                path: Some(dir.join(&filename).to_string_lossy().to_string()),
+                sequence_starts: sequence_starts[range.clone()]
+                    .iter()
+                    .map(|start| start - text_start)
+                    .collect(),
+                sequence_names: sequence_names[range.clone()].to_vec(),
+                ..args.clone()
+            };
+            let builder: SufrBuilder<u32> = SufrBuilder::new(shard_args)?;
+            let text_len = builder.text_len as usize;
+            shards.push(Shard {
+                filename,
+                text_offset,
+                text_len,
+                sequence_offset: range.start,
+                num_sequences: range.len(),
+            });
+            text_offset += text_len;
+        }
+
+        let manifest = ShardManifest {
+            version: OUTFILE_VERSION,
+            shards,
+        };
+        fs::write(&path, serde_json::to_string_pretty(&manifest)?)
+            .map_err(|e| anyhow!("{path}: {e}"))?;
+        info!(
+            "Wrote {} shard{} in {:?}",
+            manifest.shards.len(),
+            if manifest.shards.len() == 1 { "" } else { "s" },
+            now.elapsed()
+        );
+
+        Ok(path)
+    }
+
+    // --------------------------------------------------
+    /// Read the manifest of a sharded index and each of its shards
+    ///
+    /// Args:
+    /// * `filename`: the manifest
+    /// * `low_memory`: when `true`, leave the texts on disk;
+    ///   when `false`, read the texts into memory
+    pub fn read(filename: &str, low_memory: bool) -> Result<ShardedSuffixArray> {
+        let contents =
+            fs::read_to_string(filename).map_err(|e| anyhow!("{filename}: {e}"))?;
+        let manifest: ShardManifest = serde_json::from_str(&contents)
+            .map_err(|e| anyhow!("{filename}: invalid shard manifest: {e}"))?;
+        if manifest.version != OUTFILE_VERSION {
+            bail!("Unknown sufr version {}", manifest.version);
+        }
+
+        let dir = Path::new(filename).parent().unwrap_or(Path::new(""));
+        let shards = manifest
+            .shards
+            .iter()
+            .map(|shard| {
+                SufrFile::read(&dir.join(&shard.filename).to_string_lossy(), low_memory)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(ShardedSuffixArray {
+            filename: filename.to_string(),
+            manifest,
+            shards,
+        })
+    }
+
+    // --------------------------------------------------
+    /// Whether a file is the manifest of a sharded index rather than
+    /// a _.sufr_ file
+    ///
+    /// Args:
+    /// * `filename`: the file to check
+    pub fn is_manifest(filename: &str) -> Result<bool> {
+        let mut file = File::open(filename).map_err(|e| anyhow!("{filename}: {e}"))?;
+        let mut buffer = [0; 1];
+        file.read_exact(&mut buffer)?;
+        Ok(buffer[0] == b'{')
+    }
+
+    // --------------------------------------------------
+    /// Count the occurrences of queries in all the shards
+    ///
+    /// Args:
+    /// * `args`: `CountOptions`
+    pub fn count(&mut self, args: CountOptions) -> Result<Vec<CountResult>> {
+        self.search_shards(
+            |sufr, _| sufr.count(args.clone()),
+            |count, res| count.count += res.count,
+        )
+    }
+
+    // --------------------------------------------------
+    /// Extract the sequence contexts of queries from all the shards.
+    /// The suffixes and sequence starts are positions in the text of all
+    /// the shards, but the ranks are within each shard.
+    ///
+    /// Args:
+    /// * `args`: `ExtractOptions`
+    pub fn extract(&mut self, args: ExtractOptions) -> Result<Vec<ExtractResult>> {
+        self.search_shards(
+            |sufr, shard| {
+                let mut results = sufr.extract(args.clone())?;
+                for seq in results.iter_mut().flat_map(|res| res.sequences.iter_mut()) {
+                    seq.suffix += shard.text_offset;
+                    seq.sequence_start += shard.text_offset;
+                }
+                Ok(results)
+            },
+            |merged, res| merged.sequences.extend(res.sequences),
+        )
+    }
+
+    // --------------------------------------------------
+    /// Find the maximal exact matches of queries in all the shards,
+    /// which cannot span shards because they hold whole sequences.
+    /// A maximal unique match must also occur once among all the shards.
+    ///
+    /// Args:
+    /// * `args`: `MemsOptions`
+    pub fn mems(&mut self, args: MemsOptions) -> Result<Vec<MemsResult>> {
+        let shard_args = MemsOptions {
+            unique: false,
+            ..args.clone()
+        };
+        let mut found = self.search_shards(
+            |sufr, shard| {
+                let mut results = sufr.mems(shard_args.clone())?;
+                for mem in results.iter_mut().flat_map(|res| res.mems.iter_mut()) {
+                    mem.suffix += shard.text_offset;
+                }
+                Ok(results)
+            },
+            |merged, res| merged.mems.extend(res.mems),
+        )?;
+
+        for res in found.iter_mut() {
+            if args.unique {
+                let query = match &self.shards[0].residue_map {
+                    Some(residue_map) => residue_map.map_bytes(res.query.as_bytes()),
+                    _ => res.query.as_bytes().to_vec(),
+                };
+                let ranges: Vec<_> = res
+                    .mems
+                    .iter()
+                    .map(|mem| mem.query_position..mem.query_position + mem.length)
+                    .collect();
+                let counts = self.count(CountOptions {
+                    queries: ranges
+                        .iter()
+                        .map(|range| res.query[range.clone()].to_string())
+                        .collect(),
+                    max_query_len: None,
+                    low_memory: true,
+                })?;
+                let mut keep = ranges.iter().zip(counts).map(|(range, count)| {
+                    let substring = &query[range.clone()];
+                    count.count == 1
+                        && query
+                            .windows(substring.len())
+                            .filter(|window| window == &substring)
+                            .count()
+                            == 1
+                });
+                res.mems.retain(|_| keep.next().unwrap_or(false));
+            }
+            res.mems.sort_by_key(|mem| (mem.query_position, mem.suffix));
+        }
+        Ok(found)
+    }
+
+    // --------------------------------------------------
+    /// Locate queries in all the shards. The suffixes are positions in
+    /// the text of all the shards, but the ranks are within each shard.
+    ///
+    /// Args:
+    /// * `args`: `LocateOptions`
+    pub fn locate(&mut self, args: LocateOptions) -> Result<Vec<LocateResult>> {
+        self.search_shards(
+            |sufr, shard| {
+                let mut results = sufr.locate(args.clone())?;
+                for pos in results.iter_mut().flat_map(|res| res.positions.iter_mut()) {
+                    pos.suffix += shard.text_offset;
+                }
+                Ok(results)
+            },
+            |merged, res| merged.positions.extend(res.positions),
+        )
+    }
+
+    // --------------------------------------------------
+    /// List the sequences containing queries in all the shards. The
+    /// sequence numbers are among the sequences of all the shards.
+    ///
+    /// Args:
+    /// * `args`: `LocateOptions`
+    pub fn list_documents(
+        &mut self,
+        args: LocateOptions,
+    ) -> Result<Vec<DocumentResult>> {
+        self.search_shards(
+            |sufr, shard| {
+                let mut results = sufr.list_documents(args.clone())?;
+                for doc in results.iter_mut().flat_map(|res| res.documents.iter_mut()) {
+                    doc.sequence_num += shard.sequence_offset;
+                }
+                Ok(results)
+            },
+            |merged, res| merged.documents.extend(res.documents),
+        )
+    }
+
+    // --------------------------------------------------
+    /// Summarize the shards as one index. The sequence starts are
+    /// positions in the text of all the shards, and the file size
+    /// includes the manifest.
+    pub fn metadata(&self) -> Result<SufrMetadata> {
+        let mut shard_meta = self
+            .shards
+            .iter()
+            .map(|shard| shard.metadata())
+            .collect::<Result<Vec<_>>>()?;
+        let fs_meta = fs::metadata(&self.filename)?;
+        let modified: DateTime<Local> = DateTime::from(fs_meta.modified()?);
+
+        // With both strands, each shard's reverse complements follow
+        // its sequences, but all the reverse complements come last here
+        let mut fwd_starts = vec![];
+        let mut rc_starts = vec![];
+        for (shard, meta) in self.manifest.shards.iter().zip(&shard_meta) {
+            for (i, start) in meta.sequence_starts.iter().enumerate() {
+                if i < meta.sequence_names.len() {
+                    fwd_starts.push(start + shard.text_offset);
+                } else {
+                    rc_starts.push(start + shard.text_offset);
+                }
+            }
+        }
+        fwd_starts.extend(rc_starts);
+
+        let mut meta = shard_meta.remove(0);
+        for other in shard_meta {
+            meta.file_size += other.file_size;
+            meta.text_len += other.text_len;
+            meta.len_suffixes += other.len_suffixes;
+            meta.num_sequences += other.num_sequences;
+            meta.sequence_names.extend(other.sequence_names);
+            meta.sequence_metadata.extend(other.sequence_metadata);
+            meta.removed_sequences.extend(other.removed_sequences);
+        }
+        meta.filename = self.filename.clone();
+        meta.modified = modified;
+        meta.file_size += fs_meta.len() as usize;
+        meta.sequence_starts = fwd_starts;
+
+        // Each shard was partitioned and sorted on its own
+        meta.build_summary = None;
+        Ok(meta)
+    }
+
+    // --------------------------------------------------
+    /// Retrieve a suffix, which ends with the sentinel or the end
+    /// of its shard
+    ///
+    /// Args:
+    /// * `pos`: the position in the text of all the shards
+    /// * `len`: the maximum length of the suffix
+    pub fn string_at(&mut self, pos: usize, len: Option<usize>) -> Result<String> {
+        let (shard_num, offset) = self.find_shard(pos)?;
+        self.shards[shard_num].string_at(pos - offset, len)
+    }
+
+    // --------------------------------------------------
+    /// Remove sequences from the search results of the shards that hold
+    /// them (cf. [SufrFile::remove_sequences](super::sufr_file::SufrFile::remove_sequences))
+    ///
+    /// Args:
+    /// * `names`: the names of the sequences to remove
+    pub fn remove_sequences(&mut self, names: &[String]) -> Result<()> {
+        for name in names {
+            if !self
+                .shards
+                .iter()
+                .any(|shard| shard.sequence_names.contains(name))
+            {
+                bail!("Unknown sequence \"{name}\"");
+            }
+        }
+        for shard in self.shards.iter_mut() {
+            let shard_names: Vec<String> = names
+                .iter()
+                .filter(|name| shard.sequence_names.contains(name))
+                .cloned()
+                .collect();
+            if !shard_names.is_empty() {
+                shard.remove_sequences(&shard_names)?;
+            }
+        }
+        Ok(())
+    }
+
+    // --------------------------------------------------
+    /// Search every shard with LCP-LR arrays
+    /// (cf. [SufrFile::use_lcp_lr](super::sufr_file::SufrFile::use_lcp_lr))
+    ///
+    /// Args:
+    /// * `enabled`: whether to use the LCP-LR arrays
+    pub fn use_lcp_lr(&mut self, enabled: bool) {
+        for shard in self.shards.iter_mut() {
+            shard.use_lcp_lr(enabled);
+        }
+    }
+
+    // --------------------------------------------------
+    /// Run a search on every shard in parallel and merge the results of
+    /// each query across the shards, in the order of the shards.
+    ///
+    /// Args:
+    /// * `search`: searches one shard, given its place in the manifest
+    ///   to move its positions into the text of all the shards
+    /// * `merge`: adds a shard's result for a query to the merged result
+    fn search_shards<R, S, M>(&mut self, search: S, merge: M) -> Result<Vec<R>>
+    where
+        R: Send,
+        S: Fn(&mut SufrFile<u32>, &Shard) -> Result<Vec<R>> + Sync,
+        M: Fn(&mut R, R),
+    {
+        let shard_results = self
+            .shards
+            .par_iter_mut()
+            .zip(&self.manifest.shards)
+            .map(|(sufr, shard)| search(sufr, shard))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut merged: Vec<R> = vec![];
+        for results in shard_results {
+            if merged.is_empty() {
+                merged = results;
+            } else {
+                for (merged, res) in merged.iter_mut().zip(results) {
+                    merge(merged, res);
+                }
+            }
+        }
+        Ok(merged)
+    }
+
+    // --------------------------------------------------
+    /// Find the shard holding a position in the text of all the shards.
+    /// Returns the shard number and its text offset.
+    ///
+    /// Args:
+    /// * `pos`: the position in the text of all the shards
+    fn find_shard(&self, pos: usize) -> Result<(usize, usize)> {
+        let shards = &self.manifest.shards;
+        let i = shards.partition_point(|shard| shard.text_offset <= pos);
+        match i.checked_sub(1).and_then(|i| shards.get(i).map(|s| (i, s))) {
+            Some((i, shard)) if pos < shard.text_offset + shard.text_len => {
+                Ok((i, shard.text_offset))
+            }
+            _ => bail!("Position {pos} is past the end of the text"),
+        }
+    }
+}
+
+// --------------------------------------------------
+impl SuffixArrayTrait for ShardedSuffixArray {
+    fn count(&mut self, args: CountOptions) -> Result<Vec<CountResult>> {
+        self.count(args)
+    }
+
+    fn extract(&mut self, args: ExtractOptions) -> Result<Vec<ExtractResult>> {
+        self.extract(args)
+    }
+
+    fn metadata(&self) -> Result<SufrMetadata> {
+        self.metadata()
+    }
+
+    fn list(&mut self, _args: ListOptions) -> Result<()> {
+        bail!("Cannot list the suffixes of a sharded index")
+    }
+
+    fn locate(&mut self, args: LocateOptions) -> Result<Vec<LocateResult>> {
+        self.locate(args)
+    }
+
+    fn list_documents(&mut self, args: LocateOptions) -> Result<Vec<DocumentResult>> {
+        self.list_documents(args)
+    }
+
+    fn string_at(&mut self, pos: usize, len: Option<usize>) -> Result<String> {
+        self.string_at(pos, len)
+    }
+
+    fn bisect(&mut self, _args: BisectOptions) -> Result<Vec<BisectResult>> {
+        bail!("Cannot bisect a sharded index")
+    }
+
+    fn save(&self, _path: &str) -> Result<()> {
+        bail!("Cannot save a sharded index to one file")
+    }
+
+    fn append(&mut self, _args: AppendOptions) -> Result<()> {
+        bail!("Cannot append to a sharded index")
+    }
+
+    fn remove_sequences(&mut self, names: &[String]) -> Result<()> {
+        self.remove_sequences(names)
+    }
+
+    fn compact(&mut self, _num_partitions: usize) -> Result<()> {
+        bail!("Cannot compact a sharded index")
+    }
+
+    fn use_lcp_lr(&mut self, enabled: bool) {
+        self.use_lcp_lr(enabled)
+    }
+
+    fn mems(&mut self, args: MemsOptions) -> Result<Vec<MemsResult>> {
+        self.mems(args)
+    }
+
+    fn matching_statistics(
+        &mut self,
+        _args: MatchingStatisticsOptions,
+    ) -> Result<Vec<MatchingStatisticsResult>> {
+        bail!("Cannot compute matching statistics of a sharded index")
+    }
+
+    fn repeats(&mut self, _args: RepeatsOptions) -> Result<Vec<Repeat>> {
+        bail!("Cannot find repeats in a sharded index")
+    }
+
+    fn kmers(
+        &mut self,
+        _k: usize,
+    ) -> Result<Box<dyn Iterator<Item = Result<Kmer>> + '_>> {
+        bail!("Cannot count k-mers in a sharded index")
+    }
+
+    fn complexity_stats(
+        &mut self,
+        _args: ComplexityOptions,
+    ) -> Result<ComplexityStats> {
+        bail!("Cannot compute complexity statistics of a sharded index")
+    }
+}
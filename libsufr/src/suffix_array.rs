@@ -1,13 +1,23 @@
 //! Create and query suffix arrays
 use crate::{
+    sharded_suffix_array::ShardedSuffixArray,
     sufr_builder::SufrBuilder,
     sufr_file::SufrFile,
     types::{
-        BisectOptions, BisectResult, CountOptions, CountResult, ExtractOptions, ExtractResult, ListOptions,
-        LocateOptions, LocateResult, SufrBuilderArgs, SufrMetadata,
+        AppendOptions, BisectOptions, BisectResult, BuildStats, CommonOptions,
+        CommonSubstring, ComplexityOptions, ComplexityStats, CountOptions, CountResult,
+        DocumentResult, ExtractOptions, ExtractResult, Kmer, ListOptions,
+        LocateOptions, LocateResult, MapPeptidesOptions, MatchingStatisticsOptions,
+        MatchingStatisticsResult, Mem, MemsOptions, MemsResult, PeptideClass,
+        PeptideHit, PeptideMapping, Repeat, RepeatsOptions, SufrBuilderArgs,
+        SufrMetadata,
     },
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+};
 
 // --------------------------------------------------
 pub(crate) trait SuffixArrayTrait: Send + Sync {
@@ -15,9 +25,26 @@ pub(crate) trait SuffixArrayTrait: Send + Sync {
     fn extract(&mut self, args: ExtractOptions) -> Result<Vec<ExtractResult>>;
     fn list(&mut self, args: ListOptions) -> Result<()>;
     fn locate(&mut self, args: LocateOptions) -> Result<Vec<LocateResult>>;
+    fn list_documents(&mut self, args: LocateOptions) -> Result<Vec<DocumentResult>>;
     fn metadata(&self) -> Result<SufrMetadata>;
     fn string_at(&mut self, pos: usize, len: Option<usize>) -> Result<String>;
-    fn bisect(&mut self, args: BisectOptions) -> Result<Vec<BisectResult>>; 
+    fn bisect(&mut self, args: BisectOptions) -> Result<Vec<BisectResult>>;
+    fn save(&self, path: &str) -> Result<()>;
+    fn append(&mut self, args: AppendOptions) -> Result<()>;
+    fn remove_sequences(&mut self, names: &[String]) -> Result<()>;
+    fn compact(&mut self, num_partitions: usize) -> Result<()>;
+    fn use_lcp_lr(&mut self, enabled: bool);
+    fn mems(&mut self, args: MemsOptions) -> Result<Vec<MemsResult>>;
+    fn matching_statistics(
+        &mut self,
+        args: MatchingStatisticsOptions,
+    ) -> Result<Vec<MatchingStatisticsResult>>;
+    fn repeats(&mut self, args: RepeatsOptions) -> Result<Vec<Repeat>>;
+    fn kmers(
+        &mut self,
+        k: usize,
+    ) -> Result<Box<dyn Iterator<Item = Result<Kmer>> + '_>>;
+    fn complexity_stats(&mut self, args: ComplexityOptions) -> Result<ComplexityStats>;
 }
 
 // --------------------------------------------------
@@ -46,6 +73,10 @@ impl SuffixArrayTrait for SuffixArray32 {
         self.inner.locate(args)
     }
 
+    fn list_documents(&mut self, args: LocateOptions) -> Result<Vec<DocumentResult>> {
+        self.inner.list_documents(args)
+    }
+
     fn string_at(&mut self, pos: usize, len: Option<usize>) -> Result<String> {
         self.inner.string_at(pos, len)
     }
@@ -53,6 +84,52 @@ impl SuffixArrayTrait for SuffixArray32 {
     fn bisect(&mut self, args: BisectOptions) -> Result<Vec<BisectResult>> {
         self.inner.bisect(args)
     }
+
+    fn save(&self, path: &str) -> Result<()> {
+        self.inner.save(path)
+    }
+
+    fn append(&mut self, args: AppendOptions) -> Result<()> {
+        self.inner.append(args)
+    }
+
+    fn remove_sequences(&mut self, names: &[String]) -> Result<()> {
+        self.inner.remove_sequences(names)
+    }
+
+    fn compact(&mut self, num_partitions: usize) -> Result<()> {
+        self.inner.compact(num_partitions)
+    }
+
+    fn use_lcp_lr(&mut self, enabled: bool) {
+        self.inner.use_lcp_lr(enabled)
+    }
+
+    fn mems(&mut self, args: MemsOptions) -> Result<Vec<MemsResult>> {
+        self.inner.mems(args)
+    }
+
+    fn matching_statistics(
+        &mut self,
+        args: MatchingStatisticsOptions,
+    ) -> Result<Vec<MatchingStatisticsResult>> {
+        self.inner.matching_statistics(args)
+    }
+
+    fn repeats(&mut self, args: RepeatsOptions) -> Result<Vec<Repeat>> {
+        self.inner.repeats(args)
+    }
+
+    fn kmers(
+        &mut self,
+        k: usize,
+    ) -> Result<Box<dyn Iterator<Item = Result<Kmer>> + '_>> {
+        Ok(Box::new(self.inner.kmers(k)?))
+    }
+
+    fn complexity_stats(&mut self, args: ComplexityOptions) -> Result<ComplexityStats> {
+        self.inner.complexity_stats(args)
+    }
 }
 
 pub(crate) struct SuffixArray64 {
@@ -81,6 +158,10 @@ impl SuffixArrayTrait for SuffixArray64 {
         self.inner.locate(args)
     }
 
+    fn list_documents(&mut self, args: LocateOptions) -> Result<Vec<DocumentResult>> {
+        self.inner.list_documents(args)
+    }
+
     fn string_at(&mut self, pos: usize, len: Option<usize>) -> Result<String> {
         self.inner.string_at(pos, len)
     }
@@ -88,6 +169,52 @@ impl SuffixArrayTrait for SuffixArray64 {
     fn bisect(&mut self, args: BisectOptions) -> Result<Vec<BisectResult>> {
         self.inner.bisect(args)
     }
+
+    fn save(&self, path: &str) -> Result<()> {
+        self.inner.save(path)
+    }
+
+    fn append(&mut self, args: AppendOptions) -> Result<()> {
+        self.inner.append(args)
+    }
+
+    fn remove_sequences(&mut self, names: &[String]) -> Result<()> {
+        self.inner.remove_sequences(names)
+    }
+
+    fn compact(&mut self, num_partitions: usize) -> Result<()> {
+        self.inner.compact(num_partitions)
+    }
+
+    fn use_lcp_lr(&mut self, enabled: bool) {
+        self.inner.use_lcp_lr(enabled)
+    }
+
+    fn mems(&mut self, args: MemsOptions) -> Result<Vec<MemsResult>> {
+        self.inner.mems(args)
+    }
+
+    fn matching_statistics(
+        &mut self,
+        args: MatchingStatisticsOptions,
+    ) -> Result<Vec<MatchingStatisticsResult>> {
+        self.inner.matching_statistics(args)
+    }
+
+    fn repeats(&mut self, args: RepeatsOptions) -> Result<Vec<Repeat>> {
+        self.inner.repeats(args)
+    }
+
+    fn kmers(
+        &mut self,
+        k: usize,
+    ) -> Result<Box<dyn Iterator<Item = Result<Kmer>> + '_>> {
+        Ok(Box::new(self.inner.kmers(k)?))
+    }
+
+    fn complexity_stats(&mut self, args: ComplexityOptions) -> Result<ComplexityStats> {
+        self.inner.complexity_stats(args)
+    }
 }
 
 // --------------------------------------------------
@@ -125,8 +252,13 @@ impl SuffixArray {
     ///         sequence_starts: seq_data.start_positions.into_iter().collect(),
     ///         sequence_names: seq_data.sequence_names,
     ///         num_partitions: 16,
-    ///         seed_mask: None,
+    ///         seed_masks: vec![],
     ///         random_seed: 42,
+    ///         both_strands: false,
+    ///         alphabet: None,
+    ///         residue_map: None,
+    ///         suffix_start_filter: None,
+    ///         filler_runs: None,
     ///     };
     ///
     ///     let suffix_array = SuffixArray::new(builder_args)?;
@@ -146,7 +278,6 @@ impl SuffixArray {
         Self::read(&path, low_memory)
     }
 
-
     // --------------------------------------------------
     /// Bisect the index range of occurences of queries.
     /// If the index range of a prefix is already known,
@@ -155,7 +286,7 @@ impl SuffixArray {
     /// ```
     /// use anyhow::Result;
     /// use libsufr::{types::{CountOptions, CountResult, BisectOptions, BisectResult}, suffix_array::SuffixArray};
-    /// 
+    ///
     /// fn main() -> Result<()> {
     ///     let mut sufr = SuffixArray::read("../data/inputs/3.sufr", false)?;
     ///     let K = 5;
@@ -256,7 +387,7 @@ impl SuffixArray {
     /// use anyhow::Result;
     /// use libsufr::{
     ///     suffix_array::SuffixArray,
-    ///     types::{ExtractOptions, ExtractResult, ExtractSequence}
+    ///     types::{ExtractOptions, ExtractResult, ExtractSequence, Strand}
     /// };
     ///
     /// fn main() -> Result<()> {
@@ -276,18 +407,22 @@ impl SuffixArray {
     ///                 ExtractSequence {
     ///                     suffix: 7,
     ///                     rank: 3,
+    ///                     mask_num: 0,
     ///                     sequence_name: "1".to_string(),
     ///                     sequence_start: 0,
     ///                     sequence_range: 6..11,
     ///                     suffix_offset: 1,
+    ///                     strand: Strand::Forward,
     ///                 },
     ///                 ExtractSequence {
     ///                     suffix: 1,
     ///                     rank: 4,
+    ///                     mask_num: 0,
     ///                     sequence_name: "1".to_string(),
     ///                     sequence_start: 0,
     ///                     sequence_range: 0..11,
     ///                     suffix_offset: 1,
+    ///                     strand: Strand::Forward,
     ///                 },
     ///             ],
     ///         },
@@ -363,7 +498,7 @@ impl SuffixArray {
     /// use anyhow::Result;
     /// use libsufr::{
     ///     suffix_array::SuffixArray,
-    ///     types::{LocateOptions, LocatePosition, LocateResult},
+    ///     types::{LocateOptions, LocatePosition, LocateResult, Strand},
     /// };
     ///
     /// fn main() -> Result<()> {
@@ -381,14 +516,20 @@ impl SuffixArray {
     ///                 LocatePosition {
     ///                     suffix: 6,
     ///                     rank: 1,
+    ///                     mask_num: 0,
     ///                     sequence_name: "1".to_string(),
     ///                     sequence_position: 6,
+    ///                     strand: Strand::Forward,
+    ///                     sequence_metadata: None,
     ///                 },
     ///                 LocatePosition {
     ///                     suffix: 0,
     ///                     rank: 2,
+    ///                     mask_num: 0,
     ///                     sequence_name: "1".to_string(),
     ///                     sequence_position: 0,
+    ///                     strand: Strand::Forward,
+    ///                     sequence_metadata: None,
     ///                 },
     ///             ],
     ///         },
@@ -408,6 +549,502 @@ impl SuffixArray {
         self.inner.locate(args)
     }
 
+    // --------------------------------------------------
+    /// Find the sequences that contain queries with the number of hits
+    /// in each, without enumerating the hits
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{
+    ///     suffix_array::SuffixArray,
+    ///     types::{DocumentCount, LocateOptions},
+    /// };
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut suffix_array = SuffixArray::read("../data/inputs/3.sufr", true)?;
+    ///     let opts = LocateOptions {
+    ///         queries: vec!["AAAA".to_string()],
+    ///         max_query_len: None,
+    ///         low_memory: true,
+    ///     };
+    ///     let res = suffix_array.list_documents(opts)?;
+    ///     let expected = vec![DocumentCount {
+    ///         sequence_num: 1,
+    ///         sequence_name: "2".to_string(),
+    ///         count: 4,
+    ///     }];
+    ///     assert_eq!(expected, res[0].documents);
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    pub fn list_documents(
+        &mut self,
+        args: LocateOptions,
+    ) -> Result<Vec<DocumentResult>> {
+        self.inner.list_documents(args)
+    }
+
+    // --------------------------------------------------
+    /// Find the maximal exact matches (MEMs) of queries of at least a
+    /// minimum length, or only the maximal unique matches (MUMs).
+    /// Requires a fully sorted suffix array.
+    ///
+    /// Args:
+    /// * `args`: `MemsOptions`
+    ///
+    /// Given a text of "ACGTNNACGT":
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{suffix_array::SuffixArray, types::MemsOptions};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut suffix_array = SuffixArray::read("../data/inputs/1.sufr", true)?;
+    ///     let opts = |unique| MemsOptions {
+    ///         queries: vec!["CCGTGG".to_string()],
+    ///         min_len: 2,
+    ///         unique,
+    ///     };
+    ///
+    ///     // "CGT" occurs twice, and "GT" only extends to the left
+    ///     let res = suffix_array.mems(opts(false))?;
+    ///     let found: Vec<_> = res[0]
+    ///         .mems
+    ///         .iter()
+    ///         .map(|mem| (mem.query_position, mem.suffix, mem.length))
+    ///         .collect();
+    ///     assert_eq!(found, [(1, 1, 3), (1, 7, 3)]);
+    ///     assert!(suffix_array.mems(opts(true))?[0].mems.is_empty());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn mems(&mut self, args: MemsOptions) -> Result<Vec<MemsResult>> {
+        self.inner.mems(args)
+    }
+
+    // --------------------------------------------------
+    /// Compute the length of the longest prefix of each suffix of
+    /// queries that occurs in the text, along with the ranks of the
+    /// suffixes that begin with it
+    ///
+    /// Args:
+    /// * `args`: `MatchingStatisticsOptions`
+    ///
+    /// Given a text of "ACGTNNACGT":
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{suffix_array::SuffixArray, types::MatchingStatisticsOptions};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut suffix_array = SuffixArray::read("../data/inputs/1.sufr", true)?;
+    ///     let opts = MatchingStatisticsOptions {
+    ///         queries: vec!["TACGTT".to_string()],
+    ///         max_query_len: None,
+    ///         low_memory: true,
+    ///     };
+    ///     let res = suffix_array.matching_statistics(opts)?;
+    ///     let lengths: Vec<_> = res[0].statistics.iter().map(|s| s.length).collect();
+    ///     assert_eq!(lengths, [1, 4, 3, 2, 1, 1]);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn matching_statistics(
+        &mut self,
+        args: MatchingStatisticsOptions,
+    ) -> Result<Vec<MatchingStatisticsResult>> {
+        self.inner.matching_statistics(args)
+    }
+
+    // --------------------------------------------------
+    /// Find the repeated substrings of the text, longest first. Each
+    /// repeat is maximal, i.e., it cannot be extended in either direction
+    /// without losing an occurrence, and a supermaximal repeat also does
+    /// not occur within another repeat. Requires a suffix array sorted
+    /// without a maximum query length.
+    ///
+    /// Args:
+    /// * `args`: `RepeatsOptions`
+    ///
+    /// Given a text of "ACGTNNACGT":
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{suffix_array::SuffixArray, types::RepeatsOptions};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut suffix_array = SuffixArray::read("../data/inputs/1.sufr", true)?;
+    ///     let opts = RepeatsOptions {
+    ///         min_len: 1,
+    ///         supermaximal: true,
+    ///         max_repeats: Some(10),
+    ///     };
+    ///     let repeats = suffix_array.repeats(opts)?;
+    ///     let found: Vec<_> = repeats
+    ///         .iter()
+    ///         .map(|r| (r.sequence.as_str(), r.positions.len()))
+    ///         .collect();
+    ///     assert_eq!(found, [("ACGT", 2)]);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn repeats(&mut self, args: RepeatsOptions) -> Result<Vec<Repeat>> {
+        self.inner.repeats(args)
+    }
+
+    // --------------------------------------------------
+    /// Iterate over the distinct k-mers of the text with their counts
+    /// and ranks in suffix array order, streaming the suffix and LCP
+    /// arrays once. K-mers that span sequences or hold an ambiguous
+    /// character are skipped. Requires a suffix array sorted to at
+    /// least `k` characters.
+    ///
+    /// Args:
+    /// * `k`: the length of the k-mers
+    ///
+    /// Given a text of "ACGTNNACGT":
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::suffix_array::SuffixArray;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut suffix_array = SuffixArray::read("../data/inputs/1.sufr", true)?;
+    ///     let counts = suffix_array
+    ///         .kmers(3)?
+    ///         .map(|kmer| kmer.map(|kmer| (kmer.kmer, kmer.count)))
+    ///         .collect::<Result<Vec<_>>>()?;
+    ///     assert_eq!(counts, [("ACG".to_string(), 2), ("CGT".to_string(), 2)]);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn kmers(
+        &mut self,
+        k: usize,
+    ) -> Result<Box<dyn Iterator<Item = Result<Kmer>> + '_>> {
+        self.inner.kmers(k)
+    }
+
+    // --------------------------------------------------
+    /// Compute the complexity of the text from the suffix and LCP
+    /// arrays: the number of distinct substrings by length and overall,
+    /// the fraction of positions within repeats, and the linguistic
+    /// complexity of each sequence
+    /// (cf. [SufrFile::complexity_stats](super::sufr_file::SufrFile::complexity_stats))
+    ///
+    /// Args:
+    /// * `args`: `ComplexityOptions`
+    ///
+    /// Given a text of "ACGTNNACGT":
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{suffix_array::SuffixArray, types::ComplexityOptions};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut suffix_array = SuffixArray::read("../data/inputs/1.sufr", true)?;
+    ///     let stats = suffix_array.complexity_stats(ComplexityOptions {
+    ///         max_len: 2,
+    ///         min_repeat_len: 4,
+    ///     })?;
+    ///     assert_eq!(stats.distinct_by_len, [4, 4]);
+    ///     assert_eq!(stats.repeat_coverage, 0.8);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn complexity_stats(
+        &mut self,
+        args: ComplexityOptions,
+    ) -> Result<ComplexityStats> {
+        self.inner.complexity_stats(args)
+    }
+
+    // --------------------------------------------------
+    /// Find the substrings that other sequences share with the text,
+    /// longest first. Each is a maximal exact match (cf. `mems`) of at
+    /// least the minimum length, so the longest common substrings come
+    /// first. To compare two sets of sequences in one suffix array,
+    /// use the sequences of one set and exclude them from the text.
+    ///
+    /// Args:
+    /// * `args`: `CommonOptions`
+    ///
+    /// Given the sequences "ACGTACGT" (ABC) and "ACGTACGT" (DEF):
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{suffix_array::SuffixArray, types::CommonOptions};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut suffix_array = SuffixArray::read("../data/inputs/2.sufr", true)?;
+    ///     let sequences = suffix_array
+    ///         .sequences()?
+    ///         .into_iter()
+    ///         .filter(|(name, _)| name == "ABC")
+    ///         .collect();
+    ///     let opts = CommonOptions {
+    ///         sequences,
+    ///         exclude: vec!["ABC".to_string()],
+    ///         min_len: 4,
+    ///         max_results: None,
+    ///     };
+    ///     let common: Vec<_> = suffix_array
+    ///         .common(opts)?
+    ///         .into_iter()
+    ///         .map(|c| (c.query_position, c.sequence_name, c.sequence_position, c.length))
+    ///         .collect();
+    ///     assert_eq!(
+    ///         common,
+    ///         [
+    ///             (0, "DEF".to_string(), 0, 8),
+    ///             (0, "DEF".to_string(), 4, 4),
+    ///             (4, "DEF".to_string(), 0, 4),
+    ///         ]
+    ///     );
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn common(&mut self, args: CommonOptions) -> Result<Vec<CommonSubstring>> {
+        let (names, queries): (Vec<_>, Vec<_>) = args.sequences.into_iter().unzip();
+        let mems_args = MemsOptions {
+            queries,
+            min_len: args.min_len,
+            unique: false,
+        };
+        let exclude: HashSet<&String> = args.exclude.iter().collect();
+        let mut found: Vec<(usize, Mem)> = vec![];
+        for res in self.mems(mems_args)? {
+            found.extend(
+                res.mems
+                    .into_iter()
+                    .filter(|mem| !exclude.contains(&mem.sequence_name))
+                    .map(|mem| (res.query_num, mem)),
+            );
+        }
+
+        // The longest first, then in the order of the matches
+        found.sort_by_key(|(_, mem)| Reverse(mem.length));
+        if let Some(max_results) = args.max_results {
+            found.truncate(max_results);
+        }
+
+        found
+            .into_iter()
+            .map(|(query_num, mem)| {
+                Ok(CommonSubstring {
+                    sequence: self.string_at(mem.suffix, Some(mem.length))?,
+                    length: mem.length,
+                    query_name: names[query_num].clone(),
+                    query_position: mem.query_position,
+                    suffix: mem.suffix,
+                    sequence_name: mem.sequence_name,
+                    sequence_position: mem.sequence_position,
+                    strand: mem.strand,
+                })
+            })
+            .collect()
+    }
+
+    // --------------------------------------------------
+    /// The names and sequences in the suffix array, leaving out any
+    /// removed sequences and reverse complements
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::suffix_array::SuffixArray;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut suffix_array = SuffixArray::read("../data/inputs/2.sufr", true)?;
+    ///     assert_eq!(
+    ///         suffix_array.sequences()?,
+    ///         [
+    ///             ("ABC".to_string(), "ACGTACGT".to_string()),
+    ///             ("DEF".to_string(), "ACGTACGT".to_string()),
+    ///         ]
+    ///     );
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn sequences(&mut self) -> Result<Vec<(String, String)>> {
+        let meta = self.metadata()?;
+        let removed: HashSet<&String> = meta.removed_sequences.iter().collect();
+        let mut starts = meta.sequence_starts.clone();
+        starts.sort();
+
+        // Each sequence ends with a delimiter before the next one
+        let mut sequences = vec![];
+        for (name, &start) in meta.sequence_names.iter().zip(&meta.sequence_starts) {
+            if removed.contains(name) {
+                continue;
+            }
+            let i = starts.partition_point(|&other| other <= start);
+            let end = starts.get(i).map_or(meta.text_len, |&next| next) - 1;
+            sequences.push((name.clone(), self.string_at(start, Some(end - start))?));
+        }
+        Ok(sequences)
+    }
+
+    // --------------------------------------------------
+    /// Map peptides to the proteins that contain them with the residues
+    /// on either side of each hit. A peptide found in one protein is
+    /// unique to it. A peptide found in several proteins is the razor
+    /// peptide of the one with the most of the peptides (then the most
+    /// unique peptides, then the first in the index) and is shared
+    /// with the others.
+    ///
+    /// Args:
+    /// * `args`: `MapPeptidesOptions`
+    ///
+    /// Given the sequences "ACGTACGT" (ABC) and "ACGTACGT" (DEF):
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{
+    ///     suffix_array::SuffixArray,
+    ///     types::{MapPeptidesOptions, PeptideClass},
+    /// };
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut suffix_array = SuffixArray::read("../data/inputs/2.sufr", true)?;
+    ///     let opts = MapPeptidesOptions {
+    ///         peptides: vec!["CGTA".to_string()],
+    ///         max_query_len: None,
+    ///         low_memory: true,
+    ///     };
+    ///     let hits: Vec<_> = suffix_array.map_peptides(opts)?[0]
+    ///         .hits
+    ///         .iter()
+    ///         .map(|hit| (hit.sequence_name.clone(), hit.preceding, hit.following, hit.class))
+    ///         .collect();
+    ///     assert_eq!(
+    ///         hits,
+    ///         [
+    ///             ("ABC".to_string(), 'A', 'C', PeptideClass::Razor),
+    ///             ("DEF".to_string(), 'A', 'C', PeptideClass::Shared),
+    ///         ]
+    ///     );
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn map_peptides(
+        &mut self,
+        args: MapPeptidesOptions,
+    ) -> Result<Vec<PeptideMapping>> {
+        let meta = self.metadata()?;
+        if meta.both_strands {
+            bail!("Cannot map peptides to an index of both strands")
+        }
+        let sequence_nums: HashMap<&String, usize> = meta
+            .sequence_names
+            .iter()
+            .enumerate()
+            .map(|(num, name)| (name, num))
+            .collect();
+        let located = self.locate(LocateOptions {
+            queries: args.peptides,
+            max_query_len: args.max_query_len,
+            low_memory: args.low_memory,
+        })?;
+
+        // The distinct proteins of each peptide in the order of the index
+        let proteins: Vec<Vec<usize>> = located
+            .iter()
+            .map(|res| {
+                let mut nums: Vec<usize> = res
+                    .positions
+                    .iter()
+                    .map(|pos| sequence_nums[&pos.sequence_name])
+                    .collect();
+                nums.sort_unstable();
+                nums.dedup();
+                nums
+            })
+            .collect();
+
+        // The number of distinct peptides and unique peptides of each protein
+        let mut num_peptides: HashMap<usize, (usize, usize)> = HashMap::new();
+        let mut seen: HashSet<&String> = HashSet::new();
+        for (res, nums) in located.iter().zip(&proteins) {
+            if seen.insert(&res.query) {
+                for num in nums {
+                    let counts = num_peptides.entry(*num).or_default();
+                    counts.0 += 1;
+                    if nums.len() == 1 {
+                        counts.1 += 1;
+                    }
+                }
+            }
+        }
+
+        let mut mappings = vec![];
+        for (res, nums) in located.into_iter().zip(proteins) {
+            let razor = nums
+                .iter()
+                .max_by_key(|num| (num_peptides[num], Reverse(**num)))
+                .copied();
+            let len = res.query.len();
+            let mut hits = vec![];
+            for pos in res.positions {
+                let num = sequence_nums[&pos.sequence_name];
+                let class = if nums.len() == 1 {
+                    PeptideClass::Unique
+                } else if Some(num) == razor {
+                    PeptideClass::Razor
+                } else {
+                    PeptideClass::Shared
+                };
+
+                // The flanking residues stop at the protein's bounds,
+                // which are found from the sequence starts rather than
+                // the delimiter as any character may separate them
+                let seq_num = meta
+                    .sequence_starts
+                    .partition_point(|&start| start <= pos.suffix);
+                let seq_end = meta
+                    .sequence_starts
+                    .get(seq_num)
+                    .map_or(meta.text_len, |&next| next)
+                    - 1;
+                let preceding = if pos.sequence_position == 0 {
+                    '-'
+                } else {
+                    self.string_at(pos.suffix - 1, Some(1))?
+                        .chars()
+                        .next()
+                        .unwrap_or('-')
+                };
+                let following = if pos.suffix + len >= seq_end {
+                    '-'
+                } else {
+                    self.string_at(pos.suffix + len, Some(1))?
+                        .chars()
+                        .next()
+                        .unwrap_or('-')
+                };
+                hits.push((
+                    num,
+                    PeptideHit {
+                        suffix: pos.suffix,
+                        sequence_name: pos.sequence_name,
+                        sequence_position: pos.sequence_position,
+                        preceding,
+                        following,
+                        class,
+                    },
+                ));
+            }
+            hits.sort_by_key(|(num, hit)| (*num, hit.sequence_position));
+            mappings.push(PeptideMapping {
+                peptide_num: res.query_num,
+                peptide: res.query,
+                hits: hits.into_iter().map(|(_, hit)| hit).collect(),
+            });
+        }
+
+        Ok(mappings)
+    }
+
     // --------------------------------------------------
     /// Get suffix array metadata
     ///
@@ -419,8 +1056,8 @@ impl SuffixArray {
     ///     let mut suffix_array = SuffixArray::read("../data/inputs/1.sufr", true)?;
     ///     let meta = suffix_array.metadata()?;
     ///     assert_eq!(meta.filename, "../data/inputs/1.sufr".to_string());
-    ///     assert_eq!(meta.file_size, 172);
-    ///     assert_eq!(meta.file_version, 6);
+    ///     assert_eq!(meta.file_size, 279);
+    ///     assert_eq!(meta.file_version, 14);
     ///     assert_eq!(meta.is_dna, true);
     ///     assert_eq!(meta.allow_ambiguity, false);
     ///     assert_eq!(meta.ignore_softmask, false);
@@ -438,10 +1075,11 @@ impl SuffixArray {
         self.inner.metadata()
     }
 
-    /// Read a _.sufr_ file
+    /// Read a _.sufr_ file or the manifest of a sharded index
+    /// (cf. [ShardedSuffixArray](super::sharded_suffix_array::ShardedSuffixArray))
     ///
     /// Args:
-    /// * `filename`: the _.sufr_ file!
+    /// * `filename`: the _.sufr_ file or manifest
     /// * `low_memory`: when `true`, leave text on disk; when `false`, read text into memory
     ///
     /// ```
@@ -455,6 +1093,12 @@ impl SuffixArray {
     /// ```
     ///
     pub fn read(filename: &str, low_memory: bool) -> Result<SuffixArray> {
+        if ShardedSuffixArray::is_manifest(filename)? {
+            return Ok(SuffixArray {
+                inner: Box::new(ShardedSuffixArray::read(filename, low_memory)?),
+            });
+        }
+
         let text_len = crate::util::read_text_length(filename)? as u64;
         let sa: Box<dyn SuffixArrayTrait> = if text_len < u32::MAX as u64 {
             Box::new(SuffixArray32 {
@@ -468,6 +1112,252 @@ impl SuffixArray {
         Ok(SuffixArray { inner: sa })
     }
 
+    // --------------------------------------------------
+    /// Create a new suffix array that lives only in memory.
+    /// Unlike `new`, nothing is written to disk, neither the temporary
+    /// partitions nor the _.sufr_ file, and the `path` of the `args`
+    /// is ignored. The result has the same query methods and can be
+    /// written to disk later with `save`. This is meant for small texts.
+    ///
+    /// Args:
+    /// * `args`: the options for building the suffix array
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{
+    ///     types::{CountOptions, SufrBuilderArgs},
+    ///     suffix_array::SuffixArray,
+    /// };
+    ///
+    /// fn main() -> Result<()> {
+    ///     let builder_args = SufrBuilderArgs {
+    ///         text: b"ACGTNNACGT$".to_vec(),
+    ///         path: None,
+    ///         low_memory: false,
+    ///         max_query_len: None,
+    ///         is_dna: true,
+    ///         allow_ambiguity: false,
+    ///         ignore_softmask: false,
+    ///         sequence_starts: vec![0],
+    ///         sequence_names: vec!["1".to_string()],
+    ///         num_partitions: 2,
+    ///         seed_masks: vec![],
+    ///         random_seed: 42,
+    ///         both_strands: false,
+    ///         alphabet: None,
+    ///         residue_map: None,
+    ///         suffix_start_filter: None,
+    ///         filler_runs: None,
+    ///     };
+    ///
+    ///     let mut suffix_array = SuffixArray::in_memory(builder_args)?;
+    ///     let opts = CountOptions {
+    ///         queries: vec!["ACG".to_string()],
+    ///         max_query_len: None,
+    ///         low_memory: false,
+    ///     };
+    ///     assert_eq!(suffix_array.count(opts)?[0].count, 2);
+    ///     assert!(suffix_array.metadata()?.filename.is_empty());
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    pub fn in_memory(args: SufrBuilderArgs) -> Result<SuffixArray> {
+        let sa: Box<dyn SuffixArrayTrait> =
+            if (args.text.len() as u64) < u32::MAX as u64 {
+                let builder: SufrBuilder<u32> = SufrBuilder::in_memory(args)?;
+                Box::new(SuffixArray32 {
+                    inner: SufrFile::from_bytes(builder.to_bytes()?)?,
+                })
+            } else {
+                let builder: SufrBuilder<u64> = SufrBuilder::in_memory(args)?;
+                Box::new(SuffixArray64 {
+                    inner: SufrFile::from_bytes(builder.to_bytes()?)?,
+                })
+            };
+        Ok(SuffixArray { inner: sa })
+    }
+
+    // --------------------------------------------------
+    /// Write the suffix array to a _.sufr_ file, e.g., to keep one
+    /// that was created with `in_memory`.
+    ///
+    /// Args:
+    /// * `path`: the output filename
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::suffix_array::SuffixArray;
+    /// use tempfile::NamedTempFile;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let suffix_array = SuffixArray::read("../data/inputs/1.sufr", true)?;
+    ///     let outfile = NamedTempFile::new()?;
+    ///     let outpath = outfile.path().to_str().unwrap();
+    ///     suffix_array.save(outpath)?;
+    ///     let mut saved = SuffixArray::read(outpath, true)?;
+    ///     assert_eq!(saved.string_at(6, Some(3))?, "ACG".to_string());
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    pub fn save(&self, path: &str) -> Result<()> {
+        self.inner.save(path)
+    }
+
+    // --------------------------------------------------
+    /// Append sequences to the suffix array, sorting only the new
+    /// suffixes. A suffix array read from disk is rewritten in place.
+    /// The text must stay shorter than 2^32 for a suffix array that
+    /// uses 32-bit integers; rebuild it otherwise.
+    ///
+    /// Args:
+    /// * `args`: the new sequences
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{
+    ///     suffix_array::SuffixArray,
+    ///     types::{AppendOptions, CountOptions},
+    /// };
+    /// use std::fs;
+    /// use tempfile::NamedTempFile;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let outfile = NamedTempFile::new()?;
+    ///     let outpath = outfile.path().to_str().unwrap();
+    ///     fs::copy("../data/inputs/1.sufr", outpath)?;
+    ///
+    ///     let mut suffix_array = SuffixArray::read(outpath, true)?;
+    ///     suffix_array.append(AppendOptions {
+    ///         text: b"GGACT$".to_vec(),
+    ///         sequence_starts: vec![0],
+    ///         sequence_names: vec!["2".to_string()],
+    ///     })?;
+    ///     let opts = CountOptions {
+    ///         queries: vec!["AC".to_string()],
+    ///         max_query_len: None,
+    ///         low_memory: true,
+    ///     };
+    ///     assert_eq!(suffix_array.count(opts)?[0].count, 3);
+    ///
+    ///     let mut reread = SuffixArray::read(outpath, true)?;
+    ///     assert_eq!(reread.metadata()?.sequence_names, ["1", "2"]);
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    pub fn append(&mut self, args: AppendOptions) -> Result<()> {
+        self.inner.append(args)
+    }
+
+    // --------------------------------------------------
+    /// Remove sequences from the results of `count`, `locate`, and
+    /// `extract`. The sequences are only marked as removed in the
+    /// _.sufr_ file; use `compact` to rebuild the suffix array without
+    /// them.
+    ///
+    /// Args:
+    /// * `names`: the names of the sequences to remove
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{suffix_array::SuffixArray, types::LocateOptions};
+    /// use std::fs;
+    /// use tempfile::NamedTempFile;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let outfile = NamedTempFile::new()?;
+    ///     let outpath = outfile.path().to_str().unwrap();
+    ///     fs::copy("../data/inputs/2.sufr", outpath)?;
+    ///
+    ///     let mut suffix_array = SuffixArray::read(outpath, true)?;
+    ///     suffix_array.remove_sequences(&["DEF".to_string()])?;
+    ///     let opts = LocateOptions {
+    ///         queries: vec!["ACGT".to_string()],
+    ///         max_query_len: None,
+    ///         low_memory: true,
+    ///     };
+    ///     let res = suffix_array.locate(opts)?;
+    ///     assert!(res[0].positions.iter().all(|pos| pos.sequence_name == "ABC"));
+    ///
+    ///     let reread = SuffixArray::read(outpath, true)?;
+    ///     assert_eq!(reread.metadata()?.removed_sequences, ["DEF"]);
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    pub fn remove_sequences(&mut self, names: &[String]) -> Result<()> {
+        self.inner.remove_sequences(names)
+    }
+
+    // --------------------------------------------------
+    /// Search the full suffix array with LCP-LR arrays so that each
+    /// query of length `m` takes O(m + log n) comparisons of characters
+    /// (cf. [SufrFile::use_lcp_lr](super::sufr_file::SufrFile::use_lcp_lr))
+    ///
+    /// Args:
+    /// * `enabled`: whether to use the LCP-LR arrays
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{suffix_array::SuffixArray, types::CountOptions};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut suffix_array = SuffixArray::read("../data/inputs/1.sufr", false)?;
+    ///     suffix_array.use_lcp_lr(true);
+    ///     let opts = CountOptions {
+    ///         queries: vec!["AC".to_string(), "GT".to_string(), "X".to_string()],
+    ///         max_query_len: None,
+    ///         low_memory: true,
+    ///     };
+    ///     let counts: Vec<_> = suffix_array
+    ///         .count(opts)?
+    ///         .into_iter()
+    ///         .map(|res| res.count)
+    ///         .collect();
+    ///     assert_eq!(counts, [2, 2, 0]);
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    pub fn use_lcp_lr(&mut self, enabled: bool) {
+        self.inner.use_lcp_lr(enabled)
+    }
+
+    // --------------------------------------------------
+    /// Rebuild the suffix array without the removed sequences, using
+    /// the options stored in the file. A suffix array read from disk
+    /// is rewritten in place.
+    ///
+    /// Args:
+    /// * `num_partitions`: the number of partitions to sort
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::suffix_array::SuffixArray;
+    /// use std::fs;
+    /// use tempfile::NamedTempFile;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let outfile = NamedTempFile::new()?;
+    ///     let outpath = outfile.path().to_str().unwrap();
+    ///     fs::copy("../data/inputs/2.sufr", outpath)?;
+    ///
+    ///     let mut suffix_array = SuffixArray::read(outpath, true)?;
+    ///     suffix_array.remove_sequences(&["ABC".to_string()])?;
+    ///     suffix_array.compact(16)?;
+    ///     let meta = suffix_array.metadata()?;
+    ///     assert_eq!(meta.sequence_names, ["DEF"]);
+    ///     assert!(meta.removed_sequences.is_empty());
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    pub fn compact(&mut self, num_partitions: usize) -> Result<()> {
+        self.inner.compact(num_partitions)
+    }
+
     // --------------------------------------------------
     /// Retrieve a suffix
     ///
@@ -513,8 +1403,13 @@ impl SuffixArray {
     ///         sequence_starts: seq_data.start_positions.into_iter().collect(),
     ///         sequence_names: seq_data.sequence_names,
     ///         num_partitions: 16,
-    ///         seed_mask: None,
+    ///         seed_masks: vec![],
     ///         random_seed: 42,
+    ///         both_strands: false,
+    ///         alphabet: None,
+    ///         residue_map: None,
+    ///         suffix_start_filter: None,
+    ///         filler_runs: None,
     ///     };
     ///
     ///     let outpath = SuffixArray::write(builder_args)?;
@@ -525,14 +1420,65 @@ impl SuffixArray {
     /// ```
     ///
     pub fn write(args: SufrBuilderArgs) -> Result<String> {
-        let path = if (args.text.len() as u64) < u32::MAX as u64 {
+        let (path, _) = Self::write_with_stats(args)?;
+        Ok(path)
+    }
+
+    // --------------------------------------------------
+    /// Create a new suffix array, write to disk, and return the path
+    /// along with statistics about how it was built
+    ///
+    /// Args:
+    /// * `args`: the options for building the suffix array
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{
+    ///     types::SufrBuilderArgs,
+    ///     suffix_array::SuffixArray,
+    ///     util::read_sequence_file,
+    /// };
+    /// use std::path::Path;
+    /// use tempfile::NamedTempFile;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let seq_data = read_sequence_file(Path::new("../data/inputs/1.fa"), b'%')?;
+    ///     let outfile = NamedTempFile::new()?;
+    ///     let builder_args = SufrBuilderArgs {
+    ///         text: seq_data.seq,
+    ///         path: Some(outfile.path().to_string_lossy().to_string()),
+    ///         low_memory: true,
+    ///         max_query_len: None,
+    ///         is_dna: true,
+    ///         allow_ambiguity: false,
+    ///         ignore_softmask: false,
+    ///         sequence_starts: seq_data.start_positions.into_iter().collect(),
+    ///         sequence_names: seq_data.sequence_names,
+    ///         num_partitions: 2,
+    ///         seed_masks: vec![],
+    ///         random_seed: 42,
+    ///         both_strands: false,
+    ///         alphabet: None,
+    ///         residue_map: None,
+    ///         suffix_start_filter: None,
+    ///         filler_runs: None,
+    ///     };
+    ///
+    ///     let (_, stats) = SuffixArray::write_with_stats(builder_args)?;
+    ///     assert_eq!(stats.num_suffixes, 9);
+    ///     assert_eq!(stats.num_filtered_suffixes, 2);
+    ///     assert_eq!(stats.max_lcp, 4);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn write_with_stats(args: SufrBuilderArgs) -> Result<(String, BuildStats)> {
+        if (args.text.len() as u64) < u32::MAX as u64 {
             let builder: SufrBuilder<u32> = SufrBuilder::new(args)?;
-            builder.path
+            Ok((builder.path, builder.stats))
         } else {
             let builder: SufrBuilder<u64> = SufrBuilder::new(args)?;
-            builder.path
-        };
-
-        Ok(path)
+            Ok((builder.path, builder.stats))
+        }
     }
 }
@@ -6,14 +6,21 @@
 //! Call the `write` method to serialized the data structures to
 //! a file (preferably with the _.sufr_ extension) that can be read
 //! by `sufr_file`.
+//! Small texts can instead be sorted entirely in memory with
+//! `in_memory` and serialized with `to_bytes`.
 //!
 
 use crate::{
+    sufr_file::SufrFile,
     types::{
-        FromUsize, Int, SeedMask, SuffixSortType, SufrBuilderArgs, OUTFILE_VERSION,
-        SENTINEL_CHARACTER,
+        Alphabet, AppendOptions, BuildStats, FillerRuns, FromUsize, Int, ResidueMap,
+        SeedMask, SequenceRecord, SuffixSortType, SuffixStartFilter, SufrBuilderArgs,
+        OUTFILE_VERSION, SENTINEL_CHARACTER, SEQUENCE_DELIMITER,
+    },
+    util::{
+        find_lcp_full_offset, reverse_complement, slice_u8_to_vec, usize_to_bytes,
+        vec_to_slice_u8,
     },
-    util::{find_lcp_full_offset, slice_u8_to_vec, usize_to_bytes, vec_to_slice_u8},
 };
 use anyhow::{anyhow, bail, Result};
 use log::info;
@@ -23,15 +30,23 @@ use std::{
     cmp::{max, min, Ordering},
     collections::HashSet,
     fs::{self, File, OpenOptions},
-    io::{BufWriter, Seek, SeekFrom, Write},
+    io::{BufWriter, Cursor, Seek, SeekFrom, Write},
     mem,
     ops::Range,
     path::PathBuf,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering as AtomicOrdering},
+        Arc, Mutex,
+    },
     time::Instant,
 };
 use tempfile::NamedTempFile;
 
+/// The number of random suffixes to sample for each pivot. Sorting the
+/// oversampled candidates and taking regular quantiles yields pivots that
+/// divide the suffixes into partitions of nearly equal size.
+const PIVOT_OVERSAMPLING: usize = 32;
+
 // --------------------------------------------------
 /// A struct for partitioning, sorting, and writing suffixes to disk
 #[derive(Debug)]
@@ -45,6 +60,17 @@ where
     /// Whether or not the sequence is nucleotide.
     pub is_dna: bool,
 
+    /// The alphabet used to validate the text and select the
+    /// indexed suffixes, if any.
+    pub alphabet: Option<Alphabet>,
+
+    /// The reduced alphabet used to sort the suffixes, if any.
+    pub residue_map: Option<ResidueMap>,
+
+    /// The rule for selecting the positions where suffixes may start
+    /// (e.g., after enzymatic cleavage sites), if any.
+    pub suffix_start_filter: Option<SuffixStartFilter>,
+
     /// Whether or not the nucleotide sequence allows characters
     /// other than A, C, G, or T.
     pub allow_ambiguity: bool,
@@ -53,6 +79,10 @@ where
     /// softmasked/lowercase bases.
     pub ignore_softmask: bool,
 
+    /// Whether or not the reverse complement of each sequence was
+    /// appended to the text.
+    pub both_strands: bool,
+
     /// The length of the given text.
     pub text_len: T,
 
@@ -68,24 +98,56 @@ where
     pub sequence_starts: Vec<T>,
 
     /// The names of the sequences in the text. Should be the same length
-    /// as `sequence_starts`.
+    /// as `sequence_starts` or half that length when `both_strands`.
     pub sequence_names: Vec<String>,
 
-    /// The text that was indexed.
+    /// The metadata of each sequence from
+    /// [SequenceRecord::metadata](crate::types::SequenceRecord::metadata),
+    /// in the order of the names
+    pub sequence_metadata: Vec<Option<String>>,
+
+    /// The indices of the sequences removed from search results.
+    /// Their suffixes remain until the suffix array is compacted.
+    pub tombstones: Vec<usize>,
+
+    /// The text that was indexed, after any residue mapping.
     pub text: Vec<u8>,
 
+    /// The text before residue mapping, which is what will be written.
+    /// Empty when there is no residue map.
+    original_text: Vec<u8>,
+
     /// Whether the text is sorted fully, using a maximum query length,
     /// or a seed mask.
     pub sort_type: SuffixSortType,
 
     /// The number of partitions to use when building.
-    partitions: Vec<Partition>,
+    partitions: Vec<Partition<T>>,
+
+    /// The seed masks after the first (which is in `sort_type`) and
+    /// the partitions sorted under each.
+    extra_masks: Vec<(SeedMask, Vec<Partition<T>>)>,
+
+    /// Whether the partitions are kept in memory rather than
+    /// spilled to temporary files.
+    in_memory: bool,
 
-    /// The locations of long runs of Ns in nucleotide text.
-    pub n_ranges: Vec<Range<usize>>,
+    /// The bytes held in temporary files as they are written and removed
+    temp_bytes: Arc<TempBytes>,
+
+    /// The number of suffixes placed into each partition, which can be
+    /// used to judge how well the pivots divided the suffixes.
+    pub partition_sizes: Vec<usize>,
+
+    /// The locations of long runs of filler characters in the text,
+    /// e.g., Ns in nucleotides.
+    pub filler_ranges: Vec<Range<usize>>,
 
     /// The name of the output file
     pub path: String,
+
+    /// Statistics about partitioning, sorting, and writing
+    pub stats: BuildStats,
 }
 
 // --------------------------------------------------
@@ -125,8 +187,13 @@ where
     ///         sequence_starts: seq_data.start_positions.into_iter().collect(),
     ///         sequence_names: seq_data.sequence_names,
     ///         num_partitions: 1024,
-    ///         seed_mask: None,
+    ///         seed_masks: vec![],
     ///         random_seed: 42,
+    ///         both_strands: false,
+    ///         alphabet: None,
+    ///         residue_map: None,
+    ///         suffix_start_filter: None,
+    ///         filler_runs: None,
     ///     };
     ///
     ///     if text_len < u32::MAX as u64 {
@@ -141,84 +208,764 @@ where
     /// }
     /// ```
     pub fn new(args: SufrBuilderArgs) -> Result<SufrBuilder<T>> {
-        let text: Vec<_> = args
-            .text
-            .iter()
-            .map(|b| {
-                // Check for lowercase
-                if (97..=122).contains(b) {
-                    if args.ignore_softmask {
-                        b'N'
-                    } else {
-                        // only shift lowercase ASCII
-                        b & 0b1011111
-                    }
+        let mut sa = Self::build(args, false)?;
+        sa.write()?;
+        Ok(sa)
+    }
+
+    // --------------------------------------------------
+    /// Create a new suffix/LCP array without touching the disk.
+    /// The partitions are sorted in memory rather than in temporary
+    /// files, and nothing is written to the `path`. Use `to_bytes` to
+    /// serialize the result for [SufrFile::from_bytes](super::sufr_file::SufrFile::from_bytes).
+    /// This is meant for small texts, as the whole suffix array
+    /// must fit in memory.
+    ///
+    /// Args:
+    /// * `args`: the options for building the suffix array
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{sufr_builder::SufrBuilder, types::SufrBuilderArgs};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let builder_args = SufrBuilderArgs {
+    ///         text: b"ACGTNNACGT$".to_vec(),
+    ///         path: None,
+    ///         low_memory: false,
+    ///         max_query_len: None,
+    ///         is_dna: true,
+    ///         allow_ambiguity: false,
+    ///         ignore_softmask: false,
+    ///         sequence_starts: vec![0],
+    ///         sequence_names: vec!["1".to_string()],
+    ///         num_partitions: 2,
+    ///         seed_masks: vec![],
+    ///         random_seed: 42,
+    ///         both_strands: false,
+    ///         alphabet: None,
+    ///         residue_map: None,
+    ///         suffix_start_filter: None,
+    ///         filler_runs: None,
+    ///     };
+    ///
+    ///     let sufr_builder: SufrBuilder<u32> = SufrBuilder::in_memory(builder_args)?;
+    ///     assert_eq!(sufr_builder.num_suffixes, 9);
+    ///     assert_eq!(sufr_builder.to_bytes()?, std::fs::read("../data/inputs/1.sufr")?);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn in_memory(args: SufrBuilderArgs) -> Result<SufrBuilder<T>> {
+        Self::build(args, true)
+    }
+
+    // --------------------------------------------------
+    /// Validate the text and sort the suffixes.
+    ///
+    /// Args:
+    /// * `args`: the options for building the suffix array
+    /// * `in_memory`: whether to keep the partitions in memory
+    fn build(args: SufrBuilderArgs, in_memory: bool) -> Result<SufrBuilder<T>> {
+        let (num_partitions, random_seed) = (args.num_partitions, args.random_seed);
+        let (mut sa, seed_masks) = Self::prepare(args, in_memory)?;
+        sa.sort(num_partitions, random_seed)?;
+
+        // Sort the same text under each additional mask, keeping the
+        // first mask's partitions and sizes in place.
+        for seed_mask in seed_masks {
+            let sort_type =
+                mem::replace(&mut sa.sort_type, SuffixSortType::Mask(seed_mask));
+            let partitions = mem::take(&mut sa.partitions);
+            let partition_sizes = mem::take(&mut sa.partition_sizes);
+            sa.sort(num_partitions, random_seed)?;
+
+            if let SuffixSortType::Mask(seed_mask) =
+                mem::replace(&mut sa.sort_type, sort_type)
+            {
+                let masked = mem::replace(&mut sa.partitions, partitions);
+                sa.extra_masks.push((seed_mask, masked));
+            }
+            sa.partition_sizes = partition_sizes;
+        }
+        sa.finish_stats();
+
+        Ok(sa)
+    }
+
+    // --------------------------------------------------
+    /// Validate and transform the text without sorting any suffixes.
+    /// Returns the builder, set to sort under the first seed mask
+    /// (if any), and the remaining seed masks.
+    ///
+    /// Args:
+    /// * `args`: the options for building the suffix array
+    /// * `in_memory`: whether to keep the partitions in memory
+    fn prepare(
+        args: SufrBuilderArgs,
+        in_memory: bool,
+    ) -> Result<(SufrBuilder<T>, Vec<SeedMask>)> {
+        let now = Instant::now();
+        let is_dna = match &args.alphabet {
+            Some(alphabet) if args.is_dna && !alphabet.is_nucleotide() => {
+                bail!("Cannot use the {alphabet} alphabet for nucleotides")
+            }
+            Some(alphabet) => alphabet.is_nucleotide(),
+            _ => args.is_dna,
+        };
+
+        // Byte alphabets are case-sensitive
+        let (fold_case, softmask_char) = match &args.alphabet {
+            Some(alphabet) => (
+                alphabet.ambiguity_character().is_some(),
+                alphabet.ambiguity_character().unwrap_or(b'N'),
+            ),
+            _ => (true, b'N'),
+        };
+        // Fold the case in place rather than copying the text
+        let mut text = args.text;
+        let mut num_softmasked = 0;
+        if fold_case {
+            for b in text.iter_mut().filter(|b| b.is_ascii_lowercase()) {
+                *b = if args.ignore_softmask {
+                    num_softmasked += 1;
+                    softmask_char
                 } else {
-                    *b
-                }
-            })
-            .collect();
+                    b.to_ascii_uppercase()
+                };
+            }
+        }
+        Self::validate_text(&text, &args.sequence_starts, args.alphabet.as_ref())?;
+
+        let mut sequence_starts = args.sequence_starts;
+        let text = if args.both_strands {
+            if args
+                .alphabet
+                .as_ref()
+                .map_or(!is_dna, |a| a != &Alphabet::Dna)
+            {
+                bail!("Cannot index both strands of non-DNA text");
+            }
+            let (text, rc_starts) =
+                Self::add_reverse_complement(text, &sequence_starts)?;
+            sequence_starts.extend(rc_starts);
+            text
+        } else {
+            text
+        };
         let text_len = T::from_usize(text.len());
 
-        if args.seed_mask.is_some() && args.max_query_len.is_some() {
-            bail!("Cannot use max_query_len and seed_mask together");
+        // Sort the suffixes under the reduced alphabet
+        // but keep the original residues to write
+        let delimiter = sequence_starts.get(1).map(|&start| text[start - 1]);
+        let (text, original_text) = match &args.residue_map {
+            Some(_) if is_dna => bail!("Cannot use a residue map for nucleotides"),
+            Some(residue_map)
+                if delimiter.is_some_and(|delimiter| {
+                    residue_map
+                        .groups
+                        .iter()
+                        .any(|group| group.as_bytes().contains(&delimiter))
+                }) =>
+            {
+                bail!("Residue map '{residue_map}' cannot use the sequence delimiter")
+            }
+            Some(residue_map) => (residue_map.map_bytes(&text), text),
+            _ => (text, vec![]),
+        };
+
+        if is_dna && args.suffix_start_filter.is_some() {
+            bail!("Cannot use a suffix start filter for nucleotides")
+        }
+
+        let mut seed_masks = vec![];
+        for mask in &args.seed_masks {
+            let seed_mask = SeedMask::new(mask)?
+                .with_max_query_len(args.max_query_len.unwrap_or(0));
+            if seed_masks.contains(&seed_mask) {
+                bail!("Duplicate seed mask \"{mask}\"");
+            }
+            seed_masks.push(seed_mask);
         }
+        let mut seed_masks = seed_masks.into_iter();
 
-        let sort_type = if let Some(mask) = args.seed_mask {
-            let seed_mask = SeedMask::new(&mask)?;
+        let sort_type = if let Some(seed_mask) = seed_masks.next() {
             SuffixSortType::Mask(seed_mask)
         } else {
             SuffixSortType::MaxQueryLen(args.max_query_len.unwrap_or(0))
         };
 
-        // Check for long runs of Ns when ambiguous bases are allowed.
-        let mut n_ranges: Vec<Range<usize>> = vec![];
-        if args.allow_ambiguity {
-            let mut n_start: Option<usize> = None;
-            let min_n = 1000;
-            let now = Instant::now();
-            for (i, &byte) in text.iter().enumerate() {
-                if byte == b'N' {
-                    if n_start.is_none() {
-                        n_start = Some(i);
-                    }
-                } else {
-                    if let Some(prev) = n_start {
-                        if i - prev >= min_n {
-                            n_ranges.push(prev..i);
-                        }
-                    }
-                    n_start = None;
-                }
+        // Check for long runs of filler, by default Ns when
+        // ambiguous bases are allowed.
+        let filler_runs = match args.filler_runs {
+            Some(filler_runs) => Some(filler_runs),
+            _ if args.allow_ambiguity => Some(FillerRuns::default()),
+            _ => None,
+        };
+        let filler_ranges = match filler_runs {
+            Some(filler_runs) => {
+                let now = Instant::now();
+                let ranges = filler_runs.find_runs(&text);
+                info!(
+                    "Scanned for {} runs of filler in {:?}",
+                    ranges.len(),
+                    now.elapsed()
+                );
+                ranges
             }
-            info!("Scanned for runs of Ns in {:?}", now.elapsed());
-        }
+            _ => vec![],
+        };
+        let stats = BuildStats {
+            num_softmasked,
+            num_filler_runs: filler_ranges.len(),
+            filler_len: filler_ranges.iter().map(|range| range.len()).sum(),
+            prepare_secs: now.elapsed().as_secs_f64(),
+            ..Default::default()
+        };
 
-        let mut sa = SufrBuilder {
+        let sa = SufrBuilder {
             version: OUTFILE_VERSION,
-            is_dna: args.is_dna,
+            is_dna,
+            alphabet: args.alphabet,
+            residue_map: args.residue_map,
+            suffix_start_filter: args.suffix_start_filter,
             allow_ambiguity: args.allow_ambiguity,
             ignore_softmask: args.ignore_softmask,
             sort_type,
             text_len,
             num_suffixes: T::default(),
             text,
-            num_sequences: T::from_usize(args.sequence_starts.len()),
-            sequence_starts: args
-                .sequence_starts
+            original_text,
+            both_strands: args.both_strands,
+            num_sequences: T::from_usize(sequence_starts.len()),
+            sequence_starts: sequence_starts
                 .into_iter()
                 .map(T::from_usize)
                 .collect::<Vec<_>>(),
+            sequence_metadata: vec![None; args.sequence_names.len()],
             sequence_names: args.sequence_names,
+            tombstones: vec![],
             partitions: vec![],
-            n_ranges,
+            extra_masks: vec![],
+            in_memory,
+            temp_bytes: Arc::new(TempBytes::default()),
+            partition_sizes: vec![],
+            filler_ranges,
             path: args.path.unwrap_or("out.sufr".to_string()),
+            stats,
         };
-        sa.sort(args.num_partitions, args.random_seed)?;
+
+        Ok((sa, seed_masks.collect()))
+    }
+
+    // --------------------------------------------------
+    /// Create a new suffix/LCP array from an iterator of records rather
+    /// than a delimiter-joined text. The records are streamed into the
+    /// text, separated by `%` and ending with the sentinel, and the
+    /// sequence starts, names, and any metadata are recorded along the
+    /// way. The `text`, `sequence_starts`, and `sequence_names` of the
+    /// `args` must be empty, and no record may contain the `%`.
+    ///
+    /// Args:
+    /// * `records`: the `(name, sequence)` pairs or other `SequenceRecord`s
+    /// * `args`: the options for building the suffix array
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{sufr_builder::SufrBuilder, types::SufrBuilderArgs};
+    /// use tempfile::NamedTempFile;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let outfile = NamedTempFile::new()?;
+    ///     let builder_args = SufrBuilderArgs {
+    ///         text: vec![],
+    ///         low_memory: true,
+    ///         path: Some(outfile.path().to_string_lossy().to_string()),
+    ///         max_query_len: None,
+    ///         is_dna: true,
+    ///         allow_ambiguity: false,
+    ///         ignore_softmask: false,
+    ///         sequence_starts: vec![],
+    ///         sequence_names: vec![],
+    ///         num_partitions: 2,
+    ///         seed_masks: vec![],
+    ///         random_seed: 42,
+    ///         both_strands: false,
+    ///         alphabet: None,
+    ///         residue_map: None,
+    ///         suffix_start_filter: None,
+    ///         filler_runs: None,
+    ///     };
+    ///     let records = vec![("ABC", "ACGTacgt"), ("DEF", "acgtACGT")];
+    ///     let builder: SufrBuilder<u32> =
+    ///         SufrBuilder::from_records(records, builder_args)?;
+    ///     assert_eq!(builder.text, b"ACGTACGT%ACGTACGT$");
+    ///     assert_eq!(builder.sequence_starts, [0, 9]);
+    ///     assert_eq!(builder.sequence_names, ["ABC", "DEF"]);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn from_records<I, R>(
+        records: I,
+        args: SufrBuilderArgs,
+    ) -> Result<SufrBuilder<T>>
+    where
+        I: IntoIterator<Item = R>,
+        R: SequenceRecord,
+    {
+        if !args.text.is_empty()
+            || !args.sequence_starts.is_empty()
+            || !args.sequence_names.is_empty()
+        {
+            bail!("Cannot combine records with a text, sequence starts, or names");
+        }
+
+        let mut text = vec![];
+        let mut sequence_starts = vec![];
+        let mut sequence_names = vec![];
+        let mut sequence_metadata = vec![];
+        for record in records {
+            let name = record.name();
+            if record.sequence().contains(&SEQUENCE_DELIMITER) {
+                bail!(
+                    "Record \"{name}\" contains the sequence delimiter '{}'",
+                    SEQUENCE_DELIMITER as char
+                );
+            }
+            if !sequence_starts.is_empty() {
+                text.push(SEQUENCE_DELIMITER);
+            }
+            sequence_starts.push(text.len());
+            sequence_names.push(name);
+            sequence_metadata.push(record.metadata());
+            text.extend_from_slice(record.sequence());
+        }
+
+        if sequence_starts.is_empty() {
+            bail!("No records to index");
+        }
+        text.push(SENTINEL_CHARACTER);
+
+        let mut sa = Self::build(
+            SufrBuilderArgs {
+                text,
+                sequence_starts,
+                sequence_names,
+                ..args
+            },
+            false,
+        )?;
+        sa.sequence_metadata = sequence_metadata;
         sa.write()?;
         Ok(sa)
     }
 
+    // --------------------------------------------------
+    /// Append sequences to an existing suffix array. Only the new
+    /// suffixes (and the few old suffixes whose order depended on the
+    /// old end of the text) are sorted, and these are merged into the
+    /// existing suffix/LCP arrays of each seed mask, repairing the LCPs
+    /// where the two meet. The old sentinel becomes a sequence delimiter.
+    /// The options for sorting and indexing come from the existing file.
+    ///
+    /// Args:
+    /// * `sufr_file`: the existing suffix array
+    /// * `args`: the new sequences
+    /// * `path`: the output filename, or `None` to keep the result
+    ///   in memory for `to_bytes`
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{
+    ///     sufr_builder::SufrBuilder,
+    ///     sufr_file::SufrFile,
+    ///     types::AppendOptions,
+    /// };
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut sufr_file: SufrFile<u32> =
+    ///         SufrFile::read("../data/inputs/1.sufr", false)?;
+    ///     let args = AppendOptions {
+    ///         text: b"GGACT$".to_vec(),
+    ///         sequence_starts: vec![0],
+    ///         sequence_names: vec!["2".to_string()],
+    ///     };
+    ///     let builder = SufrBuilder::append(&mut sufr_file, args, None)?;
+    ///     assert_eq!(builder.text, b"ACGTNNACGT%GGACT$");
+    ///     assert_eq!(builder.num_suffixes, 14);
+    ///     assert_eq!(builder.sequence_names, ["1", "2"]);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn append(
+        sufr_file: &mut SufrFile<T>,
+        args: AppendOptions,
+        path: Option<String>,
+    ) -> Result<SufrBuilder<T>> {
+        if sufr_file.both_strands {
+            bail!("Cannot append to an index of both strands");
+        }
+        if args.sequence_starts.is_empty() {
+            bail!("No sequences to append");
+        }
+        if args.text.last() != Some(&SENTINEL_CHARACTER) {
+            bail!("The appended text must end with the sentinel");
+        }
+
+        // The old sentinel now separates the old and new sequences
+        let old_len = sufr_file.text_len.to_usize();
+        let old_end = old_len - 1;
+        let mut text = sufr_file.get_text_range(0..old_len)?;
+        text[old_end] = SEQUENCE_DELIMITER;
+        text.extend(args.text);
+        if mem::size_of::<T>() < mem::size_of::<u64>()
+            && text.len() as u64 >= u32::MAX as u64
+        {
+            bail!(
+                "Text is too long to append to a 32-bit suffix array; rebuild instead"
+            );
+        }
+
+        let sequence_starts: Vec<usize> = sufr_file
+            .sequence_starts
+            .iter()
+            .map(|start| start.to_usize())
+            .chain(args.sequence_starts.iter().map(|start| start + old_len))
+            .collect();
+        let mut sequence_names = sufr_file.sequence_names.clone();
+        sequence_names.extend(args.sequence_names);
+
+        let builder_args = SufrBuilderArgs {
+            text,
+            path: path.clone(),
+            sequence_starts,
+            sequence_names,
+            num_partitions: 1,
+            ..Self::file_args(sufr_file)
+        };
+        let (mut sa, seed_masks) = Self::prepare(builder_args, path.is_none())?;
+        sa.tombstones = sufr_file.tombstones.clone();
+        sa.sequence_metadata[..sufr_file.sequence_metadata.len()]
+            .clone_from_slice(&sufr_file.sequence_metadata);
+
+        // Merge under each seed mask in the order they were stored
+        let first_sort_type = sa.sort_type.clone();
+        let sort_types: Vec<SuffixSortType> = [first_sort_type.clone()]
+            .into_iter()
+            .chain(seed_masks.into_iter().map(SuffixSortType::Mask))
+            .collect();
+        let mask_num = sufr_file.mask_num;
+        for (num, sort_type) in sort_types.into_iter().enumerate() {
+            if !sufr_file.masked_indexes.is_empty() {
+                sufr_file.use_mask(num)?;
+            }
+            sa.sort_type = sort_type;
+            let now = Instant::now();
+            let partition = sa.merge_appended(sufr_file, old_end)?;
+            sa.stats.sort_secs += now.elapsed().as_secs_f64();
+            match &sa.sort_type {
+                SuffixSortType::Mask(seed_mask) if num > 0 => {
+                    sa.extra_masks.push((seed_mask.clone(), vec![partition]));
+                }
+                _ => {
+                    sa.num_suffixes = T::from_usize(partition.len);
+                    sa.partition_sizes = vec![partition.len];
+                    sa.partitions = vec![partition];
+                }
+            }
+        }
+        sa.sort_type = first_sort_type;
+        if !sufr_file.masked_indexes.is_empty() {
+            sufr_file.use_mask(mask_num)?;
+        }
+        sa.finish_stats();
+
+        if path.is_some() {
+            sa.write()?;
+        }
+        Ok(sa)
+    }
+
+    // --------------------------------------------------
+    /// Rebuild a suffix array without the sequences that were removed
+    /// from search results, using the options stored in the file.
+    ///
+    /// Args:
+    /// * `sufr_file`: the existing suffix array
+    /// * `num_partitions`: the number of partitions to sort
+    /// * `path`: the output filename, or `None` to keep the result
+    ///   in memory for `to_bytes`
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{sufr_builder::SufrBuilder, sufr_file::SufrFile};
+    /// use std::fs;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let bytes = fs::read("../data/inputs/2.sufr")?;
+    ///     let mut sufr_file: SufrFile<u32> = SufrFile::from_bytes(bytes)?;
+    ///     sufr_file.remove_sequences(&["ABC".to_string()])?;
+    ///     let builder = SufrBuilder::compact(&mut sufr_file, 2, None)?;
+    ///     assert_eq!(builder.text, b"ACGTACGT$");
+    ///     assert_eq!(builder.sequence_names, ["DEF"]);
+    ///     assert!(builder.tombstones.is_empty());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn compact(
+        sufr_file: &mut SufrFile<T>,
+        num_partitions: usize,
+        path: Option<String>,
+    ) -> Result<SufrBuilder<T>> {
+        // With both strands, the reverse complements are added anew
+        let num_records = sufr_file.sequence_names.len();
+        let seq_starts: Vec<usize> = sufr_file
+            .sequence_starts
+            .iter()
+            .map(|start| start.to_usize())
+            .collect();
+        let delimiter = match seq_starts.get(1) {
+            Some(&start) => sufr_file.get_text(start - 1).unwrap_or(SEQUENCE_DELIMITER),
+            _ => SEQUENCE_DELIMITER,
+        };
+
+        let mut text = vec![];
+        let mut sequence_starts = vec![];
+        let mut sequence_names = vec![];
+        let mut sequence_metadata = vec![];
+        for (i, name) in sufr_file.sequence_names.clone().into_iter().enumerate() {
+            if sufr_file.tombstones.binary_search(&i).is_ok() {
+                continue;
+            }
+            let end = seq_starts
+                .get(i + 1)
+                .map_or(sufr_file.text_len.to_usize(), |&next| next)
+                - 1;
+            if !sequence_starts.is_empty() {
+                text.push(delimiter);
+            }
+            sequence_starts.push(text.len());
+            sequence_names.push(name);
+            sequence_metadata.push(sufr_file.sequence_metadata[i].clone());
+            text.extend(sufr_file.get_text_range(seq_starts[i]..end)?);
+        }
+
+        if sequence_starts.is_empty() {
+            bail!("No sequences remain after removing {num_records}");
+        }
+        text.push(SENTINEL_CHARACTER);
+
+        let builder_args = SufrBuilderArgs {
+            text,
+            path: path.clone(),
+            sequence_starts,
+            sequence_names,
+            num_partitions,
+            both_strands: sufr_file.both_strands,
+            ..Self::file_args(sufr_file)
+        };
+        let mut sa = Self::build(builder_args, path.is_none())?;
+        sa.sequence_metadata = sequence_metadata;
+        if path.is_some() {
+            sa.write()?;
+        }
+        Ok(sa)
+    }
+
+    // --------------------------------------------------
+    /// The options for sorting and indexing stored in a suffix array,
+    /// with an empty text
+    ///
+    /// Args:
+    /// * `sufr_file`: the existing suffix array
+    fn file_args(sufr_file: &SufrFile<T>) -> SufrBuilderArgs {
+        let max_query_len = match &sufr_file.sort_type {
+            SuffixSortType::MaxQueryLen(max_query_len) => *max_query_len,
+            SuffixSortType::Mask(seed_mask) => seed_mask.max_query_len,
+        };
+        SufrBuilderArgs {
+            text: vec![],
+            path: None,
+            low_memory: true,
+            max_query_len: (max_query_len > 0).then_some(max_query_len),
+            is_dna: sufr_file.is_dna,
+            allow_ambiguity: sufr_file.allow_ambiguity,
+            ignore_softmask: sufr_file.ignore_softmask,
+            sequence_starts: vec![],
+            sequence_names: vec![],
+            num_partitions: 1,
+            seed_masks: sufr_file
+                .masked_indexes
+                .iter()
+                .map(|masked| masked.seed_mask.mask.clone())
+                .collect(),
+            random_seed: 0,
+            both_strands: false,
+            alphabet: sufr_file.alphabet.clone(),
+            residue_map: sufr_file.residue_map.clone(),
+            suffix_start_filter: sufr_file.suffix_start_filter.clone(),
+            filler_runs: None,
+        }
+    }
+
+    // --------------------------------------------------
+    /// Merge the new suffixes into the existing suffix/LCP arrays
+    /// under the current sort type.
+    ///
+    /// Args:
+    /// * `sufr_file`: the existing suffix array, using the matching mask
+    /// * `old_end`: the position of the old sentinel
+    fn merge_appended(
+        &self,
+        sufr_file: &mut SufrFile<T>,
+        old_end: usize,
+    ) -> Result<Partition<T>> {
+        // An old suffix whose comparison to a neighbor ran into the old
+        // sentinel may sort differently now that the text continues.
+        // These are sorted again along with the new suffixes. The old
+        // sentinel is dropped, and its position is considered anew.
+        let reaches_end = |suffix: usize, lcp: usize| match &self.sort_type {
+            SuffixSortType::Mask(seed_mask) => seed_mask
+                .positions
+                .get(lcp)
+                .is_some_and(|offset| suffix + offset >= old_end),
+            SuffixSortType::MaxQueryLen(_) => suffix + lcp >= old_end,
+        };
+
+        let now = Instant::now();
+        sufr_file.suffix_array_file.reset();
+        sufr_file.lcp_file.reset();
+        let mut old_suffixes = sufr_file
+            .suffix_array_file
+            .iter()
+            .zip(sufr_file.lcp_file.iter())
+            .enumerate()
+            .peekable();
+        let mut moved_ranks = vec![];
+        let mut new_sa: Vec<T> = vec![];
+        while let Some((rank, (suffix, lcp))) = old_suffixes.next() {
+            let next_lcp = old_suffixes
+                .peek()
+                .map_or(0, |(_, (_, lcp))| lcp.to_usize());
+            let pos = suffix.to_usize();
+            if pos == old_end {
+                moved_ranks.push(rank);
+            } else if reaches_end(pos, lcp.to_usize()) || reaches_end(pos, next_lcp) {
+                moved_ranks.push(rank);
+                new_sa.push(suffix);
+            }
+        }
+        let num_old = sufr_file.len_suffixes.to_usize() - moved_ranks.len();
+        let num_moved = new_sa.len();
+
+        // Sort the new suffixes with the moved ones
+        new_sa.extend(
+            (old_end..self.text.len())
+                .filter(|&pos| self.is_indexed(pos))
+                .map(T::from_usize),
+        );
+        let len = new_sa.len();
+        let mut new_lcp = vec![T::default(); len];
+        if len > 0 {
+            let mut sa_w = new_sa.clone();
+            let mut lcp_w = vec![T::default(); len];
+            self.merge_sort(&mut sa_w, &mut new_sa, len, &mut new_lcp, &mut lcp_w);
+        }
+        info!(
+            "Sorted {} new and {num_moved} moved suffixes in {:?}",
+            len - num_moved,
+            now.elapsed()
+        );
+
+        // Merge the sorted suffixes into the old ones, keeping the LCP
+        // of two suffixes that were neighbors before and finding the
+        // LCP at the seams.
+        let now = Instant::now();
+        let capacity = 4096;
+        let mut sa_out: PartitionBuilder<T> =
+            PartitionBuilder::new(capacity, self.in_memory, &self.temp_bytes)?;
+        let mut lcp_out: PartitionBuilder<T> =
+            PartitionBuilder::new(capacity, self.in_memory, &self.temp_bytes)?;
+        let mut first_suffix: Option<T> = None;
+        let mut prev: Option<(T, bool, usize)> = None;
+        let (mut max_lcp, mut lcp_sum) = (0, 0);
+        let mut emit = |suffix: T, is_old: bool, index: usize, known_lcp: T| {
+            let lcp = match prev {
+                Some((_, was_old, prev_index))
+                    if was_old == is_old && prev_index + 1 == index =>
+                {
+                    known_lcp
+                }
+                Some((prev_suffix, _, _)) => self.find_lcp(
+                    prev_suffix.to_usize(),
+                    suffix.to_usize(),
+                    self.text_len,
+                    0,
+                ),
+                _ => T::default(),
+            };
+            max_lcp = max(max_lcp, lcp.to_usize());
+            lcp_sum += lcp.to_usize();
+            first_suffix.get_or_insert(suffix);
+            prev = Some((suffix, is_old, index));
+            sa_out.add(suffix).and_then(|_| lcp_out.add(lcp))
+        };
+
+        let mut moved_ranks = moved_ranks.into_iter().peekable();
+        let mut new_suffixes = new_sa.iter().zip(&new_lcp).enumerate().peekable();
+        sufr_file.suffix_array_file.reset();
+        sufr_file.lcp_file.reset();
+        for (rank, (suffix, lcp)) in sufr_file
+            .suffix_array_file
+            .iter()
+            .zip(sufr_file.lcp_file.iter())
+            .enumerate()
+        {
+            if moved_ranks.next_if_eq(&rank).is_some() {
+                continue;
+            }
+            while let Some((i, (&new_suffix, &new_lcp))) =
+                new_suffixes.next_if(|(_, (&new_suffix, _))| {
+                    self.compare(new_suffix, suffix) == Ordering::Less
+                })
+            {
+                emit(new_suffix, false, i, new_lcp)?;
+            }
+            emit(suffix, true, rank, lcp)?;
+        }
+        for (i, (&new_suffix, &new_lcp)) in new_suffixes {
+            emit(new_suffix, false, i, new_lcp)?;
+        }
+        sa_out.write()?;
+        lcp_out.write()?;
+
+        let total_len = sa_out.total_len;
+        if total_len != num_old + len {
+            bail!("Merged {total_len} but needed to merge {}", num_old + len);
+        }
+        info!("Merged {total_len} suffixes in {:?}", now.elapsed());
+
+        let data = match (sa_out.path, lcp_out.path) {
+            (Some(sa_path), Some(lcp_path)) => {
+                PartitionData::File { sa_path, lcp_path }
+            }
+            _ => PartitionData::Memory {
+                sa: sa_out.vals,
+                lcp: lcp_out.vals,
+            },
+        };
+        Ok(Partition {
+            order: 0,
+            len: total_len,
+            first_suffix: first_suffix.map_or(0, |suffix| suffix.to_usize()),
+            last_suffix: prev.map_or(0, |(suffix, _, _)| suffix.to_usize()),
+            first_lcp: 0,
+            max_lcp,
+            lcp_sum,
+            data,
+        })
+    }
+
     // --------------------------------------------------
     // TODO: Remove? Only useful during debugging
     // Return the string at a given suffix position
@@ -234,12 +981,12 @@ where
     //}
 
     // --------------------------------------------------
-    /// If a suffix is in a long run of Ns, return the position of the final N
+    /// If a suffix is in a long run of filler, return the end of the run
     ///
     /// Args:
     /// * `suffix`: suffix position
-    fn find_n_run(&self, suffix: usize) -> Option<usize> {
-        self.n_ranges
+    fn find_filler_run(&self, suffix: usize) -> Option<usize> {
+        self.filler_ranges
             .binary_search_by(|range| {
                 if range.contains(&suffix) {
                     Ordering::Equal
@@ -250,7 +997,7 @@ where
                 }
             })
             .ok()
-            .map(|i| self.n_ranges[i].end)
+            .map(|i| self.filler_ranges[i].end)
     }
 
     // --------------------------------------------------
@@ -299,36 +1046,43 @@ where
                 }
             }
             SuffixSortType::MaxQueryLen(max_query_len) => {
-                match (&self.find_n_run(start1), &self.find_n_run(start2)) {
-                    // If the two suffixes start in long stretches of Ns
-                    // Then use the min of the end positions
-                    (Some(end1), Some(end2)) => {
-                        T::from_usize(min(end1 - start1, end2 - start2))
-                    }
-                    _ => {
-                        let text_len = self.text_len.to_usize();
-                        let len = if max_query_len > &0 {
-                            *max_query_len
-                        } else {
-                            len.to_usize()
-                        };
-                        let start1 = start1 + skip;
-                        let start2 = start2 + skip;
-                        let end1 = min(start1 + len, text_len);
-                        let end2 = min(start2 + len, text_len);
-                        unsafe {
-                            T::from_usize(
-                                skip + (start1..end1)
-                                    .zip(start2..end2)
-                                    .take_while(|(a, b)| {
-                                        self.text.get_unchecked(*a)
-                                            == self.text.get_unchecked(*b)
-                                    })
-                                    .count(),
-                            )
+                let text_len = self.text_len.to_usize();
+                let len = if max_query_len > &0 {
+                    *max_query_len
+                } else {
+                    len.to_usize()
+                };
+                let max_lcp = skip + len;
+                let end1 = min(start1 + max_lcp, text_len);
+                let end2 = min(start2 + max_lcp, text_len);
+                let mut skip = skip;
+
+                // If the two suffixes start in long runs of the same filler
+                // Then they differ where the shorter run ends, but no
+                // further than the characters would be compared
+                if let (Some(run_end1), Some(run_end2)) =
+                    (self.find_filler_run(start1), self.find_filler_run(start2))
+                {
+                    if self.text[start1] == self.text[start2] {
+                        let (run1, run2) = (run_end1 - start1, run_end2 - start2);
+                        if run1 != run2 {
+                            return T::from_usize(min(min(run1, run2), max_lcp));
                         }
+                        skip = max(skip, min(run1, max_lcp));
                     }
                 }
+
+                unsafe {
+                    T::from_usize(
+                        skip + (start1 + skip..end1)
+                            .zip(start2 + skip..end2)
+                            .take_while(|(a, b)| {
+                                self.text.get_unchecked(*a)
+                                    == self.text.get_unchecked(*b)
+                            })
+                            .count(),
+                    )
+                }
             }
         }
     }
@@ -381,6 +1135,58 @@ where
         }
     }
 
+    // --------------------------------------------------
+    /// Compare two suffixes the same way as `merge`, placing the shorter
+    /// suffix first when the two are equal up to the maximum query
+    /// length or over the "care" positions of the seed mask.
+    ///
+    /// Args:
+    /// * `start1`: the position of the first suffix
+    /// * `start2`: the position of the second suffix
+    fn compare(&self, start1: T, start2: T) -> Ordering {
+        if start1 == start2 {
+            return Ordering::Equal;
+        }
+
+        let shorter_suffix = max(start1, start2);
+        let shorter_first = if start1 == shorter_suffix {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        };
+        let max_n = self.text_len - shorter_suffix;
+        let context = match &self.sort_type {
+            SuffixSortType::Mask(seed_mask) => T::from_usize(
+                seed_mask
+                    .positions
+                    .iter()
+                    .filter(|&i| *i < max_n.to_usize())
+                    .count(),
+            ),
+            SuffixSortType::MaxQueryLen(max_query_len) => {
+                if max_query_len > &0 {
+                    min(T::from_usize(*max_query_len), max_n)
+                } else {
+                    max_n
+                }
+            }
+        };
+
+        let len_lcp = self.find_lcp(start1.to_usize(), start2.to_usize(), context, 0);
+        if len_lcp >= context {
+            shorter_first
+        } else {
+            let full_len_lcp =
+                find_lcp_full_offset(len_lcp.to_usize(), &self.sort_type);
+            match self.text[start1.to_usize() + full_len_lcp]
+                .cmp(&self.text[start2.to_usize() + full_len_lcp])
+            {
+                Ordering::Equal => shorter_first,
+                cmp => cmp,
+            }
+        }
+    }
+
     // --------------------------------------------------
     /// Find the highest partition to place a suffix for sorting.
     ///
@@ -406,24 +1212,16 @@ where
         num_partitions: usize,
         random_seed: u64,
     ) -> Result<PartitionBuildResult<T>> {
-        // Create more partitions than requested because
-        // we can't know how big they will end up being
-        let max_partitions = self.text_len.to_usize() / 4;
-        let num_partitions = if num_partitions * 10 < max_partitions {
-            num_partitions * 10
-        } else if num_partitions * 5 < max_partitions {
-            num_partitions * 5
-        } else if num_partitions * 2 < max_partitions {
-            num_partitions * 2
-        } else if num_partitions < max_partitions {
-            num_partitions
-        } else {
-            max_partitions
-        };
+        // The oversampled pivots should produce evenly sized partitions,
+        // so we only need to guard against asking for more partitions
+        // than a tiny text can fill.
+        let max_partitions = max(1, self.text_len.to_usize() / 4);
+        let num_partitions = num_partitions.clamp(1, max_partitions);
 
-        // Randomly select some pivots
+        // Select pivots from an oversampled set of random suffixes
         let now = Instant::now();
         let pivot_sa = self.select_pivots(self.text.len(), num_partitions, random_seed);
+        self.stats.pivot_secs += now.elapsed().as_secs_f64();
         let num_pivots = pivot_sa.len();
         info!(
             "Selected {num_pivots} pivot{} in {:?}",
@@ -431,10 +1229,12 @@ where
             now.elapsed()
         );
 
+        // Duplicate candidates may leave us with fewer pivots than requested
         let capacity = 4096;
         let mut builders: Vec<_> = vec![];
-        for _ in 0..num_partitions {
-            let builder: PartitionBuilder<T> = PartitionBuilder::new(capacity)?;
+        for _ in 0..=num_pivots {
+            let builder: PartitionBuilder<T> =
+                PartitionBuilder::new(capacity, self.in_memory, &self.temp_bytes)?;
             builders.push(Arc::new(Mutex::new(builder)));
         }
 
@@ -442,11 +1242,8 @@ where
         self.text
             .par_iter()
             .enumerate()
-            .try_for_each(|(i, &val)| -> Result<()> {
-                if val == SENTINEL_CHARACTER
-                    || !self.is_dna // Allow anything else if not DNA
-                    || (b"ACGT".contains(&val) || self.allow_ambiguity)
-                {
+            .try_for_each(|(i, _)| -> Result<()> {
+                if self.is_indexed(i) {
                     let suffix = T::from_usize(i);
                     let partition_num = self.upper_bound(suffix, &pivot_sa);
                     match builders[partition_num].lock() {
@@ -478,6 +1275,7 @@ where
             if num_pivots == 1 { "" } else { "s" },
             now.elapsed()
         );
+        self.stats.partition_secs += now.elapsed().as_secs_f64();
 
         //Ok((builders, num_suffixes))
         Ok(PartitionBuildResult {
@@ -493,110 +1291,196 @@ where
     /// * `num_partitions`: the number of partitions to used
     /// * `random_seed`: a value for initializing the RNG
     fn sort(&mut self, num_partitions: usize, random_seed: u64) -> Result<()> {
-        let mut partition_build = self.partition(num_partitions, random_seed)?;
-
-        // Be sure to round up to get all the suffixes
-        let num_per_partition = (partition_build.num_suffixes as f64
-            / num_partitions as f64)
-            .ceil() as usize;
+        let partition_build = self.partition(num_partitions, random_seed)?;
         let total_sort_time = Instant::now();
-        let mut num_taken = 0;
-        let mut partition_inputs = vec![vec![]; num_partitions];
-
-        // We (probably) have many more partitions than we need,
-        // so here we accumulate the small partitions from the left
-        // stopping when we reach a boundary like 1M/partition.
-        // This evens out the workload to sort the partitions.
-        #[allow(clippy::needless_range_loop)]
-        for partition_num in 0..num_partitions {
-            let boundary = num_per_partition * (partition_num + 1);
-            while !partition_build.builders.is_empty() {
-                let part = partition_build.builders.remove(0);
-                match part.lock() {
-                    Ok(builder) => {
-                        if builder.total_len > 0 {
-                            partition_inputs[partition_num]
-                                .push((builder.path.clone(), builder.total_len));
-                            num_taken += builder.total_len;
-                        }
-                    }
-                    Err(e) => panic!("Can't get partition: {e}"),
-                }
 
-                // Let the last partition soak up the rest
-                if partition_num < num_partitions - 1 && num_taken > boundary {
-                    break;
-                }
+        // Each partition lies between two pivots, so the suffixes
+        // in the on-disk partitions are already in their final order.
+        let mut partition_inputs = vec![];
+        for part in &partition_build.builders {
+            match part.lock() {
+                Ok(mut builder) => partition_inputs.push((
+                    builder.path.clone(),
+                    builder.total_len,
+                    mem::take(&mut builder.vals),
+                )),
+                Err(e) => panic!("Can't get partition: {e}"),
             }
         }
+        let num_partitions = partition_inputs.len();
+        let partition_sizes: Vec<usize> =
+            partition_inputs.iter().map(|(_, len, _)| *len).collect();
 
-        // Ensure we got all the suffixes
-        if num_taken != partition_build.num_suffixes {
-            bail!(
-                "Took {num_taken} but needed to take {}",
-                partition_build.num_suffixes
-            );
-        }
-
-        let mut partitions: Vec<Option<Partition>> =
+        let mut partitions: Vec<Option<Partition<T>>> =
             (0..num_partitions).map(|_| None).collect();
 
-        partitions.par_iter_mut().enumerate().try_for_each(
-            |(partition_num, partition)| -> Result<()> {
-                // Find the suffixes in this partition
-                let mut part_sa = vec![];
-                for (path, len) in &partition_inputs[partition_num] {
-                    let buffer = fs::read(path)?;
-                    let mut part: Vec<T> = slice_u8_to_vec(&buffer, *len);
-                    part_sa.append(&mut part);
-                    fs::remove_file(path)?;
-                }
+        partitions
+            .par_iter_mut()
+            .zip(partition_inputs.into_par_iter())
+            .enumerate()
+            .try_for_each(
+                |(partition_num, (partition, (path, len, vals)))| -> Result<()> {
+                    // Find the suffixes in this partition
+                    let mut part_sa: Vec<T> = match path {
+                        Some(path) => {
+                            let part_sa = if len > 0 {
+                                let buffer = fs::read(&path)?;
+                                slice_u8_to_vec(&buffer, len)
+                            } else {
+                                vec![]
+                            };
+                            fs::remove_file(&path)?;
+                            self.temp_bytes.remove(len * mem::size_of::<T>());
+                            part_sa
+                        }
+                        _ => vals,
+                    };
 
-                let len = part_sa.len();
-                if len > 0 {
-                    let mut sa_w = part_sa.clone();
-                    let mut lcp = vec![T::default(); len];
-                    let mut lcp_w = vec![T::default(); len];
-                    self.merge_sort(&mut sa_w, &mut part_sa, len, &mut lcp, &mut lcp_w);
-
-                    // Write to disk
-                    let mut sa_file = NamedTempFile::new()?;
-                    let _ = sa_file.write(vec_to_slice_u8(&part_sa))?;
-                    let mut lcp_file = NamedTempFile::new()?;
-                    let _ = lcp_file.write(vec_to_slice_u8(&lcp))?;
-                    let (_, sa_path) = sa_file.keep()?;
-                    let (_, lcp_path) = lcp_file.keep()?;
-
-                    *partition = Some(Partition {
-                        order: partition_num,
-                        len,
-                        first_suffix: part_sa.first().unwrap().to_usize(),
-                        last_suffix: part_sa.last().unwrap().to_usize(),
-                        sa_path,
-                        lcp_path,
-                    });
-                }
-                Ok(())
-            },
-        )?;
+                    // The suffixes were added in whatever order the threads
+                    // ran, so start from their positions to sort ties the
+                    // same way regardless of the number of threads
+                    part_sa.sort_unstable();
+
+                    let len = part_sa.len();
+                    if len > 0 {
+                        let mut sa_w = part_sa.clone();
+                        let mut lcp = vec![T::default(); len];
+                        let mut lcp_w = vec![T::default(); len];
+                        self.merge_sort(
+                            &mut sa_w,
+                            &mut part_sa,
+                            len,
+                            &mut lcp,
+                            &mut lcp_w,
+                        );
+
+                        let first_suffix = part_sa.first().unwrap().to_usize();
+                        let last_suffix = part_sa.last().unwrap().to_usize();
+                        let max_lcp =
+                            lcp.iter().skip(1).max().map_or(0, |v| v.to_usize());
+                        let lcp_sum = lcp.iter().skip(1).map(|v| v.to_usize()).sum();
+                        let data = if self.in_memory {
+                            PartitionData::Memory { sa: part_sa, lcp }
+                        } else {
+                            // Write to disk
+                            let mut sa_file = NamedTempFile::new()?;
+                            let _ = sa_file.write(vec_to_slice_u8(&part_sa))?;
+                            let mut lcp_file = NamedTempFile::new()?;
+                            let _ = lcp_file.write(vec_to_slice_u8(&lcp))?;
+                            let (_, sa_path) = sa_file.keep()?;
+                            let (_, lcp_path) = lcp_file.keep()?;
+                            self.temp_bytes.add(2 * len * mem::size_of::<T>());
+                            PartitionData::File { sa_path, lcp_path }
+                        };
+
+                        *partition = Some(Partition {
+                            order: partition_num,
+                            len,
+                            first_suffix,
+                            last_suffix,
+                            first_lcp: 0,
+                            max_lcp,
+                            lcp_sum,
+                            data,
+                        });
+                    }
+                    Ok(())
+                },
+            )?;
 
         // Get rid of None/unwrap Some, put in order
         let mut partitions: Vec<_> = partitions.into_iter().flatten().collect();
         partitions.sort_by_key(|p| p.order);
 
-        let sizes: Vec<_> = partitions.iter().map(|p| p.len).collect();
-        let total_size = sizes.iter().sum::<usize>();
+        // Fix the LCP boundaries while we know how the suffixes were sorted
+        for i in 1..partitions.len() {
+            partitions[i].first_lcp = self
+                .find_lcp(
+                    partitions[i - 1].last_suffix,
+                    partitions[i].first_suffix,
+                    self.text_len,
+                    0, // start at beginning
+                )
+                .to_usize();
+        }
+
+        // Ensure we got all the suffixes
+        let total_size = partitions.iter().map(|p| p.len).sum::<usize>();
+        if total_size != partition_build.num_suffixes {
+            bail!(
+                "Sorted {total_size} but needed to sort {}",
+                partition_build.num_suffixes
+            );
+        }
+
         info!(
             "Sorted {total_size} suffixes in {num_partitions} partitions (avg {}) in {:?}",
             total_size / num_partitions,
             total_sort_time.elapsed()
         );
-        self.num_suffixes = T::from_usize(sizes.iter().sum());
+        self.stats.sort_secs += total_sort_time.elapsed().as_secs_f64();
+
+        self.partition_sizes = partition_sizes;
+        info!(
+            "Partition sizes min {}, max {}, skew {:.2}",
+            self.partition_sizes.iter().min().unwrap_or(&0),
+            self.partition_sizes.iter().max().unwrap_or(&0),
+            self.partition_skew()
+        );
+
+        self.num_suffixes = T::from_usize(total_size);
         self.partitions = partitions;
 
         Ok(())
     }
 
+    // --------------------------------------------------
+    /// The ratio of the largest partition to the mean partition size.
+    /// A value of 1.0 means the suffixes were spread perfectly evenly;
+    /// a value of 2.0 means the largest partition held twice its share.
+    /// Returns 0.0 before the suffixes have been partitioned.
+    pub fn partition_skew(&self) -> f64 {
+        let total: usize = self.partition_sizes.iter().sum();
+        if total == 0 {
+            0.0
+        } else {
+            let mean = total as f64 / self.partition_sizes.len() as f64;
+            let largest = *self.partition_sizes.iter().max().unwrap_or(&0);
+            largest as f64 / mean
+        }
+    }
+
+    // --------------------------------------------------
+    /// Fill in the statistics that describe the sorted suffixes
+    /// of the first seed mask (or the only sort).
+    fn finish_stats(&mut self) {
+        let num_suffixes = self.num_suffixes.to_usize();
+        let max_lcp = self
+            .partitions
+            .iter()
+            .map(|p| max(p.max_lcp, p.first_lcp))
+            .max()
+            .unwrap_or(0);
+        let lcp_sum: usize = self
+            .partitions
+            .iter()
+            .map(|p| p.lcp_sum + p.first_lcp)
+            .sum();
+
+        self.stats.num_partitions = self.partition_sizes.len();
+        self.stats.partition_sizes = self.partition_sizes.clone();
+        self.stats.partition_skew = self.partition_skew();
+        self.stats.num_suffixes = num_suffixes;
+        self.stats.num_filtered_suffixes = self.text_len.to_usize() - num_suffixes;
+        self.stats.max_lcp = max_lcp;
+        self.stats.mean_lcp = if num_suffixes > 1 {
+            lcp_sum as f64 / (num_suffixes - 1) as f64
+        } else {
+            0.0
+        };
+        self.stats.peak_temp_bytes = self.temp_bytes.peak();
+    }
+
     // --------------------------------------------------
     fn merge_sort(
         &self,
@@ -768,44 +1652,224 @@ where
 
     // --------------------------------------------------
     #[inline(always)]
-    fn select_pivots(
-        &self,
-        text_len: usize,
-        num_partitions: usize,
-        random_seed: u64,
-    ) -> Vec<T> {
-        if num_partitions > 1 {
-            // Use a HashMap because selecting pivots one-at-a-time
-            // can result in duplicates.
-            let num_pivots = num_partitions - 1;
-            let mut rng: Box<dyn RngCore> = if random_seed > 0 {
-                Box::new(StdRng::seed_from_u64(random_seed))
-            } else {
-                Box::new(rand::rng())
-            };
-            let mut pivot_sa = HashSet::<T>::new();
-            loop {
-                let pos = rng.random_range(0..text_len);
-                if self.is_dna && !b"ACGT$".contains(&self.text[pos]) {
-                    continue;
-                }
-                let _ = pivot_sa.insert(T::from_usize(pos));
-                if pivot_sa.len() == num_pivots {
-                    break;
-                }
+    fn select_pivots(
+        &self,
+        text_len: usize,
+        num_partitions: usize,
+        random_seed: u64,
+    ) -> Vec<T> {
+        if num_partitions > 1 {
+            let num_pivots = num_partitions - 1;
+            let is_candidate = |pos: usize| {
+                self.text[pos] == SENTINEL_CHARACTER
+                    || self.is_alphabet_symbol(self.text[pos])
+                        && self.is_suffix_start(pos)
+            };
+
+            // Oversample the suffixes so that the pivots taken from the
+            // sorted candidates divide the suffixes into even partitions,
+            // but never ask for more candidates than there are suffixes
+            // or the sampling below could not finish.
+            let num_eligible = (0..text_len)
+                .into_par_iter()
+                .filter(|&pos| is_candidate(pos))
+                .count();
+            let num_candidates = min(num_pivots * PIVOT_OVERSAMPLING, num_eligible);
+            let mut candidates: Vec<T> = if num_candidates * 2 >= num_eligible {
+                (0..text_len)
+                    .filter(|&pos| is_candidate(pos))
+                    .map(T::from_usize)
+                    .collect()
+            } else {
+                // Use a HashSet because selecting candidates one-at-a-time
+                // can result in duplicates.
+                let mut rng: Box<dyn RngCore> = if random_seed > 0 {
+                    Box::new(StdRng::seed_from_u64(random_seed))
+                } else {
+                    Box::new(rand::rng())
+                };
+                let mut sampled = HashSet::<T>::new();
+                while sampled.len() < num_candidates {
+                    let pos = rng.random_range(0..text_len);
+                    if is_candidate(pos) {
+                        let _ = sampled.insert(T::from_usize(pos));
+                    }
+                }
+                // The order of a HashSet differs between runs
+                let mut sampled: Vec<T> = sampled.into_iter().collect();
+                sampled.sort_unstable();
+                sampled
+            };
+
+            // Sort the candidates
+            let len = candidates.len();
+            let mut sa_w = candidates.clone();
+            let mut lcp = vec![T::default(); len];
+            let mut lcp_w = vec![T::default(); len];
+            self.merge_sort(&mut sa_w, &mut candidates, len, &mut lcp, &mut lcp_w);
+
+            // Take the candidates at regular quantiles as the pivots
+            let mut pivot_sa: Vec<T> = (1..=num_pivots)
+                .map(|i| candidates[i * len / num_partitions])
+                .collect();
+            pivot_sa.dedup();
+            pivot_sa
+        } else {
+            vec![]
+        }
+    }
+
+    // --------------------------------------------------
+    /// Ensure the text holds only characters from the alphabet (if given)
+    /// and that the sentinel occurs only at the end of the text.
+    /// When an alphabet is given and the sequence delimiter is not one of
+    /// its characters, the delimiter may only separate the sequences.
+    /// Without an alphabet, a delimiter such as N may also occur within
+    /// the sequences.
+    ///
+    /// Args:
+    /// * `text`: the sequences joined by a delimiter
+    /// * `sequence_starts`: the start positions of the sequences
+    /// * `alphabet`: an optional alphabet
+    fn validate_text(
+        text: &[u8],
+        sequence_starts: &[usize],
+        alphabet: Option<&Alphabet>,
+    ) -> Result<()> {
+        let len = match text.last() {
+            Some(&SENTINEL_CHARACTER) => text.len() - 1,
+            _ => text.len(),
+        };
+        let delimiter = sequence_starts
+            .get(1)
+            .and_then(|&start| start.checked_sub(1))
+            .and_then(|pos| text.get(pos))
+            .copied()
+            .filter(|&byte| alphabet.is_some_and(|alphabet| !alphabet.is_valid(byte)));
+        let mut boundaries = sequence_starts
+            .iter()
+            .skip(1)
+            .map(|&start| start.saturating_sub(1))
+            .peekable();
+
+        for (pos, &byte) in text[..len].iter().enumerate() {
+            if boundaries.next_if_eq(&pos).is_some() {
+                continue;
+            }
+            if byte == SENTINEL_CHARACTER {
+                bail!("Text contains the sentinel character at position {pos}");
+            }
+            if Some(byte) == delimiter {
+                bail!(
+                    "Text contains the sequence delimiter '{}' at position {pos}",
+                    byte as char
+                );
+            }
+            if let Some(alphabet) = alphabet {
+                if !alphabet.is_valid(byte) {
+                    bail!(
+                        "Text contains '{}' at position {pos}, \
+                        which is not in the {alphabet} alphabet",
+                        byte as char
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // --------------------------------------------------
+    /// Whether or not a character is a (non-ambiguous) member of the
+    /// alphabet and so may start a suffix.
+    ///
+    /// Args:
+    /// * `byte`: a character from the text
+    #[inline(always)]
+    fn is_alphabet_symbol(&self, byte: u8) -> bool {
+        match &self.alphabet {
+            Some(alphabet) => alphabet.is_indexed(byte),
+            // Allow anything if not DNA
+            _ => !self.is_dna || b"ACGT".contains(&byte),
+        }
+    }
+
+    // --------------------------------------------------
+    /// Whether or not the suffix at a position is indexed.
+    ///
+    /// Args:
+    /// * `pos`: a position in the text
+    #[inline(always)]
+    fn is_indexed(&self, pos: usize) -> bool {
+        let val = self.text[pos];
+        val == SENTINEL_CHARACTER
+            || (self.allow_ambiguity || self.is_alphabet_symbol(val))
+                && self.is_suffix_start(pos)
+    }
+
+    // --------------------------------------------------
+    /// Whether or not a suffix may start at a position according to the
+    /// suffix start filter. Suffixes always start at the beginning of
+    /// each sequence, and the filter uses the residues before mapping.
+    /// A semi-specific digest indexes every suffix, as a peptide may
+    /// start anywhere when it ends at a cleavage site.
+    ///
+    /// Args:
+    /// * `pos`: a position in the text
+    #[inline(always)]
+    fn is_suffix_start(&self, pos: usize) -> bool {
+        match &self.suffix_start_filter {
+            Some(filter) if !filter.semi => {
+                let text = if self.original_text.is_empty() {
+                    &self.text
+                } else {
+                    &self.original_text
+                };
+                pos == 0
+                    || filter.is_cleavage_site(text[pos - 1], text[pos])
+                    || self
+                        .sequence_starts
+                        .binary_search(&T::from_usize(pos))
+                        .is_ok()
             }
+            _ => true,
+        }
+    }
 
-            // Sort the selected pivots
-            let mut pivot_sa: Vec<T> = pivot_sa.iter().cloned().collect();
-            let mut sa_w = pivot_sa.clone();
-            let len = pivot_sa.len();
-            let mut lcp = vec![T::default(); len];
-            let mut lcp_w = vec![T::default(); len];
-            self.merge_sort(&mut sa_w, &mut pivot_sa, len, &mut lcp, &mut lcp_w);
-            pivot_sa
-        } else {
-            vec![]
+    // --------------------------------------------------
+    /// Append the reverse complement of each sequence to the text, in the
+    /// same order as the sequences. The text must end with the sentinel,
+    /// and the reverse complements are separated by the same delimiter
+    /// as the sequences (or `%` when there is only one sequence).
+    /// Returns the new text and the start positions of the reverse
+    /// complements.
+    ///
+    /// Args:
+    /// * `text`: the sequences joined by a delimiter and ending in `$`
+    /// * `sequence_starts`: the start positions of the sequences
+    fn add_reverse_complement(
+        mut text: Vec<u8>,
+        sequence_starts: &[usize],
+    ) -> Result<(Vec<u8>, Vec<usize>)> {
+        if text.pop() != Some(SENTINEL_CHARACTER) {
+            bail!("Text must end with the sentinel character");
+        }
+        let delimiter = sequence_starts
+            .get(1)
+            .map_or(SEQUENCE_DELIMITER, |&start| text[start - 1]);
+
+        let fwd_len = text.len();
+        let mut rc_starts = Vec::with_capacity(sequence_starts.len());
+        for (i, &start) in sequence_starts.iter().enumerate() {
+            let end = sequence_starts.get(i + 1).map_or(fwd_len, |&next| next - 1);
+            text.push(delimiter);
+            rc_starts.push(text.len());
+            let rc = reverse_complement(&text[start..end]);
+            text.extend(rc);
         }
+        text.push(SENTINEL_CHARACTER);
+
+        Ok((text, rc_starts))
     }
 
     // --------------------------------------------------
@@ -814,20 +1878,53 @@ where
     ///
     /// Args:
     /// * `filename`: the name of the output file.
-    fn write(&self) -> Result<()> {
+    fn write(&mut self) -> Result<()> {
+        let now = Instant::now();
         let filename = &self.path;
         let mut file = BufWriter::new(
             File::create(filename).map_err(|e| anyhow!("{filename}: {e}"))?,
         );
+        self.serialize(&mut file)?;
+        self.stats.write_secs = now.elapsed().as_secs_f64();
+        Ok(())
+    }
+
+    // --------------------------------------------------
+    /// Serialize a suffix array built with `in_memory` to the bytes
+    /// of a _.sufr_ file, which can be read with
+    /// [SufrFile::from_bytes](super::sufr_file::SufrFile::from_bytes)
+    /// or saved to disk.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        if !self.in_memory {
+            bail!("Suffix array was already written to \"{}\"", self.path);
+        }
+        let mut cursor = Cursor::new(vec![]);
+        self.serialize(&mut cursor)?;
+        Ok(cursor.into_inner())
+    }
 
+    // --------------------------------------------------
+    /// Serialize the sorted partitions in the _.sufr_ format.
+    ///
+    /// Args:
+    /// * `file`: the output file or buffer
+    fn serialize<W: Write + Seek>(&self, file: &mut W) -> Result<()> {
         let mut bytes_out: usize = 0;
 
         // Various metadata
         let is_dna: u8 = if self.is_dna { 1 } else { 0 };
         let allow_ambiguity: u8 = if self.allow_ambiguity { 1 } else { 0 };
         let ignore_softmask: u8 = if self.ignore_softmask { 1 } else { 0 };
-        bytes_out +=
-            file.write(&[OUTFILE_VERSION, is_dna, allow_ambiguity, ignore_softmask])?;
+        let both_strands: u8 = if self.both_strands { 1 } else { 0 };
+        let alphabet: u8 = self.alphabet.as_ref().map_or(0, |a| a.to_code());
+        bytes_out += file.write(&[
+            OUTFILE_VERSION,
+            is_dna,
+            allow_ambiguity,
+            ignore_softmask,
+            both_strands,
+            alphabet,
+        ])?;
 
         // Text length
         bytes_out += file.write(&usize_to_bytes(self.text_len.to_usize()))?;
@@ -843,10 +1940,9 @@ where
         bytes_out += file.write(&usize_to_bytes(self.num_suffixes.to_usize()))?;
 
         // Max query length
-        let max_query_len = if let SuffixSortType::MaxQueryLen(val) = &self.sort_type {
-            *val
-        } else {
-            0
+        let max_query_len = match &self.sort_type {
+            SuffixSortType::MaxQueryLen(val) => *val,
+            SuffixSortType::Mask(seed_mask) => seed_mask.max_query_len,
         };
         bytes_out += file.write(&usize_to_bytes(max_query_len))?;
 
@@ -856,57 +1952,88 @@ where
         // Sequence starts
         bytes_out += file.write(vec_to_slice_u8(&self.sequence_starts))?;
 
-        // Seed mask
-        match &self.sort_type {
-            SuffixSortType::Mask(seed_mask) => {
-                bytes_out += file.write(&usize_to_bytes(seed_mask.bytes.len()))?;
-                file.write_all(&seed_mask.bytes)?;
-                bytes_out += seed_mask.bytes.len();
+        // Seed masks
+        let mut seed_masks: Vec<&SeedMask> =
+            self.extra_masks.iter().map(|(mask, _)| mask).collect();
+        if let SuffixSortType::Mask(seed_mask) = &self.sort_type {
+            seed_masks.insert(0, seed_mask);
+        }
+        bytes_out += file.write(&usize_to_bytes(seed_masks.len()))?;
+        for seed_mask in seed_masks {
+            bytes_out += file.write(&usize_to_bytes(seed_mask.bytes.len()))?;
+            file.write_all(&seed_mask.bytes)?;
+            bytes_out += seed_mask.bytes.len();
+        }
+
+        // Locations of the suffix/LCP arrays of the extra masks
+        // Will be corrected at the end
+        let extra_locs_pos = file.stream_position()?;
+        for _ in &self.extra_masks {
+            bytes_out += file.write(&usize_to_bytes(0usize))?;
+            bytes_out += file.write(&usize_to_bytes(0usize))?;
+        }
+
+        // Symbols of a byte alphabet
+        match &self.alphabet {
+            Some(Alphabet::Bytes(symbols)) => {
+                bytes_out += file.write(&usize_to_bytes(symbols.len()))?;
+                file.write_all(symbols)?;
+                bytes_out += symbols.len();
             }
             _ => bytes_out += file.write(&usize_to_bytes(0))?,
         }
 
-        // Text
-        let text_pos = bytes_out;
-        file.write_all(&self.text)?;
-        bytes_out += self.text.len();
-
-        // Stitch partitioned suffix files together
-        let sa_pos = bytes_out;
-        for partition in &self.partitions {
-            let buffer = fs::read(&partition.sa_path)?;
-            bytes_out += &buffer.len();
-            file.write_all(&buffer)?;
-            fs::remove_file(&partition.sa_path)?;
+        // Residue map
+        match &self.residue_map {
+            Some(residue_map) => {
+                let name = residue_map.name.as_bytes();
+                bytes_out += file.write(&usize_to_bytes(name.len()))?;
+                file.write_all(name)?;
+                bytes_out += name.len();
+            }
+            _ => bytes_out += file.write(&usize_to_bytes(0))?,
         }
 
-        let lcp_pos = bytes_out;
+        // Suffix start filter
+        match &self.suffix_start_filter {
+            Some(filter) => {
+                let name = filter.name.as_bytes();
+                bytes_out += file.write(&usize_to_bytes(name.len()))?;
+                file.write_all(name)?;
+                bytes_out += name.len();
+            }
+            _ => bytes_out += file.write(&usize_to_bytes(0))?,
+        }
 
-        // Stitch partitioned LCP files together
-        for (i, partition) in self.partitions.iter().enumerate() {
-            let buffer = fs::read(&partition.lcp_path)?;
-            bytes_out += &buffer.len();
+        // Text
+        let text = if self.original_text.is_empty() {
+            &self.text
+        } else {
+            &self.original_text
+        };
+        let text_pos = bytes_out;
+        file.write_all(text)?;
+        bytes_out += text.len();
 
-            if i == 0 {
-                file.write_all(&buffer)?;
-            } else {
-                // Fix LCP boundary
-                let mut lcp: Vec<T> = slice_u8_to_vec(&buffer, partition.len);
-                if let Some(val) = lcp.first_mut() {
-                    *val = self.find_lcp(
-                        self.partitions[i - 1].last_suffix,
-                        partition.first_suffix,
-                        self.text_len,
-                        0, // start at beginning
-                    );
-                }
-                file.write_all(vec_to_slice_u8(&lcp))?;
-            }
-            fs::remove_file(&partition.lcp_path)?;
+        // Suffix and LCP arrays
+        let sa_pos = bytes_out;
+        let (sa_len, lcp_len) = self.write_partitions(file, &self.partitions)?;
+        let lcp_pos = sa_pos + sa_len;
+        bytes_out += sa_len + lcp_len;
+
+        let mut extra_locs = vec![];
+        for (_, partitions) in &self.extra_masks {
+            let (sa_len, lcp_len) = self.write_partitions(file, partitions)?;
+            extra_locs.push((bytes_out, bytes_out + sa_len));
+            bytes_out += sa_len + lcp_len;
         }
 
-        // Sequence names are variable in length so they are at the end
-        _ = file.write(&bincode::serialize(&self.sequence_names)?)?;
+        // Sequence names and metadata, tombstones, and the build summary
+        // are variable in length so they are at the end
+        file.write_all(&bincode::serialize(&self.sequence_names)?)?;
+        file.write_all(&bincode::serialize(&self.sequence_metadata)?)?;
+        file.write_all(&bincode::serialize(&self.tombstones)?)?;
+        file.write_all(&bincode::serialize(&self.stats.summary())?)?;
 
         // Go back to header and record the locations
         file.seek(SeekFrom::Start(locs_pos))?;
@@ -914,14 +2041,93 @@ where
         let _ = file.write(&usize_to_bytes(sa_pos))?;
         let _ = file.write(&usize_to_bytes(lcp_pos))?;
 
+        file.seek(SeekFrom::Start(extra_locs_pos))?;
+        for (sa_pos, lcp_pos) in extra_locs {
+            let _ = file.write(&usize_to_bytes(sa_pos))?;
+            let _ = file.write(&usize_to_bytes(lcp_pos))?;
+        }
+
         Ok(())
     }
+
+    // --------------------------------------------------
+    /// Stitch the sorted partitions together into a suffix array
+    /// followed by an LCP array, removing any temporary files.
+    /// Returns the number of bytes written for each array.
+    ///
+    /// Args:
+    /// * `file`: the output file or buffer
+    /// * `partitions`: the sorted partitions
+    fn write_partitions<W: Write>(
+        &self,
+        file: &mut W,
+        partitions: &[Partition<T>],
+    ) -> Result<(usize, usize)> {
+        // Stitch partitioned suffix files together
+        let mut sa_len = 0;
+        for partition in partitions {
+            match &partition.data {
+                PartitionData::File { sa_path, .. } => {
+                    let buffer = fs::read(sa_path)?;
+                    sa_len += &buffer.len();
+                    file.write_all(&buffer)?;
+                    fs::remove_file(sa_path)?;
+                    self.temp_bytes.remove(buffer.len());
+                }
+                PartitionData::Memory { sa, .. } => {
+                    let buffer = vec_to_slice_u8(sa);
+                    sa_len += buffer.len();
+                    file.write_all(buffer)?;
+                }
+            }
+        }
+
+        // Stitch partitioned LCP files together
+        let mut lcp_len = 0;
+        for (i, partition) in partitions.iter().enumerate() {
+            match &partition.data {
+                PartitionData::File { lcp_path, .. } => {
+                    let buffer = fs::read(lcp_path)?;
+                    lcp_len += &buffer.len();
+
+                    if i == 0 {
+                        file.write_all(&buffer)?;
+                    } else {
+                        // Fix LCP boundary
+                        let mut lcp: Vec<T> = slice_u8_to_vec(&buffer, partition.len);
+                        if let Some(val) = lcp.first_mut() {
+                            *val = T::from_usize(partition.first_lcp);
+                        }
+                        file.write_all(vec_to_slice_u8(&lcp))?;
+                    }
+                    fs::remove_file(lcp_path)?;
+                    self.temp_bytes.remove(buffer.len());
+                }
+                PartitionData::Memory { lcp, .. } => {
+                    lcp_len += lcp.len() * mem::size_of::<T>();
+                    if i == 0 {
+                        file.write_all(vec_to_slice_u8(lcp))?;
+                    } else {
+                        // Fix LCP boundary
+                        let first = [T::from_usize(partition.first_lcp)];
+                        file.write_all(vec_to_slice_u8(&first))?;
+                        file.write_all(vec_to_slice_u8(&lcp[1..]))?;
+                    }
+                }
+            }
+        }
+
+        Ok((sa_len, lcp_len))
+    }
 }
 
 // --------------------------------------------------
-/// Represents the partition values written to disk
+/// Represents the sorted partition values
 #[derive(Debug)]
-struct Partition {
+struct Partition<T>
+where
+    T: Int + FromUsize<T> + Sized + Send + Sync + serde::ser::Serialize,
+{
     /// The sorted position of this parition.
     order: usize,
 
@@ -934,11 +2140,42 @@ struct Partition {
     /// The value of the last suffix. Used in stitching together the LCPs.
     last_suffix: usize,
 
-    /// The path to the file containing the suffix array.
-    sa_path: PathBuf,
+    /// The LCP of the first suffix and the last suffix of the
+    /// previous partition, which replaces the first LCP value.
+    first_lcp: usize,
+
+    /// The largest LCP within this partition, excluding the first.
+    max_lcp: usize,
+
+    /// The sum of the LCPs within this partition, excluding the first.
+    lcp_sum: usize,
+
+    /// Where the sorted suffix and LCP values live.
+    data: PartitionData<T>,
+}
+
+// --------------------------------------------------
+/// The sorted suffix and LCP values of a partition, either in
+/// temporary files or in memory.
+#[derive(Debug)]
+enum PartitionData<T>
+where
+    T: Int + FromUsize<T> + Sized + Send + Sync + serde::ser::Serialize,
+{
+    File {
+        /// The path to the file containing the suffix array.
+        sa_path: PathBuf,
+
+        /// The path to the file containing the LCP array.
+        lcp_path: PathBuf,
+    },
+    Memory {
+        /// The suffix array.
+        sa: Vec<T>,
 
-    /// The path to the file containing the LCP array.
-    lcp_path: PathBuf,
+        /// The LCP array, whose first value is not yet corrected.
+        lcp: Vec<T>,
+    },
 }
 
 // --------------------------------------------------
@@ -955,6 +2192,35 @@ where
     num_suffixes: usize,
 }
 
+// --------------------------------------------------
+/// Tracks the bytes held in temporary files across threads and the
+/// most that were held at any one time.
+#[derive(Debug, Default)]
+struct TempBytes {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+// --------------------------------------------------
+impl TempBytes {
+    /// Record bytes written to a temporary file
+    fn add(&self, num_bytes: usize) {
+        let current =
+            self.current.fetch_add(num_bytes, AtomicOrdering::SeqCst) + num_bytes;
+        self.peak.fetch_max(current, AtomicOrdering::SeqCst);
+    }
+
+    /// Record bytes freed by removing a temporary file
+    fn remove(&self, num_bytes: usize) {
+        self.current.fetch_sub(num_bytes, AtomicOrdering::SeqCst);
+    }
+
+    /// The most bytes held at one time
+    fn peak(&self) -> usize {
+        self.peak.load(AtomicOrdering::SeqCst)
+    }
+}
+
 // --------------------------------------------------
 /// A struct for writing suffixes to disk.
 #[derive(Debug)]
@@ -966,7 +2232,13 @@ where
     capacity: usize,
     len: usize,
     total_len: usize,
-    path: PathBuf,
+
+    /// The temporary file of suffixes, or `None` when all of the
+    /// suffixes are kept in `vals`.
+    path: Option<PathBuf>,
+
+    /// The tally of bytes in temporary files
+    temp_bytes: Arc<TempBytes>,
 }
 
 // --------------------------------------------------
@@ -984,7 +2256,25 @@ where
     ///   but it might be worth tuning this, perhaps use more memory to hit
     ///   disk less? Or if memory use is too high, lower and take a performance
     ///   hit for disk access?
-    fn new(capacity: usize) -> Result<Self> {
+    /// * `in_memory`: whether to keep all the suffixes in memory
+    ///   rather than writing them to disk
+    /// * `temp_bytes`: the tally of bytes in temporary files
+    fn new(
+        capacity: usize,
+        in_memory: bool,
+        temp_bytes: &Arc<TempBytes>,
+    ) -> Result<Self> {
+        if in_memory {
+            return Ok(PartitionBuilder {
+                vals: vec![],
+                len: 0,
+                total_len: 0,
+                capacity,
+                path: None,
+                temp_bytes: Arc::clone(temp_bytes),
+            });
+        }
+
         let tmp = NamedTempFile::new()?;
         let (_, path) = tmp.keep()?;
 
@@ -994,7 +2284,8 @@ where
             len: 0,
             total_len: 0,
             capacity,
-            path,
+            path: Some(path),
+            temp_bytes: Arc::clone(temp_bytes),
         })
     }
 
@@ -1004,6 +2295,12 @@ where
     /// Args:
     /// * `val`: the suffix position to add
     pub fn add(&mut self, val: T) -> Result<()> {
+        if self.path.is_none() {
+            self.vals.push(val);
+            self.total_len += 1;
+            return Ok(());
+        }
+
         self.vals[self.len] = val;
         self.len += 1;
         if self.len == self.capacity {
@@ -1017,13 +2314,11 @@ where
     /// Write the suffixes to disk. This must be called at the end to flush
     /// any remaining values after the last call(s) from `add`.
     pub fn write(&mut self) -> Result<()> {
-        if self.len > 0 {
-            let mut file = OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(&self.path)?;
+        if let (Some(path), true) = (&self.path, self.len > 0) {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
             file.write_all(vec_to_slice_u8(&self.vals[0..self.len]))?;
             self.total_len += self.len;
+            self.temp_bytes.add(self.len * mem::size_of::<T>());
         }
         Ok(())
     }
@@ -1033,10 +2328,15 @@ where
 #[cfg(test)]
 mod test {
     use super::{SufrBuilder, SufrBuilderArgs};
+    use crate::{
+        sufr_file::SufrFile,
+        types::{Alphabet, LocateOptions, SequenceRecord},
+        util::read_sequence_file,
+    };
     use anyhow::Result;
-    use std::fs;
-    use tempfile::NamedTempFile;
     use pretty_assertions::assert_eq;
+    use std::{fs, path::Path};
+    use tempfile::NamedTempFile;
 
     #[test]
     fn test_is_less() -> Result<()> {
@@ -1054,8 +2354,13 @@ mod test {
             sequence_starts: vec![0],
             sequence_names: vec!["1".to_string()],
             num_partitions: 2,
-            seed_mask: None,
+            seed_masks: vec![],
             random_seed: 0,
+            both_strands: false,
+            alphabet: None,
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
         };
         let sufr = SufrBuilder::<u32>::new(args)?;
 
@@ -1096,8 +2401,13 @@ mod test {
             sequence_starts: vec![0],
             sequence_names: vec!["1".to_string()],
             num_partitions: 2,
-            seed_mask: None,
+            seed_masks: vec![],
             random_seed: 0,
+            both_strands: false,
+            alphabet: None,
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
         };
         let sufr = SufrBuilder::<u32>::new(args)?;
 
@@ -1141,8 +2451,13 @@ mod test {
             sequence_starts: vec![0],
             sequence_names: vec!["1".to_string()],
             num_partitions: 2,
-            seed_mask: Some("101".to_string()),
+            seed_masks: vec!["101".to_string()],
             random_seed: 0,
+            both_strands: false,
+            alphabet: None,
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
         };
         let sufr: SufrBuilder<u32> = SufrBuilder::new(args)?;
 
@@ -1187,8 +2502,13 @@ mod test {
             sequence_starts: vec![0],
             sequence_names: vec!["1".to_string()],
             num_partitions: 2,
-            seed_mask: None,
+            seed_masks: vec![],
             random_seed: 0,
+            both_strands: false,
+            alphabet: None,
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
         };
         let sufr: SufrBuilder<u32> = SufrBuilder::new(args)?;
 
@@ -1235,8 +2555,13 @@ mod test {
             sequence_starts: vec![0],
             sequence_names: vec!["1".to_string()],
             num_partitions: 2,
-            seed_mask: Some("1101".to_string()),
+            seed_masks: vec!["1101".to_string()],
             random_seed: 42,
+            both_strands: false,
+            alphabet: None,
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
         };
         let sufr: SufrBuilder<u32> = SufrBuilder::new(args)?;
 
@@ -1273,8 +2598,13 @@ mod test {
             sequence_starts: vec![0],
             sequence_names: vec!["1".to_string()],
             num_partitions: 2,
-            seed_mask: None,
+            seed_masks: vec![],
             random_seed: 42,
+            both_strands: false,
+            alphabet: None,
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
         };
         let sufr: SufrBuilder<u32> = SufrBuilder::new(args)?;
 
@@ -1308,8 +2638,13 @@ mod test {
             sequence_starts: vec![0],
             sequence_names: vec!["1".to_string()],
             num_partitions: 2,
-            seed_mask: None,
+            seed_masks: vec![],
             random_seed: 42,
+            both_strands: false,
+            alphabet: None,
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
         };
 
         let sufr: SufrBuilder<u64> = SufrBuilder::new(args)?;
@@ -1363,8 +2698,13 @@ mod test {
             sequence_starts: vec![0],
             sequence_names: vec!["1".to_string()],
             num_partitions: 2,
-            seed_mask: Some("101".to_string()),
+            seed_masks: vec!["101".to_string()],
             random_seed: 42,
+            both_strands: false,
+            alphabet: None,
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
         };
         let sufr: SufrBuilder<u32> = SufrBuilder::new(args)?;
 
@@ -1403,4 +2743,342 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_select_pivots() -> Result<()> {
+        let seq =
+            read_sequence_file(Path::new("../data/inputs/long_dna_sequence.fa"), b'%')?;
+        let outfile = NamedTempFile::new()?;
+        let args = SufrBuilderArgs {
+            text: seq.seq,
+            low_memory: true,
+            path: Some(outfile.path().to_string_lossy().to_string()),
+            max_query_len: None,
+            is_dna: true,
+            allow_ambiguity: false,
+            ignore_softmask: false,
+            sequence_starts: seq.start_positions,
+            sequence_names: seq.sequence_names,
+            num_partitions: 16,
+            seed_masks: vec![],
+            random_seed: 42,
+            both_strands: false,
+            alphabet: None,
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
+        };
+        let sufr: SufrBuilder<u32> = SufrBuilder::new(args)?;
+
+        // The pivots are unique and sorted
+        let pivots = sufr.select_pivots(sufr.text.len(), 16, 42);
+        assert!(!pivots.is_empty() && pivots.len() <= 15);
+        assert!(pivots.windows(2).all(|w| sufr.is_less(w[0], w[1])));
+
+        // The same seed selects the same pivots
+        assert_eq!(pivots, sufr.select_pivots(sufr.text.len(), 16, 42));
+
+        // No pivots are needed for one partition
+        assert!(sufr.select_pivots(sufr.text.len(), 1, 42).is_empty());
+
+        // Oversampled pivots keep the largest partition near its share
+        assert_eq!(sufr.partition_sizes.len(), pivots.len() + 1);
+        assert_eq!(
+            sufr.partition_sizes.iter().sum::<usize>(),
+            sufr.num_suffixes as usize
+        );
+        assert!(sufr.partition_skew() < 2.0);
+
+        fs::remove_file(outfile)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_pivots_few_suffixes() -> Result<()> {
+        // Far fewer suffixes may start than the oversampled candidates
+        let mut text = vec![b'N'; 10_000];
+        text.extend(b"ACGT$");
+        let args = SufrBuilderArgs {
+            text,
+            low_memory: true,
+            path: None,
+            max_query_len: None,
+            is_dna: true,
+            allow_ambiguity: false,
+            ignore_softmask: false,
+            sequence_starts: vec![0],
+            sequence_names: vec!["1".to_string()],
+            num_partitions: 64,
+            seed_masks: vec![],
+            random_seed: 42,
+            both_strands: false,
+            alphabet: None,
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
+        };
+        let sufr: SufrBuilder<u32> = SufrBuilder::in_memory(args)?;
+        assert_eq!(sufr.num_suffixes, 5);
+
+        let pivots = sufr.select_pivots(sufr.text.len(), 64, 42);
+        assert!(!pivots.is_empty() && pivots.len() <= 5);
+        assert!(pivots.windows(2).all(|w| sufr.is_less(w[0], w[1])));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_text() -> Result<()> {
+        // The sentinel and delimiter can only end/separate sequences
+        let res = SufrBuilder::<u32>::validate_text(b"AC%GT$", &[0, 3], None);
+        assert!(res.is_ok());
+
+        let res = SufrBuilder::<u32>::validate_text(b"AC%G$T$", &[0, 3], None);
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Text contains the sentinel character at position 4"
+        );
+
+        let dna = Alphabet::Dna;
+        let res = SufrBuilder::<u32>::validate_text(b"A%C%GT$", &[0, 4], Some(&dna));
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Text contains the sequence delimiter '%' at position 1"
+        );
+
+        // Without an alphabet, the delimiter may occur in the sequences
+        let res = SufrBuilder::<u32>::validate_text(b"A%C%GT$", &[0, 4], None);
+        assert!(res.is_ok());
+
+        // The delimiter may be a character of the alphabet
+        let res = SufrBuilder::<u32>::validate_text(b"ANCNGT$", &[0, 4], Some(&dna));
+        assert!(res.is_ok());
+
+        let res = SufrBuilder::<u32>::validate_text(b"ACGT$$", &[0], None);
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Text contains the sentinel character at position 4"
+        );
+
+        // Ambiguity codes are valid but other characters are not
+        let res = SufrBuilder::<u32>::validate_text(b"ACNGT$", &[0], Some(&dna));
+        assert!(res.is_ok());
+
+        let res = SufrBuilder::<u32>::validate_text(b"ACGU$", &[0], Some(&dna));
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Text contains 'U' at position 3, which is not in the dna alphabet"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_n_delimited_dna() -> Result<()> {
+        // Ns both separate the sequences and occur within them
+        let seq = read_sequence_file(Path::new("../data/inputs/3.fa"), b'N')?;
+        let args = SufrBuilderArgs {
+            text: seq.seq,
+            low_memory: true,
+            path: None,
+            max_query_len: None,
+            is_dna: true,
+            allow_ambiguity: true,
+            ignore_softmask: false,
+            sequence_starts: seq.start_positions,
+            sequence_names: seq.sequence_names,
+            num_partitions: 2,
+            seed_masks: vec![],
+            random_seed: 42,
+            both_strands: false,
+            alphabet: None,
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
+        };
+        let sufr: SufrBuilder<u32> = SufrBuilder::in_memory(args.clone())?;
+        assert_eq!(sufr.num_sequences, 3);
+        assert_eq!(sufr.num_suffixes as usize, sufr.text.len());
+
+        // The DNA alphabet also allows N in the sequences
+        let res = SufrBuilder::<u32>::in_memory(SufrBuilderArgs {
+            alphabet: Some(Alphabet::Dna),
+            ..args
+        });
+        assert!(res.is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_alphabet_indexed_suffixes() -> Result<()> {
+        let text = b"MKXLVmkB$".to_vec();
+        let outfile = NamedTempFile::new()?;
+        let args = SufrBuilderArgs {
+            text,
+            low_memory: true,
+            path: Some(outfile.path().to_string_lossy().to_string()),
+            max_query_len: None,
+            is_dna: false,
+            allow_ambiguity: false,
+            ignore_softmask: true,
+            sequence_starts: vec![0],
+            sequence_names: vec!["1".to_string()],
+            num_partitions: 2,
+            seed_masks: vec![],
+            random_seed: 42,
+            both_strands: false,
+            alphabet: Some(Alphabet::ProteinExtended),
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
+        };
+        let sufr: SufrBuilder<u32> = SufrBuilder::new(args.clone())?;
+
+        // Softmasked residues are replaced with X, and neither X nor the
+        // ambiguous B start a suffix
+        assert_eq!(sufr.text, b"MKXLVXXB$");
+        assert_eq!(sufr.num_suffixes, 5);
+
+        // The text must be protein
+        let res = SufrBuilder::<u32>::new(SufrBuilderArgs {
+            alphabet: Some(Alphabet::Protein),
+            text: b"MK1$".to_vec(),
+            ..args.clone()
+        });
+        assert!(res.is_err());
+
+        // The alphabet must agree with the `is_dna` flag
+        let res = SufrBuilder::<u32>::new(SufrBuilderArgs {
+            is_dna: true,
+            ..args
+        });
+        assert!(res.is_err());
+
+        fs::remove_file(outfile)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_records() -> Result<()> {
+        // A record type carrying metadata that is not indexed
+        struct Record {
+            id: u32,
+            seq: Vec<u8>,
+            organism: String,
+        }
+
+        impl SequenceRecord for Record {
+            fn name(&self) -> String {
+                format!("seq{}", self.id)
+            }
+
+            fn sequence(&self) -> &[u8] {
+                &self.seq
+            }
+
+            fn metadata(&self) -> Option<String> {
+                Some(self.organism.clone())
+            }
+        }
+
+        let outfile = NamedTempFile::new()?;
+        let outpath = outfile.path().to_string_lossy().to_string();
+        let seq_data = read_sequence_file(Path::new("../data/inputs/2.fa"), b'%')?;
+        let args = SufrBuilderArgs {
+            text: seq_data.seq,
+            low_memory: true,
+            path: Some(outpath.clone()),
+            max_query_len: None,
+            is_dna: true,
+            allow_ambiguity: false,
+            ignore_softmask: false,
+            sequence_starts: seq_data.start_positions,
+            sequence_names: vec!["seq1".to_string(), "seq2".to_string()],
+            num_partitions: 2,
+            seed_masks: vec![],
+            random_seed: 42,
+            both_strands: false,
+            alphabet: None,
+            residue_map: None,
+            suffix_start_filter: None,
+            filler_runs: None,
+        };
+        let expected: SufrBuilder<u32> = SufrBuilder::new(args.clone())?;
+        let expected_file = fs::read(&outpath)?;
+
+        let empty_args = SufrBuilderArgs {
+            text: vec![],
+            sequence_starts: vec![],
+            sequence_names: vec![],
+            ..args.clone()
+        };
+        let records = [("seq1", "ACGTacgt"), ("seq2", "acgtACGT")];
+        let sufr: SufrBuilder<u32> =
+            SufrBuilder::from_records(records, empty_args.clone())?;
+        assert_eq!(sufr.text, expected.text);
+        assert_eq!(sufr.sequence_starts, expected.sequence_starts);
+        assert_eq!(sufr.sequence_names, expected.sequence_names);
+        assert_eq!(sufr.sequence_metadata, [None, None]);
+        assert_eq!(fs::read(&outpath)?, expected_file);
+
+        // The metadata is stored with the names and reported with hits
+        let records = [(1, b"ACGTacgt"), (2, b"acgtACGT")].map(|(id, seq)| Record {
+            id,
+            seq: seq.to_vec(),
+            organism: format!("E. coli {id}"),
+        });
+        let sufr: SufrBuilder<u32> =
+            SufrBuilder::from_records(records, empty_args.clone())?;
+        assert_eq!(sufr.text, expected.text);
+        let mut sufr_file: SufrFile<u32> = SufrFile::read(&outpath, true)?;
+        let metadata =
+            vec![Some("E. coli 1".to_string()), Some("E. coli 2".to_string())];
+        assert_eq!(sufr_file.sequence_metadata, metadata);
+        assert_eq!(sufr_file.metadata()?.sequence_metadata, metadata);
+        let res = sufr_file.locate(LocateOptions {
+            queries: vec!["GTAC".to_string()],
+            max_query_len: None,
+            low_memory: true,
+        })?;
+        let mut hits: Vec<_> = res[0]
+            .positions
+            .iter()
+            .map(|pos| (pos.sequence_name.as_str(), pos.sequence_metadata.as_deref()))
+            .collect();
+        hits.sort();
+        assert_eq!(
+            hits,
+            [("seq1", Some("E. coli 1")), ("seq2", Some("E. coli 2"))]
+        );
+
+        // The metadata survives removing sequences and compacting
+        sufr_file.remove_sequences(&["seq1".to_string()])?;
+        let mut sufr_file: SufrFile<u32> = SufrFile::read(&outpath, true)?;
+        assert_eq!(sufr_file.sequence_metadata, metadata);
+        sufr_file.compact(1)?;
+        assert_eq!(sufr_file.sequence_metadata, metadata[1..]);
+
+        // No record may contain the sequence delimiter
+        let res: Result<SufrBuilder<u32>> =
+            SufrBuilder::from_records([("a", "AC%GT")], empty_args.clone());
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Record \"a\" contains the sequence delimiter '%'"
+        );
+
+        // The records must not be combined with a text
+        let res: Result<SufrBuilder<u32>> =
+            SufrBuilder::from_records([("a", "ACGT")], args);
+        assert!(res.is_err());
+
+        // There must be records
+        let res: Result<SufrBuilder<u32>> =
+            SufrBuilder::from_records(Vec::<(&str, &str)>::new(), empty_args);
+        assert!(res.is_err());
+
+        Ok(())
+    }
 }
@@ -4,31 +4,44 @@
 //! fine-grained control over whether Sufr uses 32-bit or 64-bit
 //! integers. Most likely, you should use [libsufr::suffix_array](super::suffix_array).
 use crate::{
-    file_access::FileAccess,
-    sufr_search::{SufrSearch, SufrSearchArgs},
+    child_table::ChildTable,
+    document_array::DocumentArray,
+    file_access::{FileAccess, FileAccessIter},
+    sufr_builder::SufrBuilder,
+    sufr_search::{LcpLr, SufrSearch, SufrSearchArgs},
     types::{
-        BisectOptions, BisectResult, CountOptions, CountResult, ExtractOptions, ExtractResult, ExtractSequence,
-        FromUsize, Int, ListOptions, LocateOptions, LocatePosition, LocateResult,
-        SearchOptions, SearchResult, SeedMask, SuffixSortType, SufrMetadata,
+        Alphabet, AppendOptions, BisectOptions, BisectResult, BuildSummary,
+        ComplexityOptions, ComplexityStats, CountOptions, CountResult, DocumentCount,
+        DocumentResult, ExtractOptions, ExtractResult, ExtractSequence, FromUsize, Int,
+        Kmer, LcpInterval, ListOptions, LocateOptions, LocatePosition, LocateResult,
+        MaskedIndex, MatchingStatistic, MatchingStatisticsOptions,
+        MatchingStatisticsResult, Mem, MemsOptions, MemsResult, Repeat, RepeatsOptions,
+        ResidueMap, SearchOptions, SearchResult, SeedMask, SequenceComplexity, Strand,
+        SuffixSortType, SuffixStartFilter, SufrMetadata, SENTINEL_CHARACTER,
+        SEQUENCE_DELIMITER,
     },
-    util::{slice_u8_to_vec, usize_to_bytes},
+    util::{slice_u8_to_vec, usize_to_bytes, vec_to_slice_u8},
 };
-use anyhow::{anyhow, Result, bail};
+use anyhow::{anyhow, bail, Result};
 use chrono::{DateTime, Local};
 use home::home_dir;
 use log::info;
 use rayon::prelude::*;
 use std::{
     cell::RefCell,
-    cmp::min,
-    fs::{self, File},
-    io::{self, Read, Seek, Write},
+    cmp::{max, min, Reverse},
+    collections::{BinaryHeap, HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{self, Cursor, Read, Seek, SeekFrom, Write},
+    iter::{Enumerate, Zip},
     mem,
     ops::Range,
     path::{Path, PathBuf},
     slice,
+    sync::Arc,
     time::Instant,
 };
+use tempfile::NamedTempFile;
 use thread_local::ThreadLocal;
 
 // --------------------------------------------------
@@ -40,7 +53,8 @@ pub struct SufrFile<T>
 where
     T: Int + FromUsize<T> + Sized + Send + Sync + serde::ser::Serialize,
 {
-    /// The _.sufr_ filename
+    /// The _.sufr_ filename, which is empty when the suffix array
+    /// lives only in memory
     pub filename: String,
 
     /// The serialization version.
@@ -57,6 +71,20 @@ where
     /// nucleotides were ignored.
     pub ignore_softmask: bool,
 
+    /// When `is_dna` is `true`, whether or not the reverse complement
+    /// of each sequence was appended to the text.
+    pub both_strands: bool,
+
+    /// The alphabet used to validate the text and queries, if any.
+    pub alphabet: Option<Alphabet>,
+
+    /// The reduced alphabet under which the suffixes were sorted, if any.
+    /// Queries are mapped with this before searching.
+    pub residue_map: Option<ResidueMap>,
+
+    /// The rule used to select the positions where suffixes start, if any.
+    pub suffix_start_filter: Option<SuffixStartFilter>,
+
     /// Whether or not to query the suffix array in-memory or on disk.
     pub query_low_memory: bool,
 
@@ -72,6 +100,13 @@ where
     /// How the suffixes were sorted (fully, max query length, spaced seeds)
     pub sort_type: SuffixSortType,
 
+    /// The suffix/LCP arrays sorted under each seed mask, if any.
+    /// The first is the same as `sort_type`.
+    pub masked_indexes: Vec<MaskedIndex>,
+
+    /// The index into `masked_indexes` of the seed mask being queried
+    pub mask_num: usize,
+
     /// The length of the text. Needed for parameterization of the struct
     /// as `u32` or `u64`. Cf. [util::read_text_length](super::util::read_text_length)
     pub text_len: T,
@@ -88,6 +123,16 @@ where
     /// The names of the sequences
     pub sequence_names: Vec<String>,
 
+    /// The metadata of the sequences, if any, in the order of the names
+    pub sequence_metadata: Vec<Option<String>>,
+
+    /// The sorted indices into `sequence_names` of the sequences removed
+    /// from search results (cf. `remove_sequences`)
+    pub tombstones: Vec<usize>,
+
+    /// How the suffix array was built
+    pub build_summary: BuildSummary,
+
     /// The original text that was indexed.
     pub text: Vec<u8>,
 
@@ -109,6 +154,33 @@ where
 
     /// In-memory access to the suffix array ranks
     suffix_array_rank_mem: Vec<T>,
+
+    /// The in-memory suffix arrays, their maximum query lengths, and
+    /// ranks set aside for each seed mask when switching masks
+    masked_suffix_array_mem: Vec<(Vec<T>, Option<usize>, Vec<T>)>,
+
+    /// Whether to search the full suffix array with LCP-LR arrays
+    lcp_lr_enabled: bool,
+
+    /// The LCP-LR arrays of the current mask, once loaded
+    lcp_lr: Option<LcpLr<T>>,
+
+    /// The child table of the LCP array, once loaded
+    child_table: Option<ChildTable<T>>,
+
+    /// The document array of the current mask, once loaded
+    document_array: Option<DocumentArray<T>>,
+
+    /// The serialized suffix array when it was never written to disk
+    bytes: Option<Arc<[u8]>>,
+
+    /// When the suffix array was read or built
+    created: DateTime<Local>,
+
+    /// The byte position where the sequence names begin, which are
+    /// followed only by the sequence metadata, the tombstones, and the
+    /// build summary
+    names_pos: usize,
 }
 
 // --------------------------------------------------
@@ -144,14 +216,60 @@ where
     /// ```
     pub fn read(filename: &str, low_memory: bool) -> Result<SufrFile<T>> {
         let mut file = File::open(filename).map_err(|e| anyhow!("{filename}: {e}"))?;
+        Self::parse(&mut file, filename, None, low_memory)
+    }
+
+    // --------------------------------------------------
+    /// Read a suffix array from its serialized bytes, such as those from
+    /// [SufrBuilder::to_bytes](super::sufr_builder::SufrBuilder::to_bytes).
+    /// Nothing is read from or written to disk, including the caches
+    /// of subsampled suffix arrays; use `save` to write a _.sufr_ file.
+    ///
+    /// Args:
+    /// * `bytes`: the serialized suffix array
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::sufr_file::SufrFile;
+    /// use std::fs;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let bytes = fs::read("../data/inputs/1.sufr")?;
+    ///     let sufr_file: SufrFile<u32> = SufrFile::from_bytes(bytes)?;
+    ///     assert_eq!(sufr_file.text_len, 11);
+    ///     assert!(sufr_file.filename.is_empty());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<SufrFile<T>> {
+        let bytes: Arc<[u8]> = bytes.into();
+        let mut cursor = Cursor::new(bytes.clone());
+        Self::parse(&mut cursor, "", Some(bytes), false)
+    }
 
+    // --------------------------------------------------
+    /// Parse a serialized suffix array from a file or from memory.
+    ///
+    /// Args:
+    /// * `file`: the reader positioned at the start of the suffix array
+    /// * `filename`: the _.sufr_ filename, empty when in memory
+    /// * `bytes`: the serialized suffix array when in memory
+    /// * `low_memory`: whether to leave the `text` out of memory
+    fn parse<R: Read + Seek>(
+        file: &mut R,
+        filename: &str,
+        bytes: Option<Arc<[u8]>>,
+        low_memory: bool,
+    ) -> Result<SufrFile<T>> {
         // Meta
-        let mut buffer = [0u8; 4];
+        let mut buffer = [0u8; 6];
         file.read_exact(&mut buffer)?;
         let version = buffer[0];
         let is_dna = buffer[1] == 1;
         let allow_ambiguity = buffer[2] == 1;
         let ignore_softmask = buffer[3] == 1;
+        let both_strands = buffer[4] == 1;
+        let alphabet_code = buffer[5];
 
         // Length of text
         let mut buffer = [0; 8];
@@ -198,18 +316,78 @@ where
         let sequence_starts: Vec<T> =
             slice_u8_to_vec(&buffer, num_sequences.to_usize());
 
-        // Seed mask len
+        // Number of seed masks
         let mut buffer = [0; 8];
         file.read_exact(&mut buffer)?;
-        let seed_mask_len = u64::from_ne_bytes(buffer) as usize;
+        let num_seed_masks = u64::from_ne_bytes(buffer) as usize;
 
-        // Seed mask: stored as u8 for the 1s/0s
-        let seed_mask: Vec<u8> = if seed_mask_len > 0 {
+        // Seed masks: stored as u8 for the 1s/0s
+        let mut seed_masks = vec![];
+        for _ in 0..num_seed_masks {
+            let mut buffer = [0; 8];
+            file.read_exact(&mut buffer)?;
+            let seed_mask_len = u64::from_ne_bytes(buffer) as usize;
             let mut buffer = vec![0; seed_mask_len];
             file.read_exact(&mut buffer)?;
-            buffer
+            seed_masks.push(
+                SeedMask::from_bytes(&buffer)?
+                    .with_max_query_len(max_query_len.to_usize()),
+            );
+        }
+
+        // Positions of the suffix/LCP arrays of the masks after the first
+        let mut masked_indexes = vec![];
+        for (mask_num, seed_mask) in seed_masks.into_iter().enumerate() {
+            let (mask_sa_pos, mask_lcp_pos) = if mask_num == 0 {
+                (suffix_array_pos, lcp_pos)
+            } else {
+                let mut buffer = [0; 8];
+                file.read_exact(&mut buffer)?;
+                let mask_sa_pos = u64::from_ne_bytes(buffer) as usize;
+                file.read_exact(&mut buffer)?;
+                (mask_sa_pos, u64::from_ne_bytes(buffer) as usize)
+            };
+            masked_indexes.push(MaskedIndex {
+                seed_mask,
+                suffix_array_pos: mask_sa_pos,
+                lcp_pos: mask_lcp_pos,
+            });
+        }
+
+        // Alphabet symbols: only stored for byte alphabets
+        let mut buffer = [0; 8];
+        file.read_exact(&mut buffer)?;
+        let alphabet_len = u64::from_ne_bytes(buffer) as usize;
+        let mut alphabet_symbols = vec![0; alphabet_len];
+        file.read_exact(&mut alphabet_symbols)?;
+        let alphabet = if alphabet_code > 0 {
+            Some(Alphabet::from_bytes(alphabet_code, &alphabet_symbols)?)
+        } else {
+            None
+        };
+
+        // Residue map name
+        let mut buffer = [0; 8];
+        file.read_exact(&mut buffer)?;
+        let residue_map_len = u64::from_ne_bytes(buffer) as usize;
+        let residue_map = if residue_map_len > 0 {
+            let mut buffer = vec![0; residue_map_len];
+            file.read_exact(&mut buffer)?;
+            Some(ResidueMap::new(&String::from_utf8(buffer)?)?)
         } else {
-            vec![]
+            None
+        };
+
+        // Suffix start filter name
+        let mut buffer = [0; 8];
+        file.read_exact(&mut buffer)?;
+        let filter_len = u64::from_ne_bytes(buffer) as usize;
+        let suffix_start_filter = if filter_len > 0 {
+            let mut buffer = vec![0; filter_len];
+            file.read_exact(&mut buffer)?;
+            Some(SuffixStartFilter::new(&String::from_utf8(buffer)?)?)
+        } else {
+            None
         };
 
         // Text
@@ -224,28 +402,39 @@ where
 
         // Text file access
         let text_file: FileAccess<u8> =
-            FileAccess::new(filename, text_pos as u64, text_len)?;
+            open_access(filename, bytes.as_ref(), text_pos, text_len)?;
 
         // Suffix Array
         let suffix_array_file: FileAccess<T> =
-            FileAccess::new(filename, suffix_array_pos as u64, len_suffixes)?;
+            open_access(filename, bytes.as_ref(), suffix_array_pos, len_suffixes)?;
         file.seek_relative(suffix_array_file.size as i64)?;
 
         // LCP
         let lcp_file: FileAccess<T> =
-            FileAccess::new(filename, lcp_pos as u64, len_suffixes)?;
+            open_access(filename, bytes.as_ref(), lcp_pos, len_suffixes)?;
         file.seek_relative(lcp_file.size as i64)?;
 
-        // Sequence names are variable in length so they are at the end
+        // Suffix/LCP arrays of the other masks
+        let num_extra = masked_indexes.len().saturating_sub(1);
+        file.seek_relative(
+            (num_extra * (suffix_array_file.size + lcp_file.size)) as i64,
+        )?;
+
+        // Sequence names and metadata, tombstones, and the build summary
+        // are variable in length so they are at the end
+        let names_pos = file.stream_position()? as usize;
         let mut buffer = vec![];
         file.read_to_end(&mut buffer)?;
-        let sequence_names: Vec<String> = bincode::deserialize(&buffer)?;
-
-        let sort_type = if seed_mask.is_empty() {
-            SuffixSortType::MaxQueryLen(max_query_len.to_usize())
-        } else {
-            let seed_mask = SeedMask::from_bytes(&seed_mask)?;
-            SuffixSortType::Mask(seed_mask)
+        let mut tail = buffer.as_slice();
+        let sequence_names: Vec<String> = bincode::deserialize_from(&mut tail)?;
+        let sequence_metadata: Vec<Option<String>> =
+            bincode::deserialize_from(&mut tail)?;
+        let tombstones: Vec<usize> = bincode::deserialize_from(&mut tail)?;
+        let build_summary: BuildSummary = bincode::deserialize_from(&mut tail)?;
+
+        let sort_type = match masked_indexes.first() {
+            Some(masked) => SuffixSortType::Mask(masked.seed_mask.clone()),
+            _ => SuffixSortType::MaxQueryLen(max_query_len.to_usize()),
         };
 
         Ok(SufrFile {
@@ -254,6 +443,10 @@ where
             is_dna,
             allow_ambiguity,
             ignore_softmask,
+            both_strands,
+            alphabet,
+            residue_map,
+            suffix_start_filter,
             query_low_memory: true,
             text_pos,
             suffix_array_pos,
@@ -261,9 +454,14 @@ where
             text_len: T::from_usize(text_len),
             len_suffixes: T::from_usize(len_suffixes),
             sort_type,
+            masked_indexes,
+            mask_num: 0,
             num_sequences,
             sequence_starts,
             sequence_names,
+            sequence_metadata,
+            tombstones,
+            build_summary,
             text,
             suffix_array_file,
             lcp_file,
@@ -271,6 +469,14 @@ where
             suffix_array_mem: vec![],
             suffix_array_mem_mql: None,
             suffix_array_rank_mem: vec![],
+            masked_suffix_array_mem: vec![],
+            lcp_lr_enabled: false,
+            lcp_lr: None,
+            child_table: None,
+            document_array: None,
+            bytes,
+            created: Local::now(),
+            names_pos,
         })
     }
 
@@ -410,6 +616,327 @@ where
         Ok(String::from_utf8(bytes.to_vec())?)
     }
 
+    // --------------------------------------------------
+    /// Access a portion of the file or in-memory bytes
+    ///
+    /// Args:
+    /// * `start`: the byte position of the array
+    /// * `num_elements`: the length of the array
+    fn file_access<U>(&self, start: usize, num_elements: usize) -> Result<FileAccess<U>>
+    where
+        U: Int + FromUsize<U> + Sized + Send + Sync + serde::ser::Serialize,
+    {
+        open_access(&self.filename, self.bytes.as_ref(), start, num_elements)
+    }
+
+    // --------------------------------------------------
+    /// Write the suffix array to a _.sufr_ file. This is how to keep
+    /// a suffix array that was built only in memory; otherwise,
+    /// the file that was read is copied.
+    ///
+    /// Args:
+    /// * `path`: the output filename
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::sufr_file::SufrFile;
+    /// use std::fs;
+    /// use tempfile::NamedTempFile;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let bytes = fs::read("../data/inputs/1.sufr")?;
+    ///     let sufr_file: SufrFile<u32> = SufrFile::from_bytes(bytes.clone())?;
+    ///     let outfile = NamedTempFile::new()?;
+    ///     let outpath = outfile.path().to_str().unwrap();
+    ///     sufr_file.save(outpath)?;
+    ///     assert_eq!(fs::read(outpath)?, bytes);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn save(&self, path: &str) -> Result<()> {
+        match &self.bytes {
+            Some(bytes) => {
+                fs::write(path, bytes).map_err(|e| anyhow!("{path}: {e}"))?
+            }
+            _ => {
+                fs::copy(&self.filename, path).map_err(|e| anyhow!("{path}: {e}"))?;
+            }
+        }
+        Ok(())
+    }
+
+    // --------------------------------------------------
+    /// Append sequences without rebuilding the suffix array. Only the new
+    /// suffixes are sorted and merged into the existing suffix/LCP arrays
+    /// (cf. [SufrBuilder::append](super::sufr_builder::SufrBuilder::append)).
+    /// The _.sufr_ file is replaced with the result, or the bytes are
+    /// replaced when the suffix array lives only in memory, and the
+    /// new suffix array is read back.
+    ///
+    /// Args:
+    /// * `args`: the new sequences
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{sufr_file::SufrFile, types::AppendOptions};
+    /// use std::fs;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let bytes = fs::read("../data/inputs/1.sufr")?;
+    ///     let mut sufr_file: SufrFile<u32> = SufrFile::from_bytes(bytes)?;
+    ///     sufr_file.append(AppendOptions {
+    ///         text: b"GGACT$".to_vec(),
+    ///         sequence_starts: vec![0],
+    ///         sequence_names: vec!["2".to_string()],
+    ///     })?;
+    ///     assert_eq!(sufr_file.sequence_names, ["1", "2"]);
+    ///     assert_eq!(sufr_file.sequence_starts, [0, 11]);
+    ///     assert_eq!(sufr_file.len_suffixes, 14);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn append(&mut self, args: AppendOptions) -> Result<()> {
+        self.replace(|sufr_file, path| SufrBuilder::append(sufr_file, args, path))
+    }
+
+    // --------------------------------------------------
+    /// Remove sequences from the results of `count`, `locate`, and
+    /// `extract` without rebuilding the suffix array. The indices of the
+    /// sequences are recorded as tombstones at the end of the _.sufr_
+    /// file (or of the bytes, when in memory), and their suffixes remain
+    /// until the suffix array is compacted. With both strands, the
+    /// reverse complement of each sequence is removed, too.
+    ///
+    /// Args:
+    /// * `names`: the names of the sequences to remove
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{sufr_file::SufrFile, types::CountOptions};
+    /// use std::fs;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let bytes = fs::read("../data/inputs/2.sufr")?;
+    ///     let mut sufr_file: SufrFile<u32> = SufrFile::from_bytes(bytes)?;
+    ///     let opts = CountOptions {
+    ///         queries: vec!["ACGT".to_string()],
+    ///         max_query_len: None,
+    ///         low_memory: true,
+    ///     };
+    ///     assert_eq!(sufr_file.count(opts.clone())?[0].count, 4);
+    ///
+    ///     sufr_file.remove_sequences(&["ABC".to_string()])?;
+    ///     assert_eq!(sufr_file.tombstones, [0]);
+    ///     assert_eq!(sufr_file.count(opts)?[0].count, 2);
+    ///     assert!(sufr_file.remove_sequences(&["XYZ".to_string()]).is_err());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn remove_sequences(&mut self, names: &[String]) -> Result<()> {
+        let mut tombstones = self.tombstones.clone();
+        for name in names {
+            let mut found = false;
+            for (i, _) in self
+                .sequence_names
+                .iter()
+                .enumerate()
+                .filter(|(_, seq_name)| *seq_name == name)
+            {
+                found = true;
+                tombstones.push(i);
+            }
+            if !found {
+                bail!("Unknown sequence \"{name}\"");
+            }
+        }
+        tombstones.sort();
+        tombstones.dedup();
+
+        let mut tail = bincode::serialize(&self.sequence_names)?;
+        tail.extend(bincode::serialize(&self.sequence_metadata)?);
+        tail.extend(bincode::serialize(&tombstones)?);
+        tail.extend(bincode::serialize(&self.build_summary)?);
+        match &self.bytes {
+            Some(bytes) => {
+                let mut bytes = bytes[..self.names_pos].to_vec();
+                bytes.extend(tail);
+                let (mask_num, lcp_lr_enabled) = (self.mask_num, self.lcp_lr_enabled);
+                *self = Self::from_bytes(bytes)?;
+                if !self.masked_indexes.is_empty() {
+                    self.use_mask(mask_num)?;
+                }
+                self.use_lcp_lr(lcp_lr_enabled);
+            }
+            _ => {
+                let mut file = OpenOptions::new()
+                    .write(true)
+                    .open(&self.filename)
+                    .map_err(|e| anyhow!("{}: {e}", self.filename))?;
+                file.set_len(self.names_pos as u64)?;
+                file.seek(SeekFrom::End(0))?;
+                file.write_all(&tail)?;
+                self.tombstones = tombstones;
+            }
+        }
+        Ok(())
+    }
+
+    // --------------------------------------------------
+    /// Rebuild the suffix array without the sequences that were removed
+    /// (cf. [SufrBuilder::compact](super::sufr_builder::SufrBuilder::compact)).
+    /// The _.sufr_ file is replaced with the result, or the bytes are
+    /// replaced when the suffix array lives only in memory, and the
+    /// new suffix array is read back.
+    ///
+    /// Args:
+    /// * `num_partitions`: the number of partitions to sort
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::sufr_file::SufrFile;
+    /// use std::fs;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let bytes = fs::read("../data/inputs/2.sufr")?;
+    ///     let mut sufr_file: SufrFile<u32> = SufrFile::from_bytes(bytes)?;
+    ///     sufr_file.remove_sequences(&["DEF".to_string()])?;
+    ///     sufr_file.compact(2)?;
+    ///     assert_eq!(sufr_file.sequence_names, ["ABC"]);
+    ///     assert!(sufr_file.tombstones.is_empty());
+    ///     assert_eq!(sufr_file.len_suffixes, 9);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn compact(&mut self, num_partitions: usize) -> Result<()> {
+        self.replace(|sufr_file, path| {
+            SufrBuilder::compact(sufr_file, num_partitions, path)
+        })
+    }
+
+    // --------------------------------------------------
+    /// Replace the suffix array with one built from it, writing the
+    /// _.sufr_ file beside the original so it can be moved into place
+    ///
+    /// Args:
+    /// * `build`: builds the new suffix array from this one to the
+    ///   given path, or in memory for `None`
+    fn replace<F>(&mut self, build: F) -> Result<()>
+    where
+        F: FnOnce(&mut Self, Option<String>) -> Result<SufrBuilder<T>>,
+    {
+        let lcp_lr_enabled = self.lcp_lr_enabled;
+        if self.bytes.is_some() {
+            let builder = build(self, None)?;
+            *self = Self::from_bytes(builder.to_bytes()?)?;
+        } else {
+            let low_memory = self.text.is_empty();
+            let dir = Path::new(&self.filename)
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            let tmp = NamedTempFile::new_in(dir)?;
+            let tmp_path = tmp.path().to_string_lossy().to_string();
+            build(self, Some(tmp_path))?;
+            tmp.persist(&self.filename)
+                .map_err(|e| anyhow!("{}: {e}", self.filename))?;
+            *self = Self::read(&self.filename, low_memory)?;
+        }
+        self.use_lcp_lr(lcp_lr_enabled);
+        Ok(())
+    }
+
+    // --------------------------------------------------
+    /// The ranges of the text holding the sequences that were removed,
+    /// including their reverse complements, in order
+    fn removed_ranges(&self) -> Vec<Range<usize>> {
+        let num_names = self.sequence_names.len();
+        let text_len = self.text_len.to_usize();
+        let mut ranges: Vec<Range<usize>> = self
+            .tombstones
+            .iter()
+            .flat_map(|&i| {
+                if self.both_strands {
+                    vec![i, i + num_names]
+                } else {
+                    vec![i]
+                }
+            })
+            .filter_map(|i| {
+                let start = self.sequence_starts.get(i)?.to_usize();
+                let end = self
+                    .sequence_starts
+                    .get(i + 1)
+                    .map_or(text_len, |next| next.to_usize());
+                Some(start..end)
+            })
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+        ranges
+    }
+
+    // --------------------------------------------------
+    /// Whether the index has a semi-specific digest, whose hits must
+    /// be checked for a cleavage site at either end
+    fn is_semi_specific(&self) -> bool {
+        self.suffix_start_filter
+            .as_ref()
+            .is_some_and(|filter| filter.semi)
+    }
+
+    // --------------------------------------------------
+    /// Keep only the hits of each query that are peptides of a
+    /// semi-specific digest, i.e., that start or end at a cleavage
+    /// site or at a sequence boundary.
+    ///
+    /// Args:
+    /// * `results`: the search results for each query
+    /// * `mask_hits`: the mask/rank/suffix hits for each query
+    fn retain_digest_hits(
+        &mut self,
+        results: &[SearchResult<T>],
+        mask_hits: &mut [Vec<MaskHit<T>>],
+    ) {
+        let Some(filter) = self.suffix_start_filter.clone().filter(|f| f.semi) else {
+            return;
+        };
+        for (res, hits) in results.iter().zip(mask_hits.iter_mut()) {
+            let len = res.query.len();
+            hits.retain(|(_, _, suffix)| {
+                let start = suffix.to_usize();
+                self.is_digest_boundary(&filter, start)
+                    || self.is_digest_boundary(&filter, start + len)
+            });
+        }
+    }
+
+    // --------------------------------------------------
+    /// Whether a peptide may start or end at a position of the text
+    /// according to a digest, using the residues before mapping
+    ///
+    /// Args:
+    /// * `filter`: the digest
+    /// * `pos`: the start of a peptide or the position after its end
+    fn is_digest_boundary(&mut self, filter: &SuffixStartFilter, pos: usize) -> bool {
+        // A sequence start or the delimiter/sentinel after a sequence
+        if pos == 0
+            || pos + 1 >= self.text_len.to_usize()
+            || self
+                .sequence_starts
+                .binary_search(&T::from_usize(pos))
+                .is_ok()
+            || self
+                .sequence_starts
+                .binary_search(&T::from_usize(pos + 1))
+                .is_ok()
+        {
+            return true;
+        }
+        match (self.get_text(pos - 1), self.get_text(pos)) {
+            (Some(prev), Some(next)) => filter.is_cleavage_site(prev, next),
+            _ => false,
+        }
+    }
+
     // --------------------------------------------------
     /// Find/create a hidden "~/.sufr" directory
     fn get_sufr_dir(&self) -> Result<PathBuf> {
@@ -466,33 +993,49 @@ where
     ///     let sufr = SufrFile::<u32>::read("../data/inputs/1.sufr", true)?;
     ///     let meta = sufr.metadata()?;
     ///     assert_eq!(meta.filename, "../data/inputs/1.sufr".to_string());
-    ///     assert_eq!(meta.file_size, 172);
-    ///     assert_eq!(meta.file_version, 6);
+    ///     assert_eq!(meta.file_size, 279);
+    ///     assert_eq!(meta.file_version, 14);
     ///     assert_eq!(meta.is_dna, true);
     ///     assert_eq!(meta.allow_ambiguity, false);
     ///     assert_eq!(meta.ignore_softmask, false);
+    ///     assert_eq!(meta.both_strands, false);
+    ///     assert_eq!(meta.alphabet, None);
+    ///     assert_eq!(meta.residue_map, None);
+    ///     assert_eq!(meta.suffix_start_filter, None);
     ///     assert_eq!(meta.text_len, 11);
     ///     assert_eq!(meta.len_suffixes, 9);
     ///     assert_eq!(meta.num_sequences, 1);
     ///     assert_eq!(meta.sequence_starts, vec![0]);
     ///     assert_eq!(meta.sequence_names, vec!["1".to_string()]);
+    ///     assert_eq!(meta.sequence_metadata, vec![None]);
+    ///     assert!(meta.removed_sequences.is_empty());
     ///     assert_eq!(meta.sort_type, SuffixSortType::MaxQueryLen(0));
     ///
     ///     Ok(())
     /// }
     /// ```
     pub fn metadata(&self) -> Result<SufrMetadata> {
-        let fs_meta = fs::metadata(&self.filename)?;
-        let modified: DateTime<Local> = DateTime::from(fs_meta.modified()?);
+        let (modified, file_size) = match &self.bytes {
+            Some(bytes) => (self.created, bytes.len()),
+            _ => {
+                let fs_meta = fs::metadata(&self.filename)?;
+                let modified: DateTime<Local> = DateTime::from(fs_meta.modified()?);
+                (modified, fs_meta.len().to_usize())
+            }
+        };
 
         Ok(SufrMetadata {
             filename: self.filename.clone(),
             modified,
-            file_size: fs_meta.len().to_usize(),
+            file_size,
             file_version: self.version as usize,
             is_dna: self.is_dna,
             allow_ambiguity: self.allow_ambiguity,
             ignore_softmask: self.ignore_softmask,
+            both_strands: self.both_strands,
+            alphabet: self.alphabet.clone(),
+            residue_map: self.residue_map.clone(),
+            suffix_start_filter: self.suffix_start_filter.clone(),
             text_len: self.text_len.to_usize(),
             len_suffixes: self.len_suffixes.to_usize(),
             num_sequences: self.num_sequences.to_usize(),
@@ -502,17 +1045,137 @@ where
                 .map(|v| v.to_usize())
                 .collect::<Vec<_>>(),
             sequence_names: self.sequence_names.clone(),
+            sequence_metadata: self.sequence_metadata.clone(),
+            removed_sequences: self
+                .tombstones
+                .iter()
+                .map(|&i| self.sequence_names[i].clone())
+                .collect(),
+            build_summary: Some(self.build_summary.clone()),
             sort_type: self.sort_type.clone(),
+            seed_masks: self
+                .masked_indexes
+                .iter()
+                .map(|masked| masked.seed_mask.clone())
+                .collect(),
         })
     }
 
+    // --------------------------------------------------
+    /// Ensure queries contain only characters in the alphabet
+    /// (when the file was built with one)
+    ///
+    /// Args:
+    /// * `queries`: the query strings
+    fn validate_queries(&self, queries: &[String]) -> Result<()> {
+        if let Some(alphabet) = &self.alphabet {
+            for query in queries {
+                alphabet.validate_query(query)?;
+            }
+        }
+        Ok(())
+    }
+
+    // --------------------------------------------------
+    /// Query the suffix/LCP arrays sorted under another seed mask
+    /// of a multi-mask index
+    ///
+    /// Args:
+    /// * `mask_num`: the index of the seed mask in `masked_indexes`
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::sufr_file::SufrFile;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut sufr = SufrFile::<u32>::read("../data/inputs/1.sufr", true)?;
+    ///     assert!(sufr.use_mask(0).is_ok());
+    ///     assert!(sufr.use_mask(1).is_err());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn use_mask(&mut self, mask_num: usize) -> Result<()> {
+        if mask_num == self.mask_num {
+            return Ok(());
+        }
+
+        let masked = self
+            .masked_indexes
+            .get(mask_num)
+            .ok_or(anyhow!("Invalid seed mask number {mask_num}"))?;
+        let len_suffixes = self.len_suffixes.to_usize();
+        self.suffix_array_file =
+            self.file_access(masked.suffix_array_pos, len_suffixes)?;
+        self.lcp_file = self.file_access(masked.lcp_pos, len_suffixes)?;
+        self.suffix_array_pos = masked.suffix_array_pos;
+        self.lcp_pos = masked.lcp_pos;
+        self.sort_type = SuffixSortType::Mask(masked.seed_mask.clone());
+
+        // Set aside the suffix array in memory of the previous mask
+        // and bring back any that was loaded for this one
+        if self.masked_suffix_array_mem.len() < self.masked_indexes.len() {
+            self.masked_suffix_array_mem
+                .resize_with(self.masked_indexes.len(), Default::default);
+        }
+        let (suffix_array_mem, suffix_array_mem_mql, suffix_array_rank_mem) =
+            mem::take(&mut self.masked_suffix_array_mem[mask_num]);
+        self.masked_suffix_array_mem[self.mask_num] = (
+            mem::replace(&mut self.suffix_array_mem, suffix_array_mem),
+            mem::replace(&mut self.suffix_array_mem_mql, suffix_array_mem_mql),
+            mem::replace(&mut self.suffix_array_rank_mem, suffix_array_rank_mem),
+        );
+        self.mask_num = mask_num;
+
+        // Any other arrays in memory belong to the previous mask
+        self.lcp_lr = None;
+        self.child_table = None;
+        self.document_array = None;
+
+        Ok(())
+    }
+
+    // --------------------------------------------------
+    /// Search the full suffix array with LCP-LR arrays, which are
+    /// computed from the LCP array on the first search (and cached
+    /// in "~/.sufr" for a _.sufr_ file). Each comparison then starts
+    /// after the characters known to match, so a query of length `m`
+    /// takes O(m + log n) rather than O(m log n) in the worst case.
+    /// This uses memory for two more arrays the size of the suffix array
+    /// and does not apply when searching a subsampled suffix array.
+    ///
+    /// Args:
+    /// * `enabled`: whether to use the LCP-LR arrays
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{sufr_file::SufrFile, types::CountOptions};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut sufr = SufrFile::<u32>::read("../data/inputs/3.sufr", false)?;
+    ///     let opts = || CountOptions {
+    ///         queries: vec!["ACTG".to_string(), "GTC".to_string()],
+    ///         max_query_len: None,
+    ///         low_memory: false,
+    ///     };
+    ///     let expected = sufr.count(opts())?;
+    ///     sufr.use_lcp_lr(true);
+    ///     assert_eq!(sufr.count(opts())?, expected);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn use_lcp_lr(&mut self, enabled: bool) {
+        self.lcp_lr_enabled = enabled;
+        if !enabled {
+            self.lcp_lr = None;
+        }
+    }
+
     // --------------------------------------------------
     /// Read a suffix array into memory
     ///
     /// Args:
     /// * `max_query_len`: prefix length
     fn set_suffix_array_mem(&mut self, max_query_len: Option<usize>) -> Result<()> {
-
         let mut max_query_len = max_query_len.unwrap_or(0);
 
         // If ".sufr" file was built with a nonzero max_query_len or seed mask
@@ -543,7 +1206,7 @@ where
             info!("Using existing suffix_array_mem");
             return Ok(());
         } else if max_query_len == built_max_query_len {
-             // The requested MQL matches how the SA was built
+            // The requested MQL matches how the SA was built
             // Stuff entire SA into memory
             let now = Instant::now();
             self.suffix_array_file.reset();
@@ -552,8 +1215,12 @@ where
 
             // There will be no ranks
             self.suffix_array_rank_mem = vec![];
+        } else if self.bytes.is_some() {
+            // Nothing to cache when the suffix array is only in memory
+            let (sub_sa, sub_rank) = self.subsample_suffix_array(max_query_len);
+            self.suffix_array_mem = sub_sa;
+            self.suffix_array_rank_mem = sub_rank;
         } else {
-
             info!("Loading suffix_array_mem using max_query_len {max_query_len}");
 
             let sufr_dir = &self.get_sufr_dir()?;
@@ -562,21 +1229,17 @@ where
                 .unwrap()
                 .to_string_lossy()
                 .into_owned();
-            let cache_path =
-                sufr_dir.join(format!("locate-{max_query_len}-{basename}"));
+            let cache_path = if self.mask_num > 0 {
+                sufr_dir.join(format!(
+                    "locate-{max_query_len}-mask{}-{basename}",
+                    self.mask_num
+                ))
+            } else {
+                sufr_dir.join(format!("locate-{max_query_len}-{basename}"))
+            };
 
             // Check for stale cache
-            if let Ok(cache_meta) = fs::metadata(&cache_path) {
-                let source_meta = fs::metadata(&self.filename)?;
-                if let (Ok(source_modified), Ok(cache_modified)) =
-                    (source_meta.modified(), cache_meta.modified())
-                {
-                    if source_modified > cache_modified {
-                        info!("Removing stale cache {}", cache_path.display());
-                        fs::remove_file(&cache_path)?;
-                    }
-                }
-            }
+            self.remove_stale_cache(&cache_path)?;
 
             if cache_path.is_file() {
                 let now = Instant::now();
@@ -668,14 +1331,232 @@ where
     }
 
     // --------------------------------------------------
-    /// Bisect the index range of occurences of queries.
-    /// If the index range of a prefix is already known,
-    /// or if it is desirable to avoid enumerating every match,
-    /// this method can be used as a faster stand-in for `count`
-    /// ```
-    /// use anyhow::Result;
-    /// use libsufr::{types::{CountOptions, CountResult, BisectOptions, BisectResult}, sufr_file::SufrFile};
-    /// 
+    /// Load the LCP-LR arrays of the current mask, reading them from
+    /// the cache when they were computed before
+    fn set_lcp_lr(&mut self) -> Result<()> {
+        if self.lcp_lr.is_some() {
+            return Ok(());
+        }
+
+        let len_suffixes = self.len_suffixes.to_usize();
+        let mut arrays =
+            self.cached_arrays("lcplr", "LCP-LR arrays", 2, len_suffixes, |sufr| {
+                sufr.lcp_file.reset();
+                let lcp: Vec<T> = sufr.lcp_file.iter().collect();
+                let lcp_lr = LcpLr::new(&lcp);
+                vec![lcp_lr.left, lcp_lr.right]
+            })?;
+        let right = arrays.pop().unwrap();
+        let left = arrays.pop().unwrap();
+        self.lcp_lr = Some(LcpLr { left, right });
+
+        Ok(())
+    }
+
+    // --------------------------------------------------
+    /// Load the child table, reading it from the cache when it was
+    /// computed before
+    fn set_child_table(&mut self) -> Result<()> {
+        if self.child_table.is_some() {
+            return Ok(());
+        }
+
+        if self.sort_type != SuffixSortType::MaxQueryLen(0) {
+            bail!("LCP-intervals require a fully sorted suffix array")
+        }
+
+        let len_suffixes = self.len_suffixes.to_usize();
+        let mut arrays = self.cached_arrays(
+            "childtab",
+            "child table",
+            3,
+            len_suffixes + 1,
+            |sufr| {
+                sufr.lcp_file.reset();
+                let lcp: Vec<T> = sufr.lcp_file.iter().collect();
+                let child_table = ChildTable::new(&lcp);
+                vec![child_table.up, child_table.down, child_table.next_l_index]
+            },
+        )?;
+        let next_l_index = arrays.pop().unwrap();
+        let down = arrays.pop().unwrap();
+        let up = arrays.pop().unwrap();
+        self.child_table = Some(ChildTable {
+            up,
+            down,
+            next_l_index,
+        });
+
+        Ok(())
+    }
+
+    // --------------------------------------------------
+    /// Load the document array, reading it from the cache when it was
+    /// computed before
+    fn set_document_array(&mut self) -> Result<()> {
+        if self.document_array.is_some() {
+            return Ok(());
+        }
+
+        // The reverse complements belong to their forward sequences
+        let num_names = self.sequence_names.len();
+        let len_suffixes = self.len_suffixes.to_usize();
+        let mut arrays = self.cached_arrays(
+            "docarray",
+            "document array",
+            3,
+            len_suffixes,
+            |sufr| {
+                sufr.suffix_array_file.reset();
+                let documents: Vec<T> = sufr
+                    .suffix_array_file
+                    .iter()
+                    .map(|suffix| {
+                        let record = sufr
+                            .sequence_starts
+                            .partition_point(|start| *start <= suffix)
+                            - 1;
+                        T::from_usize(record % num_names)
+                    })
+                    .collect();
+                let document_array = DocumentArray::new(documents, num_names);
+                vec![
+                    document_array.documents,
+                    document_array.previous,
+                    document_array.ranks,
+                ]
+            },
+        )?;
+        let ranks = arrays.pop().unwrap();
+        let previous = arrays.pop().unwrap();
+        let documents = arrays.pop().unwrap();
+        self.document_array = Some(DocumentArray::from_arrays(
+            documents, previous, ranks, num_names,
+        ));
+
+        Ok(())
+    }
+
+    // --------------------------------------------------
+    /// Read arrays derived from the current mask from the cache, or
+    /// compute them and write them to the cache. The cache is written
+    /// to a temporary file that replaces it only once complete, so a
+    /// reader never sees a partial cache.
+    ///
+    /// Args:
+    /// * `prefix`: the prefix of the cache filename
+    /// * `desc`: a description of the arrays for logging
+    /// * `num_arrays`: the number of arrays
+    /// * `array_len`: the length of each array
+    /// * `compute`: a function to compute the arrays
+    fn cached_arrays<F>(
+        &mut self,
+        prefix: &str,
+        desc: &str,
+        num_arrays: usize,
+        array_len: usize,
+        compute: F,
+    ) -> Result<Vec<Vec<T>>>
+    where
+        F: FnOnce(&mut Self) -> Vec<Vec<T>>,
+    {
+        let cache_path = self.derived_cache_path(prefix)?;
+        let now = Instant::now();
+        let array_size = array_len * mem::size_of::<T>();
+        if let Some(buffer) = cache_path
+            .as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .filter(|buffer| buffer.len() == num_arrays * array_size)
+        {
+            let arrays = (0..num_arrays)
+                .map(|num| {
+                    slice_u8_to_vec(
+                        &buffer[num * array_size..(num + 1) * array_size],
+                        array_len,
+                    )
+                })
+                .collect();
+            info!("Read {desc} from cache in {:?}", now.elapsed());
+            return Ok(arrays);
+        }
+
+        let arrays = compute(self);
+        info!("Computed {desc} in {:?}", now.elapsed());
+
+        if let Some(cache_path) = &cache_path {
+            let sufr_dir = self.get_sufr_dir()?;
+            let mut tmp = NamedTempFile::new_in(&sufr_dir)
+                .map_err(|e| anyhow!("{}: {e}", sufr_dir.display()))?;
+            for array in &arrays {
+                tmp.write_all(vec_to_slice_u8(array))?;
+            }
+            tmp.persist(cache_path)
+                .map_err(|e| anyhow!("{}: {e}", cache_path.display()))?;
+            info!("Wrote {desc} to cache {}", cache_path.display());
+        }
+
+        Ok(arrays)
+    }
+
+    // --------------------------------------------------
+    /// The cache path for data derived from the arrays of the current
+    /// mask, after removing any stale copy. There is no cache when
+    /// the suffix array is only in memory.
+    ///
+    /// Args:
+    /// * `prefix`: the prefix of the cache filename
+    fn derived_cache_path(&self, prefix: &str) -> Result<Option<PathBuf>> {
+        if self.bytes.is_some() {
+            return Ok(None);
+        }
+
+        let sufr_dir = &self.get_sufr_dir()?;
+        let basename = Path::new(&self.filename)
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .into_owned();
+        let cache_path = if self.mask_num > 0 {
+            sufr_dir.join(format!("{prefix}-mask{}-{basename}", self.mask_num))
+        } else {
+            sufr_dir.join(format!("{prefix}-{basename}"))
+        };
+        self.remove_stale_cache(&cache_path)?;
+        Ok(Some(cache_path))
+    }
+
+    // --------------------------------------------------
+    /// Remove a cache file that is older than the _.sufr_ file
+    ///
+    /// Args:
+    /// * `cache_path`: the path to the cache file
+    fn remove_stale_cache(&self, cache_path: &Path) -> Result<()> {
+        if let Ok(cache_meta) = fs::metadata(cache_path) {
+            let source_meta = fs::metadata(&self.filename)?;
+            if let (Ok(source_modified), Ok(cache_modified)) =
+                (source_meta.modified(), cache_meta.modified())
+            {
+                if source_modified > cache_modified {
+                    info!("Removing stale cache {}", cache_path.display());
+                    fs::remove_file(cache_path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // --------------------------------------------------
+    /// Bisect the index range of occurences of queries.
+    /// If the index range of a prefix is already known,
+    /// or if it is desirable to avoid enumerating every match,
+    /// this method can be used as a faster stand-in for `count`.
+    /// For a fully sorted suffix array, `root_interval`, `child_interval`,
+    /// and `find_interval` descend one LCP-interval at a time instead.
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{types::{CountOptions, CountResult, BisectOptions, BisectResult}, sufr_file::SufrFile};
+    ///
     /// fn main() -> Result<()> {
     ///     let K = 5;
     ///     let alphabet = vec!['A','C','G','N','T'];
@@ -729,25 +1610,23 @@ where
     ///}
     /// ```
     pub fn bisect(&mut self, args: BisectOptions) -> Result<Vec<BisectResult>> {
+        // Each character bisects the prefix range as its own query
+        let queries: Vec<String> = args.queries.iter().map(|c| c.to_string()).collect();
+        self.validate_queries(&queries)?;
+
         // Set memory mode
         self.query_low_memory = args.low_memory;
         if !self.query_low_memory {
             self.set_suffix_array_mem(args.max_query_len)?;
         }
 
-        // Construct SufrSearch factory 
+        // Construct SufrSearch factory
         let now = Instant::now();
         let new_search = || -> Result<RefCell<SufrSearch<T>>> {
-            let suffix_array_file: FileAccess<T> = FileAccess::new(
-                &self.filename,
-                self.suffix_array_pos as u64,
-                self.len_suffixes.to_usize(),
-            )?;
-            let text_file: FileAccess<u8> = FileAccess::new(
-                &self.filename,
-                self.text_pos as u64,
-                self.text_len.to_usize(),
-            )?;
+            let suffix_array_file: FileAccess<T> =
+                self.file_access(self.suffix_array_pos, self.len_suffixes.to_usize())?;
+            let text_file: FileAccess<u8> =
+                self.file_access(self.text_pos, self.text_len.to_usize())?;
             let search_args = SufrSearchArgs {
                 text: &self.text,
                 text_len: self.text_len.to_usize(),
@@ -758,18 +1637,20 @@ where
                 len_suffixes: self.len_suffixes.to_usize(),
                 sort_type: &self.sort_type,
                 max_query_len: args.max_query_len,
+                residue_map: self.residue_map.as_ref(),
+                lcp_lr: self.lcp_lr.as_ref(),
             };
             Ok(RefCell::new(SufrSearch::new(search_args)))
         };
 
-        // Retrieve the prefix result's index range. 
+        // Retrieve the prefix result's index range.
         // If no result was passed, deafult to the full range of the suffix array.
         let n = self.len_suffixes.to_usize() - 1;
         let (lcp, search_range) = match args.prefix_result {
-            Some(result)    => (result.lcp, (result.first_position, result.last_position)),
-            _               => (0, (0, n)),
+            Some(result) => (result.lcp, (result.first_position, result.last_position)),
+            _ => (0, (0, n)),
         };
-        
+
         // Bisect each query in its own thread
         let thread_local_search: ThreadLocal<RefCell<SufrSearch<T>>> =
             ThreadLocal::new();
@@ -779,11 +1660,17 @@ where
             .into_par_iter()
             .enumerate()
             .flat_map(|(query_num, query)| -> Result<BisectResult> {
-                let mut search = 
+                let mut search =
                     thread_local_search.get_or_try(new_search)?.borrow_mut();
-                search.bisect(query_num, query as u8, lcp, search_range.0, search_range.1)
+                search.bisect(
+                    query_num,
+                    query as u8,
+                    lcp,
+                    search_range.0,
+                    search_range.1,
+                )
             })
-            .collect(); 
+            .collect();
         res.sort_by_key(|r| r.query_num);
 
         info!(
@@ -791,7 +1678,185 @@ where
             args.queries.len(),
             now.elapsed()
         );
-        
+
+        Ok(res)
+    }
+
+    // --------------------------------------------------
+    /// Compute the matching statistics of queries: for each position
+    /// in a query, the length of the longest prefix of the rest of the
+    /// query that occurs in the text and the ranks of the suffixes that
+    /// begin with it. Each prefix is found by bisecting one character
+    /// at a time, starting from the previous prefix less its first
+    /// character, which is known to occur and is found with a single
+    /// search, so the characters bisected over a query are about as
+    /// many as its length. The lengths are capped at the maximum query
+    /// length. A prefix that occurs only in removed sequences is not a
+    /// match, but the ranks of a match may include removed suffixes.
+    ///
+    /// Args:
+    /// * `args`: `MatchingStatisticsOptions`
+    ///
+    /// Given a text of "ACGTNNACGT":
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{
+    ///     sufr_file::SufrFile,
+    ///     types::{MatchingStatistic, MatchingStatisticsOptions},
+    /// };
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut sufr = SufrFile::<u32>::read("../data/inputs/1.sufr", true)?;
+    ///     let opts = MatchingStatisticsOptions {
+    ///         queries: vec!["CGTA".to_string()],
+    ///         max_query_len: None,
+    ///         low_memory: true,
+    ///     };
+    ///     let res = sufr.matching_statistics(opts)?;
+    ///     assert_eq!(
+    ///         res[0].statistics,
+    ///         vec![
+    ///             MatchingStatistic { length: 3, ranks: 3..5 },
+    ///             MatchingStatistic { length: 2, ranks: 5..7 },
+    ///             MatchingStatistic { length: 1, ranks: 7..9 },
+    ///             MatchingStatistic { length: 1, ranks: 1..3 },
+    ///         ]
+    ///     );
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn matching_statistics(
+        &mut self,
+        args: MatchingStatisticsOptions,
+    ) -> Result<Vec<MatchingStatisticsResult>> {
+        let built_max_query_len = match &self.sort_type {
+            SuffixSortType::MaxQueryLen(mql) => *mql,
+            SuffixSortType::Mask(_) => {
+                bail!("Matching statistics cannot use a seed mask")
+            }
+        };
+        let max_query_len = match (built_max_query_len, args.max_query_len) {
+            (0, runtime) => runtime.unwrap_or(0),
+            (built, Some(runtime)) if runtime > 0 => min(built, runtime),
+            (built, _) => built,
+        };
+        self.validate_queries(&args.queries)?;
+
+        // Set memory mode with the whole suffix array
+        self.query_low_memory = args.low_memory;
+        if !self.query_low_memory {
+            self.set_suffix_array_mem(None)?;
+        }
+
+        let now = Instant::now();
+        let new_search = || -> Result<RefCell<SufrSearch<T>>> {
+            let suffix_array_file: FileAccess<T> =
+                self.file_access(self.suffix_array_pos, self.len_suffixes.to_usize())?;
+            let text_file: FileAccess<u8> =
+                self.file_access(self.text_pos, self.text_len.to_usize())?;
+            let search_args = SufrSearchArgs {
+                text: &self.text,
+                text_len: self.text_len.to_usize(),
+                text_file,
+                file: suffix_array_file,
+                suffix_array: &self.suffix_array_mem,
+                rank: &self.suffix_array_rank_mem,
+                len_suffixes: self.len_suffixes.to_usize(),
+                sort_type: &self.sort_type,
+                max_query_len: args.max_query_len,
+                residue_map: self.residue_map.as_ref(),
+                lcp_lr: None,
+            };
+            Ok(RefCell::new(SufrSearch::new(search_args)))
+        };
+
+        // Compute the statistics of each query in its own thread
+        let len_suffixes = self.len_suffixes.to_usize();
+        let removed = self.removed_ranges();
+        let residue_map = self.residue_map.as_ref();
+        let thread_local_search: ThreadLocal<RefCell<SufrSearch<T>>> =
+            ThreadLocal::new();
+        let res = args
+            .queries
+            .par_iter()
+            .enumerate()
+            .map(|(query_num, query)| -> Result<MatchingStatisticsResult> {
+                let mut search =
+                    thread_local_search.get_or_try(new_search)?.borrow_mut();
+                let query_bytes = query.as_bytes();
+                let mapped = residue_map.map(|m| m.map_bytes(query_bytes));
+                let mut statistics: Vec<MatchingStatistic> = vec![];
+                for start in 0..query_bytes.len() {
+                    // The (length, first, last) of each prefix along the way
+                    let mut path = vec![(0, 0, len_suffixes - 1)];
+                    let known = statistics.last().map_or(0, |prev| prev.length);
+                    if known > 1 {
+                        let prefix = &mapped.as_deref().unwrap_or(query_bytes)
+                            [start..start + known - 1];
+                        if let Some((first, last)) = search.find_range(prefix) {
+                            path = vec![(known - 1, first, last)];
+                        }
+                    }
+
+                    let (mut length, mut first, mut last) = path[0];
+                    for &symbol in &query_bytes[start + length..] {
+                        if max_query_len > 0 && length >= max_query_len {
+                            break;
+                        }
+                        let found =
+                            search.bisect(query_num, symbol, length, first, last)?;
+                        if found.count == 0 {
+                            break;
+                        }
+                        length = found.lcp;
+                        first = found.first_position;
+                        last = found.last_position;
+                        path.push((length, first, last));
+                    }
+
+                    // Back off from prefixes found only in removed sequences
+                    if !removed.is_empty() {
+                        while path.len() > 1 {
+                            let (_, first, last) = path[path.len() - 1];
+                            let mut found = false;
+                            for rank in first..=last {
+                                let suffix = search
+                                    .get_suffix(rank)
+                                    .ok_or(anyhow!("Invalid rank {rank}"))?;
+                                if !is_removed(&removed, suffix.to_usize()) {
+                                    found = true;
+                                    break;
+                                }
+                            }
+                            if found {
+                                break;
+                            }
+                            path.pop();
+                        }
+                        (length, first, last) = path[path.len() - 1];
+                    }
+
+                    statistics.push(MatchingStatistic {
+                        length,
+                        ranks: first..last + 1,
+                    });
+                }
+                Ok(MatchingStatisticsResult {
+                    query_num,
+                    query: query.to_string(),
+                    statistics,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        info!(
+            "Matching statistics of {} quer{} finished in {:?}",
+            args.queries.len(),
+            if args.queries.len() == 1 { "y" } else { "ies" },
+            now.elapsed()
+        );
+
         Ok(res)
     }
 
@@ -833,20 +1898,37 @@ where
     /// }
     /// ```
     pub fn count(&mut self, args: CountOptions) -> Result<Vec<CountResult>> {
+        // The suffixes are needed to remove duplicates across masks
+        // and the hits in removed sequences or outside a semi-specific digest
+        let removed = self.removed_ranges();
+        let find_suffixes = self.masked_indexes.len() > 1
+            || !removed.is_empty()
+            || self.is_semi_specific();
         let search_args = SearchOptions {
             queries: args.queries,
             max_query_len: args.max_query_len,
             low_memory: args.low_memory,
-            find_suffixes: false,
+            find_suffixes,
         };
 
-        let counts: Vec<_> = self
-            .suffix_search(&search_args)?
-            .into_iter()
-            .map(|res| CountResult {
+        let mask_results = self.suffix_search_all(&search_args)?;
+        let mut mask_hits = merge_mask_hits(self.mask_num, &mask_results);
+        self.retain_digest_hits(&mask_results[0], &mut mask_hits);
+        let counts: Vec<_> = mask_results[0]
+            .iter()
+            .zip(mask_hits)
+            .map(|(res, hits)| CountResult {
                 query_num: res.query_num,
                 query: res.query.clone(),
-                count: res.locations.map_or(0, |loc| loc.ranks.len()),
+                count: if find_suffixes {
+                    hits.into_iter()
+                        .filter(|(_, _, suffix)| {
+                            !is_removed(&removed, suffix.to_usize())
+                        })
+                        .count()
+                } else {
+                    res.locations.as_ref().map_or(0, |loc| loc.ranks.len())
+                },
             })
             .collect();
 
@@ -906,24 +1988,22 @@ where
         &mut self,
         args: &SearchOptions,
     ) -> Result<Vec<SearchResult<T>>> {
+        self.validate_queries(&args.queries)?;
         self.query_low_memory = args.low_memory;
 
         if !self.query_low_memory {
             self.set_suffix_array_mem(args.max_query_len)?;
         }
+        if self.lcp_lr_enabled {
+            self.set_lcp_lr()?;
+        }
 
         let now = Instant::now();
         let new_search = || -> Result<RefCell<SufrSearch<T>>> {
-            let suffix_array_file: FileAccess<T> = FileAccess::new(
-                &self.filename,
-                self.suffix_array_pos as u64,
-                self.len_suffixes.to_usize(),
-            )?;
-            let text_file: FileAccess<u8> = FileAccess::new(
-                &self.filename,
-                self.text_pos as u64,
-                self.text_len.to_usize(),
-            )?;
+            let suffix_array_file: FileAccess<T> =
+                self.file_access(self.suffix_array_pos, self.len_suffixes.to_usize())?;
+            let text_file: FileAccess<u8> =
+                self.file_access(self.text_pos, self.text_len.to_usize())?;
             let search_args = SufrSearchArgs {
                 text: &self.text,
                 text_len: self.text_len.to_usize(),
@@ -934,6 +2014,8 @@ where
                 len_suffixes: self.len_suffixes.to_usize(),
                 sort_type: &self.sort_type,
                 max_query_len: args.max_query_len,
+                residue_map: self.residue_map.as_ref(),
+                lcp_lr: self.lcp_lr.as_ref(),
             };
             Ok(RefCell::new(SufrSearch::new(search_args)))
         };
@@ -963,6 +2045,32 @@ where
         Ok(res)
     }
 
+    // --------------------------------------------------
+    /// Search the suffix arrays of every seed mask in a multi-mask index,
+    /// returning the results for each mask in order. An index with at most
+    /// one mask returns only the results of `suffix_search`.
+    ///
+    /// Args:
+    /// * `args`: `SearchOptions`
+    pub fn suffix_search_all(
+        &mut self,
+        args: &SearchOptions,
+    ) -> Result<Vec<Vec<SearchResult<T>>>> {
+        if self.masked_indexes.len() < 2 {
+            return Ok(vec![self.suffix_search(args)?]);
+        }
+
+        let current_mask = self.mask_num;
+        let mut results = vec![];
+        for mask_num in 0..self.masked_indexes.len() {
+            self.use_mask(mask_num)?;
+            results.push(self.suffix_search(args)?);
+        }
+        self.use_mask(current_mask)?;
+
+        Ok(results)
+    }
+
     // --------------------------------------------------
     /// Extract the suffixes for a given set of queries
     ///
@@ -975,7 +2083,7 @@ where
     /// use anyhow::Result;
     /// use libsufr::{
     ///     sufr_file::SufrFile,
-    ///     types::{ExtractOptions, ExtractResult, ExtractSequence}
+    ///     types::{ExtractOptions, ExtractResult, ExtractSequence, Strand}
     /// };
     ///
     /// fn main() -> Result<()> {
@@ -996,18 +2104,22 @@ where
     ///                 ExtractSequence {
     ///                     suffix: 6,
     ///                     rank: 1,
+    ///                     mask_num: 0,
     ///                     sequence_name: "1".to_string(),
     ///                     sequence_start: 0,
     ///                     sequence_range: 5..9,
     ///                     suffix_offset: 1,
+    ///                     strand: Strand::Forward,
     ///                 },
     ///                 ExtractSequence {
     ///                     suffix: 0,
     ///                     rank: 2,
+    ///                     mask_num: 0,
     ///                     sequence_name: "1".to_string(),
     ///                     sequence_start: 0,
     ///                     sequence_range: 0..3,
     ///                     suffix_offset: 0,
+    ///                     strand: Strand::Forward,
     ///                 },
     ///             ],
     ///         },
@@ -1030,51 +2142,78 @@ where
             low_memory: args.low_memory,
             find_suffixes: true,
         };
-        let search_result = &self.suffix_search(&search_args)?;
+        let mask_results = self.suffix_search_all(&search_args)?;
+        let mut mask_hits = merge_mask_hits(self.mask_num, &mask_results);
+        self.retain_digest_hits(&mask_results[0], &mut mask_hits);
+        let removed = self.removed_ranges();
         let seq_starts = self.sequence_starts.clone();
         let seq_names = self.sequence_names.clone();
+        let num_names = seq_names.len();
         let text_len = self.text_len.to_usize();
         let now = Instant::now();
 
         // Augment the search with relative sequence positions
-        let extract_result = search_result
-            .into_par_iter()
-            .map(|res| {
-                let sequences: Vec<ExtractSequence> = match &res.locations {
-                    Some(locs) => locs
-                        .ranks
-                        .clone()
-                        .zip(locs.suffixes.clone())
-                        .map(|(rank, suffix)| {
-                            let i =
-                                seq_starts.partition_point(|&val| val <= suffix) - 1;
-                            let sequence_start = seq_starts[i].to_usize();
-                            let seq_end = if i == seq_starts.len() - 1 {
-                                text_len
-                            } else {
-                                seq_starts[i + 1].to_usize()
-                            };
-                            let suffix = suffix.to_usize();
-                            let relative_suffix_start = suffix - sequence_start;
-                            let context_start = relative_suffix_start
-                                .saturating_sub(args.prefix_len.unwrap_or(0));
+        let extract_result = mask_results[0]
+            .par_iter()
+            .zip(mask_hits)
+            .map(|(res, hits)| {
+                let sequences: Vec<ExtractSequence> = hits
+                    .into_iter()
+                    .filter(|(_, _, suffix)| !is_removed(&removed, suffix.to_usize()))
+                    .map(|(mask_num, rank, suffix)| {
+                        let i = seq_starts.partition_point(|&val| val <= suffix) - 1;
+                        let record_start = seq_starts[i].to_usize();
+                        let record_end = if i == seq_starts.len() - 1 {
+                            text_len
+                        } else {
+                            seq_starts[i + 1].to_usize()
+                        };
+                        let suffix = suffix.to_usize();
+                        let relative_suffix_start = suffix - record_start;
+                        let context_start = relative_suffix_start
+                            .saturating_sub(args.prefix_len.unwrap_or(0));
+
+                        if i < num_names {
                             let context_end = min(
-                                args.suffix_len
-                                    .map_or(seq_end, |len| relative_suffix_start + len),
-                                seq_end,
+                                args.suffix_len.map_or(record_end, |len| {
+                                    relative_suffix_start + len
+                                }),
+                                record_end,
                             );
                             ExtractSequence {
                                 rank,
+                                mask_num,
                                 suffix,
                                 sequence_name: seq_names[i].clone(),
-                                sequence_start,
+                                sequence_start: record_start,
                                 sequence_range: (context_start..context_end),
                                 suffix_offset: relative_suffix_start - context_start,
+                                strand: Strand::Forward,
                             }
-                        })
-                        .collect(),
-                    _ => vec![],
-                };
+                        } else {
+                            // Report the reverse complement context
+                            // in forward coordinates, leaving off the
+                            // trailing delimiter/sentinel.
+                            let seq_len = record_end - record_start - 1;
+                            let context_end = min(
+                                args.suffix_len
+                                    .map_or(seq_len, |len| relative_suffix_start + len),
+                                seq_len,
+                            );
+                            ExtractSequence {
+                                rank,
+                                mask_num,
+                                suffix,
+                                sequence_name: seq_names[i - num_names].clone(),
+                                sequence_start: seq_starts[i - num_names].to_usize(),
+                                sequence_range: (seq_len - context_end
+                                    ..seq_len - context_start),
+                                suffix_offset: relative_suffix_start - context_start,
+                                strand: Strand::Reverse,
+                            }
+                        }
+                    })
+                    .collect();
 
                 ExtractResult {
                     query_num: res.query_num,
@@ -1216,7 +2355,7 @@ where
     /// use anyhow::Result;
     /// use libsufr::{
     ///     sufr_file::SufrFile,
-    ///     types::{LocateOptions, LocatePosition, LocateResult},
+    ///     types::{LocateOptions, LocatePosition, LocateResult, Strand},
     /// };
     ///
     /// fn main() -> Result<()> {
@@ -1234,14 +2373,20 @@ where
     ///                 LocatePosition {
     ///                     suffix: 6,
     ///                     rank: 1,
+    ///                     mask_num: 0,
     ///                     sequence_name: "1".to_string(),
     ///                     sequence_position: 6,
+    ///                     strand: Strand::Forward,
+    ///                     sequence_metadata: None,
     ///                 },
     ///                 LocatePosition {
     ///                     suffix: 0,
     ///                     rank: 2,
+    ///                     mask_num: 0,
     ///                     sequence_name: "1".to_string(),
     ///                     sequence_position: 0,
+    ///                     strand: Strand::Forward,
+    ///                     sequence_metadata: None,
     ///                 },
     ///             ],
     ///         },
@@ -1264,25 +2409,32 @@ where
             max_query_len: args.max_query_len,
             find_suffixes: true,
         };
-        let search_result = &self.suffix_search(&search_opts)?;
-        let seq_starts = self.sequence_starts.clone();
-        let seq_names = self.sequence_names.clone();
+        let mask_results = self.suffix_search_all(&search_opts)?;
+        let mut mask_hits = merge_mask_hits(self.mask_num, &mask_results);
+        self.retain_digest_hits(&mask_results[0], &mut mask_hits);
+        let removed = self.removed_ranges();
         let mut locate_result: Vec<LocateResult> = vec![];
         let now = Instant::now();
 
         // Augment the search with relative sequence positions
-        for res in search_result {
+        for (res, hits) in mask_results[0].iter().zip(mask_hits) {
             let mut positions = vec![];
-            if let Some(locs) = &res.locations {
-                for (rank, suffix) in locs.ranks.clone().zip(locs.suffixes.clone()) {
-                    let i = seq_starts.partition_point(|&val| val <= suffix) - 1;
-                    positions.push(LocatePosition {
-                        rank,
-                        suffix: suffix.to_usize(),
-                        sequence_name: seq_names[i].clone(),
-                        sequence_position: (suffix - seq_starts[i]).to_usize(),
-                    })
+            for (mask_num, rank, suffix) in hits {
+                let suffix = suffix.to_usize();
+                if is_removed(&removed, suffix) {
+                    continue;
                 }
+                let (sequence_name, sequence_position, strand) =
+                    self.sequence_location(suffix, res.query.len());
+                positions.push(LocatePosition {
+                    rank,
+                    mask_num,
+                    suffix,
+                    sequence_name,
+                    sequence_position,
+                    strand,
+                    sequence_metadata: self.sequence_metadata_at(suffix),
+                })
             }
             locate_result.push(LocateResult {
                 query_num: res.query_num,
@@ -1295,55 +2447,1543 @@ where
 
         Ok(locate_result)
     }
-}
-
-// --------------------------------------------------
-#[cfg(test)]
-mod test {
-    use crate::{
-        sufr_file::SufrFile,
-        types::{
-            ExtractOptions, ExtractResult, ExtractSequence, LocateOptions,
-            LocatePosition, LocateResult, BisectOptions, BisectResult,
-        },
-    };
-    use anyhow::Result;
 
     // --------------------------------------------------
-    #[test]
-    fn test_extract() -> Result<()> {
-        // cargo run -- ex data/expected/1.sufr AC GT XX
-        let mut sufr_file: SufrFile<u32> =
-            SufrFile::read("../data/expected/1.sufr", false)?;
+    /// List the sequences that contain each query with the number of
+    /// hits in each. Rather than enumerating every hit as `locate` does,
+    /// the sequences are read from a document array that is computed
+    /// from the suffix array on first use (and cached in "~/.sufr" for
+    /// a _.sufr_ file), so the time is proportional to the number of
+    /// sequences found. An index with several seed masks must merge
+    /// the hits of each mask, and a semi-specific digest must check
+    /// each hit, so these enumerate them.
+    ///
+    /// Args:
+    /// * `args`: `LocateOptions`
+    ///
+    /// Given the sequences "ABC" and "DEF" of "ACGTacgt" and "acgtACGT":
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{
+    ///     sufr_file::SufrFile,
+    ///     types::{DocumentCount, DocumentResult, LocateOptions},
+    /// };
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut sufr = SufrFile::<u32>::read("../data/inputs/2.sufr", true)?;
+    ///     let opts = LocateOptions {
+    ///         queries: vec!["ACG".to_string(), "GGC".to_string()],
+    ///         max_query_len: None,
+    ///         low_memory: true,
+    ///     };
+    ///     let expected = vec![
+    ///         DocumentResult {
+    ///             query_num: 0,
+    ///             query: "ACG".to_string(),
+    ///             documents: vec![
+    ///                 DocumentCount {
+    ///                     sequence_num: 0,
+    ///                     sequence_name: "ABC".to_string(),
+    ///                     count: 2,
+    ///                 },
+    ///                 DocumentCount {
+    ///                     sequence_num: 1,
+    ///                     sequence_name: "DEF".to_string(),
+    ///                     count: 2,
+    ///                 },
+    ///             ],
+    ///         },
+    ///         DocumentResult {
+    ///             query_num: 1,
+    ///             query: "GGC".to_string(),
+    ///             documents: vec![],
+    ///         },
+    ///     ];
+    ///     assert_eq!(expected, sufr.list_documents(opts)?);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn list_documents(
+        &mut self,
+        args: LocateOptions,
+    ) -> Result<Vec<DocumentResult>> {
+        let enumerate_hits = self.masked_indexes.len() > 1 || self.is_semi_specific();
+        let search_opts = SearchOptions {
+            queries: args.queries,
+            low_memory: args.low_memory,
+            max_query_len: args.max_query_len,
+            find_suffixes: enumerate_hits,
+        };
+        let mask_results = self.suffix_search_all(&search_opts)?;
+        let now = Instant::now();
 
-        let opts = ExtractOptions {
-            queries: vec!["AC".to_string(), "GT".to_string(), "XX".to_string()],
-            max_query_len: None,
-            low_memory: true,
-            prefix_len: Some(1),
-            suffix_len: Some(3),
+        let num_names = self.sequence_names.len();
+        let doc_counts: Vec<Vec<(usize, usize)>> = if enumerate_hits {
+            let mut mask_hits = merge_mask_hits(self.mask_num, &mask_results);
+            self.retain_digest_hits(&mask_results[0], &mut mask_hits);
+            mask_hits
+                .into_iter()
+                .map(|hits| {
+                    let mut counts = vec![0; num_names];
+                    for (_, _, suffix) in hits {
+                        let record = self
+                            .sequence_starts
+                            .partition_point(|start| *start <= suffix)
+                            - 1;
+                        counts[record % num_names] += 1;
+                    }
+                    counts
+                        .into_iter()
+                        .enumerate()
+                        .filter(|(_, count)| *count > 0)
+                        .collect()
+                })
+                .collect()
+        } else {
+            self.set_document_array()?;
+            let document_array = self.document_array.as_ref().unwrap();
+            mask_results[0]
+                .iter()
+                .map(|res| {
+                    res.locations
+                        .as_ref()
+                        .map_or(vec![], |locs| document_array.list(locs.ranks.clone()))
+                })
+                .collect()
         };
 
-        let expected = [
-            ExtractResult {
-                query_num: 0,
-                query: "AC".to_string(),
-                sequences: vec![
+        let results = mask_results[0]
+            .iter()
+            .zip(doc_counts)
+            .map(|(res, counts)| DocumentResult {
+                query_num: res.query_num,
+                query: res.query.clone(),
+                documents: counts
+                    .into_iter()
+                    .filter(|(doc, _)| self.tombstones.binary_search(doc).is_err())
+                    .map(|(doc, count)| DocumentCount {
+                        sequence_num: doc,
+                        sequence_name: self.sequence_names[doc].clone(),
+                        count,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        info!("Listing documents finished in {:?}", now.elapsed());
+
+        Ok(results)
+    }
+
+    // --------------------------------------------------
+    /// The sequence name, relative position, and strand of a match
+    /// in the text
+    ///
+    /// Args:
+    /// * `suffix`: the position of the match in the text
+    /// * `len`: the length of the match
+    fn sequence_location(&self, suffix: usize, len: usize) -> (String, usize, Strand) {
+        let num_names = self.sequence_names.len();
+        let i = self
+            .sequence_starts
+            .partition_point(|val| val.to_usize() <= suffix)
+            - 1;
+        let start = self.sequence_starts[i].to_usize();
+        let sequence_position = suffix - start;
+        if i < num_names {
+            (
+                self.sequence_names[i].clone(),
+                sequence_position,
+                Strand::Forward,
+            )
+        } else {
+            // Convert to the position of the match's leftmost
+            // base on the forward strand
+            let record_end = self
+                .sequence_starts
+                .get(i + 1)
+                .map_or(self.text_len.to_usize(), |next| next.to_usize());
+            let seq_len = record_end - start - 1;
+            (
+                self.sequence_names[i - num_names].clone(),
+                seq_len.saturating_sub(sequence_position + len),
+                Strand::Reverse,
+            )
+        }
+    }
+
+    // --------------------------------------------------
+    /// The metadata of the sequence containing a position in the text,
+    /// which for a reverse complement is that of its forward sequence
+    ///
+    /// Args:
+    /// * `suffix`: a position in the text
+    fn sequence_metadata_at(&self, suffix: usize) -> Option<String> {
+        let i = self
+            .sequence_starts
+            .partition_point(|val| val.to_usize() <= suffix)
+            - 1;
+        self.sequence_metadata
+            .get(i % max(1, self.sequence_names.len()))
+            .cloned()
+            .flatten()
+    }
+
+    // --------------------------------------------------
+    /// The root LCP-interval, which holds every suffix and corresponds
+    /// to the root of the suffix tree. Traversing the LCP-intervals
+    /// requires a fully sorted suffix array and uses a child table
+    /// that is computed from the LCP array on first use (and cached
+    /// in "~/.sufr" for a _.sufr_ file).
+    ///
+    /// Given a text of "ACGTNNACGT":
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{sufr_file::SufrFile, types::LcpInterval};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut sufr = SufrFile::<u32>::read("../data/inputs/1.sufr", true)?;
+    ///     let root = sufr.root_interval()?;
+    ///     assert_eq!(root, LcpInterval { lcp: 0, first: 0, last: 8 });
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn root_interval(&mut self) -> Result<LcpInterval> {
+        self.set_child_table()?;
+        self.lcp_interval(0, self.len_suffixes.to_usize() - 1)
+    }
+
+    // --------------------------------------------------
+    /// The child intervals of an LCP-interval in order, which are
+    /// empty for a leaf
+    ///
+    /// Args:
+    /// * `interval`: an `LcpInterval`
+    ///
+    /// Given a text of "ACGTNNACGT":
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{sufr_file::SufrFile, types::LcpInterval};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut sufr = SufrFile::<u32>::read("../data/inputs/1.sufr", true)?;
+    ///     let root = sufr.root_interval()?;
+    ///     let children = sufr.child_intervals(&root)?;
+    ///     assert_eq!(
+    ///         children,
+    ///         vec![
+    ///             LcpInterval { lcp: 1, first: 0, last: 0 },
+    ///             LcpInterval { lcp: 4, first: 1, last: 2 },
+    ///             LcpInterval { lcp: 3, first: 3, last: 4 },
+    ///             LcpInterval { lcp: 2, first: 5, last: 6 },
+    ///             LcpInterval { lcp: 1, first: 7, last: 8 },
+    ///         ]
+    ///     );
+    ///     assert!(sufr.child_intervals(&children[0])?.is_empty());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn child_intervals(
+        &mut self,
+        interval: &LcpInterval,
+    ) -> Result<Vec<LcpInterval>> {
+        self.child_bounds(interval)?
+            .into_iter()
+            .map(|(first, last)| self.lcp_interval(first, last))
+            .collect()
+    }
+
+    // --------------------------------------------------
+    /// The child interval of an LCP-interval whose suffixes continue
+    /// with a given character, if any
+    ///
+    /// Args:
+    /// * `interval`: an `LcpInterval`
+    /// * `symbol`: the character following the shared prefix
+    ///
+    /// Given a text of "ACGTNNACGT":
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{sufr_file::SufrFile, types::LcpInterval};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut sufr = SufrFile::<u32>::read("../data/inputs/1.sufr", true)?;
+    ///     let root = sufr.root_interval()?;
+    ///     let child = sufr.child_interval(&root, b'C')?;
+    ///     assert_eq!(child, Some(LcpInterval { lcp: 3, first: 3, last: 4 }));
+    ///     assert_eq!(sufr.child_interval(&root, b'N')?, None);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn child_interval(
+        &mut self,
+        interval: &LcpInterval,
+        symbol: u8,
+    ) -> Result<Option<LcpInterval>> {
+        let symbol = self.residue_map.as_ref().map_or(symbol, |m| m.map(symbol));
+        for (first, last) in self.child_bounds(interval)? {
+            if self.symbol_at(first, interval.lcp) == Some(symbol) {
+                return self.lcp_interval(first, last).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    // --------------------------------------------------
+    /// The parent interval of an LCP-interval, which is `None` for
+    /// the root
+    ///
+    /// Args:
+    /// * `interval`: an `LcpInterval`
+    ///
+    /// Given a text of "ACGTNNACGT":
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{sufr_file::SufrFile, types::LcpInterval};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut sufr = SufrFile::<u32>::read("../data/inputs/1.sufr", true)?;
+    ///     let root = sufr.root_interval()?;
+    ///     let leaf = LcpInterval { lcp: 5, first: 1, last: 1 };
+    ///     assert_eq!(
+    ///         sufr.parent_interval(&leaf)?,
+    ///         Some(LcpInterval { lcp: 4, first: 1, last: 2 })
+    ///     );
+    ///     assert_eq!(sufr.parent_interval(&root)?, None);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn parent_interval(
+        &mut self,
+        interval: &LcpInterval,
+    ) -> Result<Option<LcpInterval>> {
+        let mut node = self.root_interval()?;
+        if (node.first, node.last) == (interval.first, interval.last) {
+            return Ok(None);
+        }
+
+        loop {
+            let child = self
+                .child_bounds(&node)?
+                .into_iter()
+                .find(|&(first, last)| {
+                    first <= interval.first && interval.first <= last
+                });
+            match child {
+                Some(bounds) if bounds == (interval.first, interval.last) => {
+                    return Ok(Some(node))
+                }
+                Some((first, last)) if first < last && interval.last <= last => {
+                    node = self.lcp_interval(first, last)?;
+                }
+                _ => bail!(
+                    "Ranks {}..={} are not an LCP-interval",
+                    interval.first,
+                    interval.last
+                ),
+            }
+        }
+    }
+
+    // --------------------------------------------------
+    /// Find the LCP-interval of the suffixes that begin with a query
+    /// by descending from the root, which is the locus of the query
+    /// in the suffix tree. Its `lcp` can exceed the length of the query.
+    ///
+    /// Args:
+    /// * `query`: the query string
+    ///
+    /// Given a text of "ACGTNNACGT":
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{sufr_file::SufrFile, types::LcpInterval};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut sufr = SufrFile::<u32>::read("../data/inputs/1.sufr", true)?;
+    ///     assert_eq!(
+    ///         sufr.find_interval("AC")?,
+    ///         Some(LcpInterval { lcp: 4, first: 1, last: 2 })
+    ///     );
+    ///     assert_eq!(
+    ///         sufr.find_interval("TNN")?,
+    ///         Some(LcpInterval { lcp: 8, first: 8, last: 8 })
+    ///     );
+    ///     assert_eq!(sufr.find_interval("GA")?, None);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_interval(&mut self, query: &str) -> Result<Option<LcpInterval>> {
+        self.validate_queries(&[query.to_string()])?;
+        let query = match &self.residue_map {
+            Some(residue_map) => residue_map.map_bytes(query.as_bytes()),
+            _ => query.as_bytes().to_vec(),
+        };
+
+        let (path, matched) = self.longest_match(&query, 0)?;
+        Ok((matched == query.len()).then(|| path[path.len() - 1].clone()))
+    }
+
+    // --------------------------------------------------
+    /// Find the maximal exact matches (MEMs) of queries of at least a
+    /// minimum length, which are the matches that cannot be extended
+    /// to the left or the right. For each position in a query, this
+    /// descends the LCP-intervals to the longest prefix that occurs in
+    /// the text, and the intervals along the way give the length of the
+    /// match of every other suffix. The match at the previous position
+    /// is reused to skip comparing the characters known to match, so
+    /// the text is compared in time linear in the query length (plus
+    /// one child lookup per interval on each path).
+    /// Requires a fully sorted suffix array.
+    ///
+    /// Args:
+    /// * `args`: `MemsOptions`
+    ///
+    /// Given a text of "ACGTNNACGT":
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{
+    ///     sufr_file::SufrFile,
+    ///     types::{Mem, MemsOptions, Strand},
+    /// };
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut sufr = SufrFile::<u32>::read("../data/inputs/1.sufr", true)?;
+    ///     let opts = MemsOptions {
+    ///         queries: vec!["GTNNACGA".to_string()],
+    ///         min_len: 3,
+    ///         unique: false,
+    ///     };
+    ///     let res = sufr.mems(opts)?;
+    ///     assert_eq!(
+    ///         res[0].mems,
+    ///         vec![
+    ///             Mem {
+    ///                 query_position: 0,
+    ///                 length: 7,
+    ///                 suffix: 2,
+    ///                 sequence_name: "1".to_string(),
+    ///                 sequence_position: 2,
+    ///                 strand: Strand::Forward,
+    ///             },
+    ///             Mem {
+    ///                 query_position: 4,
+    ///                 length: 3,
+    ///                 suffix: 0,
+    ///                 sequence_name: "1".to_string(),
+    ///                 sequence_position: 0,
+    ///                 strand: Strand::Forward,
+    ///             },
+    ///         ]
+    ///     );
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn mems(&mut self, args: MemsOptions) -> Result<Vec<MemsResult>> {
+        if args.min_len == 0 {
+            bail!("The minimum match length must be greater than 0")
+        }
+        self.validate_queries(&args.queries)?;
+
+        let removed = self.removed_ranges();
+        let now = Instant::now();
+        let mut mems_result = vec![];
+        for (query_num, query) in args.queries.iter().enumerate() {
+            let mapped = match &self.residue_map {
+                Some(residue_map) => residue_map.map_bytes(query.as_bytes()),
+                _ => query.as_bytes().to_vec(),
+            };
+
+            // A match at one position, less its first character, is
+            // known to occur at the next
+            let mut mems = vec![];
+            let mut prev_matched: usize = 0;
+            for query_pos in 0..mapped.len() {
+                if mapped.len() - query_pos < args.min_len {
+                    break;
+                }
+
+                let (path, matched) = self.longest_match(
+                    &mapped[query_pos..],
+                    prev_matched.saturating_sub(1),
+                )?;
+                prev_matched = matched;
+                if matched < args.min_len {
+                    continue;
+                }
+
+                // The suffixes of the deepest interval match the most
+                // characters, and each interval above it adds suffixes
+                // that match only its LCP
+                let mut inner: Option<&LcpInterval> = None;
+                for (depth, interval) in path.iter().enumerate().rev() {
+                    let length = if depth == path.len() - 1 {
+                        matched
+                    } else {
+                        interval.lcp
+                    };
+                    if length < args.min_len {
+                        break;
+                    }
+
+                    let ranks: Vec<usize> = match inner {
+                        Some(inner) => (interval.first..inner.first)
+                            .chain(inner.last + 1..=interval.last)
+                            .collect(),
+                        _ => (interval.first..=interval.last).collect(),
+                    };
+                    inner = Some(interval);
+
+                    if args.unique {
+                        let substring = &mapped[query_pos..query_pos + length];
+                        let in_query = mapped
+                            .windows(length)
+                            .filter(|window| window == &substring)
+                            .count();
+                        let in_text = (interval.first..=interval.last)
+                            .filter_map(|rank| self.suffix_array_file.get(rank))
+                            .filter(|suffix| !is_removed(&removed, suffix.to_usize()))
+                            .take(2)
+                            .count();
+                        if in_query > 1 || in_text > 1 {
+                            continue;
+                        }
+                    }
+
+                    for rank in ranks {
+                        let suffix = self
+                            .suffix_array_file
+                            .get(rank)
+                            .ok_or(anyhow!("Invalid rank {rank}"))?
+                            .to_usize();
+                        if is_removed(&removed, suffix) {
+                            continue;
+                        }
+
+                        // Skip matches that extend to the left
+                        if query_pos > 0 && suffix > 0 {
+                            let prev = self.get_text(suffix - 1).map(|symbol| {
+                                self.residue_map
+                                    .as_ref()
+                                    .map_or(symbol, |m| m.map(symbol))
+                            });
+                            if prev == Some(mapped[query_pos - 1]) {
+                                continue;
+                            }
+                        }
+
+                        let (sequence_name, sequence_position, strand) =
+                            self.sequence_location(suffix, length);
+                        mems.push(Mem {
+                            query_position: query_pos,
+                            length,
+                            suffix,
+                            sequence_name,
+                            sequence_position,
+                            strand,
+                        });
+                    }
+                }
+            }
+            mems.sort_by_key(|mem| (mem.query_position, mem.suffix));
+            mems_result.push(MemsResult {
+                query_num,
+                query: query.to_string(),
+                mems,
+            });
+        }
+
+        info!(
+            "Search for MEMs of {} quer{} finished in {:?}",
+            args.queries.len(),
+            if args.queries.len() == 1 { "y" } else { "ies" },
+            now.elapsed()
+        );
+
+        Ok(mems_result)
+    }
+
+    // --------------------------------------------------
+    /// Iterate over the distinct k-mers of the text and their counts in
+    /// suffix array order, which streams the suffix and LCP arrays once.
+    /// Only k-mers at the start of an indexed suffix are counted, and
+    /// those that span sequences or hold an ambiguous character are
+    /// skipped. The suffix array must be sorted to at least `k`.
+    ///
+    /// Args:
+    /// * `k`: the length of the k-mers
+    ///
+    /// Given a text of "ACGTNNACGT":
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::sufr_file::SufrFile;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut sufr = SufrFile::<u32>::read("../data/inputs/1.sufr", true)?;
+    ///     let kmers = sufr
+    ///         .kmers(2)?
+    ///         .map(|kmer| kmer.map(|kmer| (kmer.kmer, kmer.count, kmer.ranks)))
+    ///         .collect::<Result<Vec<_>>>()?;
+    ///     assert_eq!(
+    ///         kmers,
+    ///         [
+    ///             ("AC".to_string(), 2, 1..3),
+    ///             ("CG".to_string(), 2, 3..5),
+    ///             ("GT".to_string(), 2, 5..7),
+    ///         ]
+    ///     );
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn kmers(&mut self, k: usize) -> Result<KmerIter<'_, T>> {
+        if k == 0 {
+            bail!("The k-mer length must be greater than 0")
+        }
+        match &self.sort_type {
+            SuffixSortType::Mask(_) => bail!("Counting k-mers cannot use a seed mask"),
+            SuffixSortType::MaxQueryLen(mql) if *mql > 0 && *mql < k => {
+                bail!("Cannot count k-mers longer than the maximum query length {mql}")
+            }
+            _ => (),
+        }
+
+        let removed = self.removed_ranges();
+        self.suffix_array_file.reset();
+        self.lcp_file.reset();
+        Ok(KmerIter {
+            arrays: self
+                .suffix_array_file
+                .iter()
+                .zip(self.lcp_file.iter())
+                .enumerate(),
+            text: &self.text,
+            text_file: &mut self.text_file,
+            text_len: self.text_len.to_usize(),
+            residue_map: self.residue_map.as_ref(),
+            alphabet: self.alphabet.as_ref(),
+            is_dna: self.is_dna,
+            allow_ambiguity: self.allow_ambiguity,
+            removed,
+            len_suffixes: self.len_suffixes.to_usize(),
+            k,
+            current: None,
+        })
+    }
+
+    // --------------------------------------------------
+    /// Compute the complexity of the text in one pass over the suffix
+    /// and LCP arrays: the number of distinct substrings by length and
+    /// overall, the fraction of positions within repeats, and the
+    /// linguistic complexity of each sequence. Substrings never span
+    /// sequences, and only those that start an indexed suffix are
+    /// counted. The alphabet size is the number of distinct characters
+    /// that start a suffix. For a suffix array built with a maximum
+    /// query length, substrings are counted up to that length.
+    ///
+    /// Args:
+    /// * `args`: `ComplexityOptions`
+    ///
+    /// Given a text of "ACGTNNACGT":
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{sufr_file::SufrFile, types::ComplexityOptions};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut sufr = SufrFile::<u32>::read("../data/inputs/1.sufr", true)?;
+    ///     let stats = sufr.complexity_stats(ComplexityOptions {
+    ///         max_len: 3,
+    ///         min_repeat_len: 4,
+    ///     })?;
+    ///     assert_eq!(stats.distinct_by_len, [4, 4, 4]);
+    ///     assert_eq!(stats.num_positions, 10);
+    ///     assert_eq!(stats.repeat_positions, 8);
+    ///     assert_eq!(stats.sequences[0].distinct_substrings, 34);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn complexity_stats(
+        &mut self,
+        args: ComplexityOptions,
+    ) -> Result<ComplexityStats> {
+        let max_query_len = match &self.sort_type {
+            SuffixSortType::MaxQueryLen(mql) => *mql,
+            SuffixSortType::Mask(_) => {
+                bail!("Complexity statistics cannot use a seed mask")
+            }
+        };
+        if args.max_len == 0 || args.min_repeat_len == 0 {
+            bail!("The substring and repeat lengths must be greater than 0")
+        }
+        if max_query_len > 0 && max(args.max_len, args.min_repeat_len) > max_query_len {
+            bail!("Cannot measure substrings longer than the maximum query length {max_query_len}")
+        }
+        let cap = if max_query_len > 0 {
+            max_query_len
+        } else {
+            usize::MAX
+        };
+
+        // Each sequence ends with a delimiter or the sentinel
+        let text_len = self.text_len.to_usize();
+        let num_names = self.sequence_names.len();
+        let seq_starts: Vec<usize> = self
+            .sequence_starts
+            .iter()
+            .map(|start| start.to_usize())
+            .collect();
+        let seq_ends: Vec<usize> = (0..seq_starts.len())
+            .map(|i| seq_starts.get(i + 1).map_or(text_len, |&next| next) - 1)
+            .collect();
+        let record_of = |suffix: usize| {
+            seq_starts.partition_point(|&start| start <= suffix).max(1) - 1
+        };
+
+        let now = Instant::now();
+        let removed = self.removed_ranges();
+        let mut distinct_diff: Vec<isize> = vec![0; args.max_len + 2];
+        let mut distinct_substrings = 0;
+        let mut seq_distinct: Vec<usize> = vec![0; seq_starts.len()];
+        let mut seq_last: Vec<Option<usize>> = vec![None; seq_starts.len()];
+        let mut covered: Vec<u64> = vec![0; text_len.div_ceil(64)];
+        let mut mark_repeat = |suffix: usize, len: usize| {
+            if len >= args.min_repeat_len {
+                for pos in suffix..suffix + len {
+                    covered[pos / 64] |= 1 << (pos % 64);
+                }
+            }
+        };
+
+        // The LCPs in increasing order with their positions, where the
+        // least LCP after a position is that of the first entry past it
+        let mut min_lcps: Vec<(usize, usize)> = vec![];
+        let mut prev: Option<(usize, usize, usize)> = None;
+        let mut skipped_lcp: Option<usize> = None;
+        let mut num_kept = 0;
+
+        self.suffix_array_file.reset();
+        self.lcp_file.reset();
+        let arrays = self.suffix_array_file.iter().zip(self.lcp_file.iter());
+        for (suffix, lcp) in arrays {
+            let suffix = suffix.to_usize();
+            let mut lcp = lcp.to_usize();
+
+            // Compare with the last suffix not in a removed sequence
+            if num_kept == 0 {
+                lcp = 0;
+            }
+            if let Some(skipped) = skipped_lcp {
+                lcp = min(lcp, skipped);
+            }
+            if is_removed(&removed, suffix) {
+                skipped_lcp = Some(lcp);
+                continue;
+            }
+            skipped_lcp = None;
+            let index = num_kept;
+            num_kept += 1;
+
+            // Each prefix longer than the shared one is a new substring
+            let record = record_of(suffix);
+            let dist = seq_ends[record] - suffix;
+            if dist > lcp {
+                let end = min(dist, args.max_len);
+                if lcp < end {
+                    distinct_diff[lcp + 1] += 1;
+                    distinct_diff[end + 1] -= 1;
+                }
+            }
+            distinct_substrings += min(dist, cap).saturating_sub(min(lcp, cap));
+
+            // The same for the suffixes of each sequence
+            while min_lcps.last().is_some_and(|&(top, _)| top >= lcp) {
+                min_lcps.pop();
+            }
+            min_lcps.push((lcp, index));
+            let shared = seq_last[record].map_or(0, |last| {
+                min_lcps[min_lcps.partition_point(|&(_, pos)| pos <= last)].0
+            });
+            seq_distinct[record] += min(dist, cap).saturating_sub(min(shared, cap));
+            seq_last[record] = Some(index);
+
+            // A suffix is repeated as far as it matches either neighbor
+            if let Some((prev_suffix, prev_dist, prev_lcp)) = prev {
+                mark_repeat(prev_suffix, min(max(prev_lcp, lcp), prev_dist));
+            }
+            prev = Some((suffix, dist, lcp));
+        }
+        if let Some((prev_suffix, prev_dist, prev_lcp)) = prev {
+            mark_repeat(prev_suffix, min(prev_lcp, prev_dist));
+        }
+
+        let mut distinct_by_len = vec![];
+        let mut num_distinct: isize = 0;
+        for diff in &distinct_diff[1..=args.max_len] {
+            num_distinct += diff;
+            distinct_by_len.push(num_distinct as usize);
+        }
+
+        // The alphabet is every character that starts a suffix
+        let alphabet_size = distinct_by_len[0];
+        let removed_seqs: HashSet<usize> = self.tombstones.iter().copied().collect();
+        let sequences: Vec<SequenceComplexity> = (0..num_names)
+            .filter(|i| !removed_seqs.contains(i))
+            .map(|i| {
+                let length = seq_ends[i] - seq_starts[i];
+                let most = max_distinct_substrings(length, alphabet_size, cap);
+                SequenceComplexity {
+                    name: self.sequence_names[i].clone(),
+                    length,
+                    distinct_substrings: seq_distinct[i],
+                    linguistic_complexity: if most > 0 {
+                        seq_distinct[i] as f64 / most as f64
+                    } else {
+                        0.
+                    },
+                }
+            })
+            .collect();
+
+        let num_positions = text_len
+            - seq_starts.len()
+            - removed.iter().map(|range| range.len() - 1).sum::<usize>();
+        let repeat_positions =
+            covered.iter().map(|bits| bits.count_ones() as usize).sum();
+
+        info!("Computed complexity statistics in {:?}", now.elapsed());
+
+        Ok(ComplexityStats {
+            distinct_by_len,
+            distinct_substrings,
+            num_positions,
+            repeat_positions,
+            repeat_coverage: if num_positions > 0 {
+                repeat_positions as f64 / num_positions as f64
+            } else {
+                0.
+            },
+            sequences,
+        })
+    }
+
+    // --------------------------------------------------
+    /// Find the repeated substrings of the text by streaming the suffix
+    /// and LCP arrays, where each LCP-interval of at least the minimum
+    /// length is a repeat that cannot be extended to the right. A repeat
+    /// is maximal when the characters before its occurrences differ, and
+    /// supermaximal when it also contains no other repeat and no two of
+    /// its occurrences follow the same character. The start of a sequence
+    /// counts as a unique character on either side, so repeats never span
+    /// sequences. Requires a fully sorted suffix array.
+    ///
+    /// Args:
+    /// * `args`: `RepeatsOptions`
+    ///
+    /// Given a text of "ACGTNNACGT":
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{sufr_file::SufrFile, types::RepeatsOptions};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut sufr = SufrFile::<u32>::read("../data/inputs/1.sufr", true)?;
+    ///     let opts = RepeatsOptions {
+    ///         min_len: 2,
+    ///         supermaximal: false,
+    ///         max_repeats: None,
+    ///     };
+    ///     let repeats = sufr.repeats(opts)?;
+    ///     assert_eq!(repeats.len(), 1);
+    ///     assert_eq!(repeats[0].sequence, "ACGT");
+    ///     assert_eq!(repeats[0].ranks, 1..3);
+    ///     let positions: Vec<_> =
+    ///         repeats[0].positions.iter().map(|p| p.sequence_position).collect();
+    ///     assert_eq!(positions, [0, 6]);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn repeats(&mut self, args: RepeatsOptions) -> Result<Vec<Repeat>> {
+        if self.sort_type != SuffixSortType::MaxQueryLen(0) {
+            bail!("Finding repeats requires a fully sorted suffix array")
+        }
+        if args.min_len == 0 {
+            bail!("The minimum repeat length must be greater than 0")
+        }
+
+        // The position of the delimiter that ends each sequence
+        let text_len = self.text_len.to_usize();
+        let seq_starts: Vec<usize> = self
+            .sequence_starts
+            .iter()
+            .map(|start| start.to_usize())
+            .collect();
+        let seq_ends: Vec<usize> = (0..seq_starts.len())
+            .map(|i| seq_starts.get(i + 1).map_or(text_len, |&next| next) - 1)
+            .collect();
+        let dist_to_end = |suffix: usize| {
+            let i = seq_starts.partition_point(|&start| start <= suffix);
+            seq_ends[i.max(1) - 1].saturating_sub(suffix)
+        };
+
+        let now = Instant::now();
+        let len_suffixes = self.len_suffixes.to_usize();
+        let removed = self.removed_ranges();
+        let (text, text_file) = (&self.text, &mut self.text_file);
+        let residue_map = self.residue_map.as_ref();
+        let mut left_of =
+            |suffix: usize| preceding_symbol(text, text_file, residue_map, suffix);
+
+        // Each open interval holds its LCP, first rank, whether it
+        // contains another interval, and the number of ranks before it
+        // that are not in removed sequences
+        let mut stack: Vec<(usize, usize, bool, usize)> = vec![(0, 0, false, 0)];
+        let mut last_break = 0;
+        let mut prev_left = None;
+
+        // The last rank of each preceding character and the latest rank
+        // whose preceding character also precedes an earlier suffix
+        let mut last_seen: HashMap<u8, usize> = HashMap::new();
+        let mut last_repeated_left = None;
+
+        // The number of ranks not in removed sequences so far
+        let mut num_kept = 0;
+        let mut prev_kept = false;
+
+        // Keep only the longest repeats (then by rank) in a heap whose
+        // top is the one to drop first
+        let mut found: BinaryHeap<(Reverse<usize>, usize, usize)> = BinaryHeap::new();
+        let mut close =
+            |(lcp, first, has_child, kept_before): (usize, usize, bool, usize),
+             last: usize,
+             last_break: usize,
+             last_repeated_left: Option<usize>,
+             num_kept: usize| {
+                if lcp < args.min_len || last_break <= first {
+                    return;
+                }
+                // Every suffix follows a different character (or none)
+                if args.supermaximal
+                    && (has_child
+                        || last_repeated_left.is_some_and(|rank| rank >= first))
+                {
+                    return;
+                }
+                if num_kept - kept_before < 2 {
+                    return;
+                }
+                found.push((Reverse(lcp), first, last));
+                if args.max_repeats.is_some_and(|max| found.len() > max) {
+                    found.pop();
+                }
+            };
+
+        self.suffix_array_file.reset();
+        self.lcp_file.reset();
+        let arrays = self.suffix_array_file.iter().zip(self.lcp_file.iter());
+        for (rank, (suffix, lcp)) in arrays.enumerate() {
+            let suffix = suffix.to_usize();
+            if rank > 0 {
+                // The shared prefix stops at the end of a sequence
+                let lcp = min(lcp.to_usize(), dist_to_end(suffix));
+                let mut first = rank - 1;
+                let mut kept_before = num_kept - usize::from(prev_kept);
+                let mut child = false;
+                while lcp < stack[stack.len() - 1].0 {
+                    let interval = stack.pop().unwrap();
+                    close(interval, rank - 1, last_break, last_repeated_left, num_kept);
+                    (first, kept_before) = (interval.1, interval.3);
+                    let top = stack.len() - 1;
+                    if stack[top].0 >= lcp {
+                        stack[top].2 = true;
+                    } else {
+                        child = true;
+                    }
+                }
+                if lcp > stack[stack.len() - 1].0 {
+                    stack.push((lcp, first, child, kept_before));
+                }
+            }
+
+            // Note where the preceding character changes or repeats
+            let left = left_of(suffix);
+            if rank > 0 && (left.is_none() || left != prev_left) {
+                last_break = rank;
+            }
+            if let Some(symbol) = left {
+                if let Some(prev) = last_seen.insert(symbol, rank) {
+                    last_repeated_left = max(last_repeated_left, Some(prev));
+                }
+            }
+            prev_left = left;
+
+            prev_kept = !is_removed(&removed, suffix);
+            num_kept += usize::from(prev_kept);
+        }
+        while let Some(interval) = stack.pop() {
+            close(
+                interval,
+                len_suffixes - 1,
+                last_break,
+                last_repeated_left,
+                num_kept,
+            );
+        }
+
+        // The longest first, then in rank order
+        let mut repeats = vec![];
+        for (Reverse(length), first, last) in found.into_sorted_vec() {
+            let mut suffixes = (first..=last)
+                .map(|rank| {
+                    self.suffix_array_file
+                        .get(rank)
+                        .map(|suffix| (suffix.to_usize(), rank))
+                        .ok_or(anyhow!("Invalid rank {rank}"))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            suffixes.retain(|&(suffix, _)| !is_removed(&removed, suffix));
+            suffixes.sort();
+
+            let positions = suffixes
+                .iter()
+                .map(|&(suffix, rank)| {
+                    let (sequence_name, sequence_position, strand) =
+                        self.sequence_location(suffix, length);
+                    LocatePosition {
+                        rank,
+                        mask_num: self.mask_num,
+                        suffix,
+                        sequence_name,
+                        sequence_position,
+                        strand,
+                        sequence_metadata: self.sequence_metadata_at(suffix),
+                    }
+                })
+                .collect();
+            repeats.push(Repeat {
+                sequence: self.string_at(suffixes[0].0, Some(length))?,
+                length,
+                ranks: first..last + 1,
+                positions,
+            });
+        }
+
+        info!("Found {} repeats in {:?}", repeats.len(), now.elapsed());
+
+        Ok(repeats)
+    }
+
+    // --------------------------------------------------
+    /// Descend the LCP-intervals along the longest prefix of a query
+    /// that occurs in the text. Returns the intervals from the root down
+    /// to the one whose suffixes all share the prefix, and the length
+    /// of the prefix.
+    ///
+    /// When the first characters of the query are known to occur, as
+    /// after a match of the query shifted by one position, the descent
+    /// over them only looks up the child intervals without comparing
+    /// any text (as a suffix link would), so that finding the match at
+    /// every position of a query compares each character about once.
+    ///
+    /// Args:
+    /// * `query`: the query with any residue map applied
+    /// * `known`: the length of a prefix of the query known to occur
+    fn longest_match(
+        &mut self,
+        query: &[u8],
+        known: usize,
+    ) -> Result<(Vec<LcpInterval>, usize)> {
+        let mut path = vec![self.root_interval()?];
+        let mut matched = 0;
+        loop {
+            let node = path[path.len() - 1].clone();
+            let end = min(node.lcp, query.len());
+            // The characters known to occur need no comparison
+            matched = max(matched, min(known, end));
+            if end > matched {
+                let suffix = self
+                    .suffix_array_file
+                    .get(node.first)
+                    .ok_or(anyhow!("Invalid rank {}", node.first))?
+                    .to_usize();
+                let text = self.get_text_range(suffix + matched..suffix + end)?;
+                let same = text
+                    .iter()
+                    .zip(&query[matched..end])
+                    .take_while(|&(&symbol, &qry)| {
+                        self.residue_map.as_ref().map_or(symbol, |m| m.map(symbol))
+                            == qry
+                    })
+                    .count();
+                matched += same;
+                if matched < end {
+                    return Ok((path, matched));
+                }
+            }
+
+            if matched == query.len() {
+                return Ok((path, matched));
+            }
+
+            match self.child_interval(&node, query[matched])? {
+                Some(child) => path.push(child),
+                _ => return Ok((path, matched)),
+            }
+        }
+    }
+
+    // --------------------------------------------------
+    /// Visit every LCP-interval that holds more than one suffix from
+    /// the bottom up, so that each interval is visited after all of
+    /// its children and the root is visited last. This streams the
+    /// LCP array and does not need the child table.
+    ///
+    /// Args:
+    /// * `visit`: a function to call with each interval
+    ///
+    /// Given a text of "ACGTNNACGT":
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::{sufr_file::SufrFile, types::LcpInterval};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut sufr = SufrFile::<u32>::read("../data/inputs/1.sufr", true)?;
+    ///     let mut repeats = vec![];
+    ///     sufr.bottom_up_intervals(|interval| {
+    ///         if interval.lcp > 0 {
+    ///             repeats.push(interval.clone());
+    ///         }
+    ///         Ok(())
+    ///     })?;
+    ///     assert_eq!(
+    ///         repeats,
+    ///         vec![
+    ///             LcpInterval { lcp: 4, first: 1, last: 2 },
+    ///             LcpInterval { lcp: 3, first: 3, last: 4 },
+    ///             LcpInterval { lcp: 2, first: 5, last: 6 },
+    ///             LcpInterval { lcp: 1, first: 7, last: 8 },
+    ///         ]
+    ///     );
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn bottom_up_intervals<F>(&mut self, mut visit: F) -> Result<()>
+    where
+        F: FnMut(&LcpInterval) -> Result<()>,
+    {
+        if self.sort_type != SuffixSortType::MaxQueryLen(0) {
+            bail!("LCP-intervals require a fully sorted suffix array")
+        }
+
+        let last = self.len_suffixes.to_usize() - 1;
+        let mut stack: Vec<(usize, usize)> = vec![(0, 0)];
+        self.lcp_file.reset();
+        for (rank, lcp) in self.lcp_file.iter().enumerate().skip(1) {
+            let lcp = lcp.to_usize();
+            let mut first = rank - 1;
+            while lcp < stack.last().map_or(0, |&(top, _)| top) {
+                let (top, top_first) = stack.pop().unwrap();
+                visit(&LcpInterval {
+                    lcp: top,
+                    first: top_first,
+                    last: rank - 1,
+                })?;
+                first = top_first;
+            }
+            if lcp > stack.last().map_or(0, |&(top, _)| top) {
+                stack.push((lcp, first));
+            }
+        }
+
+        while let Some((lcp, first)) = stack.pop() {
+            visit(&LcpInterval { lcp, first, last })?;
+        }
+
+        Ok(())
+    }
+
+    // --------------------------------------------------
+    /// The LCP-interval of the ranks `first..=last`, which must be
+    /// the root or a child interval
+    ///
+    /// Args:
+    /// * `first`: the first rank of the interval
+    /// * `last`: the last rank of the interval
+    fn lcp_interval(&mut self, first: usize, last: usize) -> Result<LcpInterval> {
+        let lcp = if first == last {
+            let suffix = self
+                .suffix_array_file
+                .get(first)
+                .ok_or(anyhow!("Invalid rank {first}"))?;
+            self.text_len.to_usize() - suffix.to_usize()
+        } else {
+            let l_index = self
+                .child_table
+                .as_ref()
+                .ok_or(anyhow!("Child table not loaded"))?
+                .first_l_index(first, last);
+            self.lcp_file
+                .get(l_index)
+                .ok_or(anyhow!("Invalid rank {l_index}"))?
+                .to_usize()
+        };
+        Ok(LcpInterval { lcp, first, last })
+    }
+
+    // --------------------------------------------------
+    /// The ranks of the child intervals of an LCP-interval
+    ///
+    /// Args:
+    /// * `interval`: an `LcpInterval`
+    fn child_bounds(&mut self, interval: &LcpInterval) -> Result<Vec<(usize, usize)>> {
+        if interval.is_leaf() {
+            return Ok(vec![]);
+        }
+
+        self.set_child_table()?;
+        let child_table = self.child_table.as_ref().unwrap();
+        let mut first = interval.first;
+        let mut bounds = vec![];
+        for l_index in child_table.l_indexes(interval.first, interval.last) {
+            bounds.push((first, l_index - 1));
+            first = l_index;
+        }
+        bounds.push((first, interval.last));
+        Ok(bounds)
+    }
+
+    // --------------------------------------------------
+    /// The character at an offset into the suffix of a rank with
+    /// any residue map applied
+    ///
+    /// Args:
+    /// * `rank`: the rank of the suffix
+    /// * `offset`: the offset into the suffix
+    fn symbol_at(&mut self, rank: usize, offset: usize) -> Option<u8> {
+        let suffix = self.suffix_array_file.get(rank)?.to_usize();
+        let symbol = self.get_text(suffix + offset)?;
+        Some(self.residue_map.as_ref().map_or(symbol, |m| m.map(symbol)))
+    }
+}
+
+// --------------------------------------------------
+/// Access a portion of a _.sufr_ file or of its serialized bytes
+/// when the suffix array lives only in memory
+///
+/// Args:
+/// * `filename`: the _.sufr_ filename
+/// * `bytes`: the serialized suffix array, if in memory
+/// * `start`: the byte position of the array
+/// * `num_elements`: the length of the array
+fn open_access<U>(
+    filename: &str,
+    bytes: Option<&Arc<[u8]>>,
+    start: usize,
+    num_elements: usize,
+) -> Result<FileAccess<U>>
+where
+    U: Int + FromUsize<U> + Sized + Send + Sync + serde::ser::Serialize,
+{
+    match bytes {
+        Some(bytes) => Ok(FileAccess::from_bytes(
+            bytes.clone(),
+            start as u64,
+            num_elements,
+        )),
+        _ => FileAccess::new(filename, start as u64, num_elements),
+    }
+}
+
+// --------------------------------------------------
+/// An iterator over the distinct k-mers of a suffix array
+/// (cf. [SufrFile::kmers])
+pub struct KmerIter<'a, T>
+where
+    T: Int + FromUsize<T> + Sized + Send + Sync + serde::ser::Serialize,
+{
+    /// The ranks, suffixes, and LCPs
+    arrays: Enumerate<Zip<FileAccessIter<'a, T>, FileAccessIter<'a, T>>>,
+
+    /// The text, if held in memory
+    text: &'a [u8],
+
+    /// The text on disk
+    text_file: &'a mut FileAccess<u8>,
+
+    /// The length of the text
+    text_len: usize,
+
+    /// The residue map, if any
+    residue_map: Option<&'a ResidueMap>,
+
+    /// The alphabet, if any
+    alphabet: Option<&'a Alphabet>,
+
+    /// Whether the text is nucleotides
+    is_dna: bool,
+
+    /// Whether ambiguous characters are indexed
+    allow_ambiguity: bool,
+
+    /// The text ranges of the removed sequences
+    removed: Vec<Range<usize>>,
+
+    /// The number of suffixes
+    len_suffixes: usize,
+
+    /// The length of the k-mers
+    k: usize,
+
+    /// The k-mer, first rank, and count of the run of suffixes so far
+    current: Option<(Vec<u8>, usize, usize)>,
+}
+
+impl<T> KmerIter<'_, T>
+where
+    T: Int + FromUsize<T> + Sized + Send + Sync + serde::ser::Serialize,
+{
+    // --------------------------------------------------
+    /// The k-mer at the start of a suffix, if it lies within one
+    /// sequence and holds no ambiguous character
+    ///
+    /// Args:
+    /// * `suffix`: the suffix position
+    fn kmer_at(&mut self, suffix: usize) -> Result<Option<Vec<u8>>> {
+        let end = suffix + self.k;
+        if end > self.text_len {
+            return Ok(None);
+        }
+        let kmer = if self.text.is_empty() {
+            self.text_file.get_range(suffix..end)?
+        } else {
+            self.text[suffix..end].to_vec()
+        };
+        let is_valid = |&symbol: &u8| {
+            symbol != SEQUENCE_DELIMITER
+                && symbol != SENTINEL_CHARACTER
+                && (self.allow_ambiguity
+                    || match self.alphabet {
+                        Some(alphabet) => alphabet.is_indexed(symbol),
+                        _ => !self.is_dna || b"ACGT".contains(&symbol),
+                    })
+        };
+        Ok(kmer.iter().all(is_valid).then(|| match self.residue_map {
+            Some(residue_map) => residue_map.map_bytes(&kmer),
+            _ => kmer,
+        }))
+    }
+
+    // --------------------------------------------------
+    /// Finish the current k-mer at a rank, unless every occurrence
+    /// was removed
+    ///
+    /// Args:
+    /// * `rank`: the rank after the last suffix of the k-mer
+    fn finish(&mut self, rank: usize) -> Option<Result<Kmer>> {
+        let (kmer, first, count) = self.current.take()?;
+        (count > 0).then(|| {
+            Ok(Kmer {
+                kmer: String::from_utf8(kmer)?,
+                count,
+                ranks: first..rank,
+            })
+        })
+    }
+}
+
+impl<T> Iterator for KmerIter<'_, T>
+where
+    T: Int + FromUsize<T> + Sized + Send + Sync + serde::ser::Serialize,
+{
+    type Item = Result<Kmer>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((rank, (suffix, lcp))) = self.arrays.next() {
+            let suffix = suffix.to_usize();
+
+            // A suffix that shares fewer than k characters with the
+            // one before it starts a new k-mer
+            let mut finished = None;
+            if rank == 0 || lcp.to_usize() < self.k {
+                finished = self.finish(rank);
+                match self.kmer_at(suffix) {
+                    Ok(kmer) => self.current = kmer.map(|kmer| (kmer, rank, 0)),
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            if let Some((_, _, count)) = self.current.as_mut() {
+                if !is_removed(&self.removed, suffix) {
+                    *count += 1;
+                }
+            }
+            if finished.is_some() {
+                return finished;
+            }
+        }
+
+        let len_suffixes = self.len_suffixes;
+        self.finish(len_suffixes)
+    }
+}
+
+// --------------------------------------------------
+/// The character before a suffix, mapped through any residue map, or
+/// `None` when the suffix starts a sequence
+///
+/// Args:
+/// * `text`: the text, if held in memory
+/// * `text_file`: the text on disk
+/// * `residue_map`: the optional residue map
+/// * `suffix`: the suffix position
+fn preceding_symbol(
+    text: &[u8],
+    text_file: &mut FileAccess<u8>,
+    residue_map: Option<&ResidueMap>,
+    suffix: usize,
+) -> Option<u8> {
+    let symbol = if suffix == 0 {
+        None
+    } else if text.is_empty() {
+        text_file.get(suffix - 1)
+    } else {
+        text.get(suffix - 1).copied()
+    }?;
+    (symbol != SEQUENCE_DELIMITER && symbol != SENTINEL_CHARACTER)
+        .then(|| residue_map.map_or(symbol, |map| map.map(symbol)))
+}
+
+// --------------------------------------------------
+/// The most distinct substrings a sequence could hold, i.e., the sum
+/// over each length of the lesser of the number of possible strings
+/// and the number of positions
+///
+/// Args:
+/// * `len`: the length of the sequence
+/// * `alphabet_size`: the number of distinct characters
+/// * `max_len`: the longest substrings to count
+fn max_distinct_substrings(len: usize, alphabet_size: usize, max_len: usize) -> usize {
+    let max_len = min(len, max_len);
+    let mut total = 0;
+    let mut possible: usize = 1;
+    for sub_len in 1..=max_len {
+        possible = possible.saturating_mul(alphabet_size);
+        let positions = len - sub_len + 1;
+        if possible >= positions {
+            // Every longer length is limited by the positions
+            let last = len - max_len + 1;
+            return total + (last + positions) * (positions - last + 1) / 2;
+        }
+        total += possible;
+    }
+    total
+}
+
+// --------------------------------------------------
+/// Whether a suffix starts in one of the removed sequences
+///
+/// Args:
+/// * `removed`: the ordered text ranges of the removed sequences
+/// * `suffix`: the suffix position
+fn is_removed(removed: &[Range<usize>], suffix: usize) -> bool {
+    let i = removed.partition_point(|range| range.end <= suffix);
+    removed.get(i).is_some_and(|range| range.contains(&suffix))
+}
+
+// --------------------------------------------------
+/// A hit as the seed mask, the rank in its suffix array, and the suffix
+type MaskHit<T> = (usize, usize, T);
+
+// --------------------------------------------------
+/// Merge the search results from each seed mask into the distinct
+/// mask/rank/suffix hits for each query. Each rank is in the suffix
+/// array of the first mask that found the suffix.
+///
+/// Args:
+/// * `mask_num`: the number of the mask searched when there is only one
+/// * `mask_results`: the search results for each mask
+fn merge_mask_hits<T>(
+    mask_num: usize,
+    mask_results: &[Vec<SearchResult<T>>],
+) -> Vec<Vec<MaskHit<T>>>
+where
+    T: Int + FromUsize<T> + Sized + Send + Sync,
+{
+    let num_queries = mask_results.first().map_or(0, |res| res.len());
+    let mut hits: Vec<Vec<MaskHit<T>>> = vec![vec![]; num_queries];
+    let mut seen: Vec<HashSet<T>> = vec![HashSet::new(); num_queries];
+    for (i, results) in mask_results.iter().enumerate() {
+        let mask_num = if mask_results.len() > 1 { i } else { mask_num };
+        for (query_num, res) in results.iter().enumerate() {
+            if let Some(locs) = &res.locations {
+                for (rank, &suffix) in locs.ranks.clone().zip(&locs.suffixes) {
+                    if seen[query_num].insert(suffix) {
+                        hits[query_num].push((mask_num, rank, suffix));
+                    }
+                }
+            }
+        }
+    }
+    hits
+}
+
+// --------------------------------------------------
+#[cfg(test)]
+mod test {
+    use crate::{
+        sufr_file::SufrFile,
+        types::{
+            Alphabet, BisectOptions, BisectResult, ExtractOptions, ExtractResult,
+            ExtractSequence, LocateOptions, LocatePosition, LocateResult, Strand,
+        },
+    };
+    use anyhow::Result;
+
+    // --------------------------------------------------
+    #[test]
+    fn test_extract() -> Result<()> {
+        // cargo run -- ex data/expected/1.sufr AC GT XX
+        let mut sufr_file: SufrFile<u32> =
+            SufrFile::read("../data/expected/1.sufr", false)?;
+
+        let opts = ExtractOptions {
+            queries: vec!["AC".to_string(), "GT".to_string(), "XX".to_string()],
+            max_query_len: None,
+            low_memory: true,
+            prefix_len: Some(1),
+            suffix_len: Some(3),
+        };
+
+        let expected = [
+            ExtractResult {
+                query_num: 0,
+                query: "AC".to_string(),
+                sequences: vec![
                     ExtractSequence {
                         suffix: 6,
                         rank: 1,
+                        mask_num: 0,
                         sequence_name: "1".to_string(),
                         sequence_start: 0,
                         sequence_range: 5..9,
                         suffix_offset: 1,
+                        strand: Strand::Forward,
                     },
                     ExtractSequence {
                         suffix: 0,
                         rank: 2,
+                        mask_num: 0,
                         sequence_name: "1".to_string(),
                         sequence_start: 0,
                         sequence_range: 0..3,
                         suffix_offset: 0,
+                        strand: Strand::Forward,
                     },
                 ],
             },
@@ -1354,18 +3994,22 @@ mod test {
                     ExtractSequence {
                         suffix: 8,
                         rank: 5,
+                        mask_num: 0,
                         sequence_name: "1".to_string(),
                         sequence_start: 0,
                         sequence_range: 7..11,
                         suffix_offset: 1,
+                        strand: Strand::Forward,
                     },
                     ExtractSequence {
                         suffix: 2,
                         rank: 6,
+                        mask_num: 0,
                         sequence_name: "1".to_string(),
                         sequence_start: 0,
                         sequence_range: 1..5,
                         suffix_offset: 1,
+                        strand: Strand::Forward,
                     },
                 ],
             },
@@ -1424,45 +4068,66 @@ mod test {
                     positions: vec![
                         LocatePosition {
                             rank: 1,
+                            mask_num: 0,
                             suffix: 0,
                             sequence_name: "1".to_string(),
                             sequence_position: 0,
+                            strand: Strand::Forward,
+                            sequence_metadata: None,
                         },
                         LocatePosition {
                             rank: 2,
+                            mask_num: 0,
                             suffix: 12,
                             sequence_name: "1".to_string(),
                             sequence_position: 12,
+                            strand: Strand::Forward,
+                            sequence_metadata: None,
                         },
                         LocatePosition {
                             rank: 3,
+                            mask_num: 0,
                             suffix: 10,
                             sequence_name: "1".to_string(),
                             sequence_position: 10,
+                            strand: Strand::Forward,
+                            sequence_metadata: None,
                         },
                         LocatePosition {
                             rank: 4,
+                            mask_num: 0,
                             suffix: 1,
                             sequence_name: "1".to_string(),
                             sequence_position: 1,
+                            strand: Strand::Forward,
+                            sequence_metadata: None,
                         },
                         LocatePosition {
                             rank: 5,
+                            mask_num: 0,
                             suffix: 3,
                             sequence_name: "1".to_string(),
                             sequence_position: 3,
+                            strand: Strand::Forward,
+                            sequence_metadata: None,
                         },
                         LocatePosition {
                             rank: 6,
+                            mask_num: 0,
                             suffix: 5,
                             sequence_name: "1".to_string(),
                             sequence_position: 5,
+                            strand: Strand::Forward,
+                            sequence_metadata: None,
                         },
                         LocatePosition {
                             rank: 7,
+                            mask_num: 0,
                             suffix: 7,
                             sequence_name: "1".to_string(),
                             sequence_position: 7,
+                            strand: Strand::Forward,
+                            sequence_metadata: None,
                         },
                     ]
                 }]
@@ -1488,45 +4153,66 @@ mod test {
                     positions: vec![
                         LocatePosition {
                             rank: 8,
+                            mask_num: 0,
                             suffix: 13,
                             sequence_name: "1".to_string(),
                             sequence_position: 13,
+                            strand: Strand::Forward,
+                            sequence_metadata: None,
                         },
                         LocatePosition {
                             rank: 9,
+                            mask_num: 0,
                             suffix: 11,
                             sequence_name: "1".to_string(),
                             sequence_position: 11,
+                            strand: Strand::Forward,
+                            sequence_metadata: None,
                         },
                         LocatePosition {
                             rank: 10,
+                            mask_num: 0,
                             suffix: 9,
                             sequence_name: "1".to_string(),
                             sequence_position: 9,
+                            strand: Strand::Forward,
+                            sequence_metadata: None,
                         },
                         LocatePosition {
                             rank: 11,
+                            mask_num: 0,
                             suffix: 2,
                             sequence_name: "1".to_string(),
                             sequence_position: 2,
+                            strand: Strand::Forward,
+                            sequence_metadata: None,
                         },
                         LocatePosition {
                             rank: 12,
+                            mask_num: 0,
                             suffix: 4,
                             sequence_name: "1".to_string(),
                             sequence_position: 4,
+                            strand: Strand::Forward,
+                            sequence_metadata: None,
                         },
                         LocatePosition {
                             rank: 13,
+                            mask_num: 0,
                             suffix: 6,
                             sequence_name: "1".to_string(),
                             sequence_position: 6,
+                            strand: Strand::Forward,
+                            sequence_metadata: None,
                         },
                         LocatePosition {
                             rank: 14,
+                            mask_num: 0,
                             suffix: 8,
                             sequence_name: "1".to_string(),
                             sequence_position: 8,
+                            strand: Strand::Forward,
+                            sequence_metadata: None,
                         },
                     ]
                 }]
@@ -1552,27 +4238,39 @@ mod test {
                     positions: vec![
                         LocatePosition {
                             rank: 3,
+                            mask_num: 0,
                             suffix: 10,
                             sequence_name: "1".to_string(),
                             sequence_position: 10,
+                            strand: Strand::Forward,
+                            sequence_metadata: None,
                         },
                         LocatePosition {
                             rank: 4,
+                            mask_num: 0,
                             suffix: 1,
                             sequence_name: "1".to_string(),
                             sequence_position: 1,
+                            strand: Strand::Forward,
+                            sequence_metadata: None,
                         },
                         LocatePosition {
                             rank: 5,
+                            mask_num: 0,
                             suffix: 3,
                             sequence_name: "1".to_string(),
                             sequence_position: 3,
+                            strand: Strand::Forward,
+                            sequence_metadata: None,
                         },
                         LocatePosition {
                             rank: 6,
+                            mask_num: 0,
                             suffix: 5,
                             sequence_name: "1".to_string(),
                             sequence_position: 5,
+                            strand: Strand::Forward,
+                            sequence_metadata: None,
                         },
                     ]
                 }]
@@ -1596,9 +4294,12 @@ mod test {
                     query: "ABABB".to_string(),
                     positions: vec![LocatePosition {
                         rank: 6,
+                        mask_num: 0,
                         suffix: 5,
                         sequence_name: "1".to_string(),
                         sequence_position: 5,
+                        strand: Strand::Forward,
+                        sequence_metadata: None,
                     },]
                 }]
             );
@@ -1689,58 +4390,109 @@ mod test {
     // --------------------------------------------------
     #[test]
     fn test_bisect() -> Result<()> {
-        use anyhow::Result;
         let mut sufr = SufrFile::<u32>::read("../data/inputs/3.sufr", false)?;
-        
+
         // 1. without prefix result; should search the whole array for suffixes beginning with A.
         let opt_sans_pfx = BisectOptions {
             queries: vec!['A'],
             max_query_len: None,
             low_memory: false,
-            prefix_result: None
+            prefix_result: None,
         };
-        
+
         let res_sans_pfx = sufr.bisect(opt_sans_pfx)?;
         println!("{:?}", res_sans_pfx);
         assert_eq!(
             res_sans_pfx,
-            vec![BisectResult { query_num: 0, query: 'A', count: 27, first_position: 1, last_position: 27, lcp: 1 }]
+            vec![BisectResult {
+                query_num: 0,
+                query: 'A',
+                count: 27,
+                first_position: 1,
+                last_position: 27,
+                lcp: 1
+            }]
         );
-        
+
         // 1. with prefix; recursively, we are searching for suffixes AC and AT.
         let opt_with_pfx = BisectOptions {
-            queries: vec!['C','T'],
+            queries: vec!['C', 'T'],
             max_query_len: None,
             low_memory: false,
             prefix_result: Some(res_sans_pfx[0].clone()),
         };
-        
+
         let res_with_pfx = sufr.bisect(opt_with_pfx)?;
         println!("{:?}", res_with_pfx);
-        
+
         assert_eq!(
             res_with_pfx,
-            vec![BisectResult { query_num: 0, query: 'C', count: 4, first_position: 12, last_position: 15, lcp: 2 },
-                BisectResult { query_num: 1, query: 'T', count: 5, first_position: 23, last_position: 27, lcp: 2 }]
+            vec![
+                BisectResult {
+                    query_num: 0,
+                    query: 'C',
+                    count: 4,
+                    first_position: 12,
+                    last_position: 15,
+                    lcp: 2
+                },
+                BisectResult {
+                    query_num: 1,
+                    query: 'T',
+                    count: 5,
+                    first_position: 23,
+                    last_position: 27,
+                    lcp: 2
+                }
+            ]
         );
-        
+
         // 2. with secondary prefix; searching for suffixes ACG, ACA.
         let opt_with_pfx2 = BisectOptions {
-            queries: vec!['G','A'],
+            queries: vec!['G', 'A'],
             max_query_len: None,
             low_memory: false,
             prefix_result: Some(res_with_pfx[0].clone()),
         };
-        
+
         let res_with_pfx2 = sufr.bisect(opt_with_pfx2)?;
         println!("{:?}", res_with_pfx2);
-        
+
         assert_eq!(
             res_with_pfx2,
-            vec![BisectResult { query_num: 0, query: 'G', count: 1, first_position: 13, last_position: 13, lcp: 3 },
-                BisectResult { query_num: 1, query: 'A', count: 0, first_position: 0, last_position: 0, lcp: 0 }]
+            vec![
+                BisectResult {
+                    query_num: 0,
+                    query: 'G',
+                    count: 1,
+                    first_position: 13,
+                    last_position: 13,
+                    lcp: 3
+                },
+                BisectResult {
+                    query_num: 1,
+                    query: 'A',
+                    count: 0,
+                    first_position: 0,
+                    last_position: 0,
+                    lcp: 0
+                }
+            ]
+        );
+
+        // 3. each character is validated as its own query
+        sufr.alphabet = Some(Alphabet::Dna);
+        let res = sufr.bisect(BisectOptions {
+            queries: vec!['A', 'U'],
+            max_query_len: None,
+            low_memory: false,
+            prefix_result: None,
+        });
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Query 'U' contains 'U', which is not in the dna alphabet"
         );
-        
+
         Ok(())
     }
     // --------------------------------------------------
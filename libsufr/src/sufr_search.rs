@@ -3,13 +3,14 @@
 use crate::{
     file_access::FileAccess,
     types::{
-        Comparison, FromUsize, Int, BisectResult, SearchResult, SearchResultLocations, SuffixSortType,
+        BisectResult, Comparison, FromUsize, Int, ResidueMap, SearchResult,
+        SearchResultLocations, SuffixSortType,
     },
     util::find_lcp_full_offset,
 };
 use anyhow::Result;
 use std::{
-    cmp::{min, Ordering},
+    cmp::{max, min, Ordering},
     ops::Range,
 };
 
@@ -39,7 +40,7 @@ where
     /// In-memory rank array
     pub rank: &'a [T],
 
-    /// The number of suffixes 
+    /// The number of suffixes
     pub len_suffixes: usize,
 
     /// How the suffixes were built?
@@ -47,6 +48,81 @@ where
 
     /// The maximum query length to use when querying
     pub max_query_len: Option<usize>,
+
+    /// A reduced alphabet to apply to the text and queries
+    pub residue_map: Option<&'a ResidueMap>,
+
+    /// The LCP-LR arrays of the full suffix array, if loaded
+    pub lcp_lr: Option<&'a LcpLr<T>>,
+}
+
+// --------------------------------------------------
+/// The LCPs of each midpoint of a binary search to the bounds of its
+/// interval (Manber & Myers), which let a search skip comparing the
+/// characters it already knows match and so run in O(m + log n)
+#[derive(Debug, PartialEq)]
+pub struct LcpLr<T>
+where
+    T: Int + FromUsize<T> + Sized + Send + Sync + serde::ser::Serialize,
+{
+    /// The LCP of each suffix to the lower bound of the interval
+    /// for which it is the midpoint
+    pub left: Vec<T>,
+
+    /// The LCP of each suffix to the upper bound of the interval
+    /// for which it is the midpoint
+    pub right: Vec<T>,
+}
+
+// --------------------------------------------------
+impl<T> LcpLr<T>
+where
+    T: Int + FromUsize<T> + Sized + Send + Sync + serde::ser::Serialize,
+{
+    /// Compute the LCP-LR arrays from an LCP array
+    ///
+    /// Args:
+    /// * `lcp`: the LCP of each suffix to the one before it
+    pub fn new(lcp: &[T]) -> LcpLr<T> {
+        let len = lcp.len();
+        let mut lcp_lr = LcpLr {
+            left: vec![T::default(); len],
+            right: vec![T::default(); len],
+        };
+        if len > 0 {
+            lcp_lr.fill(lcp, 0, len + 1);
+        }
+        lcp_lr
+    }
+
+    /// Fill in the midpoints of the intervals within the exclusive
+    /// bounds `low` and `high`, where 0 and `len + 1` stand for bounds
+    /// before the first suffix and after the last that share nothing.
+    /// Returns the LCP of the suffixes at the bounds.
+    ///
+    /// Args:
+    /// * `lcp`: the LCP array
+    /// * `low`: the lower bound, offset by one
+    /// * `high`: the upper bound, offset by one
+    fn fill(&mut self, lcp: &[T], low: usize, high: usize) -> T {
+        if high - low == 1 {
+            return if low == 0 || high > lcp.len() {
+                T::default()
+            } else {
+                lcp[high - 1]
+            };
+        }
+        let mid = low + (high - low) / 2;
+        let left = self.fill(lcp, low, mid);
+        let right = self.fill(lcp, mid, high);
+        self.left[mid - 1] = left;
+        self.right[mid - 1] = right;
+        if left < right {
+            left
+        } else {
+            right
+        }
+    }
 }
 
 // --------------------------------------------------
@@ -64,6 +140,8 @@ where
     len_suffixes: usize,
     sort_type: &'a SuffixSortType,
     max_query_len: Option<usize>,
+    residue_map: Option<&'a ResidueMap>,
+    lcp_lr: Option<&'a LcpLr<T>>,
 }
 
 // --------------------------------------------------
@@ -90,6 +168,8 @@ where
             },
             sort_type: args.sort_type,
             max_query_len: args.max_query_len,
+            residue_map: args.residue_map,
+            lcp_lr: args.lcp_lr,
         }
     }
 
@@ -107,13 +187,24 @@ where
         query: &str,
         find_suffixes: bool,
     ) -> Result<SearchResult<T>> {
-        let qry = query.as_bytes();
-        let n = self.len_suffixes;
-        if let Some(start) = self.suffix_search_first(qry, 0, n - 1, 0, 0) {
-            let end = self
-                .suffix_search_last(qry, start, n - 1, n, 0, 0)
-                .unwrap_or(start);
-
+        let mapped;
+        let qry = match self.residue_map {
+            Some(residue_map) => {
+                mapped = residue_map.map_bytes(query.as_bytes());
+                &mapped
+            }
+            _ => query.as_bytes(),
+        };
+        let found = match self.lcp_lr {
+            // The LCP-LR arrays only describe the full suffix array
+            Some(lcp_lr) if self.suffix_array_rank_mem.is_empty() => {
+                let start = self.lcp_lr_search(qry, lcp_lr, false);
+                let end = self.lcp_lr_search(qry, lcp_lr, true);
+                (start < end).then(|| (start, end - 1))
+            }
+            _ => self.find_range(qry),
+        };
+        if let Some((start, end)) = found {
             // Rank is empty when we have the full SA in memory
             // AND when doing low-memory searches
             let ranks = if self.suffix_array_rank_mem.is_empty() {
@@ -168,6 +259,22 @@ where
         }
     }
 
+    // --------------------------------------------------
+    /// Find the first and last positions of the suffixes that start
+    /// with a query by bisecting the whole query at once
+    ///
+    /// Args:
+    /// * `qry`: the query with any residue map applied
+    pub fn find_range(&mut self, qry: &[u8]) -> Option<(usize, usize)> {
+        let n = self.len_suffixes;
+        self.suffix_search_first(qry, 0, n - 1, 0, 0).map(|start| {
+            let end = self
+                .suffix_search_last(qry, start, n - 1, n, 0, 0)
+                .unwrap_or(start);
+            (start, end)
+        })
+    }
+
     // --------------------------------------------------
     /// Find the first and last positions of a query char in a suffix array,
     /// given a range of viable positions.
@@ -188,15 +295,16 @@ where
         high: usize,
     ) -> Result<BisectResult> {
         let query = qry as char;
+        let qry = self.residue_map.map_or(qry, |m| m.map(qry));
         if let Some(start) = self.bisect_char_first(qry, lcp, low, high, low) {
             // something was found
             let max_pos = min(high + 1, self.len_suffixes);
             let end = self
                 .bisect_char_last(qry, lcp, start, high, max_pos)
                 .unwrap_or(start);
-           Ok(BisectResult {
-                query_num: query_num,
-                query: query,
+            Ok(BisectResult {
+                query_num,
+                query,
                 count: end - start + 1,
                 first_position: start,
                 last_position: end,
@@ -204,9 +312,9 @@ where
             })
         } else {
             // nothing was found
-           Ok(BisectResult {
-                query_num: query_num,
-                query: query,
+            Ok(BisectResult {
+                query_num,
+                query,
                 count: 0,
                 first_position: 0,
                 last_position: 0,
@@ -287,7 +395,103 @@ where
             None
         }
     }
-    
+
+    // --------------------------------------------------
+    /// Find the first rank whose suffix is greater than the query, or
+    /// when `after_matches` is false, the first rank whose suffix is
+    /// not less than (i.e., starts with) the query. Returns the number
+    /// of suffixes when there is none.
+    /// The LCPs of the query to the bounds of the search and the LCP-LR
+    /// arrays decide most steps without comparing, and comparisons
+    /// start after the characters known to match.
+    ///
+    /// Args:
+    /// * `qry`: the query
+    /// * `lcp_lr`: the LCP-LR arrays of the full suffix array
+    /// * `after_matches`: whether to find the end rather than the start
+    ///   of the suffixes that match
+    fn lcp_lr_search(
+        &mut self,
+        qry: &[u8],
+        lcp_lr: &LcpLr<T>,
+        after_matches: bool,
+    ) -> usize {
+        // LCPs past the length compared by the search are not meaningful
+        let max_lcp = match self.sort_type {
+            SuffixSortType::MaxQueryLen(mql) => match (*mql, self.max_query_len) {
+                (0, Some(val)) | (val, None) | (val, Some(0)) => val,
+                (mql, Some(val)) => min(mql, val),
+            },
+            SuffixSortType::Mask(seed_mask) => match self.max_query_len {
+                Some(mql) if mql > 0 => min(mql, seed_mask.weight),
+                _ => seed_mask.weight,
+            },
+        };
+        let cap_lcp = |lcp: usize| match max_lcp {
+            0 => lcp,
+            max_lcp => min(lcp, max_lcp),
+        };
+
+        // The bounds are offset by one so that 0 and n + 1 can stand
+        // for the ends, which share no prefix with the query
+        let (mut low, mut high) = (0, self.len_suffixes + 1);
+        let (mut low_lcp, mut high_lcp) = (0, 0);
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+
+            // When the query shares more with a bound than the midpoint
+            // does, the query is on the bound's side of the midpoint;
+            // when less, it compares with the midpoint as with the bound
+            let known = if low_lcp > high_lcp {
+                let left = cap_lcp(lcp_lr.left[mid - 1].to_usize());
+                match left.cmp(&low_lcp) {
+                    Ordering::Greater => Some((true, low_lcp)),
+                    Ordering::Less => Some((false, left)),
+                    Ordering::Equal => None,
+                }
+            } else if high_lcp > low_lcp {
+                let right = cap_lcp(lcp_lr.right[mid - 1].to_usize());
+                match right.cmp(&high_lcp) {
+                    Ordering::Greater => Some((false, high_lcp)),
+                    Ordering::Less => Some((true, right)),
+                    Ordering::Equal => None,
+                }
+            } else {
+                None
+            };
+
+            let (go_right, lcp) = match known {
+                Some(known) => known,
+                _ => {
+                    let suffix = match self.get_suffix(mid - 1) {
+                        Some(suffix) => suffix.to_usize(),
+                        _ => break,
+                    };
+                    let skip = max(low_lcp, high_lcp);
+                    let cmp = self.compare(qry, suffix, skip);
+                    let go_right = match cmp.cmp {
+                        Ordering::Greater => true,
+                        Ordering::Equal => after_matches,
+                        Ordering::Less => false,
+                    };
+
+                    // The comparison may look past the maximum query length
+                    (go_right, cap_lcp(cmp.lcp))
+                }
+            };
+
+            if go_right {
+                low = mid;
+                low_lcp = lcp;
+            } else {
+                high = mid;
+                high_lcp = lcp;
+            }
+        }
+
+        high - 1
+    }
+
     // --------------------------------------------------
     /// Compare a query string to a suffix.
     /// When the suffix array was sorted using a seed mask, only
@@ -335,17 +539,17 @@ where
                 (lcp, max_query_len)
             }
             SuffixSortType::Mask(seed_mask) => {
-                let max_query_len = self.max_query_len.unwrap_or(0);
-                let lcp = if skip >= seed_mask.weight
-                    || (max_query_len > 0 && skip >= max_query_len)
-                {
+                // The suffixes are only sorted on the "care" positions,
+                // which may be limited by a build-time max query length,
+                // so stop there or at a lower runtime limit.
+                let max_query_len = match self.max_query_len {
+                    Some(mql) if mql > 0 => min(mql, seed_mask.weight),
+                    _ => seed_mask.weight,
+                };
+                let lcp = if skip >= max_query_len {
                     skip
                 } else {
-                    let end = if max_query_len > 0 {
-                        min(max_query_len, seed_mask.weight)
-                    } else {
-                        seed_mask.weight
-                    };
+                    let end = max_query_len;
                     let mask_pos = &seed_mask.positions[skip..end];
                     let query_len =
                         mask_pos.iter().filter(|&v| v < &query.len()).count();
@@ -418,7 +622,8 @@ where
 
             if mid_cmp.cmp == Ordering::Equal
                 && (mid == min_pos
-                    || self.compare_char(qry, mid_minus_one, lcp).cmp == Ordering::Greater)
+                    || self.compare_char(qry, mid_minus_one, lcp).cmp
+                        == Ordering::Greater)
             {
                 Some(mid)
             } else if mid_cmp.cmp == Ordering::Greater {
@@ -471,17 +676,12 @@ where
     /// Compare a query character to a suffix character at a given location.
     ///
     /// This function is a mess! It's a prototype of a prototype...
-    /// 
+    ///
     /// Args:
     /// * `query`: char to search for
     /// * `suffix_pos`: suffix position
     /// * `loc`: character location within the suffix.
-    fn compare_char(
-        &mut self,
-        query: u8,
-        suffix_pos: usize,
-        loc: usize,
-    ) -> Comparison {
+    fn compare_char(&mut self, query: u8, suffix_pos: usize, loc: usize) -> Comparison {
         let max_query_len = match &self.sort_type {
             SuffixSortType::MaxQueryLen(mql) => {
                 // The "MaxQueryLen(mql)" refers to how the suffix array
@@ -507,18 +707,15 @@ where
         } else {
             // Get the next chars
             let full_offset = find_lcp_full_offset(loc, self.sort_type);
-            match (
-                Some(query),
-                &self.get_text(suffix_pos + full_offset),
-            ) {
+            match (Some(query), &self.get_text(suffix_pos + full_offset)) {
                 // Entire query matched
                 (None, _) => Ordering::Equal,
 
                 // Compare next char
-                 (Some(a), Some(b)) => a.cmp(b),
+                (Some(a), Some(b)) => a.cmp(b),
 
                 // Panic at the disco
-                 _ => unreachable!(),
+                _ => unreachable!(),
             }
         };
         Comparison { lcp: loc + 1, cmp }
@@ -526,25 +723,37 @@ where
 
     // --------------------------------------------------
     fn get_text(&mut self, pos: usize) -> Option<u8> {
-        if self.text.is_empty() {
+        let val = if self.text.is_empty() {
             self.text_file.get(pos)
         } else {
             self.text.get(pos).copied()
+        };
+        match self.residue_map {
+            Some(residue_map) => val.map(|b| residue_map.map(b)),
+            _ => val,
         }
     }
 
     // --------------------------------------------------
     pub fn get_text_range(&mut self, pos: Range<usize>) -> Result<Vec<u8>> {
         // this is too expensive, copies loooooong stretches of text into vec
-        if self.text.is_empty() {
-            self.text_file.get_range(pos.clone())
+        let text = if self.text.is_empty() {
+            self.text_file.get_range(pos.clone())?
         } else {
-            Ok(self.text.get(pos.clone()).expect("text").to_vec())
+            self.text.get(pos.clone()).expect("text").to_vec()
+        };
+        match self.residue_map {
+            Some(residue_map) => Ok(residue_map.map_bytes(&text)),
+            _ => Ok(text),
         }
     }
 
     // --------------------------------------------------
-    fn get_suffix(&mut self, pos: usize) -> Option<T> {
+    /// Get the suffix at a position of the suffix array
+    ///
+    /// Args:
+    /// * `pos`: a position in the suffix array
+    pub fn get_suffix(&mut self, pos: usize) -> Option<T> {
         if self.suffix_array_mem.is_empty() {
             self.suffix_array_file.get(pos)
         } else {
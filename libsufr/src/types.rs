@@ -3,6 +3,7 @@
 use anyhow::{bail, Result};
 use chrono::{DateTime, Local};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::{
     cmp::Ordering,
     fmt::{self, Debug, Display},
@@ -13,12 +14,15 @@ use std::{
 
 // --------------------------------------------------
 /// Serialization version
-pub const OUTFILE_VERSION: u8 = 6;
+pub const OUTFILE_VERSION: u8 = 14;
 
 /// The sentinel character placed at the end of the text
 /// (and so must not occur in the given text)
 pub const SENTINEL_CHARACTER: u8 = b'$';
 
+/// The character placed between sequences when none is given
+pub const SEQUENCE_DELIMITER: u8 = b'%';
+
 // --------------------------------------------------
 /// Describes the suffixes in a _.sufr_ file were sorted
 #[derive(Debug, Clone, PartialEq)]
@@ -49,6 +53,25 @@ pub struct SeedMask {
 
     /// The number of "care" positions (popcount of 1)
     pub weight: usize,
+
+    /// When greater than zero, the mask is repeated to cover this many
+    /// positions, and the "care" positions are cut off at this length.
+    pub max_query_len: usize,
+}
+
+// --------------------------------------------------
+/// The locations of the suffix and LCP arrays sorted
+/// under one of the seed masks in a _.sufr_ file
+#[derive(Debug, PartialEq, Clone)]
+pub struct MaskedIndex {
+    /// The seed mask used to sort the suffixes
+    pub seed_mask: SeedMask,
+
+    /// The byte position in the file where the suffix array begins.
+    pub suffix_array_pos: usize,
+
+    /// The byte position in the file where the LCP array begins.
+    pub lcp_pos: usize,
 }
 
 // --------------------------------------------------
@@ -71,6 +94,7 @@ impl SeedMask {
     ///         positions: vec![0, 1, 3, 4, 6, 8],
     ///         differences: vec![0, 0, 1, 1, 2, 3],
     ///         weight: 6,
+    ///         max_query_len: 0,
     ///     };
     ///     assert_eq!(mask, expected);
     ///     Ok(())
@@ -93,6 +117,7 @@ impl SeedMask {
             positions,
             differences,
             weight,
+            max_query_len: 0,
         })
     }
 
@@ -112,6 +137,7 @@ impl SeedMask {
     ///         positions: vec![0, 1, 3, 4, 6, 8],
     ///         differences: vec![0, 0, 1, 1, 2, 3],
     ///         weight: 6,
+    ///         max_query_len: 0,
     ///     };
     ///     assert_eq!(mask, expected);
     ///     Ok(())
@@ -140,9 +166,57 @@ impl SeedMask {
             positions,
             differences,
             weight,
+            max_query_len: 0,
         })
     }
 
+    /// Limit the mask to a maximum query length. A shorter mask is
+    /// repeated end-to-end, and only the "care" positions before the
+    /// maximum query length are used to sort and compare suffixes.
+    /// A value of zero leaves the mask as given.
+    ///
+    /// Args:
+    /// * `max_query_len`: the number of positions covered by the mask
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::types::SeedMask;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mask = SeedMask::new("1101")?.with_max_query_len(10);
+    ///     assert_eq!(mask.positions, vec![0, 1, 3, 4, 5, 7, 8, 9]);
+    ///     assert_eq!(mask.weight, 8);
+    ///
+    ///     let mask = SeedMask::new("110101")?.with_max_query_len(4);
+    ///     assert_eq!(mask.positions, vec![0, 1, 3]);
+    ///     assert_eq!(mask.to_string(), "110101");
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_max_query_len(self, max_query_len: usize) -> Self {
+        if max_query_len == 0 {
+            return self;
+        }
+
+        let period = self.bytes.len();
+        let positions: Vec<usize> = (0..max_query_len.div_ceil(period))
+            .flat_map(|repeat| {
+                self.positions.iter().map(move |pos| repeat * period + pos)
+            })
+            .take_while(|&pos| pos < max_query_len)
+            .collect();
+        let differences = Self::get_differences(&positions);
+        let weight = positions.len();
+
+        Self {
+            positions,
+            differences,
+            weight,
+            max_query_len,
+            ..self
+        }
+    }
+
     /// Determine if a seed mask is valid
     ///
     /// ```
@@ -205,6 +279,510 @@ impl Display for SeedMask {
     }
 }
 
+// --------------------------------------------------
+/// Describes the characters that may occur in a text and which
+/// of those characters may start an indexed suffix
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Alphabet {
+    /// Nucleotides: suffixes start with A, C, G, or T; IUPAC
+    /// ambiguity codes are allowed in the text
+    Dna,
+
+    /// Ribonucleotides: suffixes start with A, C, G, or U; IUPAC
+    /// ambiguity codes are allowed in the text
+    Rna,
+
+    /// The 20 standard amino acids; X is allowed in the text
+    Protein,
+
+    /// The 20 standard amino acids plus selenocysteine (U) and
+    /// pyrrolysine (O); B, Z, J, and X are allowed in the text
+    ProteinExtended,
+
+    /// An arbitrary set of bytes, all of which may start a suffix
+    Bytes(Vec<u8>),
+}
+
+const NUCLEOTIDE_AMBIGUITY: &[u8] = b"RYSWKMBDHVN";
+const AMINO_ACIDS: &[u8] = b"ACDEFGHIKLMNPQRSTVWY";
+
+// --------------------------------------------------
+impl Alphabet {
+    /// Create a new `Alphabet` from a name: "dna", "rna", "protein",
+    /// or "protein-extended" (case-insensitive). Any other value must be
+    /// given as "bytes:" followed by the characters of the alphabet.
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::types::Alphabet;
+    ///
+    /// fn main() -> Result<()> {
+    ///     assert_eq!(Alphabet::new("DNA")?, Alphabet::Dna);
+    ///     assert_eq!(Alphabet::new("protein")?, Alphabet::Protein);
+    ///     assert_eq!(Alphabet::new("bytes:01")?, Alphabet::Bytes(b"01".to_vec()));
+    ///     assert!(Alphabet::new("klingon").is_err());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn new(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "dna" => Ok(Alphabet::Dna),
+            "rna" => Ok(Alphabet::Rna),
+            "protein" => Ok(Alphabet::Protein),
+            "protein-extended" => Ok(Alphabet::ProteinExtended),
+            _ => match name.strip_prefix("bytes:") {
+                Some(chars) => Self::from_bytes(5, chars.as_bytes()),
+                _ => bail!("Invalid alphabet '{name}'"),
+            },
+        }
+    }
+
+    /// Instantiate an `Alphabet` from the code and symbols stored
+    /// in a `SufrFile`, cf. `to_code`
+    pub fn from_bytes(code: u8, symbols: &[u8]) -> Result<Self> {
+        match code {
+            1 => Ok(Alphabet::Dna),
+            2 => Ok(Alphabet::Rna),
+            3 => Ok(Alphabet::Protein),
+            4 => Ok(Alphabet::ProteinExtended),
+            5 => {
+                if symbols.is_empty() {
+                    bail!("Byte alphabet cannot be empty")
+                }
+                if symbols.contains(&SENTINEL_CHARACTER) {
+                    bail!("Byte alphabet cannot contain the sentinel character")
+                }
+                let mut symbols = symbols.to_vec();
+                symbols.sort();
+                symbols.dedup();
+                Ok(Alphabet::Bytes(symbols))
+            }
+            _ => bail!("Unknown alphabet code {code}"),
+        }
+    }
+
+    /// The code used to store the alphabet in a _.sufr_ file
+    pub fn to_code(&self) -> u8 {
+        match self {
+            Alphabet::Dna => 1,
+            Alphabet::Rna => 2,
+            Alphabet::Protein => 3,
+            Alphabet::ProteinExtended => 4,
+            Alphabet::Bytes(_) => 5,
+        }
+    }
+
+    /// The characters that may start an indexed suffix
+    pub fn symbols(&self) -> &[u8] {
+        match self {
+            Alphabet::Dna => b"ACGT",
+            Alphabet::Rna => b"ACGU",
+            Alphabet::Protein => AMINO_ACIDS,
+            Alphabet::ProteinExtended => b"ACDEFGHIKLMNOPQRSTUVWY",
+            Alphabet::Bytes(symbols) => symbols,
+        }
+    }
+
+    /// Whether or not the alphabet is nucleotide
+    pub fn is_nucleotide(&self) -> bool {
+        matches!(self, Alphabet::Dna | Alphabet::Rna)
+    }
+
+    /// The character used to replace softmasked/lowercase values,
+    /// or `None` when the alphabet is case-sensitive
+    pub fn ambiguity_character(&self) -> Option<u8> {
+        match self {
+            Alphabet::Dna | Alphabet::Rna => Some(b'N'),
+            Alphabet::Protein | Alphabet::ProteinExtended => Some(b'X'),
+            Alphabet::Bytes(_) => None,
+        }
+    }
+
+    /// Whether or not a character may start an indexed suffix
+    ///
+    /// ```
+    /// use libsufr::types::Alphabet;
+    ///
+    /// assert!(Alphabet::Dna.is_indexed(b'A'));
+    /// assert!(!Alphabet::Dna.is_indexed(b'N'));
+    /// assert!(Alphabet::Dna.is_valid(b'N'));
+    /// assert!(!Alphabet::Dna.is_valid(b'U'));
+    /// ```
+    pub fn is_indexed(&self, byte: u8) -> bool {
+        self.symbols().contains(&byte)
+    }
+
+    /// Whether or not a character may occur in the text
+    pub fn is_valid(&self, byte: u8) -> bool {
+        self.is_indexed(byte)
+            || match self {
+                Alphabet::Dna | Alphabet::Rna => NUCLEOTIDE_AMBIGUITY.contains(&byte),
+                Alphabet::Protein => byte == b'X',
+                Alphabet::ProteinExtended => b"BZJX".contains(&byte),
+                Alphabet::Bytes(_) => false,
+            }
+    }
+
+    /// Ensure that a query contains only characters in the alphabet
+    pub fn validate_query(&self, query: &str) -> Result<()> {
+        if let Some(byte) = query.bytes().find(|&b| !self.is_valid(b)) {
+            bail!(
+                "Query '{query}' contains '{}', which is not in the {self} alphabet",
+                byte as char
+            )
+        }
+        Ok(())
+    }
+}
+
+impl Display for Alphabet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Alphabet::Dna => write!(f, "dna"),
+            Alphabet::Rna => write!(f, "rna"),
+            Alphabet::Protein => write!(f, "protein"),
+            Alphabet::ProteinExtended => write!(f, "protein-extended"),
+            Alphabet::Bytes(symbols) => {
+                write!(f, "bytes:{}", String::from_utf8_lossy(symbols))
+            }
+        }
+    }
+}
+
+// --------------------------------------------------
+/// A mapping of amino acids into groups for indexing proteins under a
+/// reduced alphabet. Each residue is replaced by the first residue of
+/// its group, and characters not in any group are left unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResidueMap {
+    /// The name of a built-in map or "groups:" followed by the groups
+    pub name: String,
+
+    /// The residues in each group, e.g., "LVIM"
+    pub groups: Vec<String>,
+
+    /// A lookup table from each byte to its group's representative
+    table: Vec<u8>,
+}
+
+// --------------------------------------------------
+impl ResidueMap {
+    /// Create a new `ResidueMap` from the name of a built-in map:
+    /// * "murphy-10": Murphy, Wallqvist, and Levy (2000)
+    /// * "se-b-14": Peterson et al. (2009)
+    /// * "dayhoff-6": Dayhoff's exchange groups
+    ///
+    /// Alternately, give "groups:" followed by comma-separated groups,
+    /// where no residue may occur in more than one group. The groups are
+    /// uppercased like the text, and they cannot contain the sequence
+    /// delimiter or the sentinel.
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::types::ResidueMap;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let map = ResidueMap::new("dayhoff-6")?;
+    ///     assert_eq!(map.groups.len(), 6);
+    ///     assert_eq!(map.map_bytes(b"MKLVX"), b"IHIIX");
+    ///
+    ///     let map = ResidueMap::new("groups:KR,de")?;
+    ///     assert_eq!(map.map_bytes(b"RKED"), b"KKDD");
+    ///     assert!(ResidueMap::new("groups:KR,%").is_err());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn new(name: &str) -> Result<Self> {
+        let groups: Vec<String> = match name.to_lowercase().as_str() {
+            "murphy-10" => {
+                vec!["LVIM", "C", "A", "G", "ST", "P", "FYW", "EDNQ", "KR", "H"]
+            }
+            "se-b-14" => vec![
+                "A", "C", "D", "EQ", "FY", "G", "H", "IV", "KR", "LM", "N", "P", "ST",
+                "W",
+            ],
+            "dayhoff-6" => vec!["AGPST", "C", "DENQ", "FWY", "HKR", "ILMV"],
+            _ => match name.strip_prefix("groups:") {
+                Some(groups) => groups.split(',').collect(),
+                _ => bail!("Invalid residue map '{name}'"),
+            },
+        }
+        .into_iter()
+        .map(|group| group.to_ascii_uppercase())
+        .collect();
+
+        let mut table: Vec<u8> = (0..=255).collect();
+        let mut seen = [false; 256];
+        for group in &groups {
+            let representative = match group.bytes().next() {
+                Some(byte) => byte,
+                _ => bail!("Residue map '{name}' contains an empty group"),
+            };
+            for byte in group.bytes() {
+                if byte == SENTINEL_CHARACTER
+                    || byte == SEQUENCE_DELIMITER
+                    || seen[byte as usize]
+                {
+                    bail!("Residue map '{name}' cannot use '{}'", byte as char)
+                }
+                seen[byte as usize] = true;
+                table[byte as usize] = representative;
+            }
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            groups,
+            table,
+        })
+    }
+
+    /// Map one character
+    #[inline(always)]
+    pub fn map(&self, byte: u8) -> u8 {
+        self.table[byte as usize]
+    }
+
+    /// Map a sequence of characters
+    pub fn map_bytes(&self, bytes: &[u8]) -> Vec<u8> {
+        bytes.iter().map(|&b| self.map(b)).collect()
+    }
+}
+
+impl Display for ResidueMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+// --------------------------------------------------
+/// A rule for restricting the suffixes that are indexed to those
+/// starting at a sequence start or at a cleavage site, e.g., to
+/// index only the peptides from an enzymatic digest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuffixStartFilter {
+    /// The name of a built-in rule or the "cleave:" specification
+    pub name: String,
+
+    /// A suffix may start after any of these residues
+    pub after: Vec<u8>,
+
+    /// Except when the suffix starts with one of these residues
+    pub not_before: Vec<u8>,
+
+    /// Whether the digest is semi-specific, so that a peptide needs a
+    /// cleavage site at only one of its ends. Every suffix is indexed,
+    /// and the hits of a query are checked against the rule.
+    pub semi: bool,
+}
+
+// --------------------------------------------------
+impl SuffixStartFilter {
+    /// Create a new `SuffixStartFilter` from the name of a built-in rule:
+    /// * "trypsin": after K or R, but not before P
+    /// * "lys-c": after K
+    /// * "glu-c": after E
+    ///
+    /// Prefix a rule with "nop:" to ignore its exceptions, such as the
+    /// proline rule of trypsin (e.g., "nop:trypsin" also starts suffixes
+    /// with P after K or R), and with "semi:" for a semi-specific digest
+    /// (e.g., "semi:trypsin"), where a peptide needs a cleavage site at
+    /// only one of its ends.
+    /// Alternately, give "cleave:" followed by the residues to cut after
+    /// and an optional "/" and residues that prevent the cut.
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::types::SuffixStartFilter;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let filter = SuffixStartFilter::new("trypsin")?;
+    ///     assert_eq!(filter.after, b"KR");
+    ///     assert_eq!(filter.not_before, b"P");
+    ///     assert!(!filter.semi);
+    ///     assert_eq!(SuffixStartFilter::new("cleave:kr/p")?.after, b"KR");
+    ///     assert!(SuffixStartFilter::new("nop:trypsin")?.not_before.is_empty());
+    ///     assert!(SuffixStartFilter::new("semi:trypsin")?.semi);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn new(name: &str) -> Result<Self> {
+        let mut rule = name;
+        let mut ignore_exceptions = false;
+        let mut semi = false;
+        loop {
+            if let Some(rest) = rule.strip_prefix("nop:") {
+                ignore_exceptions = true;
+                rule = rest;
+            } else if let Some(rest) = rule.strip_prefix("semi:") {
+                semi = true;
+                rule = rest;
+            } else {
+                break;
+            }
+        }
+
+        let (after, not_before) = match rule.to_lowercase().as_str() {
+            "trypsin" => (b"KR".to_vec(), b"P".to_vec()),
+            "lys-c" => (b"K".to_vec(), vec![]),
+            "glu-c" => (b"E".to_vec(), vec![]),
+            _ => match rule.strip_prefix("cleave:") {
+                Some(spec) => {
+                    let spec = spec.to_uppercase();
+                    match spec.split_once('/') {
+                        Some((after, not_before)) => {
+                            (after.as_bytes().to_vec(), not_before.as_bytes().to_vec())
+                        }
+                        _ => (spec.as_bytes().to_vec(), vec![]),
+                    }
+                }
+                _ => bail!("Invalid suffix start filter '{name}'"),
+            },
+        };
+
+        if after.is_empty() {
+            bail!("Suffix start filter '{name}' must cleave after some residue")
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            after,
+            not_before: if ignore_exceptions {
+                vec![]
+            } else {
+                not_before
+            },
+            semi,
+        })
+    }
+
+    /// Whether or not a suffix may start at a cleavage site
+    /// between two residues
+    ///
+    /// Args:
+    /// * `prev`: the residue before the start of the suffix
+    /// * `next`: the first residue of the suffix
+    #[inline(always)]
+    pub fn is_cleavage_site(&self, prev: u8, next: u8) -> bool {
+        self.after.contains(&prev) && !self.not_before.contains(&next)
+    }
+}
+
+impl Display for SuffixStartFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+// --------------------------------------------------
+/// Describes the long runs of "filler" characters (e.g., Ns in
+/// nucleotides or Xs in protein) that are skipped over when comparing
+/// suffixes during sorting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FillerRuns {
+    /// The filler characters. When empty, a run of any single repeated
+    /// character (a homopolymer) is filler.
+    pub characters: Vec<u8>,
+
+    /// The minimum length of a run of filler
+    pub min_len: usize,
+}
+
+// --------------------------------------------------
+impl Default for FillerRuns {
+    fn default() -> Self {
+        Self {
+            characters: vec![b'N'],
+            min_len: 1000,
+        }
+    }
+}
+
+// --------------------------------------------------
+impl FillerRuns {
+    /// Create a new `FillerRuns` from the filler characters, where "*"
+    /// indicates runs of any repeated character.
+    ///
+    /// Args:
+    /// * `characters`: the filler characters, e.g., "NX"
+    /// * `min_len`: the minimum length of a run
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use libsufr::types::FillerRuns;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let filler = FillerRuns::new("X", 3)?;
+    ///     assert_eq!(filler.find_runs(b"MXXXKXXAAAA$"), [1..4]);
+    ///
+    ///     let filler = FillerRuns::new("*", 3)?;
+    ///     assert_eq!(filler.find_runs(b"MXXXKXXAAAA$"), [1..4, 7..11]);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn new(characters: &str, min_len: usize) -> Result<Self> {
+        if characters.is_empty() {
+            bail!("Filler characters cannot be empty")
+        }
+        if min_len < 2 {
+            bail!("Filler runs must be at least 2 characters long")
+        }
+
+        Ok(Self {
+            characters: if characters == "*" {
+                vec![]
+            } else {
+                characters.as_bytes().to_vec()
+            },
+            min_len,
+        })
+    }
+
+    /// Find the ranges of the text covered by long runs of a
+    /// single filler character
+    ///
+    /// Args:
+    /// * `text`: the text to scan
+    pub fn find_runs(&self, text: &[u8]) -> Vec<Range<usize>> {
+        let is_filler = |byte: &u8| {
+            *byte != SENTINEL_CHARACTER
+                && (self.characters.is_empty() || self.characters.contains(byte))
+        };
+
+        let mut runs = vec![];
+        let mut run_start = 0;
+        for i in 1..=text.len() {
+            if i == text.len() || text[i] != text[run_start] {
+                if i - run_start >= self.min_len && is_filler(&text[run_start]) {
+                    runs.push(run_start..i);
+                }
+                run_start = i;
+            }
+        }
+        runs
+    }
+}
+
+// --------------------------------------------------
+/// The strand of a nucleotide sequence on which a query was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strand {
+    /// The sequence as given in the input
+    Forward,
+
+    /// The reverse complement of the input sequence
+    Reverse,
+}
+
+impl Display for Strand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Strand::Forward => write!(f, "+"),
+            Strand::Reverse => write!(f, "-"),
+        }
+    }
+}
+
 // --------------------------------------------------
 /// A struct for use in searching the suffix array
 #[derive(Debug, Clone)]
@@ -217,7 +795,7 @@ pub struct SearchOptions {
     /// value will be used instead.
     pub max_query_len: Option<usize>,
 
-    /// When `true`, the suffix array will be placed into memory. 
+    /// When `true`, the suffix array will be placed into memory.
     /// When `false`, the suffix array will be read from disk.
     pub low_memory: bool,
 
@@ -354,7 +932,7 @@ pub struct BisectOptions {
     /// Maximum query length for search
     pub max_query_len: Option<usize>,
 
-    /// When `true`, the suffix array will be placed into memory. 
+    /// When `true`, the suffix array will be placed into memory.
     /// When `false`, the suffix array will be read from disk.
     pub low_memory: bool,
 
@@ -385,6 +963,50 @@ pub struct BisectResult {
     pub lcp: usize,
 }
 
+// --------------------------------------------------
+/// An LCP-interval of an enhanced suffix array, which corresponds to a
+/// node of the suffix tree: the suffixes at ranks `first..=last` share
+/// a prefix of length `lcp`. A singleton interval is a leaf, and its
+/// `lcp` is the length of its suffix.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LcpInterval {
+    /// The length of the prefix shared by the suffixes, which is
+    /// the string depth of the node
+    pub lcp: usize,
+
+    /// The first rank of the interval
+    pub first: usize,
+
+    /// The last rank of the interval
+    pub last: usize,
+}
+
+impl LcpInterval {
+    /// The number of suffixes in the interval
+    ///
+    /// ```
+    /// use libsufr::types::LcpInterval;
+    ///
+    /// let interval = LcpInterval { lcp: 2, first: 3, last: 5 };
+    /// assert_eq!(interval.count(), 3);
+    /// ```
+    pub fn count(&self) -> usize {
+        self.last - self.first + 1
+    }
+
+    /// Whether the interval holds a single suffix
+    ///
+    /// ```
+    /// use libsufr::types::LcpInterval;
+    ///
+    /// assert!(LcpInterval { lcp: 4, first: 3, last: 3 }.is_leaf());
+    /// assert!(!LcpInterval { lcp: 2, first: 3, last: 5 }.is_leaf());
+    /// ```
+    pub fn is_leaf(&self) -> bool {
+        self.first == self.last
+    }
+}
+
 // --------------------------------------------------
 /// Options for counting the occurrences of suffixes
 #[derive(Debug, Clone)]
@@ -395,7 +1017,7 @@ pub struct CountOptions {
     /// Maximum query length for search
     pub max_query_len: Option<usize>,
 
-    /// When `true`, the suffix array will be placed into memory. 
+    /// When `true`, the suffix array will be placed into memory.
     /// When `false`, the suffix array will be read from disk.
     pub low_memory: bool,
 }
@@ -424,7 +1046,7 @@ pub struct ExtractOptions {
     /// Maximum query length for search
     pub max_query_len: Option<usize>,
 
-    /// When `true`, the suffix array will be placed into memory. 
+    /// When `true`, the suffix array will be placed into memory.
     /// When `false`, the suffix array will be read from disk.
     pub low_memory: bool,
 
@@ -468,6 +1090,10 @@ pub struct ExtractSequence {
     /// The rank of the suffix in the suffix array
     pub rank: usize,
 
+    /// The seed mask whose suffix array has the suffix at `rank`,
+    /// as hits are merged from every mask (0 without seed masks)
+    pub mask_num: usize,
+
     /// The name of the sequence containing a query hit
     pub sequence_name: String,
 
@@ -475,9 +1101,14 @@ pub struct ExtractSequence {
     pub sequence_start: usize,
 
     /// The hit's relative start/stop range inside the sequence
-    /// including the prefix/suffix lengths shown
+    /// including the prefix/suffix lengths shown.
+    /// For hits on the reverse strand, this range is given in the
+    /// coordinates of the forward sequence.
     pub sequence_range: Range<usize>,
 
+    /// The strand of the sequence containing the hit
+    pub strand: Strand,
+
     /// The query hit's start position from the beginning of the shown context
     /// E.g., if the user requested a prefix of 10, then this value will be
     /// between 0-10, depending on the location of the hit inside the sequence.
@@ -523,7 +1154,7 @@ pub struct LocateOptions {
     /// value will be used instead.
     pub max_query_len: Option<usize>,
 
-    /// When `true`, the suffix array will be placed into memory. 
+    /// When `true`, the suffix array will be placed into memory.
     /// When `false`, the suffix array will be read from disk.
     pub low_memory: bool,
 }
@@ -555,11 +1186,328 @@ pub struct LocatePosition {
     /// The rank of the suffix in the suffix array
     pub rank: usize,
 
+    /// The seed mask whose suffix array has the suffix at `rank`,
+    /// as hits are merged from every mask (0 without seed masks)
+    pub mask_num: usize,
+
     /// The name of the sequence containing a query hit
     pub sequence_name: String,
 
-    /// The start position of the hit in the sequence
+    /// The start position of the hit in the sequence.
+    /// For hits on the reverse strand, this is the position of the
+    /// leftmost base of the hit in the forward sequence.
+    pub sequence_position: usize,
+
+    /// The strand of the sequence containing the hit
+    pub strand: Strand,
+
+    /// The metadata of the sequence containing the hit, if any
+    pub sequence_metadata: Option<String>,
+}
+
+// --------------------------------------------------
+/// A struct representing the sequences that contain a query
+#[derive(Debug, PartialEq)]
+pub struct DocumentResult {
+    /// The ordinal position of the original query
+    pub query_num: usize,
+
+    /// The query string
+    pub query: String,
+
+    /// The sequences containing the query in the order of the index.
+    /// This will be empty when the query was not present.
+    pub documents: Vec<DocumentCount>,
+}
+
+// --------------------------------------------------
+/// A struct representing the number of hits of a query in a sequence
+#[derive(Debug, PartialEq)]
+pub struct DocumentCount {
+    /// The index of the sequence among the indexed sequences
+    pub sequence_num: usize,
+
+    /// The name of the sequence
+    pub sequence_name: String,
+
+    /// The number of hits in the sequence, counting both strands
+    pub count: usize,
+}
+
+// --------------------------------------------------
+/// Options for mapping peptides to the proteins that contain them
+#[derive(Debug, Clone)]
+pub struct MapPeptidesOptions {
+    /// The peptide sequences
+    pub peptides: Vec<String>,
+
+    /// A maximum query length to use.
+    /// If the suffix array was sorted with a shorter MQL, that
+    /// value will be used instead.
+    pub max_query_len: Option<usize>,
+
+    /// When `true`, the suffix array will be placed into memory.
+    /// When `false`, the suffix array will be read from disk.
+    pub low_memory: bool,
+}
+
+// --------------------------------------------------
+/// How specific a peptide is to a protein that contains it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeptideClass {
+    /// The peptide is found in only this protein
+    Unique,
+
+    /// The peptide is found in several proteins, and this one has the
+    /// most of the peptides
+    Razor,
+
+    /// The peptide is found in several proteins, and another one has
+    /// more of the peptides
+    Shared,
+}
+
+impl Display for PeptideClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PeptideClass::Unique => write!(f, "unique"),
+            PeptideClass::Razor => write!(f, "razor"),
+            PeptideClass::Shared => write!(f, "shared"),
+        }
+    }
+}
+
+// --------------------------------------------------
+/// A struct representing the proteins that contain a peptide
+#[derive(Debug, PartialEq)]
+pub struct PeptideMapping {
+    /// The ordinal position of the peptide
+    pub peptide_num: usize,
+
+    /// The peptide sequence
+    pub peptide: String,
+
+    /// The hits ordered by protein then position.
+    /// This will be empty when the peptide was not present.
+    pub hits: Vec<PeptideHit>,
+}
+
+// --------------------------------------------------
+/// A struct representing a peptide in the context of a protein
+#[derive(Debug, PartialEq)]
+pub struct PeptideHit {
+    /// The position of the suffix in the text
+    pub suffix: usize,
+
+    /// The name of the protein
+    pub sequence_name: String,
+
+    /// The start position of the peptide in the protein
+    pub sequence_position: usize,
+
+    /// The residue before the peptide, or '-' at the protein's start
+    pub preceding: char,
+
+    /// The residue after the peptide, or '-' at the protein's end
+    pub following: char,
+
+    /// How specific the peptide is to the protein
+    pub class: PeptideClass,
+}
+
+// --------------------------------------------------
+/// Options for finding the maximal exact matches of queries
+#[derive(Debug, Clone)]
+pub struct MemsOptions {
+    /// Vector of query strings
+    pub queries: Vec<String>,
+
+    /// The minimum length of a match
+    pub min_len: usize,
+
+    /// When `true`, only report maximal unique matches (MUMs), whose
+    /// matching string occurs once in the query and once in the text
+    pub unique: bool,
+}
+
+// --------------------------------------------------
+/// A struct representing the maximal exact matches of a query
+#[derive(Debug, PartialEq)]
+pub struct MemsResult {
+    /// The ordinal position of the original query
+    pub query_num: usize,
+
+    /// The query string
+    pub query: String,
+
+    /// The matches ordered by query position and then suffix
+    pub mems: Vec<Mem>,
+}
+
+// --------------------------------------------------
+/// A maximal exact match between a query and the text, which cannot
+/// be extended to the left or the right
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mem {
+    /// The start position of the match in the query
+    pub query_position: usize,
+
+    /// The length of the match
+    pub length: usize,
+
+    /// The position of the match in the text
+    pub suffix: usize,
+
+    /// The name of the sequence containing the match
+    pub sequence_name: String,
+
+    /// The start position of the match in the sequence.
+    /// For matches on the reverse strand, this is the position of the
+    /// leftmost base of the match in the forward sequence.
+    pub sequence_position: usize,
+
+    /// The strand of the sequence containing the match
+    pub strand: Strand,
+}
+
+// --------------------------------------------------
+/// Options for finding repeated substrings of the text
+#[derive(Debug, Clone)]
+pub struct RepeatsOptions {
+    /// The minimum length of a repeat
+    pub min_len: usize,
+
+    /// When `true`, only report supermaximal repeats, which are not
+    /// contained in any other repeat. Otherwise report every maximal
+    /// repeat, which cannot be extended to the left or the right
+    /// without losing an occurrence.
+    pub supermaximal: bool,
+
+    /// The number of the longest repeats to report, or all for `None`
+    pub max_repeats: Option<usize>,
+}
+
+// --------------------------------------------------
+/// A repeated substring of the text
+#[derive(Debug, PartialEq)]
+pub struct Repeat {
+    /// The repeated substring
+    pub sequence: String,
+
+    /// The length of the repeat
+    pub length: usize,
+
+    /// The range of ranks of the suffixes that begin with the repeat
+    pub ranks: Range<usize>,
+
+    /// The occurrences of the repeat in text order
+    pub positions: Vec<LocatePosition>,
+}
+
+// --------------------------------------------------
+/// Options for finding the substrings that other sequences share with
+/// the text
+#[derive(Debug, Clone)]
+pub struct CommonOptions {
+    /// The names and sequences to compare with the text
+    pub sequences: Vec<(String, String)>,
+
+    /// The names of the sequences of the text to leave out, e.g., the
+    /// sequences being compared when both sets are in one suffix array
+    pub exclude: Vec<String>,
+
+    /// The minimum length of a shared substring
+    pub min_len: usize,
+
+    /// When present, only report this many of the longest substrings
+    pub max_results: Option<usize>,
+}
+
+// --------------------------------------------------
+/// A substring shared by another sequence and the text, which cannot
+/// be extended to the left or the right in both
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommonSubstring {
+    /// The shared substring as it appears in the text
+    pub sequence: String,
+
+    /// The length of the substring
+    pub length: usize,
+
+    /// The name of the other sequence
+    pub query_name: String,
+
+    /// The start position of the substring in the other sequence
+    pub query_position: usize,
+
+    /// The position of the substring in the text
+    pub suffix: usize,
+
+    /// The name of the sequence of the text containing the substring
+    pub sequence_name: String,
+
+    /// The start position of the substring in the sequence of the text.
+    /// For matches on the reverse strand, this is the position of the
+    /// leftmost base in the forward sequence.
     pub sequence_position: usize,
+
+    /// The strand of the sequence of the text
+    pub strand: Strand,
+}
+
+// --------------------------------------------------
+/// A distinct k-mer of the text
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kmer {
+    /// The k-mer, mapped through any residue map
+    pub kmer: String,
+
+    /// The number of occurrences, not counting removed sequences
+    pub count: usize,
+
+    /// The range of ranks of the suffixes that begin with the k-mer
+    pub ranks: Range<usize>,
+}
+
+// --------------------------------------------------
+/// Options for computing the matching statistics of queries
+#[derive(Debug, Clone)]
+pub struct MatchingStatisticsOptions {
+    /// Vector of query strings
+    pub queries: Vec<String>,
+
+    /// Maximum query length for search, which caps the lengths
+    pub max_query_len: Option<usize>,
+
+    /// When `true`, the suffix array will be placed into memory.
+    /// When `false`, the suffix array will be read from disk.
+    pub low_memory: bool,
+}
+
+// --------------------------------------------------
+/// A struct representing the matching statistics of a query
+#[derive(Debug, PartialEq)]
+pub struct MatchingStatisticsResult {
+    /// The ordinal position of the original query
+    pub query_num: usize,
+
+    /// The query string
+    pub query: String,
+
+    /// The matching statistic of each position in the query
+    pub statistics: Vec<MatchingStatistic>,
+}
+
+// --------------------------------------------------
+/// The longest prefix of a query suffix that occurs in the text
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchingStatistic {
+    /// The length of the longest prefix
+    pub length: usize,
+
+    /// The range of ranks of the suffixes that begin with the prefix,
+    /// which is every rank when the length is 0
+    pub ranks: Range<usize>,
 }
 
 // --------------------------------------------------
@@ -578,6 +1526,7 @@ pub struct SufrBuilderArgs {
 
     /// Maximum query length determines a prefix length of the suffixes.
     /// Without this value, suffixes will be fully sorted.
+    /// With `seed_masks`, each mask is repeated or cut off to this length.
     pub max_query_len: Option<usize>,
 
     /// Indicates that the input is nucleotides, which has implications
@@ -613,13 +1562,299 @@ pub struct SufrBuilderArgs {
     /// times the number of threads used to process concurrently.
     pub num_partitions: usize,
 
-    /// An optional seed mask of 1/0 for care/don't-care positions,
-    /// cf. `SeedMask`.
-    pub seed_mask: Option<String>,
+    /// Optional seed masks of 1/0 for care/don't-care positions,
+    /// cf. `SeedMask`. Each mask sorts its own suffix/LCP arrays
+    /// over the shared text.
+    pub seed_masks: Vec<String>,
 
     /// A seed value for reproducibility when randomly choosing the
-    /// suffixes for partitioning.
+    /// suffixes for partitioning. With any value but 0, the same text,
+    /// options, and number of partitions give the same output regardless
+    /// of the number of threads; 0 chooses different suffixes each time.
     pub random_seed: u64,
+
+    /// Whether or not to also index the reverse complement of each
+    /// nucleotide sequence (requires `is_dna`). The reverse complements
+    /// are appended to the `text` in the same order as the sequences,
+    /// and the `sequence_names` are not duplicated.
+    pub both_strands: bool,
+
+    /// An optional alphabet used to validate the `text` and to select
+    /// which suffixes are indexed. When `None`, the `is_dna` flag
+    /// determines the indexed suffixes and the `text` is not validated.
+    pub alphabet: Option<Alphabet>,
+
+    /// An optional reduced amino-acid alphabet under which to sort the
+    /// suffixes. The original residues are kept in the stored text, and
+    /// queries are mapped the same way when searching.
+    pub residue_map: Option<ResidueMap>,
+
+    /// An optional rule to index only the suffixes starting at
+    /// the beginning of a sequence or at a cleavage site.
+    pub suffix_start_filter: Option<SuffixStartFilter>,
+
+    /// The long runs of filler characters to skip over when sorting.
+    /// When `None`, runs of at least 1,000 Ns are skipped if
+    /// `allow_ambiguity` is set.
+    pub filler_runs: Option<FillerRuns>,
+}
+
+// --------------------------------------------------
+/// The new sequences to append to an existing suffix array
+#[derive(Clone, Debug)]
+pub struct AppendOptions {
+    /// The new sequences joined by `%` and ending with the
+    /// sentinel `$`, e.g., as from `read_sequence_file`
+    pub text: Vec<u8>,
+
+    /// The start positions of the new sequences in `text`
+    pub sequence_starts: Vec<usize>,
+
+    /// The names of the new sequences
+    pub sequence_names: Vec<String>,
+}
+
+// --------------------------------------------------
+/// A named sequence that can be indexed with
+/// [SufrBuilder::from_records](crate::sufr_builder::SufrBuilder::from_records).
+/// Implement this for your own record types to carry along any
+/// per-record metadata; only the name and sequence are indexed.
+pub trait SequenceRecord {
+    /// The name used to report the positions of hits in the sequence
+    fn name(&self) -> String;
+
+    /// The bases/residues of the sequence
+    fn sequence(&self) -> &[u8];
+
+    /// Optional metadata (e.g., a description or taxonomy) stored with
+    /// the sequence name and reported with the hits in the sequence
+    fn metadata(&self) -> Option<String> {
+        None
+    }
+}
+
+impl<N, S> SequenceRecord for (N, S)
+where
+    N: AsRef<str>,
+    S: AsRef<[u8]>,
+{
+    fn name(&self) -> String {
+        self.0.as_ref().to_string()
+    }
+
+    fn sequence(&self) -> &[u8] {
+        self.1.as_ref()
+    }
+}
+
+// --------------------------------------------------
+/// Statistics collected while building a suffix array, which can be
+/// written as JSON to judge how the partitioning and sorting went
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BuildStats {
+    /// The number of partitions the suffixes were sorted in
+    pub num_partitions: usize,
+
+    /// The number of suffixes placed into each partition
+    pub partition_sizes: Vec<usize>,
+
+    /// The ratio of the largest partition to the mean partition size
+    pub partition_skew: f64,
+
+    /// The number of suffixes that were indexed
+    pub num_suffixes: usize,
+
+    /// The number of positions that do not start a suffix because of
+    /// ambiguity/softmask rules, the suffix start filter, or delimiters
+    pub num_filtered_suffixes: usize,
+
+    /// The number of softmasked/lowercase characters that were ignored
+    pub num_softmasked: usize,
+
+    /// The number of long runs of filler (e.g., Ns) skipped when
+    /// comparing suffixes
+    pub num_filler_runs: usize,
+
+    /// The total length of the runs of filler
+    pub filler_len: usize,
+
+    /// The longest LCP between neighboring suffixes
+    pub max_lcp: usize,
+
+    /// The mean LCP between neighboring suffixes
+    pub mean_lcp: f64,
+
+    /// The most bytes held in temporary files at any one time, as the
+    /// unsorted partitions and sorted suffix/LCP arrays are written
+    /// and removed
+    pub peak_temp_bytes: usize,
+
+    /// Seconds spent validating and transforming the text
+    pub prepare_secs: f64,
+
+    /// Seconds spent selecting pivots
+    pub pivot_secs: f64,
+
+    /// Seconds spent placing suffixes into partitions
+    pub partition_secs: f64,
+
+    /// Seconds spent sorting the partitions
+    pub sort_secs: f64,
+
+    /// Seconds spent writing the suffix array
+    pub write_secs: f64,
+}
+
+// --------------------------------------------------
+impl BuildStats {
+    /// The subset of the statistics stored in the _.sufr_ file.
+    /// Timings are left out so that the same build always
+    /// produces the same file.
+    ///
+    /// ```
+    /// use libsufr::types::BuildStats;
+    /// let stats = BuildStats {
+    ///     num_partitions: 2,
+    ///     partition_sizes: vec![4, 5],
+    ///     max_lcp: 4,
+    ///     sort_secs: 0.5,
+    ///     ..Default::default()
+    /// };
+    /// let summary = stats.summary();
+    /// assert_eq!(summary.num_partitions, 2);
+    /// assert_eq!(summary.max_lcp, 4);
+    /// ```
+    pub fn summary(&self) -> BuildSummary {
+        BuildSummary {
+            num_partitions: self.num_partitions,
+            partition_skew: self.partition_skew,
+            num_filtered_suffixes: self.num_filtered_suffixes,
+            num_softmasked: self.num_softmasked,
+            num_filler_runs: self.num_filler_runs,
+            filler_len: self.filler_len,
+            max_lcp: self.max_lcp,
+            mean_lcp: self.mean_lcp,
+        }
+    }
+
+    /// Serialize the statistics as pretty-printed JSON
+    ///
+    /// ```
+    /// use libsufr::types::BuildStats;
+    /// let stats = BuildStats {
+    ///     num_partitions: 2,
+    ///     ..Default::default()
+    /// };
+    /// assert!(stats.to_json().unwrap().contains(r#""num_partitions": 2"#));
+    /// ```
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+// --------------------------------------------------
+/// Options for computing the complexity statistics of the text
+#[derive(Debug, Clone)]
+pub struct ComplexityOptions {
+    /// The longest substrings to count by length
+    pub max_len: usize,
+
+    /// The minimum length of a repeat when finding the positions
+    /// covered by repeats
+    pub min_repeat_len: usize,
+}
+
+// --------------------------------------------------
+/// Statistics on the complexity of the text for judging the quality
+/// of a reference, which can be written as JSON
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ComplexityStats {
+    /// The number of distinct substrings of each length, starting
+    /// with length 1
+    pub distinct_by_len: Vec<usize>,
+
+    /// The number of distinct substrings of any length, or up to the
+    /// maximum query length of the suffix array
+    pub distinct_substrings: usize,
+
+    /// The number of characters in the sequences
+    pub num_positions: usize,
+
+    /// The number of positions within a repeat of at least the
+    /// minimum repeat length
+    pub repeat_positions: usize,
+
+    /// The fraction of positions within a repeat
+    pub repeat_coverage: f64,
+
+    /// The complexity of each sequence
+    pub sequences: Vec<SequenceComplexity>,
+}
+
+// --------------------------------------------------
+impl ComplexityStats {
+    /// Serialize the statistics as pretty-printed JSON
+    ///
+    /// ```
+    /// use libsufr::types::ComplexityStats;
+    /// let stats = ComplexityStats {
+    ///     distinct_substrings: 30,
+    ///     ..Default::default()
+    /// };
+    /// assert!(stats.to_json().unwrap().contains(r#""distinct_substrings": 30"#));
+    /// ```
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+// --------------------------------------------------
+/// The complexity of one sequence
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SequenceComplexity {
+    /// The name of the sequence
+    pub name: String,
+
+    /// The length of the sequence
+    pub length: usize,
+
+    /// The number of distinct substrings of the sequence
+    pub distinct_substrings: usize,
+
+    /// The linguistic complexity, which is the number of distinct
+    /// substrings over the most that a sequence of this length
+    /// could hold given the size of the alphabet
+    pub linguistic_complexity: f64,
+}
+
+// --------------------------------------------------
+/// A compact summary of how a suffix array was built,
+/// stored in the _.sufr_ file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BuildSummary {
+    /// The number of partitions the suffixes were sorted in
+    pub num_partitions: usize,
+
+    /// The ratio of the largest partition to the mean partition size
+    pub partition_skew: f64,
+
+    /// The number of positions that do not start a suffix
+    pub num_filtered_suffixes: usize,
+
+    /// The number of softmasked/lowercase characters that were ignored
+    pub num_softmasked: usize,
+
+    /// The number of long runs of filler skipped when comparing suffixes
+    pub num_filler_runs: usize,
+
+    /// The total length of the runs of filler
+    pub filler_len: usize,
+
+    /// The longest LCP between neighboring suffixes
+    pub max_lcp: usize,
+
+    /// The mean LCP between neighboring suffixes
+    pub mean_lcp: f64,
 }
 
 // --------------------------------------------------
@@ -647,6 +1882,18 @@ pub struct SufrMetadata {
     /// Ignore softmask
     pub ignore_softmask: bool,
 
+    /// Both strands indexed
+    pub both_strands: bool,
+
+    /// Alphabet
+    pub alphabet: Option<Alphabet>,
+
+    /// Reduced residue map
+    pub residue_map: Option<ResidueMap>,
+
+    /// Suffix start filter
+    pub suffix_start_filter: Option<SuffixStartFilter>,
+
     /// Text length
     pub text_len: usize,
 
@@ -662,13 +1909,25 @@ pub struct SufrMetadata {
     /// Names of sequences
     pub sequence_names: Vec<String>,
 
+    /// Metadata of sequences, if any, in the order of the names
+    pub sequence_metadata: Vec<Option<String>>,
+
+    /// Names of sequences removed from search results
+    pub removed_sequences: Vec<String>,
+
+    /// How the suffix array was built, if known
+    pub build_summary: Option<BuildSummary>,
+
     /// Sort type
     pub sort_type: SuffixSortType,
+
+    /// Seed masks, if any
+    pub seed_masks: Vec<SeedMask>,
 }
 
 #[cfg(test)]
 mod tests {
-    use super::SeedMask;
+    use super::{Alphabet, FillerRuns, ResidueMap, SeedMask, SuffixStartFilter};
     use anyhow::Result;
     use pretty_assertions::assert_eq;
 
@@ -700,6 +1959,7 @@ mod tests {
             positions: vec![0, 2],
             differences: vec![0, 1],
             weight: 2,
+            max_query_len: 0,
         };
         assert_eq!(res.unwrap(), expected);
 
@@ -712,12 +1972,35 @@ mod tests {
             positions: vec![0, 1, 2, 4, 5, 7, 8, 10, 15, 16],
             differences: vec![0, 0, 0, 1, 1, 2, 2, 3, 7, 7],
             weight: 10,
+            max_query_len: 0,
         };
         assert_eq!(res.unwrap(), expected);
 
         Ok(())
     }
 
+    #[test]
+    fn test_seed_mask_max_query_len() -> Result<()> {
+        // Zero leaves the mask alone
+        let mask = SeedMask::new("101")?;
+        assert_eq!(mask.clone().with_max_query_len(0), mask);
+
+        // Shorter masks are repeated
+        let mask = SeedMask::new("101")?.with_max_query_len(8);
+        assert_eq!(mask.positions, [0, 2, 3, 5, 6]);
+        assert_eq!(mask.differences, [0, 1, 1, 2, 2]);
+        assert_eq!(mask.weight, 5);
+        assert_eq!(mask.max_query_len, 8);
+        assert_eq!(mask.bytes, [1, 0, 1]);
+
+        // Longer masks are cut off
+        let mask = SeedMask::new("11000111")?.with_max_query_len(5);
+        assert_eq!(mask.positions, [0, 1]);
+        assert_eq!(mask.weight, 2);
+
+        Ok(())
+    }
+
     #[test]
     fn test_display_seed_mask() -> Result<()> {
         for val in &["101", "11101101101000011"] {
@@ -755,7 +2038,7 @@ mod tests {
     #[test]
     fn test_seed_mask_difference() -> Result<()> {
         // Empty is not a failure
-        assert_eq!(SeedMask::get_differences(&[]), []);
+        assert_eq!(SeedMask::get_differences(&[]), Vec::<usize>::new());
 
         // "11011" -> [0, 1, 3, 4]
         //           - 0  1  2  3
@@ -776,4 +2059,125 @@ mod tests {
         assert_eq!(SeedMask::get_differences(&[0, 3, 4, 6]), [0, 2, 2, 3]);
         Ok(())
     }
+
+    #[test]
+    fn test_alphabet() -> Result<()> {
+        assert!(Alphabet::new("").is_err());
+        assert!(Alphabet::new("bytes:").is_err());
+        assert!(Alphabet::new("bytes:AB$").is_err());
+        assert_eq!(
+            Alphabet::new("Protein-Extended")?,
+            Alphabet::ProteinExtended
+        );
+        assert_eq!(
+            Alphabet::new("bytes:BAAB")?,
+            Alphabet::Bytes(b"AB".to_vec())
+        );
+
+        // Round trip through the file representation and display
+        for alphabet in [
+            Alphabet::Dna,
+            Alphabet::Rna,
+            Alphabet::Protein,
+            Alphabet::ProteinExtended,
+            Alphabet::Bytes(b"01".to_vec()),
+        ] {
+            let symbols = alphabet.symbols().to_vec();
+            assert_eq!(
+                Alphabet::from_bytes(alphabet.to_code(), &symbols)?,
+                alphabet
+            );
+            assert_eq!(Alphabet::new(&alphabet.to_string())?, alphabet);
+        }
+
+        assert!(Alphabet::Rna.is_indexed(b'U'));
+        assert!(!Alphabet::Rna.is_valid(b'T'));
+        assert!(Alphabet::Protein.is_valid(b'X'));
+        assert!(!Alphabet::Protein.is_indexed(b'X'));
+        assert!(!Alphabet::Protein.is_valid(b'U'));
+        assert!(Alphabet::ProteinExtended.is_indexed(b'U'));
+        assert!(Alphabet::Bytes(b"ab".to_vec()).is_indexed(b'a'));
+        assert!(!Alphabet::Bytes(b"ab".to_vec()).is_valid(b'A'));
+
+        assert!(Alphabet::Dna.validate_query("ACGTN").is_ok());
+        assert!(Alphabet::Dna.validate_query("ACGU").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_residue_map() -> Result<()> {
+        assert!(ResidueMap::new("murphy-11").is_err());
+        assert!(ResidueMap::new("groups:KR,,DE").is_err());
+        assert!(ResidueMap::new("groups:KR,RD").is_err());
+        assert!(ResidueMap::new("groups:K$").is_err());
+
+        // Every standard amino acid is in exactly one group
+        for name in ["murphy-10", "se-b-14", "dayhoff-6"] {
+            let map = ResidueMap::new(name)?;
+            let mut residues: Vec<u8> =
+                map.groups.iter().flat_map(|g| g.bytes()).collect();
+            residues.sort();
+            assert_eq!(residues, b"ACDEFGHIKLMNPQRSTVWY");
+        }
+
+        let map = ResidueMap::new("murphy-10")?;
+        assert_eq!(map.groups.len(), 10);
+        assert_eq!(map.map_bytes(b"IVLMSTX%$"), b"LLLLSSX%$");
+        assert_eq!(map.to_string(), "murphy-10");
+        Ok(())
+    }
+
+    #[test]
+    fn test_suffix_start_filter() -> Result<()> {
+        assert!(SuffixStartFilter::new("pepsin").is_err());
+        assert!(SuffixStartFilter::new("cleave:").is_err());
+        assert!(SuffixStartFilter::new("cleave:/P").is_err());
+
+        let trypsin = SuffixStartFilter::new("trypsin")?;
+        assert!(trypsin.is_cleavage_site(b'K', b'A'));
+        assert!(trypsin.is_cleavage_site(b'R', b'A'));
+        assert!(!trypsin.is_cleavage_site(b'K', b'P'));
+        assert!(!trypsin.is_cleavage_site(b'A', b'K'));
+
+        let nop = SuffixStartFilter::new("nop:trypsin")?;
+        assert!(nop.is_cleavage_site(b'K', b'P'));
+        assert_eq!(nop.to_string(), "nop:trypsin");
+
+        let semi = SuffixStartFilter::new("semi:nop:trypsin")?;
+        assert!(semi.semi);
+        assert!(semi.is_cleavage_site(b'K', b'P'));
+        assert!(!trypsin.semi);
+        assert!(SuffixStartFilter::new("semi:").is_err());
+
+        let glu_c = SuffixStartFilter::new("Glu-C")?;
+        assert!(glu_c.is_cleavage_site(b'E', b'P'));
+        assert!(!glu_c.is_cleavage_site(b'D', b'A'));
+
+        let custom = SuffixStartFilter::new("cleave:FW/P")?;
+        assert!(custom.is_cleavage_site(b'W', b'A'));
+        assert!(!custom.is_cleavage_site(b'F', b'P'));
+
+        // The residues of a custom rule are uppercased
+        let lower = SuffixStartFilter::new("cleave:fw/p")?;
+        assert_eq!(lower.after, custom.after);
+        assert_eq!(lower.not_before, custom.not_before);
+        Ok(())
+    }
+
+    #[test]
+    fn test_filler_runs() -> Result<()> {
+        assert!(FillerRuns::new("", 10).is_err());
+        assert!(FillerRuns::new("N", 1).is_err());
+        assert_eq!(FillerRuns::default(), FillerRuns::new("N", 1000)?);
+
+        let filler = FillerRuns::new("NX", 3)?;
+        assert!(filler.find_runs(b"").is_empty());
+        assert_eq!(filler.find_runs(b"NNNXXXNN%XXX$"), [0..3, 3..6, 9..12]);
+
+        // Homopolymers, but not the sentinel
+        let filler = FillerRuns::new("*", 2)?;
+        assert_eq!(filler.find_runs(b"AACGGGT$$"), [0..2, 3..6]);
+
+        Ok(())
+    }
 }
@@ -19,7 +19,7 @@ use std::{fs::File, io::Read, path::Path, slice};
 pub fn find_lcp_full_offset(lcp: usize, sort_type: &SuffixSortType) -> usize {
     match sort_type {
         SuffixSortType::Mask(seed_mask) => {
-            if lcp == 0 || lcp > seed_mask.bytes.len() {
+            if lcp == 0 || lcp > seed_mask.weight {
                 lcp
             } else {
                 // E.g., LCP = 1, so get the 0th offset
@@ -42,12 +42,12 @@ pub fn find_lcp_full_offset(lcp: usize, sort_type: &SuffixSortType) -> usize {
 /// Args:
 /// * `path`: to FASTA/FASTQ file
 /// * `sequence_delimiter`: the character/byte you wish to place
-///    between sequences. For many applications (both nucleotide and
-///    protein), you might choose a character like `%` that sorts below
-///    the alphabet (A-Z) but above the sentinel `$` that is placed at
-///    the end of the returned text; however, when building a suffix array
-///    for use in a Burrows-Wheeler Transform, it might be better to use
-///    `N` for nucleotides and `X` for protein.
+///   between sequences. For many applications (both nucleotide and
+///   protein), you might choose a character like `%` that sorts below
+///   the alphabet (A-Z) but above the sentinel `$` that is placed at
+///   the end of the returned text; however, when building a suffix array
+///   for use in a Burrows-Wheeler Transform, it might be better to use
+///   `N` for nucleotides and `X` for protein.
 pub fn read_sequence_file(
     path: &Path,
     sequence_delimiter: u8,
@@ -88,6 +88,34 @@ pub fn read_sequence_file(
     })
 }
 
+// --------------------------------------------------
+/// Reverse complement a nucleotide sequence. IUPAC ambiguity codes
+/// are complemented, and any other bytes (e.g., sequence delimiters)
+/// are left unchanged.
+///
+/// Args:
+/// * `seq`: uppercase nucleotides
+pub fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    seq.iter()
+        .rev()
+        .map(|b| match b {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            b'T' => b'A',
+            b'R' => b'Y',
+            b'Y' => b'R',
+            b'K' => b'M',
+            b'M' => b'K',
+            b'B' => b'V',
+            b'V' => b'B',
+            b'D' => b'H',
+            b'H' => b'D',
+            _ => *b,
+        })
+        .collect()
+}
+
 // --------------------------------------------------
 /// Find length of the input text from a _.sufr_
 /// file to determine the `Int` type, `u32` or `u64`
@@ -97,8 +125,9 @@ pub fn read_sequence_file(
 pub fn read_text_length(filename: &str) -> Result<usize> {
     let mut file = File::open(filename).map_err(|e| anyhow!("{filename}: {e}"))?;
 
-    // Meta (version, is_dna)
-    let mut buffer = [0; 4];
+    // Meta (version, is_dna, allow_ambiguity, ignore_softmask,
+    // both_strands, alphabet)
+    let mut buffer = [0; 6];
     file.read_exact(&mut buffer)?;
 
     let outfile_version = buffer[0];
@@ -172,8 +201,8 @@ where
 #[cfg(test)]
 mod tests {
     use super::{
-        find_lcp_full_offset, read_sequence_file, read_text_length, slice_u8_to_vec,
-        usize_to_bytes, vec_to_slice_u8,
+        find_lcp_full_offset, read_sequence_file, read_text_length, reverse_complement,
+        slice_u8_to_vec, usize_to_bytes, vec_to_slice_u8,
     };
     use crate::types::{SeedMask, SuffixSortType};
     use anyhow::Result;
@@ -184,7 +213,7 @@ mod tests {
     fn test_read_sequence_file() -> Result<()> {
         let file = Path::new("../data/inputs/2.fa");
         let sequence_delimiter = b'N';
-        let res = read_sequence_file(&file, sequence_delimiter);
+        let res = read_sequence_file(file, sequence_delimiter);
         assert!(res.is_ok());
         let data = res.unwrap();
         assert_eq!(data.seq, b"ACGTacgtNacgtACGT$");
@@ -193,6 +222,16 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_reverse_complement() -> Result<()> {
+        assert_eq!(reverse_complement(b""), b"");
+        assert_eq!(reverse_complement(b"ACGT"), b"ACGT");
+        assert_eq!(reverse_complement(b"AACGN"), b"NCGTT");
+        assert_eq!(reverse_complement(b"AC%GG"), b"CC%GT");
+        assert_eq!(reverse_complement(b"RYKM"), b"KMRY");
+        Ok(())
+    }
+
     #[test]
     fn test_read_text_length() -> Result<()> {
         let sufr_file = "../data/inputs/2.sufr";
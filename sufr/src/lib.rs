@@ -2,16 +2,21 @@ use anyhow::{anyhow, bail, Result};
 use clap::{builder::PossibleValue, Parser, ValueEnum};
 use format_num::NumberFormat;
 use libsufr::{
+    sharded_suffix_array::ShardedSuffixArray,
     suffix_array::SuffixArray,
     types::{
-        CountOptions, ExtractOptions, ListOptions, LocateOptions, SuffixSortType,
-        SufrBuilderArgs,
+        Alphabet, AppendOptions, CommonOptions, ComplexityOptions, CountOptions,
+        ExtractOptions, FillerRuns, ListOptions, LocateOptions, MapPeptidesOptions,
+        MatchingStatisticsOptions, MemsOptions, RepeatsOptions, ResidueMap,
+        SuffixSortType, SuffixStartFilter, SufrBuilderArgs, SEQUENCE_DELIMITER,
     },
     util::read_sequence_file,
 };
 use log::info;
 use regex::Regex;
 use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, HashSet},
     ffi::OsStr,
     fmt::Debug,
     fs::{self, File},
@@ -47,6 +52,15 @@ pub struct Cli {
 
 #[derive(Parser, Debug)]
 pub enum Command {
+    /// Append sequences to a sufr file
+    Append(AppendArgs),
+
+    /// Find substrings shared by sequences in sufr files
+    Common(CommonArgs),
+
+    /// Rebuild a sufr file without its removed sequences
+    Compact(CompactArgs),
+
     /// Create sufr file
     Create(CreateArgs),
 
@@ -55,6 +69,9 @@ pub enum Command {
     /// Extract suffixes from a sufr file
     Extract(ExtractArgs),
 
+    /// Count the k-mers in a sufr file
+    Kmers(KmersArgs),
+
     /// List the suffix array from a sufr file
     List(ListArgs),
 
@@ -64,6 +81,24 @@ pub enum Command {
     /// Locate sequences in a sufr file
     Locate(LocateArgs),
 
+    /// Map peptides to the proteins in a sufr file
+    MapPeptides(MapPeptidesArgs),
+
+    /// Compute matching statistics of sequences in a sufr file
+    MatchingStats(MatchingStatsArgs),
+
+    /// Find maximal exact matches of sequences in a sufr file
+    Mems(MemsArgs),
+
+    /// Remove sequences from search results
+    Remove(RemoveArgs),
+
+    /// Find repeated sequences in a sufr file
+    Repeats(RepeatsArgs),
+
+    /// Measure the complexity of the sequences in a sufr file
+    Stats(StatsArgs),
+
     /// Summarize sufr file
     Summarize(SummarizeArgs),
 }
@@ -80,6 +115,71 @@ pub enum Command {
 //    pub verbose: bool,
 //}
 
+#[derive(Debug, Parser)]
+#[command(about, alias = "ap")]
+pub struct AppendArgs {
+    /// Sufr file
+    #[arg(value_name = "SUFR")]
+    pub file: String,
+
+    /// Input file of sequences to append
+    #[arg(value_name = "INPUT")]
+    pub input: String,
+
+    /// Output file (default: rewrite the sufr file)
+    #[arg(short, long, value_name = "OUTPUT")]
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+#[command(about, alias = "cn")]
+pub struct CommonArgs {
+    /// Output
+    #[arg(short, long, value_name = "OUT")]
+    pub output: Option<String>,
+
+    /// Minimum length of a shared substring
+    #[arg(short = 'L', long, value_name = "LEN", default_value = "20")]
+    pub min_len: usize,
+
+    /// Only report the longest shared substrings
+    #[arg(short = 'n', long, value_name = "NUM")]
+    pub top: Option<usize>,
+
+    /// Names of the sequences to compare, from OTHER if given or else
+    /// from SUFR (comma-separate or repeat for multiple names)
+    #[arg(short, long, value_name = "NAME", value_delimiter = ',')]
+    pub sequences: Vec<String>,
+
+    /// Very low memory
+    #[arg(short, long)]
+    pub very_low_memory: bool,
+
+    /// Sufr file
+    #[arg(value_name = "SUFR")]
+    pub file: String,
+
+    /// Sufr file of the sequences to compare with SUFR
+    #[arg(value_name = "OTHER")]
+    pub other: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+#[command(about, alias = "cm")]
+pub struct CompactArgs {
+    /// Sufr file
+    #[arg(value_name = "SUFR")]
+    pub file: String,
+
+    /// Subproblem count
+    #[arg(short, long, value_name = "NUM_PARTS", default_value = "16")]
+    pub num_partitions: usize,
+
+    /// Output file (default: rewrite the sufr file)
+    #[arg(short, long, value_name = "OUTPUT")]
+    pub output: Option<String>,
+}
+
 #[derive(Debug, Parser)]
 #[command(about, alias = "cr")]
 pub struct CreateArgs {
@@ -92,7 +192,7 @@ pub struct CreateArgs {
     pub num_partitions: usize,
 
     /// Max context
-    #[arg(short, long, value_name = "CONTEXT", conflicts_with = "seed_mask")]
+    #[arg(short, long, value_name = "CONTEXT")]
     pub max_query_len: Option<usize>,
 
     /// Output file
@@ -115,13 +215,46 @@ pub struct CreateArgs {
     #[arg(short('D'), long, default_value = "%", value_name = "DELIM")]
     pub sequence_delimiter: char,
 
-    /// Spaced seeds mask
-    #[arg(short, long, value_name = "MASK")]
-    pub seed_mask: Option<String>,
+    /// Spaced seeds mask (comma-separate or repeat for multiple masks)
+    #[arg(short, long, value_name = "MASK", value_delimiter = ',')]
+    pub seed_mask: Vec<String>,
 
-    /// Random seed
+    /// Random seed (0 for a different partitioning each run)
     #[arg(short, long, value_name = "RANDSEED", default_value = "42")]
     pub random_seed: u64,
+
+    /// Also index the reverse complement of each sequence
+    #[arg(short, long, requires = "is_dna")]
+    pub both_strands: bool,
+
+    /// Validate input (dna, rna, protein, protein-extended, bytes:<CHARS>)
+    #[arg(short('A'), long, value_name = "ALPHABET")]
+    pub alphabet: Option<String>,
+
+    /// Reduced amino acid alphabet (murphy-10, se-b-14, dayhoff-6, groups:<G1,G2,...>)
+    #[arg(short('R'), long, value_name = "MAP")]
+    pub residue_map: Option<String>,
+
+    /// Only index peptides from a digest (trypsin, lys-c, glu-c, nop:<RULE>, semi:<RULE>, cleave:<AFTER>[/<NOT_BEFORE>])
+    #[arg(long, value_name = "RULE")]
+    pub digest: Option<String>,
+
+    /// Characters whose long runs are skipped when sorting ("*" for any repeated character)
+    #[arg(long, value_name = "CHARS")]
+    pub filler: Option<String>,
+
+    /// Minimum length of a run of filler characters
+    #[arg(long, value_name = "LEN", default_value = "1000", requires = "filler")]
+    pub min_filler_run: usize,
+
+    /// Split the index into shards with texts of at most this length
+    /// and write a manifest of the shards as the output
+    #[arg(long, value_name = "LEN")]
+    pub shard_len: Option<usize>,
+
+    /// Write statistics about the build as JSON to this file
+    #[arg(long, value_name = "FILE", conflicts_with = "shard_len")]
+    pub stats: Option<String>,
 }
 
 #[derive(Debug, Parser)]
@@ -139,6 +272,10 @@ pub struct ExtractArgs {
     #[arg(short, long, conflicts_with = "low_memory")]
     pub very_low_memory: bool,
 
+    /// Skip characters known to match with LCP-LR arrays
+    #[arg(long)]
+    pub lcp_lr: bool,
+
     /// Prefix length
     #[arg(short, long, value_name = "PREFIX_LEN")]
     pub prefix_len: Option<usize>,
@@ -160,6 +297,38 @@ pub struct ExtractArgs {
     pub query: Vec<String>,
 }
 
+#[derive(Debug, Parser)]
+#[command(about, alias = "km")]
+pub struct KmersArgs {
+    /// Output
+    #[arg(short, long, value_name = "OUT")]
+    pub output: Option<String>,
+
+    /// K-mer length
+    #[arg(short, long, value_name = "K")]
+    pub kmer_len: usize,
+
+    /// Only report the most frequent k-mers
+    #[arg(short = 'n', long, value_name = "NUM", conflicts_with = "histogram")]
+    pub top: Option<usize>,
+
+    /// Minimum count of a k-mer
+    #[arg(short = 'c', long, value_name = "COUNT", default_value = "1")]
+    pub min_count: usize,
+
+    /// Report the number of k-mers with each count
+    #[arg(short = 'H', long)]
+    pub histogram: bool,
+
+    /// Very low memory
+    #[arg(short, long)]
+    pub very_low_memory: bool,
+
+    /// Sufr file
+    #[arg(value_name = "SUFR")]
+    pub file: String,
+}
+
 #[derive(Debug, Parser)]
 #[command(about, alias = "ls")]
 pub struct ListArgs {
@@ -219,6 +388,10 @@ pub struct CountArgs {
     #[arg(short, long, conflicts_with = "low_memory")]
     pub very_low_memory: bool,
 
+    /// Skip characters known to match with LCP-LR arrays
+    #[arg(long)]
+    pub lcp_lr: bool,
+
     /// Sufr file
     #[arg(value_name = "SUFR")]
     pub file: String,
@@ -247,10 +420,18 @@ pub struct LocateArgs {
     #[arg(short, long, conflicts_with = "low_memory")]
     pub very_low_memory: bool,
 
+    /// Skip characters known to match with LCP-LR arrays
+    #[arg(long)]
+    pub lcp_lr: bool,
+
     /// Show absolute position in text
     #[arg(short, long)]
     pub abs: bool,
 
+    /// Show only the sequences containing each query with their counts
+    #[arg(short, long, conflicts_with = "abs")]
+    pub docs_only: bool,
+
     /// Sufr file
     #[arg(value_name = "SUFR")]
     pub file: String,
@@ -260,6 +441,158 @@ pub struct LocateArgs {
     pub query: Vec<String>,
 }
 
+#[derive(Debug, Parser)]
+#[command(about, alias = "mp")]
+pub struct MapPeptidesArgs {
+    /// Output
+    #[arg(short, long, value_name = "OUT")]
+    pub output: Option<String>,
+
+    /// Maximum query length
+    #[arg(short, long, value_name = "LEN")]
+    pub max_query_len: Option<usize>,
+
+    /// Low memory
+    #[arg(short, long)]
+    pub low_memory: bool,
+
+    /// Very low memory
+    #[arg(short, long, conflicts_with = "low_memory")]
+    pub very_low_memory: bool,
+
+    /// Name or number of the column of peptides in tab-delimited files
+    #[arg(short, long, value_name = "COL")]
+    pub column: Option<String>,
+
+    /// Sufr file
+    #[arg(value_name = "SUFR")]
+    pub file: String,
+
+    /// Peptide or file of peptides (one per line, tab-delimited, or mzIdentML)
+    #[arg(value_name = "PEPTIDE", required = true)]
+    pub peptides: Vec<String>,
+}
+
+#[derive(Debug, Parser)]
+#[command(about, alias = "ms")]
+pub struct MatchingStatsArgs {
+    /// Output
+    #[arg(short, long, value_name = "OUT")]
+    pub output: Option<String>,
+
+    /// Maximum query length
+    #[arg(short, long, value_name = "LEN")]
+    pub max_query_len: Option<usize>,
+
+    /// Low memory
+    #[arg(short, long)]
+    pub low_memory: bool,
+
+    /// Very low memory
+    #[arg(short, long, conflicts_with = "low_memory")]
+    pub very_low_memory: bool,
+
+    /// Show the ranks of the suffixes matching each position
+    #[arg(short = 'r', long)]
+    pub show_ranks: bool,
+
+    /// Sufr file
+    #[arg(value_name = "SUFR")]
+    pub file: String,
+
+    /// Query sequence or FASTA/FASTQ file
+    #[arg(value_name = "QUERY", required = true)]
+    pub query: Vec<String>,
+}
+
+#[derive(Debug, Parser)]
+#[command(about, alias = "me")]
+pub struct MemsArgs {
+    /// Output
+    #[arg(short, long, value_name = "OUT")]
+    pub output: Option<String>,
+
+    /// Minimum match length
+    #[arg(short = 'L', long, value_name = "LEN", default_value = "20")]
+    pub min_len: usize,
+
+    /// Only report maximal unique matches (MUMs)
+    #[arg(short, long)]
+    pub unique: bool,
+
+    /// Very low memory
+    #[arg(short, long)]
+    pub very_low_memory: bool,
+
+    /// Sufr file
+    #[arg(value_name = "SUFR")]
+    pub file: String,
+
+    /// Query sequence or FASTA/FASTQ file
+    #[arg(value_name = "QUERY", required = true)]
+    pub query: Vec<String>,
+}
+
+#[derive(Debug, Parser)]
+#[command(about, alias = "rm")]
+pub struct RemoveArgs {
+    /// Sufr file
+    #[arg(value_name = "SUFR")]
+    pub file: String,
+
+    /// Names of the sequences to remove
+    #[arg(value_name = "NAME", required = true)]
+    pub names: Vec<String>,
+}
+
+#[derive(Debug, Parser)]
+#[command(about, alias = "re")]
+pub struct RepeatsArgs {
+    /// Output
+    #[arg(short, long, value_name = "OUT")]
+    pub output: Option<String>,
+
+    /// Minimum repeat length
+    #[arg(short = 'L', long, value_name = "LEN", default_value = "20")]
+    pub min_len: usize,
+
+    /// Only report the longest repeats
+    #[arg(short = 'n', long, value_name = "NUM")]
+    pub top: Option<usize>,
+
+    /// Only report supermaximal repeats
+    #[arg(short, long)]
+    pub supermaximal: bool,
+
+    /// Sufr file
+    #[arg(value_name = "SUFR")]
+    pub file: String,
+}
+
+#[derive(Debug, Parser)]
+#[command(about, alias = "st")]
+pub struct StatsArgs {
+    /// Output
+    #[arg(short, long, value_name = "OUT")]
+    pub output: Option<String>,
+
+    /// Longest substrings to count by length
+    #[arg(short = 'L', long, value_name = "LEN", default_value = "10")]
+    pub max_len: usize,
+
+    /// Minimum length of a repeat
+    #[arg(short = 'r', long, value_name = "LEN", default_value = "20")]
+    pub min_repeat_len: usize,
+
+    /// Output JSON
+    #[arg(short, long)]
+    pub json: bool,
+
+    /// Sufr file
+    #[arg(value_name = "SUFR")]
+    pub file: String,
+}
+
 #[derive(Debug, Parser)]
 #[command(about, alias = "su")]
 pub struct SummarizeArgs {
@@ -288,9 +621,135 @@ impl ValueEnum for LogLevel {
     }
 }
 
+// --------------------------------------------------
+pub fn append(args: &AppendArgs) -> Result<()> {
+    // Read sequence input
+    let now = Instant::now();
+    let seq_data = read_sequence_file(Path::new(&args.input), SEQUENCE_DELIMITER)?;
+    let num_fmt = NumberFormat::new();
+    info!(
+        "Read input of len {} in {:?}",
+        num_fmt.format(",.0", seq_data.seq.len() as f64),
+        now.elapsed()
+    );
+
+    let outfile = match &args.output {
+        Some(output) => {
+            fs::copy(&args.file, output).map_err(|e| anyhow!("{output}: {e}"))?;
+            output.clone()
+        }
+        _ => args.file.clone(),
+    };
+
+    let now = Instant::now();
+    let num_sequences = seq_data.sequence_names.len();
+    let mut suffix_array = SuffixArray::read(&outfile, true)?;
+    suffix_array.append(AppendOptions {
+        text: seq_data.seq,
+        sequence_starts: seq_data.start_positions,
+        sequence_names: seq_data.sequence_names,
+    })?;
+    info!(
+        "Appended {num_sequences} sequence{} to '{outfile}' in {:?}",
+        if num_sequences == 1 { "" } else { "s" },
+        now.elapsed()
+    );
+
+    Ok(())
+}
+
+// --------------------------------------------------
+pub fn common(args: &CommonArgs) -> Result<()> {
+    let mut suffix_array = SuffixArray::read(&args.file, args.very_low_memory)?;
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(out_name) => Box::new(File::create(out_name)?),
+        _ => Box::new(io::stdout()),
+    };
+
+    // Compare the sequences of another file or a subset of this one
+    let (mut sequences, exclude) = match &args.other {
+        Some(other) => (SuffixArray::read(other, true)?.sequences()?, vec![]),
+        _ if args.sequences.is_empty() => {
+            bail!("Provide OTHER or the names of the sequences to compare")
+        }
+        _ => (suffix_array.sequences()?, args.sequences.clone()),
+    };
+    if !args.sequences.is_empty() {
+        let missing: Vec<_> = args
+            .sequences
+            .iter()
+            .filter(|name| !sequences.iter().any(|(seq_name, _)| &seq_name == name))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            bail!("Unknown sequence name(s): {}", missing.join(", "))
+        }
+        sequences.retain(|(name, _)| args.sequences.contains(name));
+    }
+
+    let both_strands = suffix_array.metadata()?.both_strands;
+    let num_sequences = sequences.len();
+    let now = Instant::now();
+    let common_args = CommonOptions {
+        sequences,
+        exclude,
+        min_len: args.min_len,
+        max_results: args.top,
+    };
+
+    for common in suffix_array.common(common_args)? {
+        let sequence_position = if both_strands {
+            format!("{}{}", common.sequence_position, common.strand)
+        } else {
+            common.sequence_position.to_string()
+        };
+        writeln!(
+            output,
+            "{} {} {} {sequence_position} {} {}",
+            common.query_name,
+            common.query_position,
+            common.sequence_name,
+            common.length,
+            common.sequence
+        )?;
+    }
+
+    info!(
+        "Compared {num_sequences} sequence{} in {:?}",
+        if num_sequences == 1 { "" } else { "s" },
+        now.elapsed()
+    );
+
+    Ok(())
+}
+
+// --------------------------------------------------
+pub fn compact(args: &CompactArgs) -> Result<()> {
+    let outfile = match &args.output {
+        Some(output) => {
+            fs::copy(&args.file, output).map_err(|e| anyhow!("{output}: {e}"))?;
+            output.clone()
+        }
+        _ => args.file.clone(),
+    };
+
+    let now = Instant::now();
+    let mut suffix_array = SuffixArray::read(&outfile, true)?;
+    let num_removed = suffix_array.metadata()?.removed_sequences.len();
+    suffix_array.compact(args.num_partitions)?;
+    info!(
+        "Compacted {num_removed} removed sequence{} from '{outfile}' in {:?}",
+        if num_removed == 1 { "" } else { "s" },
+        now.elapsed()
+    );
+
+    Ok(())
+}
+
 // --------------------------------------------------
 pub fn count(args: &CountArgs) -> Result<()> {
     let mut suffix_array = SuffixArray::read(&args.file, args.very_low_memory)?;
+    suffix_array.use_lcp_lr(args.lcp_lr);
     let mut output: Box<dyn Write> = match &args.output {
         Some(out_name) => Box::new(File::create(out_name)?),
         _ => Box::new(io::stdout()),
@@ -332,11 +791,16 @@ pub fn create(args: &CreateArgs) -> Result<()> {
     );
 
     let outfile = &args.output.clone().unwrap_or(format!(
-        "{}.sufr",
+        "{}.{}",
         PathBuf::from(&args.input)
             .file_stem()
             .unwrap_or(OsStr::new("out"))
-            .to_string_lossy()
+            .to_string_lossy(),
+        if args.shard_len.is_some() {
+            "shards"
+        } else {
+            "sufr"
+        }
     ));
 
     let builder_args = SufrBuilderArgs {
@@ -350,12 +814,39 @@ pub fn create(args: &CreateArgs) -> Result<()> {
         sequence_starts: seq_data.start_positions.into_iter().collect(),
         sequence_names: seq_data.sequence_names,
         num_partitions: args.num_partitions,
-        seed_mask: args.seed_mask.clone(),
+        seed_masks: args.seed_mask.clone(),
         random_seed: args.random_seed,
+        both_strands: args.both_strands,
+        alphabet: args.alphabet.as_deref().map(Alphabet::new).transpose()?,
+        residue_map: args
+            .residue_map
+            .as_deref()
+            .map(ResidueMap::new)
+            .transpose()?,
+        suffix_start_filter: args
+            .digest
+            .as_deref()
+            .map(SuffixStartFilter::new)
+            .transpose()?,
+        filler_runs: args
+            .filler
+            .as_deref()
+            .map(|chars| FillerRuns::new(chars, args.min_filler_run))
+            .transpose()?,
     };
 
     let now = Instant::now();
-    let path = SuffixArray::write(builder_args)?;
+    let path = match args.shard_len {
+        Some(shard_len) => ShardedSuffixArray::write(builder_args, shard_len)?,
+        _ => {
+            let (path, stats) = SuffixArray::write_with_stats(builder_args)?;
+            if let Some(stats_file) = &args.stats {
+                fs::write(stats_file, stats.to_json()?)
+                    .map_err(|e| anyhow!("{stats_file}: {e}"))?;
+            }
+            path
+        }
+    };
     let meta = fs::metadata(&path)?;
     let bytes_written = meta.len();
 
@@ -373,6 +864,7 @@ pub fn create(args: &CreateArgs) -> Result<()> {
 // --------------------------------------------------
 pub fn extract(args: &ExtractArgs) -> Result<()> {
     let mut suffix_array = SuffixArray::read(&args.file, args.very_low_memory)?;
+    suffix_array.use_lcp_lr(args.lcp_lr);
     let queries = parse_locate_queries(&args.query)?;
     let now = Instant::now();
     let extract_args = ExtractOptions {
@@ -391,6 +883,7 @@ pub fn extract(args: &ExtractArgs) -> Result<()> {
         Some(out_name) => Box::new(File::create(out_name)?),
         _ => Box::new(io::stdout()),
     };
+    let both_strands = suffix_array.metadata()?.both_strands;
     for res in suffix_array.extract(extract_args)? {
         if res.sequences.is_empty() {
             eprintln!("{} not found", res.query);
@@ -398,14 +891,19 @@ pub fn extract(args: &ExtractArgs) -> Result<()> {
             for seq in res.sequences {
                 writeln!(
                     output,
-                    ">{}:{}-{} {} {}\n{}",
+                    ">{}:{}-{}{} {} {}\n{}",
                     seq.sequence_name,
                     seq.sequence_range.start,
                     seq.sequence_range.end,
+                    if both_strands {
+                        format!("({})", seq.strand)
+                    } else {
+                        "".to_string()
+                    },
                     res.query,
                     seq.suffix_offset,
                     suffix_array.string_at(
-                        seq.sequence_start + seq.sequence_range.start,
+                        seq.suffix - seq.suffix_offset,
                         Some(seq.sequence_range.end - seq.sequence_range.start),
                     )?
                 )?;
@@ -417,6 +915,55 @@ pub fn extract(args: &ExtractArgs) -> Result<()> {
     Ok(())
 }
 
+// --------------------------------------------------
+pub fn kmers(args: &KmersArgs) -> Result<()> {
+    let mut suffix_array = SuffixArray::read(&args.file, args.very_low_memory)?;
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(out_name) => Box::new(File::create(out_name)?),
+        _ => Box::new(io::stdout()),
+    };
+
+    let now = Instant::now();
+    let mut num_kmers = 0;
+    let mut histogram: BTreeMap<usize, usize> = BTreeMap::new();
+
+    // The most frequent k-mers, where the heap top is the least of them
+    let mut top: BinaryHeap<(Reverse<usize>, String)> = BinaryHeap::new();
+    for kmer in suffix_array.kmers(args.kmer_len)? {
+        let kmer = kmer?;
+        if kmer.count < args.min_count {
+            continue;
+        }
+        num_kmers += 1;
+
+        if args.histogram {
+            *histogram.entry(kmer.count).or_default() += 1;
+        } else if let Some(max) = args.top {
+            top.push((Reverse(kmer.count), kmer.kmer));
+            if top.len() > max {
+                top.pop();
+            }
+        } else {
+            writeln!(output, "{} {}", kmer.kmer, kmer.count)?;
+        }
+    }
+
+    for (count, num) in histogram {
+        writeln!(output, "{count} {num}")?;
+    }
+    for (Reverse(count), kmer) in top.into_sorted_vec() {
+        writeln!(output, "{kmer} {count}")?;
+    }
+
+    info!(
+        "Counted {num_kmers} distinct {}-mers in {:?}",
+        args.kmer_len,
+        now.elapsed()
+    );
+
+    Ok(())
+}
+
 // --------------------------------------------------
 pub fn list(args: &ListArgs) -> Result<()> {
     let mut suffix_array = SuffixArray::read(&args.file, args.very_low_memory)?;
@@ -468,11 +1015,13 @@ fn parse_locate_queries(queries: &[String]) -> Result<Vec<String>> {
 // --------------------------------------------------
 pub fn locate(args: &LocateArgs) -> Result<()> {
     let mut suffix_array = SuffixArray::read(&args.file, args.very_low_memory)?;
+    suffix_array.use_lcp_lr(args.lcp_lr);
     let mut output: Box<dyn Write> = match &args.output {
         Some(out_name) => Box::new(File::create(out_name)?),
         _ => Box::new(io::stdout()),
     };
 
+    let both_strands = suffix_array.metadata()?.both_strands;
     let queries = parse_locate_queries(&args.query)?;
     let num_queries = queries.len();
     let now = Instant::now();
@@ -486,6 +1035,24 @@ pub fn locate(args: &LocateArgs) -> Result<()> {
         },
     };
 
+    if args.docs_only {
+        for res in suffix_array.list_documents(loc_args)? {
+            if res.documents.is_empty() {
+                eprintln!("{} not found", res.query);
+                continue;
+            }
+
+            writeln!(output, "{}", res.query)?;
+            for doc in res.documents {
+                writeln!(output, "{} {}", doc.sequence_name, doc.count)?;
+            }
+            writeln!(output, "//")?;
+        }
+
+        info!("Locate of {} finished in {:?}", num_queries, now.elapsed());
+        return Ok(());
+    }
+
     for mut res in suffix_array.locate(loc_args)? {
         if res.positions.is_empty() {
             eprintln!("{} not found", res.query);
@@ -523,7 +1090,11 @@ pub fn locate(args: &LocateArgs) -> Result<()> {
                     prev_seq = pos.sequence_name;
                     buffer = vec![];
                 }
-                buffer.push(pos.sequence_position.to_string());
+                buffer.push(if both_strands {
+                    format!("{}{}", pos.sequence_position, pos.strand)
+                } else {
+                    pos.sequence_position.to_string()
+                });
             }
 
             if !buffer.is_empty() {
@@ -539,15 +1110,292 @@ pub fn locate(args: &LocateArgs) -> Result<()> {
     Ok(())
 }
 
+// --------------------------------------------------
+// Read the peptides from the arguments, which may be files of one
+// peptide per line, a column of a tab-delimited file, or the peptide
+// sequences of an mzIdentML file. Each distinct peptide is kept once.
+fn parse_peptides(values: &[String], column: Option<&str>) -> Result<Vec<String>> {
+    let mzid_peptide =
+        Regex::new(r"<PeptideSequence>\s*([A-Za-z]+)\s*</PeptideSequence>").unwrap();
+    let mut peptides = vec![];
+    for value in values {
+        let path = Path::new(&value);
+        if !path.exists() {
+            peptides.push(value.to_string());
+            continue;
+        }
+
+        let contents = fs::read_to_string(path)?;
+        let is_mzid = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("mzid"));
+        if is_mzid {
+            peptides.extend(
+                mzid_peptide
+                    .captures_iter(&contents)
+                    .map(|caps| caps[1].to_string()),
+            );
+        } else if let Some(column) = column {
+            let mut lines = contents.lines();
+            let headers: Vec<&str> = lines.next().unwrap_or("").split('\t').collect();
+            let col_num = match headers.iter().position(|header| header == &column) {
+                Some(col_num) => col_num,
+                _ => match parse_index(column) {
+                    Ok(num) if num > 0 && num <= headers.len() => num - 1,
+                    _ => bail!(r#"Column "{column}" not found in "{value}""#),
+                },
+            };
+            peptides.extend(
+                lines.filter_map(|line| {
+                    line.split('\t').nth(col_num).map(str::to_string)
+                }),
+            );
+        } else {
+            peptides.extend(contents.split_whitespace().map(str::to_string));
+        }
+        info!("Read peptides from '{value}'");
+    }
+
+    let mut seen = HashSet::new();
+    Ok(peptides
+        .into_iter()
+        .map(|peptide| peptide.trim().to_uppercase())
+        .filter(|peptide| !peptide.is_empty() && seen.insert(peptide.clone()))
+        .collect())
+}
+
+// --------------------------------------------------
+pub fn map_peptides(args: &MapPeptidesArgs) -> Result<()> {
+    let mut suffix_array = SuffixArray::read(&args.file, args.very_low_memory)?;
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(out_name) => Box::new(File::create(out_name)?),
+        _ => Box::new(io::stdout()),
+    };
+
+    let peptides = parse_peptides(&args.peptides, args.column.as_deref())?;
+    let num_peptides = peptides.len();
+    let now = Instant::now();
+    let map_args = MapPeptidesOptions {
+        peptides,
+        max_query_len: args.max_query_len,
+        low_memory: if args.very_low_memory {
+            true
+        } else {
+            args.low_memory
+        },
+    };
+
+    for mapping in suffix_array.map_peptides(map_args)? {
+        if mapping.hits.is_empty() {
+            eprintln!("{} not found", mapping.peptide);
+            continue;
+        }
+
+        for hit in mapping.hits {
+            writeln!(
+                output,
+                "{} {} {} {} {} {}",
+                mapping.peptide,
+                hit.sequence_name,
+                hit.sequence_position,
+                hit.preceding,
+                hit.following,
+                hit.class
+            )?;
+        }
+    }
+
+    info!(
+        "Mapped {num_peptides} peptide{} in {:?}",
+        if num_peptides == 1 { "" } else { "s" },
+        now.elapsed()
+    );
+
+    Ok(())
+}
+
+// --------------------------------------------------
+pub fn matching_stats(args: &MatchingStatsArgs) -> Result<()> {
+    let mut suffix_array = SuffixArray::read(&args.file, args.very_low_memory)?;
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(out_name) => Box::new(File::create(out_name)?),
+        _ => Box::new(io::stdout()),
+    };
+
+    let (names, queries): (Vec<_>, Vec<_>) =
+        parse_sequence_queries(&args.query)?.into_iter().unzip();
+    let num_queries = queries.len();
+    let now = Instant::now();
+    let ms_args = MatchingStatisticsOptions {
+        queries,
+        max_query_len: args.max_query_len,
+        low_memory: if args.very_low_memory {
+            true
+        } else {
+            args.low_memory
+        },
+    };
+
+    for res in suffix_array.matching_statistics(ms_args)? {
+        writeln!(output, ">{}", names[res.query_num])?;
+        if args.show_ranks {
+            for (pos, stat) in res.statistics.iter().enumerate() {
+                writeln!(
+                    output,
+                    "{pos} {} {}-{}",
+                    stat.length, stat.ranks.start, stat.ranks.end
+                )?;
+            }
+        } else {
+            writeln!(
+                output,
+                "{}",
+                res.statistics
+                    .iter()
+                    .map(|stat| stat.length.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            )?;
+        }
+    }
+
+    info!(
+        "Matching statistics of {num_queries} finished in {:?}",
+        now.elapsed()
+    );
+
+    Ok(())
+}
+
+// --------------------------------------------------
+pub fn mems(args: &MemsArgs) -> Result<()> {
+    let mut suffix_array = SuffixArray::read(&args.file, args.very_low_memory)?;
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(out_name) => Box::new(File::create(out_name)?),
+        _ => Box::new(io::stdout()),
+    };
+
+    let both_strands = suffix_array.metadata()?.both_strands;
+    let (names, queries): (Vec<_>, Vec<_>) =
+        parse_sequence_queries(&args.query)?.into_iter().unzip();
+    let num_queries = queries.len();
+    let now = Instant::now();
+    let mems_args = MemsOptions {
+        queries,
+        min_len: args.min_len,
+        unique: args.unique,
+    };
+
+    for res in suffix_array.mems(mems_args)? {
+        let name = &names[res.query_num];
+        if res.mems.is_empty() {
+            eprintln!("{name} not found");
+            continue;
+        }
+
+        for mem in res.mems {
+            let sequence_position = if both_strands {
+                format!("{}{}", mem.sequence_position, mem.strand)
+            } else {
+                mem.sequence_position.to_string()
+            };
+            writeln!(
+                output,
+                "{name} {} {} {sequence_position} {}",
+                mem.query_position, mem.sequence_name, mem.length
+            )?;
+        }
+    }
+
+    info!("MEMs of {num_queries} finished in {:?}", now.elapsed());
+
+    Ok(())
+}
+
+// --------------------------------------------------
+pub fn repeats(args: &RepeatsArgs) -> Result<()> {
+    let mut suffix_array = SuffixArray::read(&args.file, true)?;
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(out_name) => Box::new(File::create(out_name)?),
+        _ => Box::new(io::stdout()),
+    };
+
+    let both_strands = suffix_array.metadata()?.both_strands;
+    let repeats = suffix_array.repeats(RepeatsOptions {
+        min_len: args.min_len,
+        supermaximal: args.supermaximal,
+        max_repeats: args.top,
+    })?;
+
+    for repeat in repeats {
+        writeln!(output, "{} {}", repeat.sequence, repeat.length)?;
+
+        // The positions are in text order, so each sequence is contiguous
+        let mut prev_seq = "".to_string();
+        let mut buffer = vec![];
+        for pos in repeat.positions {
+            if pos.sequence_name != prev_seq {
+                if !buffer.is_empty() {
+                    writeln!(output, "{prev_seq} {}", buffer.join(","))?;
+                }
+
+                prev_seq = pos.sequence_name;
+                buffer = vec![];
+            }
+            buffer.push(if both_strands {
+                format!("{}{}", pos.sequence_position, pos.strand)
+            } else {
+                pos.sequence_position.to_string()
+            });
+        }
+
+        if !buffer.is_empty() {
+            writeln!(output, "{prev_seq} {}", buffer.join(","))?;
+        }
+
+        writeln!(output, "//")?;
+    }
+
+    Ok(())
+}
+
+// --------------------------------------------------
+// Read named queries from FASTA/FASTQ files, or use a query string
+// as its own name
+fn parse_sequence_queries(queries: &[String]) -> Result<Vec<(String, String)>> {
+    let mut ret = vec![];
+    for query in queries {
+        if Path::new(&query).exists() {
+            let seq_data = read_sequence_file(Path::new(&query), SEQUENCE_DELIMITER)?;
+            let num_seqs = seq_data.start_positions.len();
+            for (i, name) in seq_data.sequence_names.into_iter().enumerate() {
+                let start = seq_data.start_positions[i];
+                let end = seq_data
+                    .start_positions
+                    .get(i + 1)
+                    .map_or(seq_data.seq.len(), |next| *next)
+                    - 1;
+                ret.push((name, String::from_utf8(seq_data.seq[start..end].to_vec())?));
+            }
+            info!("Read {num_seqs} queries from '{query}'");
+        } else {
+            ret.push((query.to_string(), query.to_string()));
+        }
+    }
+
+    Ok(ret)
+}
+
 // --------------------------------------------------
 // Parse an index from a string representation of an integer.
 // Ensures the number does not start with '+'.
 fn parse_index(input: &str) -> Result<usize> {
     let value_error = || anyhow!(r#"illegal list value: "{input}""#);
-    input
-        .starts_with('+')
-        .then(|| Err(value_error()))
-        .unwrap_or_else(|| input.parse::<usize>().map_err(|_| value_error()))
+    if input.starts_with('+') {
+        Err(value_error())
+    } else {
+        input.parse::<usize>().map_err(|_| value_error())
+    }
 }
 
 // --------------------------------------------------
@@ -573,6 +1421,81 @@ fn parse_pos(range: &str) -> Result<PositionList> {
         .collect::<Result<_, _>>()
 }
 
+// --------------------------------------------------
+pub fn remove(args: &RemoveArgs) -> Result<()> {
+    let mut suffix_array = SuffixArray::read(&args.file, true)?;
+    suffix_array.remove_sequences(&args.names)?;
+    info!(
+        "Removed {} sequence{} from '{}'",
+        args.names.len(),
+        if args.names.len() == 1 { "" } else { "s" },
+        args.file
+    );
+
+    Ok(())
+}
+
+// --------------------------------------------------
+pub fn stats(args: &StatsArgs) -> Result<()> {
+    let mut suffix_array = SuffixArray::read(&args.file, true)?;
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(out_name) => Box::new(File::create(out_name)?),
+        _ => Box::new(io::stdout()),
+    };
+
+    let stats = suffix_array.complexity_stats(ComplexityOptions {
+        max_len: args.max_len,
+        min_repeat_len: args.min_repeat_len,
+    })?;
+    if args.json {
+        writeln!(output, "{}", stats.to_json()?)?;
+        return Ok(());
+    }
+
+    let num_fmt = NumberFormat::new();
+    let mut rows = vec![vec![
+        "Positions".to_string(),
+        num_fmt.format(",.0", stats.num_positions as f64),
+    ]];
+    rows.push(vec![
+        "Distinct substrings".to_string(),
+        num_fmt.format(",.0", stats.distinct_substrings as f64),
+    ]);
+    for (i, num) in stats.distinct_by_len.iter().enumerate() {
+        rows.push(vec![
+            format!("Distinct {}-mers", i + 1),
+            num_fmt.format(",.0", *num as f64),
+        ]);
+    }
+    rows.push(vec![
+        "Repeat coverage".to_string(),
+        format!(
+            "{} ({:.2}%)",
+            num_fmt.format(",.0", stats.repeat_positions as f64),
+            stats.repeat_coverage * 100.
+        ),
+    ]);
+    writeln!(output, "{}", Table::from_iter(rows))?;
+
+    let mut rows = vec![vec![
+        "Sequence".to_string(),
+        "Length".to_string(),
+        "Distinct substrings".to_string(),
+        "Complexity".to_string(),
+    ]];
+    for seq in stats.sequences {
+        rows.push(vec![
+            seq.name,
+            num_fmt.format(",.0", seq.length as f64),
+            num_fmt.format(",.0", seq.distinct_substrings as f64),
+            format!("{:.4}", seq.linguistic_complexity),
+        ]);
+    }
+    writeln!(output, "{}", Table::from_iter(rows))?;
+
+    Ok(())
+}
+
 // --------------------------------------------------
 pub fn summarize(args: &SummarizeArgs) -> Result<()> {
     let suffix_array = SuffixArray::read(&args.file, true)?;
@@ -600,6 +1523,24 @@ pub fn summarize(args: &SummarizeArgs) -> Result<()> {
         "Ignore Softmask".to_string(),
         meta.ignore_softmask.to_string(),
     ]);
+    rows.push(vec![
+        "Both Strands".to_string(),
+        meta.both_strands.to_string(),
+    ]);
+    rows.push(vec![
+        "Alphabet".to_string(),
+        meta.alphabet.map_or("none".to_string(), |a| a.to_string()),
+    ]);
+    rows.push(vec![
+        "Residue Map".to_string(),
+        meta.residue_map
+            .map_or("none".to_string(), |m| m.to_string()),
+    ]);
+    rows.push(vec![
+        "Digest".to_string(),
+        meta.suffix_start_filter
+            .map_or("none".to_string(), |f| f.to_string()),
+    ]);
     rows.push(vec![
         "Text Length".to_string(),
         num_fmt.format(",.0", meta.text_len as f64),
@@ -611,7 +1552,20 @@ pub fn summarize(args: &SummarizeArgs) -> Result<()> {
 
     match meta.sort_type {
         SuffixSortType::Mask(seed_mask) => {
-            rows.push(vec!["Seed mask".to_string(), seed_mask.mask])
+            rows.push(vec![
+                "Seed mask".to_string(),
+                meta.seed_masks
+                    .into_iter()
+                    .map(|seed_mask| seed_mask.mask)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ]);
+            if seed_mask.max_query_len > 0 {
+                rows.push(vec![
+                    "Max query len".to_string(),
+                    num_fmt.format(",.0", seed_mask.max_query_len as f64),
+                ]);
+            }
         }
         SuffixSortType::MaxQueryLen(max_query_len) => rows.push(vec![
             "Max query len".to_string(),
@@ -638,6 +1592,47 @@ pub fn summarize(args: &SummarizeArgs) -> Result<()> {
         "Sequence names".to_string(),
         textwrap::wrap(&seq_names, 40).join("\n"),
     ]);
+    if !meta.removed_sequences.is_empty() {
+        let removed = meta.removed_sequences.join(", ");
+        rows.push(vec![
+            "Removed sequences".to_string(),
+            textwrap::wrap(&removed, 40).join("\n"),
+        ]);
+    }
+    if let Some(summary) = meta.build_summary {
+        rows.push(vec![
+            "Partitions".to_string(),
+            format!(
+                "{} (skew {:.2})",
+                num_fmt.format(",.0", summary.num_partitions as f64),
+                summary.partition_skew
+            ),
+        ]);
+        rows.push(vec![
+            "Filtered suffixes".to_string(),
+            num_fmt.format(",.0", summary.num_filtered_suffixes as f64),
+        ]);
+        rows.push(vec![
+            "Softmasked".to_string(),
+            num_fmt.format(",.0", summary.num_softmasked as f64),
+        ]);
+        rows.push(vec![
+            "Filler runs".to_string(),
+            format!(
+                "{} (total length {})",
+                num_fmt.format(",.0", summary.num_filler_runs as f64),
+                num_fmt.format(",.0", summary.filler_len as f64)
+            ),
+        ]);
+        rows.push(vec![
+            "Max LCP".to_string(),
+            num_fmt.format(",.0", summary.max_lcp as f64),
+        ]);
+        rows.push(vec![
+            "Mean LCP".to_string(),
+            format!("{:.2}", summary.mean_lcp),
+        ]);
+    }
 
     let table = Table::from_iter(rows);
     println!("{table}");
@@ -1,8 +1,8 @@
 use anyhow::Result;
 use clap::Parser;
 use log::info;
+use std::{fs::File, io::BufWriter};
 use sufr::{Cli, Command, LogLevel};
-use std::{io::BufWriter, fs::File};
 
 // --------------------------------------------------
 fn main() {
@@ -22,9 +22,9 @@ fn run(args: Cli) -> Result<()> {
         })
         .target(match args.log_file {
             // Optional log file, default to STDOUT
-            Some(ref filename) => env_logger::Target::Pipe(Box::new(
-                BufWriter::new(File::create(filename)?),
-            )),
+            Some(ref filename) => env_logger::Target::Pipe(Box::new(BufWriter::new(
+                File::create(filename)?,
+            ))),
             _ => env_logger::Target::Stdout,
         })
         .init();
@@ -45,6 +45,18 @@ fn run(args: Cli) -> Result<()> {
         //    sufr::check(args)?;
         //    Ok(())
         //}
+        Some(Command::Append(args)) => {
+            sufr::append(args)?;
+            Ok(())
+        }
+        Some(Command::Common(args)) => {
+            sufr::common(args)?;
+            Ok(())
+        }
+        Some(Command::Compact(args)) => {
+            sufr::compact(args)?;
+            Ok(())
+        }
         Some(Command::Count(args)) => {
             sufr::count(args)?;
             Ok(())
@@ -57,6 +69,10 @@ fn run(args: Cli) -> Result<()> {
             sufr::extract(args)?;
             Ok(())
         }
+        Some(Command::Kmers(args)) => {
+            sufr::kmers(args)?;
+            Ok(())
+        }
         Some(Command::List(args)) => {
             sufr::list(args)?;
             Ok(())
@@ -65,6 +81,30 @@ fn run(args: Cli) -> Result<()> {
             sufr::locate(args)?;
             Ok(())
         }
+        Some(Command::MapPeptides(args)) => {
+            sufr::map_peptides(args)?;
+            Ok(())
+        }
+        Some(Command::MatchingStats(args)) => {
+            sufr::matching_stats(args)?;
+            Ok(())
+        }
+        Some(Command::Mems(args)) => {
+            sufr::mems(args)?;
+            Ok(())
+        }
+        Some(Command::Remove(args)) => {
+            sufr::remove(args)?;
+            Ok(())
+        }
+        Some(Command::Repeats(args)) => {
+            sufr::repeats(args)?;
+            Ok(())
+        }
+        Some(Command::Stats(args)) => {
+            sufr::stats(args)?;
+            Ok(())
+        }
         Some(Command::Summarize(args)) => {
             sufr::summarize(args)?;
             Ok(())
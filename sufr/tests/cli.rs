@@ -2,7 +2,7 @@ use anyhow::Result;
 use assert_cmd::Command;
 use libsufr::{
     sufr_file::SufrFile,
-    types::{SeedMask, OUTFILE_VERSION},
+    types::{Alphabet, SeedMask, OUTFILE_VERSION},
 };
 use pretty_assertions::assert_eq;
 use regex::Regex;
@@ -10,7 +10,7 @@ use std::{
     fs::{self, File},
     io::{BufRead, BufReader},
 };
-use tempfile::NamedTempFile;
+use tempfile::{NamedTempFile, TempDir};
 
 const PRG: &str = "sufr";
 const SEQ1: &str = "../data/inputs/1.fa";
@@ -21,6 +21,7 @@ const UNIPROT: &str = "../data/inputs/uniprot.fa";
 const SUFR1: &str = "../data/expected/1.sufr";
 const SUFR2: &str = "../data/expected/2.sufr";
 const SUFR3: &str = "../data/expected/3.sufr";
+const UNIPROT_SUFR: &str = "../data/expected/uniprot.sufr";
 
 struct CreateOptions {
     is_dna: bool,
@@ -1147,7 +1148,7 @@ fn summarize_sufr1() -> Result<()> {
     summarize(
         SUFR1,
         vec![
-            ("File Size", "172 bytes"),
+            ("File Size", "279 bytes"),
             ("File Version", &OUTFILE_VERSION.to_string()),
             ("DNA", "true"),
             ("Allow Ambiguity", "false"),
@@ -1159,12 +1160,20 @@ fn summarize_sufr1() -> Result<()> {
             ("Num sequences", "1"),
             ("Sequence starts", "0"),
             ("Sequence names", "1"),
+            ("Partitions", "2 (skew 1.11)"),
+            ("Filtered suffixes", "2"),
+            ("Max LCP", "4"),
+            ("Mean LCP", "1.25"),
         ],
     )
 }
 
 // --------------------------------------------------
-fn file_is_sorted(filename: &str, mask: Option<&str>) -> Result<()> {
+fn file_is_sorted(
+    filename: &str,
+    mask: Option<&str>,
+    max_query_len: Option<usize>,
+) -> Result<()> {
     // Create the sufr file
     let sufr_file = NamedTempFile::new()?;
     let sufr_path = &sufr_file.path().to_string_lossy();
@@ -1175,11 +1184,19 @@ fn file_is_sorted(filename: &str, mask: Option<&str>) -> Result<()> {
         filename.to_string(),
     ];
 
-    let seed_mask: Option<SeedMask> = mask.map(SeedMask::new).transpose()?;
+    let seed_mask: Option<SeedMask> = mask
+        .map(|mask| -> Result<SeedMask> {
+            Ok(SeedMask::new(mask)?.with_max_query_len(max_query_len.unwrap_or(0)))
+        })
+        .transpose()?;
     if let Some(mask) = &seed_mask {
         args.push("-s".to_string());
         args.push(mask.to_string());
     }
+    if let Some(len) = max_query_len {
+        args.push("-m".to_string());
+        args.push(len.to_string());
+    }
 
     let output = Command::cargo_bin(PRG)?.args(&args).output()?;
     assert!(output.status.success());
@@ -1226,23 +1243,880 @@ fn file_is_sorted(filename: &str, mask: Option<&str>) -> Result<()> {
 // --------------------------------------------------
 #[test]
 fn long_dna_is_sorted() -> Result<()> {
-    file_is_sorted(LONG, None)
+    file_is_sorted(LONG, None, None)
 }
 
 // --------------------------------------------------
 #[test]
 fn masked_long_dna_is_sorted() -> Result<()> {
-    file_is_sorted(LONG, Some("111010010100110111"))
+    file_is_sorted(LONG, Some("111010010100110111"), None)
 }
 
 // --------------------------------------------------
 #[test]
 fn uniprot_is_sorted() -> Result<()> {
-    file_is_sorted(UNIPROT, None)
+    file_is_sorted(UNIPROT, None, None)
 }
 
 // --------------------------------------------------
 #[test]
 fn masked_uniprot_is_sorted() -> Result<()> {
-    file_is_sorted(UNIPROT, Some("10111011"))
+    file_is_sorted(UNIPROT, Some("10111011"), None)
+}
+
+// --------------------------------------------------
+#[test]
+fn masked_max_query_len_long_dna_is_sorted() -> Result<()> {
+    // A repeated mask
+    file_is_sorted(LONG, Some("1101"), Some(40))?;
+
+    // A truncated mask
+    file_is_sorted(LONG, Some("111010010100110111"), Some(8))
+}
+
+// --------------------------------------------------
+#[test]
+fn masked_max_query_len_summarize() -> Result<()> {
+    let outfile = NamedTempFile::new()?;
+    let outpath = &outfile.path().to_string_lossy();
+    Command::cargo_bin(PRG)?
+        .args([
+            "create", "--dna", "-s", "101", "-m", "12", "-o", outpath, SEQ3,
+        ])
+        .assert()
+        .success();
+
+    summarize(outpath, vec![("Seed mask", "101"), ("Max query len", "12")])
+}
+
+// --------------------------------------------------
+#[test]
+fn both_strands_seq3() -> Result<()> {
+    let outfile = NamedTempFile::new()?;
+    let outpath = &outfile.path().to_string_lossy();
+    Command::cargo_bin(PRG)?
+        .args(["create", "--dna", "--both-strands", "-o", outpath, SEQ3])
+        .assert()
+        .success();
+
+    // "CCGTTG" only occurs as the reverse complement of "CAACGG"
+    let output = Command::cargo_bin(PRG)?
+        .args(["locate", outpath, "CCGTTG", "CAACGG"])
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+    let actual = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(actual, "CCGTTG\n1 18-\n//\nCAACGG\n1 18+\n//\n");
+
+    let output = Command::cargo_bin(PRG)?
+        .args(["extract", "-p", "2", "-s", "8", outpath, "CCGTTG"])
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+    let actual = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(actual, ">1:16-26(-) CCGTTG 2\nGCCCGTTGCA\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn both_strands_requires_dna() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["create", "--both-strands", SEQ3])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn alphabet_validates_text_and_queries() -> Result<()> {
+    // 1.fa has a "T," which is not RNA
+    let output = Command::cargo_bin(PRG)?
+        .args(["create", "--alphabet", "rna", SEQ1])
+        .output()
+        .expect("fail");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(stderr.contains("not in the rna alphabet"));
+
+    let outfile = NamedTempFile::new()?;
+    let outpath = &outfile.path().to_string_lossy();
+    Command::cargo_bin(PRG)?
+        .args(["create", "--alphabet", "protein", "-o", outpath, UNIPROT])
+        .assert()
+        .success();
+
+    let sufr_file: SufrFile<u32> = SufrFile::read(outpath, false)?;
+    assert_eq!(sufr_file.alphabet, Some(Alphabet::Protein));
+
+    let output = Command::cargo_bin(PRG)?
+        .args(["count", outpath, "MKL", "ACGT1"])
+        .output()
+        .expect("fail");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(stderr.contains("not in the protein alphabet"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn residue_map_uniprot() -> Result<()> {
+    let outfile = NamedTempFile::new()?;
+    let outpath = &outfile.path().to_string_lossy();
+    Command::cargo_bin(PRG)?
+        .args([
+            "create",
+            "--residue-map",
+            "murphy-10",
+            "-o",
+            outpath,
+            UNIPROT,
+        ])
+        .assert()
+        .success();
+
+    // Both queries map to "LKLL" and so match [LVIM][KR][LVIM][LVIM]
+    let output = Command::cargo_bin(PRG)?
+        .args(["count", outpath, "MKLV", "IRIM"])
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+    let actual = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(actual, "MKLV 106\nIRIM 106\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn digest_uniprot() -> Result<()> {
+    let outfile = NamedTempFile::new()?;
+    let outpath = &outfile.path().to_string_lossy();
+    Command::cargo_bin(PRG)?
+        .args(["create", "--digest", "trypsin", "-o", outpath, UNIPROT])
+        .assert()
+        .success();
+
+    // Only suffixes at protein N-termini and tryptic sites are indexed
+    let output = Command::cargo_bin(PRG)?
+        .args(["count", outpath, "M", "K"])
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+    let actual = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(actual, "M 458\nK 551\n");
+
+    summarize(outpath, vec![("Digest", "trypsin"), ("DNA", "false")])?;
+
+    // Digests are for protein
+    Command::cargo_bin(PRG)?
+        .args([
+            "create", "--dna", "--digest", "trypsin", "-o", outpath, SEQ1,
+        ])
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_seed_masks() -> Result<()> {
+    let outfile = NamedTempFile::new()?;
+    let outpath = &outfile.path().to_string_lossy();
+    Command::cargo_bin(PRG)?
+        .args(["create", "--dna", "-s", "101,11011", "-o", outpath, SEQ3])
+        .assert()
+        .success();
+
+    // Hits found by both masks are only counted once
+    let output = Command::cargo_bin(PRG)?
+        .args(["count", outpath, "TGA", "GAT"])
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+    let actual = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(actual, "TGA 17\nGAT 10\n");
+
+    summarize(outpath, vec![("Seed mask", "101, 11011")])
+}
+
+// --------------------------------------------------
+#[test]
+fn filler_runs_uniprot() -> Result<()> {
+    let outfile = NamedTempFile::new()?;
+    let outpath = &outfile.path().to_string_lossy();
+    Command::cargo_bin(PRG)?
+        .args([
+            "create",
+            "--filler",
+            "*",
+            "--min-filler-run",
+            "3",
+            "-o",
+            outpath,
+            UNIPROT,
+        ])
+        .assert()
+        .success();
+
+    // Skipping homopolymer runs does not change the results
+    let output = Command::cargo_bin(PRG)?
+        .args(["count", outpath, "MKLV", "EEE", "AAAG"])
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+    let actual = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let output = Command::cargo_bin(PRG)?
+        .args(["count", UNIPROT_SUFR, "MKLV", "EEE", "AAAG"])
+        .output()
+        .expect("fail");
+    let expected = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(actual, expected);
+
+    // The run length requires filler characters
+    Command::cargo_bin(PRG)?
+        .args(["create", "--min-filler-run", "3", "-o", outpath, UNIPROT])
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn append_matches_create() -> Result<()> {
+    let outfile = NamedTempFile::new()?;
+    let outpath = &outfile.path().to_string_lossy();
+    Command::cargo_bin(PRG)?
+        .args(["create", "--dna", "-o", outpath, SEQ2])
+        .assert()
+        .success();
+
+    let appended_file = NamedTempFile::new()?;
+    let appended = &appended_file.path().to_string_lossy();
+    Command::cargo_bin(PRG)?
+        .args(["append", "-o", appended, outpath, SEQ3])
+        .assert()
+        .success();
+
+    // Appending gives the same suffix/LCP arrays as creating from all
+    // the sequences, though the build summaries differ
+    let list = |path: &str| -> Result<Vec<u8>> {
+        let output = Command::cargo_bin(PRG)?.args(["list", path]).output()?;
+        assert!(output.status.success());
+        Ok(output.stdout)
+    };
+    let combined_fasta = NamedTempFile::new()?;
+    let combined_path = &combined_fasta.path().to_string_lossy();
+    fs::write(
+        combined_fasta.path(),
+        [fs::read(SEQ2)?, fs::read(SEQ3)?].concat(),
+    )?;
+    let combined_file = NamedTempFile::new()?;
+    let combined = &combined_file.path().to_string_lossy();
+    Command::cargo_bin(PRG)?
+        .args(["create", "--dna", "-o", combined, combined_path])
+        .assert()
+        .success();
+    assert_eq!(list(appended)?, list(combined)?);
+
+    // Without an output file, the sufr file is rewritten
+    Command::cargo_bin(PRG)?
+        .args(["append", outpath, SEQ3])
+        .assert()
+        .success();
+    assert_eq!(list(outpath)?, list(combined)?);
+
+    // An index of both strands cannot be appended to
+    Command::cargo_bin(PRG)?
+        .args(["create", "--dna", "--both-strands", "-o", outpath, SEQ2])
+        .assert()
+        .success();
+    Command::cargo_bin(PRG)?
+        .args(["append", outpath, SEQ3])
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn remove_then_compact() -> Result<()> {
+    let outfile = NamedTempFile::new()?;
+    let outpath = &outfile.path().to_string_lossy();
+    Command::cargo_bin(PRG)?
+        .args(["create", "--dna", "-o", outpath, SEQ3])
+        .assert()
+        .success();
+
+    // An index of the sequences that remain
+    let fasta = fs::read_to_string(SEQ3)?;
+    let kept: Vec<_> = fasta
+        .split('>')
+        .filter(|rec| !rec.is_empty() && !rec.starts_with("2\n"))
+        .map(|rec| format!(">{rec}"))
+        .collect();
+    let kept_fasta = NamedTempFile::new()?;
+    let kept_fasta_path = &kept_fasta.path().to_string_lossy();
+    fs::write(kept_fasta.path(), kept.join(""))?;
+    let kept_file = NamedTempFile::new()?;
+    let kept_path = &kept_file.path().to_string_lossy();
+    Command::cargo_bin(PRG)?
+        .args(["create", "--dna", "-o", kept_path, kept_fasta_path])
+        .assert()
+        .success();
+
+    // Unknown sequences cannot be removed
+    Command::cargo_bin(PRG)?
+        .args(["remove", outpath, "4"])
+        .assert()
+        .failure();
+    Command::cargo_bin(PRG)?
+        .args(["remove", outpath, "2"])
+        .assert()
+        .success();
+
+    // Removed sequences are left out of the results
+    for action in ["count", "locate", "extract"] {
+        let removed = Command::cargo_bin(PRG)?
+            .args([action, outpath, "TG", "GTC"])
+            .output()?;
+        assert!(removed.status.success());
+        let expected = Command::cargo_bin(PRG)?
+            .args([action, kept_path, "TG", "GTC"])
+            .output()?;
+        assert!(expected.status.success());
+        if action == "extract" {
+            // The contexts of the last sequence end differently
+            let headers = |stdout: Vec<u8>| -> Result<Vec<String>> {
+                let mut headers: Vec<_> = String::from_utf8(stdout)?
+                    .lines()
+                    .filter(|line| line.starts_with('>'))
+                    .map(|line| line.split('-').next().unwrap_or("").to_string())
+                    .collect();
+                headers.sort();
+                Ok(headers)
+            };
+            assert_eq!(headers(removed.stdout)?, headers(expected.stdout)?);
+        } else {
+            assert_eq!(removed.stdout, expected.stdout);
+        }
+    }
+
+    let summary = Command::cargo_bin(PRG)?
+        .args(["summarize", outpath])
+        .output()?;
+    assert!(summary.status.success());
+    let summary = String::from_utf8(summary.stdout)?;
+    let regex = Regex::new(r"[|] Removed sequences\s+[|] ([^|]+)")?;
+    assert_eq!(
+        regex
+            .captures(&summary)
+            .map(|caps| caps[1].trim().to_string()),
+        Some("2".to_string())
+    );
+
+    // Compacting gives the same file as creating without them
+    let compacted_file = NamedTempFile::new()?;
+    let compacted = &compacted_file.path().to_string_lossy();
+    Command::cargo_bin(PRG)?
+        .args(["compact", "-o", compacted, outpath])
+        .assert()
+        .success();
+    assert_eq!(fs::read(compacted.as_ref())?, fs::read(kept_path.as_ref())?);
+
+    Command::cargo_bin(PRG)?
+        .args(["compact", outpath])
+        .assert()
+        .success();
+    assert_eq!(fs::read(outpath.as_ref())?, fs::read(kept_path.as_ref())?);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sharded_matches_unsharded() -> Result<()> {
+    let dir = TempDir::new()?;
+    let manifest = dir.path().join("3.shards");
+    let manifest = &manifest.to_string_lossy();
+    Command::cargo_bin(PRG)?
+        .args(["create", "--dna", "--shard-len", "80", "-o", manifest, SEQ3])
+        .assert()
+        .success();
+    assert!(dir.path().join("3.0.sufr").exists());
+    assert!(dir.path().join("3.1.sufr").exists());
+
+    for action in ["count", "locate", "extract"] {
+        let sharded = Command::cargo_bin(PRG)?
+            .args([action, manifest, "TG", "GTC"])
+            .output()?;
+        assert!(sharded.status.success());
+        let unsharded = Command::cargo_bin(PRG)?
+            .args([action, SUFR3, "TG", "GTC"])
+            .output()?;
+        assert!(unsharded.status.success());
+        if action == "extract" {
+            // The contexts of the last sequence in a shard end there
+            let headers = |stdout: Vec<u8>| -> Result<Vec<String>> {
+                let mut headers: Vec<_> = String::from_utf8(stdout)?
+                    .lines()
+                    .filter(|line| line.starts_with('>'))
+                    .map(|line| line.split('-').next().unwrap_or("").to_string())
+                    .collect();
+                headers.sort();
+                Ok(headers)
+            };
+            assert_eq!(headers(sharded.stdout)?, headers(unsharded.stdout)?);
+        } else {
+            assert_eq!(sharded.stdout, unsharded.stdout);
+        }
+    }
+
+    // A sequence cannot be split across shards
+    Command::cargo_bin(PRG)?
+        .args(["create", "--dna", "--shard-len", "30", "-o", manifest, SEQ3])
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn create_with_stats() -> Result<()> {
+    let sufr_file = NamedTempFile::new()?;
+    let sufr_path = &sufr_file.path().to_string_lossy();
+    let stats_file = NamedTempFile::new()?;
+    let stats_path = &stats_file.path().to_string_lossy();
+    Command::cargo_bin(PRG)?
+        .args([
+            "create", "--dna", "-n", "2", "--stats", stats_path, "-o", sufr_path, SEQ1,
+        ])
+        .assert()
+        .success();
+
+    let stats = fs::read_to_string(stats_file.path())?;
+    for field in [
+        r#""num_suffixes": 9"#,
+        r#""num_filtered_suffixes": 2"#,
+        r#""max_lcp": 4"#,
+        r#""mean_lcp": 1.25"#,
+        r#""partition_sizes": ["#,
+        r#""sort_secs": "#,
+        r#""peak_temp_bytes": "#,
+    ] {
+        assert!(stats.contains(field), "missing {field}");
+    }
+
+    // Statistics are only collected for a single index
+    Command::cargo_bin(PRG)?
+        .args([
+            "create",
+            "--dna",
+            "--shard-len",
+            "80",
+            "--stats",
+            stats_path,
+            SEQ3,
+        ])
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn count_with_lcp_lr() -> Result<()> {
+    let queries = [
+        "MAFSAEDVLKEYDRRRR",
+        "VQVECPKAPVEWNNPPS",
+        "GHFSGIKYKG",
+        "KAQAW",
+        "L",
+    ];
+    for action in ["count", "locate"] {
+        for memory in ["-l", "-v"] {
+            let expected = Command::cargo_bin(PRG)?
+                .args([action, memory, UNIPROT_SUFR])
+                .args(queries)
+                .output()?;
+            assert!(expected.status.success());
+            let output = Command::cargo_bin(PRG)?
+                .args([action, memory, "--lcp-lr", UNIPROT_SUFR])
+                .args(queries)
+                .output()?;
+            assert!(output.status.success());
+            assert_eq!(output.stdout, expected.stdout);
+        }
+    }
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn mems() -> Result<()> {
+    let queries = NamedTempFile::new()?;
+    fs::write(
+        &queries,
+        ">r1\nTTCTGACTGCAAGGGCAATACGTGAGTAAAT\n>r2\nGGATTAAAAAAAGAGTGATA\n",
+    )?;
+    let queries_path = queries.path().to_str().unwrap();
+
+    let output = Command::cargo_bin(PRG)?
+        .args(["mems", "-L", "6", SUFR3, queries_path, "GGGGGG"])
+        .output()?;
+    assert!(output.status.success());
+    let expected = [
+        "r1 0 1 9 12",
+        "r1 0 3 10 6",
+        "r1 12 1 22 8",
+        "r1 20 3 29 11",
+        "r2 0 2 5 17",
+        "r2 5 2 11 6",
+        "r2 6 2 10 6",
+        "",
+    ]
+    .join("\n");
+    assert_eq!(String::from_utf8(output.stdout)?, expected);
+    assert_eq!(String::from_utf8(output.stderr)?, "GGGGGG not found\n");
+
+    // Only the matches that occur once in the query and the text
+    let output = Command::cargo_bin(PRG)?
+        .args(["mems", "-L", "6", "--unique", SUFR3, queries_path])
+        .output()?;
+    assert!(output.status.success());
+    let expected = [
+        "r1 0 1 9 12",
+        "r1 12 1 22 8",
+        "r1 20 3 29 11",
+        "r2 0 2 5 17",
+        "",
+    ]
+    .join("\n");
+    assert_eq!(String::from_utf8(output.stdout)?, expected);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn matching_stats() -> Result<()> {
+    let queries = NamedTempFile::new()?;
+    fs::write(&queries, ">r2\nGGATTAAAAAAAGAGTGATA\n")?;
+    let queries_path = queries.path().to_str().unwrap();
+
+    let output = Command::cargo_bin(PRG)?
+        .args(["matching-stats", SUFR3, queries_path, "CGTA"])
+        .output()?;
+    assert!(output.status.success());
+    let expected = [
+        ">r2",
+        "17 16 15 14 13 12 11 10 9 8 7 6 5 4 4 5 4 3 2 1",
+        ">CGTA",
+        "3 3 2 1",
+        "",
+    ]
+    .join("\n");
+    assert_eq!(String::from_utf8(output.stdout)?, expected);
+
+    // The lengths are capped at the maximum query length
+    let output = Command::cargo_bin(PRG)?
+        .args(["ms", "-r", "-m", "4", SUFR3, "GGATTAAA"])
+        .output()?;
+    assert!(output.status.success());
+    let expected = [
+        ">GGATTAAA",
+        "0 4 58-59",
+        "1 4 51-52",
+        "2 4 26-27",
+        "3 4 94-95",
+        "4 4 71-73",
+        "5 3 1-7",
+        "6 2 1-12",
+        "7 1 1-28",
+        "",
+    ]
+    .join("\n");
+    assert_eq!(String::from_utf8(output.stdout)?, expected);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn repeats() -> Result<()> {
+    let output = Command::cargo_bin(PRG)?
+        .args(["repeats", "-L", "5", SUFR3])
+        .output()?;
+    assert!(output.status.success());
+    let expected = [
+        "AAAAAA 6",
+        "2 10,11",
+        "//",
+        "TTCTGA 6",
+        "1 9",
+        "3 10",
+        "//",
+        "AAAAA 5",
+        "2 10,11,12",
+        "//",
+        "AGCTT 5",
+        "1 0",
+        "3 7",
+        "//",
+        "",
+    ]
+    .join("\n");
+    assert_eq!(String::from_utf8(output.stdout)?, expected);
+
+    // Supermaximal repeats do not occur within another repeat
+    let output = Command::cargo_bin(PRG)?
+        .args(["re", "-L", "4", "-s", "-n", "3", SUFR3])
+        .output()?;
+    assert!(output.status.success());
+    let expected = [
+        "AAAAAA 6", "2 10,11", "//", "TTCTGA 6", "1 9", "3 10", "//", "AGCTT 5", "1 0",
+        "3 7", "//", "",
+    ]
+    .join("\n");
+    assert_eq!(String::from_utf8(output.stdout)?, expected);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn common() -> Result<()> {
+    // Compare one sequence with the others in the same file
+    let output = Command::cargo_bin(PRG)?
+        .args(["common", "-L", "5", "-s", "1", SUFR3])
+        .output()?;
+    assert!(output.status.success());
+    let expected = ["1 9 3 10 6 TTCTGA", "1 0 3 7 5 AGCTT", ""].join("\n");
+    assert_eq!(String::from_utf8(output.stdout)?, expected);
+
+    // Compare the sequences of another file
+    let fasta = NamedTempFile::new()?;
+    fs::write(&fasta, ">q\nGGTTCTGACTGCAACCCGTGAGTAAAT\n")?;
+    let other = NamedTempFile::new()?;
+    let other_path = &other.path().to_string_lossy();
+    Command::cargo_bin(PRG)?
+        .args([
+            "create",
+            "--dna",
+            "-o",
+            other_path,
+            &fasta.path().to_string_lossy(),
+        ])
+        .assert()
+        .success();
+
+    let output = Command::cargo_bin(PRG)?
+        .args(["cn", "-L", "5", SUFR3, other_path])
+        .output()?;
+    assert!(output.status.success());
+    let expected = [
+        "q 2 1 9 13 TTCTGACTGCAAC",
+        "q 15 3 28 12 CCGTGAGTAAAT",
+        "q 2 3 10 6 TTCTGA",
+        "",
+    ]
+    .join("\n");
+    assert_eq!(String::from_utf8(output.stdout)?, expected);
+
+    // Only the longest common substring
+    let output = Command::cargo_bin(PRG)?
+        .args(["cn", "-L", "5", "-n", "1", other_path, SUFR3])
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        "1 9 q 2 13 TTCTGACTGCAAC\n"
+    );
+
+    // Either another file or the sequences to compare are required
+    Command::cargo_bin(PRG)?
+        .args(["cn", SUFR3])
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn kmers() -> Result<()> {
+    let output = Command::cargo_bin(PRG)?
+        .args(["kmers", "-k", "3", "-c", "4", SUFR3])
+        .output()?;
+    assert!(output.status.success());
+    let expected = ["AAA 6", "CTG 5", "GTG 4", "TGA 4", "TGT 4", ""].join("\n");
+    assert_eq!(String::from_utf8(output.stdout)?, expected);
+
+    // The most frequent, ordered by count
+    let output = Command::cargo_bin(PRG)?
+        .args(["km", "-k", "3", "-n", "2", SUFR3])
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout)?, "AAA 6\nCTG 5\n");
+
+    // The number of k-mers with each count
+    let output = Command::cargo_bin(PRG)?
+        .args(["km", "-k", "3", "--histogram", SUFR3])
+        .output()?;
+    assert!(output.status.success());
+    let expected = ["1 23", "2 17", "3 4", "4 3", "5 1", "6 1", ""].join("\n");
+    assert_eq!(String::from_utf8(output.stdout)?, expected);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn stats() -> Result<()> {
+    let output = Command::cargo_bin(PRG)?
+        .args(["stats", "-L", "2", "-r", "5", SUFR3])
+        .output()?;
+    assert!(output.status.success());
+    let expected = [
+        "+---------------------+-------------+",
+        "| Positions           | 110         |",
+        "+---------------------+-------------+",
+        "| Distinct substrings | 1,844       |",
+        "+---------------------+-------------+",
+        "| Distinct 1-mers     | 4           |",
+        "+---------------------+-------------+",
+        "| Distinct 2-mers     | 17          |",
+        "+---------------------+-------------+",
+        "| Repeat coverage     | 27 (24.55%) |",
+        "+---------------------+-------------+",
+        "+----------+--------+---------------------+------------+",
+        "| Sequence | Length | Distinct substrings | Complexity |",
+        "+----------+--------+---------------------+------------+",
+        "| 1        | 46     | 908                 | 0.8990     |",
+        "+----------+--------+---------------------+------------+",
+        "| 2        | 24     | 251                 | 0.9194     |",
+        "+----------+--------+---------------------+------------+",
+        "| 3        | 40     | 756                 | 0.9934     |",
+        "+----------+--------+---------------------+------------+",
+        "",
+    ]
+    .join("\n");
+    assert_eq!(String::from_utf8(output.stdout)?, expected);
+
+    // The same as JSON
+    let output = Command::cargo_bin(PRG)?
+        .args(["st", "-L", "2", "-r", "5", "--json", SUFR3])
+        .output()?;
+    assert!(output.status.success());
+    let stats = String::from_utf8(output.stdout)?;
+    for field in [
+        r#""distinct_substrings": 1844"#,
+        r#""num_positions": 110"#,
+        r#""repeat_positions": 27"#,
+        r#""name": "3""#,
+        r#""linguistic_complexity""#,
+    ] {
+        assert!(stats.contains(field), "missing {field}");
+    }
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn locate_docs_only() -> Result<()> {
+    // cargo run -- lo -d data/expected/3.sufr A GTC XX
+    for memory in ["", "-l", "-v"] {
+        let mut args = vec!["lo", "-d", SUFR3, "A", "GTC", "XX"];
+        if !memory.is_empty() {
+            args.push(memory);
+        }
+        let output = Command::cargo_bin(PRG)?.args(&args).output()?;
+        assert!(output.status.success());
+        let expected = [
+            "A", "1 8", "2 9", "3 10", "//", "GTC", "1 1", "2 1", "//", "",
+        ]
+        .join("\n");
+        assert_eq!(String::from_utf8(output.stdout)?, expected);
+        assert_eq!(String::from_utf8(output.stderr)?, "XX not found\n");
+    }
+
+    // Sequences cannot be listed with absolute positions
+    let output = Command::cargo_bin(PRG)?
+        .args(["lo", "-d", "-a", SUFR3, "A"])
+        .output()?;
+    assert!(!output.status.success());
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn map_peptides() -> Result<()> {
+    // cargo run -- mp -c peptide data/expected/uniprot.sufr \
+    //     data/inputs/peptides.tsv
+    let expected = [
+        "ELEYWCTYAR sp|P0C9J0|1109L_ASFK5 37 R S unique",
+        "CSYFTDTR sp|P0C9J1|1109L_ASFP4 88 R I unique",
+        "VIDTENIK sp|P0C9J1|1109L_ASFP4 124 R H unique",
+        "MQLQAATR sp|P0C9J1|1109L_ASFP4 154 R L razor",
+        "MQLQAATR sp|P0C9I9|1109L_ASFWA 154 R L shared",
+        "CSYFTDSR sp|P26710|1109L_ASFL5 88 R I shared",
+        "CSYFTDSR sp|P0C9I9|1109L_ASFWA 88 R I razor",
+        "HLINQDDL sp|P0C9I9|1109L_ASFWA 282 R - unique",
+        "FWCTYAK sp|P0C9J0|1109L_ASFK5 203 K H shared",
+        "FWCTYAK sp|P26710|1109L_ASFL5 203 K H shared",
+        "FWCTYAK sp|P0C9J1|1109L_ASFP4 203 K H razor",
+        "FWCTYAK sp|P0C9I9|1109L_ASFWA 203 K H shared",
+        "",
+    ]
+    .join("\n");
+
+    // A column of a tab-delimited file by name or number, or mzIdentML
+    for args in [
+        vec!["-c", "peptide", "../data/inputs/peptides.tsv"],
+        vec!["-c", "2", "../data/inputs/peptides.tsv", "-l"],
+        vec!["../data/inputs/peptides.mzid", "-v"],
+    ] {
+        let output = Command::cargo_bin(PRG)?
+            .arg("mp")
+            .arg(UNIPROT_SUFR)
+            .args(&args)
+            .output()?;
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout)?, expected);
+        assert_eq!(String::from_utf8(output.stderr)?, "WWWWWWWW not found\n");
+    }
+
+    // Peptides given as arguments
+    let output = Command::cargo_bin(PRG)?
+        .args(["map-peptides", UNIPROT_SUFR, "hlinqddl"])
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        "HLINQDDL sp|P0C9I9|1109L_ASFWA 282 R - unique\n"
+    );
+
+    // An unknown column
+    let output = Command::cargo_bin(PRG)?
+        .args(["mp", "-c", "4", UNIPROT_SUFR, "../data/inputs/peptides.tsv"])
+        .output()?;
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr)?,
+        "Error: Column \"4\" not found in \"../data/inputs/peptides.tsv\"\n"
+    );
+
+    Ok(())
 }